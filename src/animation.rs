@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use windows_numerics::{Vector2, Vector3};
+
+use crate::scene::Color;
+
+pub trait Interpolate: Copy {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vector2 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Vector2::new(
+            self.X.interpolate(&other.X, t),
+            self.Y.interpolate(&other.Y, t),
+        )
+    }
+}
+
+impl Interpolate for Vector3 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Vector3::new(
+            self.X.interpolate(&other.X, t),
+            self.Y.interpolate(&other.Y, t),
+            self.Z.interpolate(&other.Z, t),
+        )
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let channel = |from: u8, to: u8| -> u8 {
+            (from as f32)
+                .interpolate(&(to as f32), t)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        };
+        Color {
+            a: channel(self.a, other.a),
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    CubicBezier(Vector2, Vector2),
+}
+
+impl Easing {
+    pub fn ease(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::CubicBezier(control_point1, control_point2) => {
+                cubic_bezier(*control_point1, *control_point2, t)
+            }
+        }
+    }
+}
+
+// Solves the curve for the parameter whose x is 't' and returns its y, the
+// same way CSS and Composition evaluate cubic bezier easing functions.
+fn cubic_bezier(control_point1: Vector2, control_point2: Vector2, t: f32) -> f32 {
+    let sample = |a: f32, b: f32, s: f32| -> f32 {
+        let inverse = 1.0 - s;
+        3.0 * inverse * inverse * s * a + 3.0 * inverse * s * s * b + s * s * s
+    };
+    let t = t.clamp(0.0, 1.0);
+    let mut low = 0.0;
+    let mut high = 1.0;
+    let mut s = t;
+    for _ in 0..32 {
+        let x = sample(control_point1.X, control_point2.X, s);
+        if (x - t).abs() < 1e-6 {
+            break;
+        }
+        if x < t {
+            low = s;
+        } else {
+            high = s;
+        }
+        s = (low + high) / 2.0;
+    }
+    sample(control_point1.Y, control_point2.Y, s)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IterationBehavior {
+    Count(u32),
+    Forever,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeyFrame<T> {
+    pub progress: f32,
+    pub value: T,
+    pub easing: Easing,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyFrameAnimation<T> {
+    key_frames: Vec<KeyFrame<T>>,
+    pub duration: Duration,
+    pub iteration_behavior: IterationBehavior,
+}
+
+impl<T: Interpolate> KeyFrameAnimation<T> {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            key_frames: Vec::new(),
+            duration,
+            iteration_behavior: IterationBehavior::Count(1),
+        }
+    }
+
+    pub fn insert_key_frame(&mut self, progress: f32, value: T) {
+        self.insert_key_frame_with_easing(progress, value, Easing::Linear);
+    }
+
+    pub fn insert_key_frame_with_easing(&mut self, progress: f32, value: T, easing: Easing) {
+        let progress = progress.clamp(0.0, 1.0);
+        let key_frame = KeyFrame {
+            progress,
+            value,
+            easing,
+        };
+        // Like Composition, inserting at an existing progress replaces that key frame.
+        match self
+            .key_frames
+            .binary_search_by(|existing| existing.progress.total_cmp(&progress))
+        {
+            Ok(index) => self.key_frames[index] = key_frame,
+            Err(index) => self.key_frames.insert(index, key_frame),
+        }
+    }
+
+    pub fn key_frames(&self) -> &[KeyFrame<T>] {
+        &self.key_frames
+    }
+
+    // How long the animation takes to play through once, counting every
+    // iteration; animations that repeat forever count one iteration.
+    pub fn total_duration(&self) -> Duration {
+        match self.iteration_behavior {
+            IterationBehavior::Count(count) => self.duration * count,
+            IterationBehavior::Forever => self.duration,
+        }
+    }
+
+    pub fn progress_at(&self, time: Duration) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        let iterations = time.as_secs_f64() / self.duration.as_secs_f64();
+        if let IterationBehavior::Count(count) = self.iteration_behavior
+            && iterations >= count as f64
+        {
+            return 1.0;
+        }
+        iterations.fract() as f32
+    }
+
+    // 'initial' stands in for the property's value when the first key frame
+    // doesn't sit at progress 0, matching how Composition starts animations.
+    pub fn value_at(&self, time: Duration, initial: T) -> T {
+        let progress = self.progress_at(time);
+        let mut previous_progress = 0.0;
+        let mut previous_value = initial;
+        for key_frame in &self.key_frames {
+            if progress <= key_frame.progress {
+                let span = key_frame.progress - previous_progress;
+                let t = if span > 0.0 {
+                    (progress - previous_progress) / span
+                } else {
+                    1.0
+                };
+                return previous_value.interpolate(&key_frame.value, key_frame.easing.ease(t));
+            }
+            previous_progress = key_frame.progress;
+            previous_value = key_frame.value;
+        }
+        previous_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ease_in_out() -> Easing {
+        Easing::CubicBezier(Vector2::new(0.42, 0.0), Vector2::new(0.58, 1.0))
+    }
+
+    fn seconds(seconds: f32) -> Duration {
+        Duration::from_secs_f32(seconds)
+    }
+
+    #[test]
+    fn cubic_bezier_starts_at_0_and_ends_at_1() {
+        for easing in [
+            ease_in_out(),
+            Easing::CubicBezier(Vector2::new(0.25, 0.1), Vector2::new(0.25, 1.0)),
+            Easing::CubicBezier(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0)),
+        ] {
+            assert!(easing.ease(0.0).abs() < 1e-4);
+            assert!((easing.ease(1.0) - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn cubic_bezier_clamps_progress_outside_0_to_1() {
+        let easing = ease_in_out();
+        assert_eq!(easing.ease(-0.5), easing.ease(0.0));
+        assert_eq!(easing.ease(1.5), easing.ease(1.0));
+    }
+
+    #[test]
+    fn cubic_bezier_never_goes_backwards() {
+        let easing = ease_in_out();
+        let mut previous = easing.ease(0.0);
+        for step in 1..=100 {
+            let value = easing.ease(step as f32 / 100.0);
+            assert!(value >= previous - 1e-5, "{value} after {previous}");
+            previous = value;
+        }
+    }
+
+    #[test]
+    fn cubic_bezier_follows_the_curve_in_between() {
+        let easing = ease_in_out();
+        assert!((easing.ease(0.5) - 0.5).abs() < 1e-3);
+        assert!(easing.ease(0.25) < 0.25);
+        assert!(easing.ease(0.75) > 0.75);
+        // A straight line through the corners is linear.
+        let linear = Easing::CubicBezier(Vector2::new(0.25, 0.25), Vector2::new(0.75, 0.75));
+        assert!((linear.ease(0.3) - 0.3).abs() < 1e-3);
+    }
+
+    #[test]
+    fn values_interpolate_between_key_frames() {
+        let mut animation = KeyFrameAnimation::new(seconds(2.0));
+        animation.insert_key_frame(0.0, 0.0);
+        animation.insert_key_frame(0.5, 100.0);
+        animation.insert_key_frame(1.0, 50.0);
+        assert_eq!(animation.value_at(seconds(0.0), -1.0), 0.0);
+        assert_eq!(animation.value_at(seconds(0.5), -1.0), 50.0);
+        assert_eq!(animation.value_at(seconds(1.0), -1.0), 100.0);
+        assert_eq!(animation.value_at(seconds(1.5), -1.0), 75.0);
+        assert_eq!(animation.value_at(seconds(2.0), -1.0), 50.0);
+    }
+
+    #[test]
+    fn key_frames_ease_into_their_value() {
+        let mut animation = KeyFrameAnimation::new(seconds(1.0));
+        animation.insert_key_frame(0.0, 0.0);
+        animation.insert_key_frame_with_easing(1.0, 100.0, ease_in_out());
+        let value = animation.value_at(seconds(0.25), 0.0);
+        assert!((value - ease_in_out().ease(0.25) * 100.0).abs() < 1e-3);
+        assert!(value < 25.0);
+    }
+
+    #[test]
+    fn the_initial_value_leads_into_the_first_key_frame() {
+        let mut animation = KeyFrameAnimation::new(seconds(1.0));
+        animation.insert_key_frame(0.5, 100.0);
+        assert_eq!(animation.value_at(seconds(0.0), 20.0), 20.0);
+        assert_eq!(animation.value_at(seconds(0.25), 20.0), 60.0);
+        assert_eq!(animation.value_at(seconds(0.75), 20.0), 100.0);
+    }
+
+    #[test]
+    fn an_animation_without_key_frames_holds_the_initial_value() {
+        let animation = KeyFrameAnimation::<f32>::new(seconds(1.0));
+        assert_eq!(animation.value_at(seconds(0.5), 7.0), 7.0);
+    }
+
+    #[test]
+    fn inserting_at_an_equal_progress_replaces_the_key_frame() {
+        let mut animation = KeyFrameAnimation::new(seconds(1.0));
+        animation.insert_key_frame(1.0, 10.0);
+        animation.insert_key_frame(0.0, 0.0);
+        animation.insert_key_frame_with_easing(1.0, 20.0, ease_in_out());
+        assert_eq!(animation.key_frames().len(), 2);
+        assert_eq!(animation.key_frames()[0].progress, 0.0);
+        assert_eq!(animation.key_frames()[1].value, 20.0);
+        assert_eq!(animation.key_frames()[1].easing, ease_in_out());
+    }
+
+    #[test]
+    fn key_frame_progress_is_clamped_and_sorted() {
+        let mut animation = KeyFrameAnimation::new(seconds(1.0));
+        animation.insert_key_frame(1.5, 3.0);
+        animation.insert_key_frame(0.5, 2.0);
+        animation.insert_key_frame(-1.0, 1.0);
+        let progress: Vec<f32> = animation.key_frames().iter().map(|k| k.progress).collect();
+        assert_eq!(progress, [0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn progress_repeats_for_each_iteration_then_holds_at_the_end() {
+        let mut animation = KeyFrameAnimation::<f32>::new(seconds(2.0));
+        animation.iteration_behavior = IterationBehavior::Count(3);
+        assert_eq!(animation.total_duration(), seconds(6.0));
+        assert_eq!(animation.progress_at(seconds(1.0)), 0.5);
+        assert_eq!(animation.progress_at(seconds(2.5)), 0.25);
+        assert_eq!(animation.progress_at(seconds(5.0)), 0.5);
+        assert_eq!(animation.progress_at(seconds(6.0)), 1.0);
+        assert_eq!(animation.progress_at(seconds(60.0)), 1.0);
+    }
+
+    #[test]
+    fn forever_animations_keep_repeating() {
+        let mut animation = KeyFrameAnimation::new(seconds(2.0));
+        animation.iteration_behavior = IterationBehavior::Forever;
+        animation.insert_key_frame(0.0, 0.0);
+        animation.insert_key_frame(1.0, 100.0);
+        assert_eq!(animation.total_duration(), seconds(2.0));
+        assert_eq!(animation.progress_at(seconds(101.0)), 0.5);
+        assert_eq!(animation.value_at(seconds(101.0), 0.0), 50.0);
+    }
+
+    #[test]
+    fn zero_length_animations_are_finished() {
+        let mut animation = KeyFrameAnimation::new(Duration::ZERO);
+        animation.insert_key_frame(0.0, 0.0);
+        animation.insert_key_frame(1.0, 100.0);
+        assert_eq!(animation.progress_at(Duration::ZERO), 1.0);
+        assert_eq!(animation.value_at(Duration::ZERO, 0.0), 100.0);
+    }
+
+    #[test]
+    fn vectors_and_colors_interpolate_per_component() {
+        let from = Vector3::new(0.0, 10.0, -4.0);
+        let to = Vector3::new(10.0, 0.0, 4.0);
+        assert_eq!(from.interpolate(&to, 0.25), Vector3::new(2.5, 7.5, -2.0));
+        let from = Color::rgb(0, 100, 255);
+        let to = Color {
+            a: 0,
+            ..Color::rgb(255, 200, 0)
+        };
+        assert_eq!(
+            from.interpolate(&to, 0.5),
+            Color {
+                a: 128,
+                r: 128,
+                g: 150,
+                b: 128
+            }
+        );
+    }
+}
@@ -0,0 +1,524 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, info, info_span, warn};
+use windows::{
+    UI::Composition::Compositor,
+    Win32::{
+        Foundation::HWND,
+        Graphics::Dwm::{DWM_TIMING_INFO, DwmGetCompositionTimingInfo},
+        System::WinRT::{RO_INIT_SINGLETHREADED, RoInitialize},
+        UI::Controls::WM_MOUSELEAVE,
+        UI::Input::KeyboardAndMouse::VK_SPACE,
+        UI::Shell::ShellExecuteW,
+        UI::WindowsAndMessaging::{
+            DispatchMessageW, GetMessageW, MSG, SW_SHOWNORMAL, SetTimer, TranslateMessage,
+            WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEMOVE, WM_TIMER,
+        },
+    },
+    core::{HRESULT, HSTRING, Result, w},
+};
+use windows_numerics::Vector2;
+
+use crate::{
+    cli::{Mode, Options, USAGE, debug_layers_from_env},
+    clock::{FrameClock, SystemClock},
+    composition::CompositorInterop,
+    d2d::{create_d2d_device, create_d2d_factory, factory_options},
+    d3d::{DebugMessages, create_d3d_device, device_flags},
+    error::{self, Context, Error, Step},
+    export::{AnimationWriter, ApngWriter, ExportFormat, GifWriter, PipeWriter, Y4mWriter},
+    interop::{
+        create_dispatcher_queue_controller_for_current_thread,
+        shutdown_dispatcher_queue_controller_and_wait,
+    },
+    links::{LinkEvent, LinkHover, LinkTarget, link_at},
+    logging::init_logging,
+    metrics::{LayoutEngine, estimate_report, metrics_report},
+    numerics::ToVector2,
+    outline::text_outline,
+    overlay::{FrameRate, Overlay},
+    render::{HeadlessRenderer, export_animation, render_frames},
+    scene::Scene,
+    scene_file::{self, SceneFileError},
+    scene_host::SceneHost,
+    svg::{SvgOptions, TextBlock, TextMode, scene_to_svg},
+    text::{TextFactory, text_lines, text_metrics, text_size},
+    text_input::TextInput,
+    watch::FileWatcher,
+    window::{Window, mouse_position},
+};
+
+// How often the scene file is checked and the frame counter updated.
+const TIMER_INTERVAL_MS: u32 = 500;
+// How often text with animated font axes is drawn again, about once a
+// frame at 60Hz.
+const FRAME_INTERVAL_MS: u32 = 16;
+
+// A scene file that fails to load is reported and the current scene kept,
+// since it's often just saved mid-edit.
+fn reload_scene(host: &mut SceneHost, path: &Path) -> Result<()> {
+    let scene = scene_file::load_scene(path).and_then(|scene| {
+        scene
+            .check_expressions()
+            .map_err(|error| SceneFileError::Invalid {
+                path: "scene".to_owned(),
+                message: error.to_string(),
+            })?;
+        Ok(scene)
+    });
+    match scene {
+        Ok(scene) => {
+            info!(path = %path.display(), "Reloading scene");
+            host.update(scene)
+        }
+        Err(error) => {
+            warn!(path = %path.display(), %error, "Failed to reload scene");
+            Ok(())
+        }
+    }
+}
+
+// Links are styled while the mouse is over them, and clicking one opens its
+// URL or reports its action.
+fn handle_link_message(
+    message: &MSG,
+    links: &mut LinkHover,
+    window: &mut Window,
+    host: &mut SceneHost,
+) -> Result<()> {
+    let link_under_mouse = || -> Result<Option<(String, usize)>> {
+        let hit = host.hit_test(mouse_position(message.lParam), window.size()?);
+        Ok(link_at(host.scene(), hit.as_ref()))
+    };
+    let events = match message.message {
+        WM_MOUSEMOVE => links.mouse_move(link_under_mouse()?),
+        WM_LBUTTONDOWN => links.mouse_down(link_under_mouse()?),
+        WM_LBUTTONUP => links.mouse_up(link_under_mouse()?),
+        WM_MOUSELEAVE => links.reset(),
+        _ => return Ok(()),
+    };
+    handle_link_events(events, window, host)
+}
+
+fn handle_link_events(
+    events: Vec<LinkEvent>,
+    window: &mut Window,
+    host: &mut SceneHost,
+) -> Result<()> {
+    for event in events {
+        match event {
+            LinkEvent::Enter(id, index) => {
+                window.set_over_link(true);
+                host.set_link_hovered(&id, index, true)?;
+            }
+            LinkEvent::Leave(id, index) => {
+                window.set_over_link(false);
+                host.set_link_hovered(&id, index, false)?;
+            }
+            LinkEvent::Click(id, index) => {
+                let Some(link) = host
+                    .scene()
+                    .visual(&id)
+                    .and_then(|visual| visual.links.get(index))
+                else {
+                    continue;
+                };
+                match &link.target {
+                    LinkTarget::Url(url) => {
+                        info!(id, url, "Opening link");
+                        // Anything above 32 is success.
+                        let result = unsafe {
+                            ShellExecuteW(
+                                Some(window.handle()),
+                                w!("open"),
+                                &HSTRING::from(url),
+                                None,
+                                None,
+                                SW_SHOWNORMAL,
+                            )
+                        };
+                        if result.0 as usize <= 32 {
+                            warn!(url, code = result.0 as usize, "Failed to open link");
+                        }
+                    }
+                    LinkTarget::Action(action) => info!(id, action, "Clicked link"),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The number of frames the desktop compositor has presented.
+fn composed_frames() -> Result<u64> {
+    let mut timing_info = DWM_TIMING_INFO {
+        cbSize: std::mem::size_of::<DWM_TIMING_INFO>() as u32,
+        ..Default::default()
+    };
+    unsafe { DwmGetCompositionTimingInfo(HWND::default(), &mut timing_info)? };
+    Ok(timing_info.cFrame)
+}
+
+fn run(options: &Options) -> error::Result<()> {
+    unsafe { RoInitialize(RO_INIT_SINGLETHREADED) }.context(Step::Startup, "initialize WinRT")?;
+    let controller = create_dispatcher_queue_controller_for_current_thread()
+        .context(Step::Startup, "create the dispatcher queue")?;
+
+    let window_width = options.width;
+    let window_height = options.height;
+
+    let scene = scene_file::load_checked_scene(options.scene.as_deref())?;
+
+    let compositor = Compositor::new().context(Step::Startup, "create the compositor")?;
+    let root = compositor
+        .CreateSpriteVisual()
+        .context(Step::Surface, "create the root visual")?;
+    root.SetRelativeSizeAdjustment(Vector2::new(1.0, 1.0))
+        .context(Step::Surface, "size the root visual")?;
+
+    let mut window = Window::new("Composition Text Demo", window_width, window_height)
+        .context(Step::Startup, "create the window")?;
+    window
+        .create_window_target(&compositor, false)
+        .and_then(|target| {
+            target.SetRoot(&root)?;
+            Ok(target)
+        })
+        .context(Step::Startup, "attach the compositor to the window")?;
+
+    // Init D3D and D2D
+    let d3d_device = create_d3d_device(device_flags(options.debug_layers))
+        .context(Step::Device, "create the Direct3D device")?;
+    let debug_messages = DebugMessages::new(&d3d_device);
+    let d2d_factory = create_d2d_factory(factory_options(options.debug_layers))
+        .context(Step::Device, "create the Direct2D factory")?;
+    let d2d_device = create_d2d_device(&d2d_factory, &d3d_device)
+        .context(Step::Device, "create the Direct2D device")?;
+    let comp_graphics = compositor
+        .create_graphics_device_from_d2d_device(&d2d_device)
+        .context(Step::Device, "create the composition graphics device")?;
+
+    // Build the visual tree for our scene
+    let mut host = SceneHost::new(
+        &compositor,
+        &root,
+        &comp_graphics,
+        Rc::new(SystemClock::default()),
+        scene,
+        options.overlays,
+    )
+    .context(Step::Surface, "build the visual tree")?;
+
+    // Reload the scene whenever its file changes and keep the frame
+    // counter and animated font axes current
+    let mut watcher = options.scene.as_deref().map(FileWatcher::new);
+    let start = Instant::now();
+    let mut frame_rate = FrameRate::default();
+    let timer = unsafe { SetTimer(None, 0, TIMER_INTERVAL_MS, None) };
+    let frame_timer = unsafe { SetTimer(None, 0, FRAME_INTERVAL_MS, None) };
+
+    // Typing goes to the first editable visual until Tab moves on
+    let mut text_input = TextInput::default();
+    let mut links = LinkHover::default();
+    text_input
+        .focus_next(&mut window, &mut host)
+        .context(Step::Surface, "focus the editable text")?;
+
+    // Pump messages and exit
+    let _span = info_span!("message_loop").entered();
+    let mut message = MSG::default();
+    unsafe {
+        while GetMessageW(&mut message, None, 0, 0).into() {
+            if message.message == WM_TIMER
+                && message.hwnd.is_invalid()
+                && message.wParam.0 == frame_timer
+            {
+                host.update_font_axes()
+                    .context(Step::Surface, "draw the animated font axes")?;
+            }
+            if message.message == WM_TIMER && message.hwnd.is_invalid() && message.wParam.0 == timer
+            {
+                if let Some(debug_messages) = &debug_messages {
+                    debug_messages.drain();
+                }
+                if let Some(watcher) = &mut watcher
+                    && watcher.poll()
+                {
+                    reload_scene(&mut host, watcher.path())
+                        .context(Step::Surface, "apply the reloaded scene")?;
+                    text_input
+                        .scene_changed(&mut window, &mut host)
+                        .context(Step::Surface, "update the edited text")?;
+                    handle_link_events(links.reset(), &mut window, &mut host)
+                        .context(Step::Surface, "update the links")?;
+                }
+                if host.overlays().contains(Overlay::Counter) {
+                    let (fps, frame) = frame_rate.sample(
+                        start.elapsed(),
+                        composed_frames().context(Step::Render, "read composition timing")?,
+                    );
+                    host.update_counter(fps, frame)
+                        .context(Step::Surface, "draw the frame counter")?;
+                }
+            }
+            // Keys go to the focused text before the window's shortcuts.
+            let is_text_input = text_input
+                .handle_message(&message, &mut window, &mut host)
+                .context(Step::Surface, "edit the text")?;
+            if !is_text_input {
+                handle_link_message(&message, &mut links, &mut window, &mut host)
+                    .context(Step::Surface, "show the links")?;
+            }
+            // Bit 30 is set on auto-repeated key presses.
+            if !is_text_input
+                && message.message == WM_KEYDOWN
+                && message.lParam.0 & (1 << 30) == 0
+                && message.wParam.0 == VK_SPACE.0 as usize
+            {
+                let is_paused = host
+                    .toggle_paused()
+                    .context(Step::Surface, "pause or resume the animations")?;
+                debug!(is_paused, "Toggled animations");
+            }
+            if !is_text_input
+                && message.message == WM_KEYDOWN
+                && message.lParam.0 & (1 << 30) == 0
+                && let Some(overlay) =
+                    char::from_u32(message.wParam.0 as u32).and_then(Overlay::from_key)
+            {
+                let mut overlays = host.overlays();
+                overlays.toggle(overlay);
+                debug!(
+                    overlay = overlay.name(),
+                    visible = overlays.contains(overlay),
+                    "Toggled overlay"
+                );
+                host.set_overlays(overlays)
+                    .context(Step::Surface, "draw the overlays")?;
+            }
+            let _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+            for event in window.take_ime_events() {
+                text_input
+                    .handle_ime_event(event, &mut host)
+                    .context(Step::Surface, "edit the text")?;
+            }
+        }
+    }
+    if let Some(debug_messages) = &debug_messages {
+        debug_messages.drain();
+    }
+    let code = shutdown_dispatcher_queue_controller_and_wait(&controller, message.wParam.0 as i32)
+        .context(Step::Startup, "shut down the dispatcher queue")?;
+    if code != 0 {
+        Err(windows::core::Error::from(HRESULT(message.wParam.0 as i32)))
+            .context(Step::Startup, "run the message loop")
+    } else {
+        Ok(())
+    }
+}
+
+struct OffscreenRender {
+    scene: Scene,
+    renderer: HeadlessRenderer,
+    clock: FrameClock,
+    frame_count: u64,
+}
+
+impl OffscreenRender {
+    fn new(options: &Options) -> error::Result<Self> {
+        let scene = scene_file::load_checked_scene(options.scene.as_deref())?;
+        let renderer = HeadlessRenderer::new(
+            options.width,
+            options.height,
+            options.overlays,
+            options.debug_layers,
+        )
+        .context(Step::Device, "create the offscreen renderer")?;
+        let clock = FrameClock::new(options.fps());
+        let frame_count = options.frame_count.unwrap_or_else(|| {
+            clock.frame_count(options.duration.unwrap_or_else(|| scene.preview_duration()))
+        });
+        Ok(Self {
+            scene,
+            renderer,
+            clock,
+            frame_count,
+        })
+    }
+
+    fn export(&self, writer: Box<dyn AnimationWriter>) -> error::Result<()> {
+        export_animation(
+            &self.renderer,
+            &self.scene,
+            &self.clock,
+            self.frame_count,
+            writer,
+        )
+    }
+}
+
+fn run_render_frames(options: &Options, directory: &Path) -> error::Result<()> {
+    let render = OffscreenRender::new(options)?;
+    render_frames(
+        &render.renderer,
+        &render.scene,
+        &render.clock,
+        render.frame_count,
+        directory,
+    )
+}
+
+fn run_export(options: &Options, path: &Path, format: ExportFormat) -> error::Result<()> {
+    let render = OffscreenRender::new(options)?;
+    let fps = render.clock.fps();
+    let create_error =
+        |error: std::io::Error| Error::io(format!("create {}", path.display()), error);
+    // Y4M is often piped straight into an encoder, so '-' means stdout.
+    let file: Box<dyn Write> = if format == ExportFormat::Y4m && path == Path::new("-") {
+        Box::new(BufWriter::new(std::io::stdout().lock()))
+    } else {
+        Box::new(BufWriter::new(File::create(path).map_err(create_error)?))
+    };
+    let writer: Box<dyn AnimationWriter> = match format {
+        ExportFormat::Gif => Box::new(
+            GifWriter::new(
+                file,
+                options.width,
+                options.height,
+                fps,
+                options.loop_count,
+                options.quantization,
+            )
+            .map_err(create_error)?,
+        ),
+        ExportFormat::Apng => Box::new(
+            ApngWriter::new(
+                file,
+                options.width,
+                options.height,
+                fps,
+                u32::try_from(render.frame_count).map_err(|_| {
+                    create_error(std::io::Error::other("too many frames for an APNG"))
+                })?,
+                options.loop_count,
+            )
+            .map_err(create_error)?,
+        ),
+        ExportFormat::Y4m => Box::new(
+            Y4mWriter::new(file, options.width, options.height, fps).map_err(create_error)?,
+        ),
+    };
+    render.export(writer)
+}
+
+fn run_pipe(options: &Options, command: &str) -> error::Result<()> {
+    let render = OffscreenRender::new(options)?;
+    let writer = PipeWriter::spawn(command, options.width, options.height, render.clock.fps())
+        .map_err(|error| Error::io(format!("start '{}'", command), error))?;
+    render.export(Box::new(writer))
+}
+
+fn run_svg(options: &Options, path: &Path) -> error::Result<()> {
+    let scene = scene_file::load_checked_scene(options.scene.as_deref())?;
+    let text_factory = TextFactory::new().context(Step::Font, "create the DirectWrite factory")?;
+    let mut blocks = HashMap::new();
+    for visual in &scene.visuals {
+        let block = text_factory
+            .create_text_layout(&visual.axes_at(Duration::ZERO))
+            .and_then(|text_layout| {
+                // Outlines are of the glyphs in a line, so text on a path is
+                // written as text along an SVG path instead.
+                let outline = match options.svg_text {
+                    TextMode::Outlines if visual.path.is_none() => {
+                        Some(text_outline(&text_layout)?)
+                    }
+                    _ => None,
+                };
+                let size = match &visual.path {
+                    Some(path) => path.frame(visual.font_size).1,
+                    None => text_size(&text_layout)?.to_vector2(),
+                };
+                Ok(TextBlock {
+                    size,
+                    lines: text_lines(&visual.text, &text_layout)?,
+                    outline,
+                })
+            })
+            .context(Step::Font, format!("lay out the text of '{}'", visual.id))?;
+        blocks.insert(visual.id.clone(), block);
+    }
+    let svg = scene_to_svg(
+        &scene,
+        &blocks,
+        &SvgOptions {
+            width: options.width,
+            height: options.height,
+            text_mode: options.svg_text,
+        },
+    );
+    std::fs::write(path, svg).map_err(|error| Error::io(format!("write {}", path.display()), error))
+}
+
+fn run_dump_metrics(options: &Options) -> error::Result<()> {
+    let scene = scene_file::load_checked_scene(options.scene.as_deref())?;
+    let report = match options.layout_engine.unwrap_or(LayoutEngine::DirectWrite) {
+        LayoutEngine::DirectWrite => {
+            let text_factory =
+                TextFactory::new().context(Step::Font, "create the DirectWrite factory")?;
+            let mut visuals = Vec::with_capacity(scene.visuals.len());
+            for visual in &scene.visuals {
+                let visual_at_zero = visual.axes_at(Duration::ZERO);
+                let metrics = text_factory
+                    .create_text_layout(&visual_at_zero)
+                    .and_then(|text_layout| text_metrics(&text_layout))
+                    .map(|metrics| metrics.on_path(&visual_at_zero))
+                    .context(Step::Font, format!("lay out the text of '{}'", visual.id))?;
+                visuals.push((visual, metrics));
+            }
+            metrics_report(LayoutEngine::DirectWrite, &visuals)
+        }
+        LayoutEngine::Estimate => estimate_report(&scene),
+    };
+    println!("{:#}", report);
+    Ok(())
+}
+
+pub fn main() {
+    let mut options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(message) = init_logging(options.log_filter.as_deref(), options.log_format) {
+        eprintln!("{}\n\n{}", message, USAGE);
+        std::process::exit(2);
+    }
+    options.debug_layers |= debug_layers_from_env();
+    let result = match &options.mode {
+        Mode::Window => run(&options),
+        Mode::RenderFrames { directory } => run_render_frames(&options, directory),
+        Mode::Export { path, format } => run_export(&options, path, *format),
+        Mode::Pipe { command } => run_pipe(&options, command),
+        Mode::Svg { path } => run_svg(&options, path),
+        Mode::DumpMetrics => run_dump_metrics(&options),
+        Mode::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(error.exit_code());
+    }
+}
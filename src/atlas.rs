@@ -0,0 +1,428 @@
+use std::{collections::HashMap, hash::Hash};
+
+// A rectangle of pixels in an atlas page.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Allocation {
+    pub page: usize,
+    pub rect: AtlasRect,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Acquired<K> {
+    pub allocation: Allocation,
+    // False when the key already had a slot of the same size, which still
+    // holds whatever was drawn into it.
+    pub is_new: bool,
+    // Released keys whose slots were taken back to make room.
+    pub evicted: Vec<K>,
+}
+
+struct Entry {
+    allocation: Allocation,
+    // When the entry was released, for evicting the least recently released
+    // first. None while it's in use.
+    released: Option<u64>,
+}
+
+#[derive(Copy, Clone)]
+struct Slot {
+    left: u32,
+    width: u32,
+    used: bool,
+}
+
+// A row of slots as tall as the shelf. Slots cover the page's full width.
+struct Shelf {
+    top: u32,
+    height: u32,
+    slots: Vec<Slot>,
+}
+
+struct Page {
+    shelves: Vec<Shelf>,
+    bottom: u32,
+}
+
+// Packs rectangles into square pages with a shelf packer, which suits text:
+// labels of the same font size have the same height and line up on a shelf.
+// Each rect gets 'padding' pixels of space around it so filtering doesn't
+// pull in its neighbors.
+//
+// Keys that are released keep their slot until it's needed. When nothing
+// fits, a new page is added up to 'max_pages', and after that the least
+// recently released slots are freed until the rect fits. Keys in use are
+// never evicted, so acquiring fails once the atlas is full of them.
+pub struct Atlas<K> {
+    page_size: u32,
+    max_pages: usize,
+    padding: u32,
+    pages: Vec<Page>,
+    entries: HashMap<K, Entry>,
+    releases: u64,
+}
+
+impl<K: Clone + Eq + Hash> Atlas<K> {
+    pub fn new(page_size: u32, max_pages: usize, padding: u32) -> Self {
+        Self {
+            page_size,
+            max_pages,
+            padding,
+            pages: Vec::new(),
+            entries: HashMap::new(),
+            releases: 0,
+        }
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    pub fn padding(&self) -> u32 {
+        self.padding
+    }
+
+    // Returns a slot of 'width' by 'height' for 'key', keeping its current
+    // slot if it's the same size. Fails if the rect is too big for a page or
+    // every page is full of keys in use, with the released keys that were
+    // evicted while looking for room, which are gone either way. The key's
+    // own slot is freed when it fails too.
+    pub fn acquire(&mut self, key: K, width: u32, height: u32) -> Result<Acquired<K>, Vec<K>> {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            let rect = entry.allocation.rect;
+            if rect.width == width && rect.height == height {
+                entry.released = None;
+                return Ok(Acquired {
+                    allocation: entry.allocation,
+                    is_new: false,
+                    evicted: Vec::new(),
+                });
+            }
+            let allocation = entry.allocation;
+            self.entries.remove(&key);
+            self.free(allocation);
+        }
+
+        let (padded_width, padded_height) = (width + self.padding * 2, height + self.padding * 2);
+        if padded_width > self.page_size || padded_height > self.page_size {
+            return Err(Vec::new());
+        }
+        let mut evicted = Vec::new();
+        let allocation = loop {
+            if let Some(allocation) = self.allocate(padded_width, padded_height) {
+                break allocation;
+            }
+            let Some((oldest, _)) = self
+                .entries
+                .iter()
+                .filter_map(|(key, entry)| Some((key, entry.released?)))
+                .min_by_key(|(_, released)| *released)
+            else {
+                return Err(evicted);
+            };
+            let oldest = oldest.clone();
+            let entry = self.entries.remove(&oldest).unwrap();
+            self.free(entry.allocation);
+            evicted.push(oldest);
+        };
+        let allocation = Allocation {
+            page: allocation.page,
+            rect: AtlasRect {
+                x: allocation.rect.x + self.padding,
+                y: allocation.rect.y + self.padding,
+                width,
+                height,
+            },
+        };
+        self.entries.insert(
+            key,
+            Entry {
+                allocation,
+                released: None,
+            },
+        );
+        Ok(Acquired {
+            allocation,
+            is_new: true,
+            evicted,
+        })
+    }
+
+    // Marks the key's slot as unused, so it can be evicted. Acquiring the
+    // key again at the same size gets the slot back.
+    pub fn release(&mut self, key: &K) {
+        if let Some(entry) = self.entries.get_mut(key)
+            && entry.released.is_none()
+        {
+            self.releases += 1;
+            entry.released = Some(self.releases);
+        }
+    }
+
+    // Finds room for a padded rect: on the shelf closest to its height that
+    // wastes less than half of it, then on a new shelf, then on a new page.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<Allocation> {
+        let mut best: Option<(u32, usize, usize, usize)> = None;
+        for (page_index, page) in self.pages.iter().enumerate() {
+            for (shelf_index, shelf) in page.shelves.iter().enumerate() {
+                if shelf.height < height
+                    || shelf.height > height + height / 2
+                    || best.is_some_and(|(best_height, ..)| best_height <= shelf.height)
+                {
+                    continue;
+                }
+                if let Some(slot) = shelf
+                    .slots
+                    .iter()
+                    .position(|slot| !slot.used && slot.width >= width)
+                {
+                    best = Some((shelf.height, page_index, shelf_index, slot));
+                }
+            }
+        }
+        if let Some((_, page, shelf, slot)) = best {
+            return Some(self.place(page, shelf, slot, width));
+        }
+
+        let page_size = self.page_size;
+        let new_shelf = |page: &mut Page| {
+            (page.bottom + height <= page_size).then(|| {
+                page.shelves.push(Shelf {
+                    top: page.bottom,
+                    height,
+                    slots: vec![Slot {
+                        left: 0,
+                        width: page_size,
+                        used: false,
+                    }],
+                });
+                page.bottom += height;
+                page.shelves.len() - 1
+            })
+        };
+        for page_index in 0..self.pages.len() {
+            if let Some(shelf) = new_shelf(&mut self.pages[page_index]) {
+                return Some(self.place(page_index, shelf, 0, width));
+            }
+        }
+        if self.pages.len() < self.max_pages {
+            let mut page = Page {
+                shelves: Vec::new(),
+                bottom: 0,
+            };
+            let shelf = new_shelf(&mut page)?;
+            self.pages.push(page);
+            return Some(self.place(self.pages.len() - 1, shelf, 0, width));
+        }
+        None
+    }
+
+    // Splits 'width' off the start of a free slot.
+    fn place(&mut self, page: usize, shelf: usize, slot: usize, width: u32) -> Allocation {
+        let shelf_ref = &mut self.pages[page].shelves[shelf];
+        let free = shelf_ref.slots[slot];
+        shelf_ref.slots[slot] = Slot {
+            left: free.left,
+            width,
+            used: true,
+        };
+        if free.width > width {
+            shelf_ref.slots.insert(
+                slot + 1,
+                Slot {
+                    left: free.left + width,
+                    width: free.width - width,
+                    used: false,
+                },
+            );
+        }
+        Allocation {
+            page,
+            rect: AtlasRect {
+                x: free.left,
+                y: shelf_ref.top,
+                width,
+                height: shelf_ref.height,
+            },
+        }
+    }
+
+    // Frees the padded slot around an allocation, merging it with free
+    // neighbors and dropping empty shelves from the bottom of the page.
+    fn free(&mut self, allocation: Allocation) {
+        let page = &mut self.pages[allocation.page];
+        let top = allocation.rect.y - self.padding;
+        let left = allocation.rect.x - self.padding;
+        let Some(shelf) = page.shelves.iter_mut().find(|shelf| shelf.top == top) else {
+            return;
+        };
+        let Some(index) = shelf.slots.iter().position(|slot| slot.left == left) else {
+            return;
+        };
+        shelf.slots[index].used = false;
+        if index + 1 < shelf.slots.len() && !shelf.slots[index + 1].used {
+            shelf.slots[index].width += shelf.slots.remove(index + 1).width;
+        }
+        if index > 0 && !shelf.slots[index - 1].used {
+            let width = shelf.slots.remove(index).width;
+            shelf.slots[index - 1].width += width;
+        }
+        while page
+            .shelves
+            .last()
+            .is_some_and(|shelf| shelf.slots.len() == 1 && !shelf.slots[0].used)
+        {
+            page.bottom -= page.shelves.pop().unwrap().height;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> AtlasRect {
+        AtlasRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn place(
+        atlas: &mut Atlas<&'static str>,
+        key: &'static str,
+        width: u32,
+        height: u32,
+    ) -> Allocation {
+        atlas.acquire(key, width, height).unwrap().allocation
+    }
+
+    #[test]
+    fn rects_are_padded_and_packed_along_a_shelf() {
+        let mut atlas = Atlas::new(100, 1, 1);
+        let first = atlas.acquire("a", 10, 10).unwrap();
+        assert_eq!(
+            first,
+            Acquired {
+                allocation: Allocation {
+                    page: 0,
+                    rect: rect(1, 1, 10, 10),
+                },
+                is_new: true,
+                evicted: Vec::new(),
+            }
+        );
+        assert_eq!(place(&mut atlas, "b", 10, 10).rect, rect(13, 1, 10, 10));
+        // Close enough in height to share the shelf.
+        assert_eq!(place(&mut atlas, "c", 10, 8).rect, rect(25, 1, 10, 8));
+    }
+
+    #[test]
+    fn rects_of_another_height_go_on_a_new_shelf() {
+        let mut atlas = Atlas::new(100, 1, 1);
+        place(&mut atlas, "a", 10, 10);
+        assert_eq!(place(&mut atlas, "b", 10, 20).rect, rect(1, 13, 10, 20));
+        assert_eq!(place(&mut atlas, "c", 10, 4).rect, rect(1, 35, 10, 4));
+        assert_eq!(place(&mut atlas, "d", 10, 10).rect, rect(13, 1, 10, 10));
+    }
+
+    #[test]
+    fn a_key_keeps_its_slot_at_the_same_size() {
+        let mut atlas = Atlas::new(100, 1, 0);
+        let first = place(&mut atlas, "a", 10, 10);
+        atlas.release(&"a");
+        let again = atlas.acquire("a", 10, 10).unwrap();
+        assert_eq!(again.allocation, first);
+        assert!(!again.is_new);
+
+        // Back in use, so it isn't evicted.
+        assert!(atlas.acquire("b", 100, 100).is_err());
+    }
+
+    #[test]
+    fn a_key_at_a_new_size_gets_a_new_slot() {
+        let mut atlas = Atlas::new(100, 1, 0);
+        place(&mut atlas, "a", 10, 10);
+        let resized = atlas.acquire("a", 20, 10).unwrap();
+        assert!(resized.is_new);
+        // The old slot was freed and merged back into the shelf.
+        assert_eq!(resized.allocation.rect, rect(0, 0, 20, 10));
+    }
+
+    #[test]
+    fn rects_bigger_than_a_page_dont_fit() {
+        let mut atlas = Atlas::new(100, 1, 1);
+        assert_eq!(atlas.acquire("a", 99, 10), Err(Vec::new()));
+        assert_eq!(atlas.acquire("a", 10, 100), Err(Vec::new()));
+        assert_eq!(place(&mut atlas, "a", 98, 98).rect, rect(1, 1, 98, 98));
+    }
+
+    #[test]
+    fn pages_are_added_up_to_the_limit() {
+        let mut atlas = Atlas::new(40, 2, 0);
+        assert_eq!(place(&mut atlas, "a", 40, 40).page, 0);
+        assert_eq!(place(&mut atlas, "b", 40, 40).page, 1);
+        assert_eq!(atlas.acquire("c", 10, 10), Err(Vec::new()));
+    }
+
+    #[test]
+    fn the_least_recently_released_slot_is_evicted_first() {
+        let mut atlas = Atlas::new(40, 1, 0);
+        place(&mut atlas, "a", 20, 40);
+        place(&mut atlas, "b", 20, 40);
+        atlas.release(&"b");
+        atlas.release(&"a");
+        // Releasing again doesn't make it more recent.
+        atlas.release(&"b");
+        let acquired = atlas.acquire("c", 20, 40).unwrap();
+        assert_eq!(acquired.evicted, vec!["b"]);
+        assert_eq!(acquired.allocation.rect, rect(20, 0, 20, 40));
+        assert!(!atlas.acquire("a", 20, 40).unwrap().is_new);
+    }
+
+    #[test]
+    fn freed_neighbors_merge_into_one_slot() {
+        let mut atlas = Atlas::new(30, 1, 0);
+        place(&mut atlas, "a", 10, 30);
+        place(&mut atlas, "b", 10, 30);
+        place(&mut atlas, "c", 10, 30);
+        atlas.release(&"a");
+        atlas.release(&"c");
+        atlas.release(&"b");
+        let acquired = atlas.acquire("d", 30, 30).unwrap();
+        assert_eq!(acquired.evicted, vec!["a", "c", "b"]);
+        assert_eq!(acquired.allocation.rect, rect(0, 0, 30, 30));
+    }
+
+    #[test]
+    fn empty_shelves_at_the_bottom_are_dropped() {
+        let mut atlas = Atlas::new(30, 1, 0);
+        place(&mut atlas, "a", 30, 10);
+        place(&mut atlas, "b", 30, 10);
+        atlas.release(&"b");
+        // Too tall for b's shelf, so it only fits once that shelf is gone.
+        let acquired = atlas.acquire("c", 30, 20).unwrap();
+        assert_eq!(acquired.evicted, vec!["b"]);
+        assert_eq!(acquired.allocation.rect, rect(0, 10, 30, 20));
+    }
+
+    #[test]
+    fn failing_to_acquire_reports_what_it_evicted() {
+        let mut atlas = Atlas::new(40, 1, 0);
+        place(&mut atlas, "a", 20, 40);
+        place(&mut atlas, "b", 20, 40);
+        atlas.release(&"b");
+        assert_eq!(atlas.acquire("c", 30, 40), Err(vec!["b"]));
+        // b's slot is really gone.
+        assert!(atlas.acquire("b", 20, 40).unwrap().is_new);
+    }
+}
@@ -0,0 +1,1431 @@
+use std::ops::Range;
+
+use crate::unicode::find_range;
+
+// Characters' Bidi_Class values, named after their abbreviations in the
+// Unicode Bidirectional Algorithm (UAX #9).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BidiClass {
+    L,
+    R,
+    Al,
+    En,
+    Es,
+    Et,
+    An,
+    Cs,
+    Nsm,
+    Bn,
+    B,
+    S,
+    Ws,
+    On,
+    Lre,
+    Lro,
+    Rle,
+    Rlo,
+    Pdf,
+    Lri,
+    Rli,
+    Fsi,
+    Pdi,
+}
+
+use BidiClass::*;
+
+// How deep embeddings and isolates can nest, and how many brackets can be
+// open at once while pairing them.
+const MAX_DEPTH: u8 = 125;
+const MAX_BRACKET_DEPTH: usize = 63;
+
+pub fn bidi_class(character: char) -> BidiClass {
+    find_range(BIDI_CLASSES, character, |&(start, end, _)| (start, end))
+        .map_or(L, |&(_, _, class)| class)
+}
+
+// The level of the text's first strong character, skipping isolates, or 0
+// if it has none. This is rule P2 run over all of the text rather than its
+// first paragraph, since DirectWrite lays out a visual's paragraphs in a
+// single direction.
+pub fn base_level(text: &str) -> u8 {
+    let classes: Vec<BidiClass> = text.chars().map(bidi_class).collect();
+    first_strong(&classes).unwrap_or(0)
+}
+
+// Every character's embedding level after running the BiDi algorithm over
+// some text, where odd levels read right to left. Indices are in chars.
+pub struct BidiLevels {
+    classes: Vec<BidiClass>,
+    levels: Vec<u8>,
+    // Where each paragraph ends and its level.
+    paragraphs: Vec<(usize, u8)>,
+}
+
+impl BidiLevels {
+    // Rules P1 through I2. Each paragraph gets 'base_level', or the level
+    // of its first strong character when it's None.
+    pub fn new(text: &str, base_level: Option<u8>) -> Self {
+        let characters: Vec<char> = text.chars().collect();
+        let classes: Vec<BidiClass> = characters.iter().copied().map(bidi_class).collect();
+        let mut levels = vec![0; classes.len()];
+        let mut paragraphs = Vec::new();
+        let mut start = 0;
+        while start < classes.len() {
+            let end = classes[start..]
+                .iter()
+                .position(|&class| class == B)
+                .map_or(classes.len(), |index| start + index + 1);
+            let level =
+                base_level.unwrap_or_else(|| first_strong(&classes[start..end]).unwrap_or(0));
+            resolve_paragraph(
+                &characters[start..end],
+                &classes[start..end],
+                level,
+                &mut levels[start..end],
+            );
+            paragraphs.push((end, level));
+            start = end;
+        }
+        Self {
+            classes,
+            levels,
+            paragraphs,
+        }
+    }
+
+    // The levels of a line of the text after rule L1, which puts separators
+    // and the whitespace before them and at the end of the line back at the
+    // paragraph's level.
+    pub fn line_levels(&self, line: Range<usize>) -> Vec<u8> {
+        let paragraph_level = self
+            .paragraphs
+            .iter()
+            .find(|(end, _)| *end > line.start)
+            .map_or(0, |(_, level)| *level);
+        let mut levels = self.levels[line.clone()].to_vec();
+        let mut trailing = true;
+        for (level, class) in levels.iter_mut().zip(&self.classes[line]).rev() {
+            match class {
+                S | B => {
+                    *level = paragraph_level;
+                    trailing = true;
+                }
+                Ws | Lri | Rli | Fsi | Pdi if trailing => *level = paragraph_level,
+                class if is_removed(*class) && trailing => *level = paragraph_level,
+                _ => trailing = false,
+            }
+        }
+        levels
+    }
+}
+
+// Classes X9 removes, which get the level of the character before them.
+fn is_removed(class: BidiClass) -> bool {
+    matches!(class, Lre | Lro | Rle | Rlo | Pdf | Bn)
+}
+
+fn direction(level: u8) -> BidiClass {
+    if level.is_multiple_of(2) { L } else { R }
+}
+
+// The least level above 'level' with the given direction.
+fn next_level(level: u8, right_to_left: bool) -> u8 {
+    if right_to_left {
+        (level + 1) | 1
+    } else {
+        (level + 2) & !1
+    }
+}
+
+// Rules P2 and P3: the level of the first strong character outside of
+// isolates, if there is one.
+fn first_strong(classes: &[BidiClass]) -> Option<u8> {
+    let mut depth = 0;
+    for class in classes {
+        match class {
+            L if depth == 0 => return Some(0),
+            R | Al if depth == 0 => return Some(1),
+            Lri | Rli | Fsi => depth += 1,
+            Pdi if depth > 0 => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// BD9: the index of the PDI closing each isolate initiator, if it has one.
+fn matching_pdis(classes: &[BidiClass]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; classes.len()];
+    let mut open = Vec::new();
+    for (index, class) in classes.iter().enumerate() {
+        match class {
+            Lri | Rli | Fsi => open.push(index),
+            Pdi => {
+                if let Some(initiator) = open.pop() {
+                    matches[initiator] = Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    matches
+}
+
+#[derive(Copy, Clone)]
+struct Status {
+    level: u8,
+    override_class: Option<BidiClass>,
+    isolate: bool,
+}
+
+// A run of characters at the same level, continued past any isolates in
+// it, which the weak and neutral rules resolve as one sequence.
+struct Sequence {
+    indices: Vec<usize>,
+    level: u8,
+    sos: BidiClass,
+    eos: BidiClass,
+}
+
+fn resolve_paragraph(
+    characters: &[char],
+    original: &[BidiClass],
+    paragraph_level: u8,
+    levels: &mut [u8],
+) {
+    let matching_pdis = matching_pdis(original);
+    let mut classes = original.to_vec();
+    explicit_levels(
+        original,
+        &matching_pdis,
+        paragraph_level,
+        &mut classes,
+        levels,
+    );
+    for sequence in isolating_run_sequences(original, &matching_pdis, paragraph_level, levels) {
+        resolve_weak(&sequence, &mut classes);
+        resolve_brackets(&sequence, characters, original, &mut classes);
+        resolve_neutral(&sequence, &mut classes);
+    }
+
+    // I1 and I2.
+    for index in 0..original.len() {
+        if is_removed(original[index]) {
+            levels[index] = if index == 0 {
+                paragraph_level
+            } else {
+                levels[index - 1]
+            };
+            continue;
+        }
+        levels[index] += match (levels[index] % 2, classes[index]) {
+            (0, R) => 1,
+            (0, An | En) => 2,
+            (1, L | En | An) => 1,
+            _ => 0,
+        };
+    }
+}
+
+// X1 through X8: levels from embeddings, overrides and isolates. Overrides
+// also change the classes of the characters they cover.
+fn explicit_levels(
+    original: &[BidiClass],
+    matching_pdis: &[Option<usize>],
+    paragraph_level: u8,
+    classes: &mut [BidiClass],
+    levels: &mut [u8],
+) {
+    let mut stack = vec![Status {
+        level: paragraph_level,
+        override_class: None,
+        isolate: false,
+    }];
+    let mut overflow_isolates = 0;
+    let mut overflow_embeddings = 0;
+    let mut valid_isolates = 0;
+    for index in 0..original.len() {
+        let last = *stack.last().unwrap();
+        match original[index] {
+            class @ (Rle | Lre | Rlo | Lro) => {
+                levels[index] = last.level;
+                let level = next_level(last.level, matches!(class, Rle | Rlo));
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    stack.push(Status {
+                        level,
+                        override_class: match class {
+                            Rlo => Some(R),
+                            Lro => Some(L),
+                            _ => None,
+                        },
+                        isolate: false,
+                    });
+                } else if overflow_isolates == 0 {
+                    overflow_embeddings += 1;
+                }
+            }
+            class @ (Rli | Lri | Fsi) => {
+                levels[index] = last.level;
+                if let Some(override_class) = last.override_class {
+                    classes[index] = override_class;
+                }
+                let right_to_left = match class {
+                    Rli => true,
+                    Lri => false,
+                    _ => {
+                        let end = matching_pdis[index].unwrap_or(original.len());
+                        first_strong(&original[index + 1..end]) == Some(1)
+                    }
+                };
+                let level = next_level(last.level, right_to_left);
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    valid_isolates += 1;
+                    stack.push(Status {
+                        level,
+                        override_class: None,
+                        isolate: true,
+                    });
+                } else {
+                    overflow_isolates += 1;
+                }
+            }
+            Pdi => {
+                let matched = overflow_isolates > 0 || valid_isolates > 0;
+                if overflow_isolates > 0 {
+                    overflow_isolates -= 1;
+                } else if valid_isolates > 0 {
+                    overflow_embeddings = 0;
+                    while !stack.last().unwrap().isolate {
+                        stack.pop();
+                    }
+                    stack.pop();
+                    valid_isolates -= 1;
+                }
+                let last = *stack.last().unwrap();
+                levels[index] = last.level;
+                // An unmatched PDI stays a neutral, like ICU and FriBidi
+                // treat it.
+                if let Some(override_class) = last.override_class
+                    && matched
+                {
+                    classes[index] = override_class;
+                }
+            }
+            Pdf => {
+                levels[index] = last.level;
+                if overflow_isolates == 0 {
+                    if overflow_embeddings > 0 {
+                        overflow_embeddings -= 1;
+                    } else if !last.isolate && stack.len() >= 2 {
+                        stack.pop();
+                    }
+                }
+            }
+            B => levels[index] = paragraph_level,
+            Bn => levels[index] = last.level,
+            _ => {
+                levels[index] = last.level;
+                if let Some(override_class) = last.override_class {
+                    classes[index] = override_class;
+                }
+            }
+        }
+    }
+}
+
+// X10: splits what X9 keeps into level runs and links the runs that end
+// with an isolate initiator to the run starting with its matching PDI.
+fn isolating_run_sequences(
+    original: &[BidiClass],
+    matching_pdis: &[Option<usize>],
+    paragraph_level: u8,
+    levels: &[u8],
+) -> Vec<Sequence> {
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    for index in (0..original.len()).filter(|&index| !is_removed(original[index])) {
+        match runs.last_mut() {
+            Some(run) if levels[run[0]] == levels[index] => run.push(index),
+            _ => runs.push(vec![index]),
+        }
+    }
+
+    let mut linked = vec![false; runs.len()];
+    let mut sequences = Vec::new();
+    for start in 0..runs.len() {
+        if linked[start] {
+            continue;
+        }
+        let mut indices = runs[start].clone();
+        while let Some(pdi) = matching_pdis[*indices.last().unwrap()] {
+            let Some(next) = runs.iter().position(|run| run[0] == pdi) else {
+                break;
+            };
+            linked[next] = true;
+            indices.extend(&runs[next]);
+        }
+
+        let (first, last) = (indices[0], *indices.last().unwrap());
+        let level = levels[first];
+        let before = (0..first)
+            .rev()
+            .find(|&index| !is_removed(original[index]))
+            .map_or(paragraph_level, |index| levels[index]);
+        let after = if matches!(original[last], Lri | Rli | Fsi) {
+            paragraph_level
+        } else {
+            (last + 1..original.len())
+                .find(|&index| !is_removed(original[index]))
+                .map_or(paragraph_level, |index| levels[index])
+        };
+        sequences.push(Sequence {
+            indices,
+            level,
+            sos: direction(level.max(before)),
+            eos: direction(level.max(after)),
+        });
+    }
+    sequences
+}
+
+// W1 through W7.
+fn resolve_weak(sequence: &Sequence, classes: &mut [BidiClass]) {
+    let indices = &sequence.indices;
+
+    let mut previous = sequence.sos;
+    for &index in indices {
+        if classes[index] == Nsm {
+            classes[index] = match previous {
+                Lri | Rli | Fsi | Pdi => On,
+                previous => previous,
+            };
+        }
+        previous = classes[index];
+    }
+
+    let mut last_strong = sequence.sos;
+    for &index in indices {
+        match classes[index] {
+            class @ (L | R | Al) => last_strong = class,
+            En if last_strong == Al => classes[index] = An,
+            _ => {}
+        }
+    }
+    for &index in indices {
+        if classes[index] == Al {
+            classes[index] = R;
+        }
+    }
+
+    for position in 1..indices.len().saturating_sub(1) {
+        let (previous, current, next) = (
+            classes[indices[position - 1]],
+            classes[indices[position]],
+            classes[indices[position + 1]],
+        );
+        classes[indices[position]] = match (previous, current, next) {
+            (En, Es | Cs, En) => En,
+            (An, Cs, An) => An,
+            _ => continue,
+        };
+    }
+
+    let mut position = 0;
+    while position < indices.len() {
+        if classes[indices[position]] != Et {
+            position += 1;
+            continue;
+        }
+        let end = position
+            + indices[position..]
+                .iter()
+                .take_while(|&&index| classes[index] == Et)
+                .count();
+        if (position > 0 && classes[indices[position - 1]] == En)
+            || (end < indices.len() && classes[indices[end]] == En)
+        {
+            for &index in &indices[position..end] {
+                classes[index] = En;
+            }
+        }
+        position = end;
+    }
+
+    for &index in indices {
+        if matches!(classes[index], Es | Et | Cs) {
+            classes[index] = On;
+        }
+    }
+
+    let mut last_strong = sequence.sos;
+    for &index in indices {
+        match classes[index] {
+            class @ (L | R) => last_strong = class,
+            En if last_strong == L => classes[index] = L,
+            _ => {}
+        }
+    }
+}
+
+// The direction a resolved class counts as for the neutral rules, where
+// numbers count as right to left.
+fn strong_direction(class: BidiClass) -> Option<BidiClass> {
+    match class {
+        L => Some(L),
+        R | En | An => Some(R),
+        _ => None,
+    }
+}
+
+// N0: pairs of brackets take the direction of the text inside them, so
+// the brackets in "(abc)" face the same way as the letters.
+fn resolve_brackets(
+    sequence: &Sequence,
+    characters: &[char],
+    original: &[BidiClass],
+    classes: &mut [BidiClass],
+) {
+    let indices = &sequence.indices;
+    let mut pairs = Vec::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    for (position, &index) in indices.iter().enumerate() {
+        if classes[index] != On {
+            continue;
+        }
+        let character = canonical_bracket(characters[index]);
+        if let Some(&(_, closing)) = BRACKETS.iter().find(|(opening, _)| *opening == character) {
+            if open.len() == MAX_BRACKET_DEPTH {
+                break;
+            }
+            open.push((canonical_bracket(closing), position));
+        } else if let Some(depth) = open.iter().rposition(|&(closing, _)| closing == character) {
+            pairs.push((open[depth].1, position));
+            open.truncate(depth);
+        }
+    }
+    pairs.sort_unstable();
+
+    let embedding = direction(sequence.level);
+    for (opening, closing) in pairs {
+        let mut inside = None;
+        for &index in &indices[opening + 1..closing] {
+            match strong_direction(classes[index]) {
+                Some(class) if class == embedding => {
+                    inside = Some(class);
+                    break;
+                }
+                Some(class) => inside = Some(class),
+                None => {}
+            }
+        }
+        let resolved = match inside {
+            Some(class) if class == embedding => embedding,
+            Some(opposite) => {
+                let before = indices[..opening]
+                    .iter()
+                    .rev()
+                    .find_map(|&index| strong_direction(classes[index]))
+                    .unwrap_or(sequence.sos);
+                if before == opposite {
+                    opposite
+                } else {
+                    embedding
+                }
+            }
+            None => continue,
+        };
+        for position in [opening, closing] {
+            classes[indices[position]] = resolved;
+            // Marks on a bracket were given its class by W1.
+            for &index in &indices[position + 1..] {
+                if original[index] != Nsm {
+                    break;
+                }
+                classes[index] = resolved;
+            }
+        }
+    }
+}
+
+// U+2329 and U+232A are canonically equivalent to U+3008 and U+3009 and
+// pair with them.
+fn canonical_bracket(character: char) -> char {
+    match character {
+        '\u{2329}' => '\u{3008}',
+        '\u{232A}' => '\u{3009}',
+        character => character,
+    }
+}
+
+// N1 and N2: runs of neutrals between text of the same direction take that
+// direction, and the rest take the embedding direction.
+fn resolve_neutral(sequence: &Sequence, classes: &mut [BidiClass]) {
+    let indices = &sequence.indices;
+    let is_neutral = |class: BidiClass| matches!(class, B | S | Ws | On | Lri | Rli | Fsi | Pdi);
+    let mut position = 0;
+    while position < indices.len() {
+        if !is_neutral(classes[indices[position]]) {
+            position += 1;
+            continue;
+        }
+        let end = position
+            + indices[position..]
+                .iter()
+                .take_while(|&&index| is_neutral(classes[index]))
+                .count();
+        let before = match position {
+            0 => sequence.sos,
+            _ => strong_direction(classes[indices[position - 1]]).unwrap_or(L),
+        };
+        let after = match indices.get(end) {
+            Some(&index) => strong_direction(classes[index]).unwrap_or(L),
+            None => sequence.eos,
+        };
+        let resolved = if before == after {
+            before
+        } else {
+            direction(sequence.level)
+        };
+        for &index in &indices[position..end] {
+            classes[index] = resolved;
+        }
+        position = end;
+    }
+}
+
+// Bidi_Paired_Bracket pairs as of Unicode 14, by opening bracket.
+const BRACKETS: &[(char, char)] = &[
+    ('\u{0028}', '\u{0029}'),
+    ('\u{005B}', '\u{005D}'),
+    ('\u{007B}', '\u{007D}'),
+    ('\u{0F3A}', '\u{0F3B}'),
+    ('\u{0F3C}', '\u{0F3D}'),
+    ('\u{169B}', '\u{169C}'),
+    ('\u{2045}', '\u{2046}'),
+    ('\u{207D}', '\u{207E}'),
+    ('\u{208D}', '\u{208E}'),
+    ('\u{2308}', '\u{2309}'),
+    ('\u{230A}', '\u{230B}'),
+    ('\u{2329}', '\u{232A}'),
+    ('\u{2768}', '\u{2769}'),
+    ('\u{276A}', '\u{276B}'),
+    ('\u{276C}', '\u{276D}'),
+    ('\u{276E}', '\u{276F}'),
+    ('\u{2770}', '\u{2771}'),
+    ('\u{2772}', '\u{2773}'),
+    ('\u{2774}', '\u{2775}'),
+    ('\u{27C5}', '\u{27C6}'),
+    ('\u{27E6}', '\u{27E7}'),
+    ('\u{27E8}', '\u{27E9}'),
+    ('\u{27EA}', '\u{27EB}'),
+    ('\u{27EC}', '\u{27ED}'),
+    ('\u{27EE}', '\u{27EF}'),
+    ('\u{2983}', '\u{2984}'),
+    ('\u{2985}', '\u{2986}'),
+    ('\u{2987}', '\u{2988}'),
+    ('\u{2989}', '\u{298A}'),
+    ('\u{298B}', '\u{298C}'),
+    ('\u{298D}', '\u{2990}'),
+    ('\u{298F}', '\u{298E}'),
+    ('\u{2991}', '\u{2992}'),
+    ('\u{2993}', '\u{2994}'),
+    ('\u{2995}', '\u{2996}'),
+    ('\u{2997}', '\u{2998}'),
+    ('\u{29D8}', '\u{29D9}'),
+    ('\u{29DA}', '\u{29DB}'),
+    ('\u{29FC}', '\u{29FD}'),
+    ('\u{2E22}', '\u{2E23}'),
+    ('\u{2E24}', '\u{2E25}'),
+    ('\u{2E26}', '\u{2E27}'),
+    ('\u{2E28}', '\u{2E29}'),
+    ('\u{2E55}', '\u{2E56}'),
+    ('\u{2E57}', '\u{2E58}'),
+    ('\u{2E59}', '\u{2E5A}'),
+    ('\u{2E5B}', '\u{2E5C}'),
+    ('\u{3008}', '\u{3009}'),
+    ('\u{300A}', '\u{300B}'),
+    ('\u{300C}', '\u{300D}'),
+    ('\u{300E}', '\u{300F}'),
+    ('\u{3010}', '\u{3011}'),
+    ('\u{3014}', '\u{3015}'),
+    ('\u{3016}', '\u{3017}'),
+    ('\u{3018}', '\u{3019}'),
+    ('\u{301A}', '\u{301B}'),
+    ('\u{FE59}', '\u{FE5A}'),
+    ('\u{FE5B}', '\u{FE5C}'),
+    ('\u{FE5D}', '\u{FE5E}'),
+    ('\u{FF08}', '\u{FF09}'),
+    ('\u{FF3B}', '\u{FF3D}'),
+    ('\u{FF5B}', '\u{FF5D}'),
+    ('\u{FF5F}', '\u{FF60}'),
+    ('\u{FF62}', '\u{FF63}'),
+];
+
+// Code points whose Bidi_Class isn't L as of Unicode 14, which is what the
+// rest default to. Ranges run over unassigned code points between ones of
+// the same class.
+const BIDI_CLASSES: &[(u32, u32, BidiClass)] = &[
+    (0x0000, 0x0008, Bn),
+    (0x0009, 0x0009, S),
+    (0x000A, 0x000A, B),
+    (0x000B, 0x000B, S),
+    (0x000C, 0x000C, Ws),
+    (0x000D, 0x000D, B),
+    (0x000E, 0x001B, Bn),
+    (0x001C, 0x001E, B),
+    (0x001F, 0x001F, S),
+    (0x0020, 0x0020, Ws),
+    (0x0021, 0x0022, On),
+    (0x0023, 0x0025, Et),
+    (0x0026, 0x002A, On),
+    (0x002B, 0x002B, Es),
+    (0x002C, 0x002C, Cs),
+    (0x002D, 0x002D, Es),
+    (0x002E, 0x002F, Cs),
+    (0x0030, 0x0039, En),
+    (0x003A, 0x003A, Cs),
+    (0x003B, 0x0040, On),
+    (0x005B, 0x0060, On),
+    (0x007B, 0x007E, On),
+    (0x007F, 0x0084, Bn),
+    (0x0085, 0x0085, B),
+    (0x0086, 0x009F, Bn),
+    (0x00A0, 0x00A0, Cs),
+    (0x00A1, 0x00A1, On),
+    (0x00A2, 0x00A5, Et),
+    (0x00A6, 0x00A9, On),
+    (0x00AB, 0x00AC, On),
+    (0x00AD, 0x00AD, Bn),
+    (0x00AE, 0x00AF, On),
+    (0x00B0, 0x00B1, Et),
+    (0x00B2, 0x00B3, En),
+    (0x00B4, 0x00B4, On),
+    (0x00B6, 0x00B8, On),
+    (0x00B9, 0x00B9, En),
+    (0x00BB, 0x00BF, On),
+    (0x00D7, 0x00D7, On),
+    (0x00F7, 0x00F7, On),
+    (0x02B9, 0x02BA, On),
+    (0x02C2, 0x02CF, On),
+    (0x02D2, 0x02DF, On),
+    (0x02E5, 0x02ED, On),
+    (0x02EF, 0x02FF, On),
+    (0x0300, 0x036F, Nsm),
+    (0x0374, 0x0375, On),
+    (0x037E, 0x037E, On),
+    (0x0384, 0x0385, On),
+    (0x0387, 0x0387, On),
+    (0x03F6, 0x03F6, On),
+    (0x0483, 0x0489, Nsm),
+    (0x058A, 0x058E, On),
+    (0x058F, 0x058F, Et),
+    (0x0591, 0x05BD, Nsm),
+    (0x05BE, 0x05BE, R),
+    (0x05BF, 0x05BF, Nsm),
+    (0x05C0, 0x05C0, R),
+    (0x05C1, 0x05C2, Nsm),
+    (0x05C3, 0x05C3, R),
+    (0x05C4, 0x05C5, Nsm),
+    (0x05C6, 0x05C6, R),
+    (0x05C7, 0x05C7, Nsm),
+    (0x05D0, 0x05F4, R),
+    (0x0600, 0x0605, An),
+    (0x0606, 0x0607, On),
+    (0x0608, 0x0608, Al),
+    (0x0609, 0x060A, Et),
+    (0x060B, 0x060B, Al),
+    (0x060C, 0x060C, Cs),
+    (0x060D, 0x060D, Al),
+    (0x060E, 0x060F, On),
+    (0x0610, 0x061A, Nsm),
+    (0x061B, 0x064A, Al),
+    (0x064B, 0x065F, Nsm),
+    (0x0660, 0x0669, An),
+    (0x066A, 0x066A, Et),
+    (0x066B, 0x066C, An),
+    (0x066D, 0x066F, Al),
+    (0x0670, 0x0670, Nsm),
+    (0x0671, 0x06D5, Al),
+    (0x06D6, 0x06DC, Nsm),
+    (0x06DD, 0x06DD, An),
+    (0x06DE, 0x06DE, On),
+    (0x06DF, 0x06E4, Nsm),
+    (0x06E5, 0x06E6, Al),
+    (0x06E7, 0x06E8, Nsm),
+    (0x06E9, 0x06E9, On),
+    (0x06EA, 0x06ED, Nsm),
+    (0x06EE, 0x06EF, Al),
+    (0x06F0, 0x06F9, En),
+    (0x06FA, 0x0710, Al),
+    (0x0711, 0x0711, Nsm),
+    (0x0712, 0x072F, Al),
+    (0x0730, 0x074A, Nsm),
+    (0x074D, 0x07A5, Al),
+    (0x07A6, 0x07B0, Nsm),
+    (0x07B1, 0x07B1, Al),
+    (0x07C0, 0x07EA, R),
+    (0x07EB, 0x07F3, Nsm),
+    (0x07F4, 0x07F5, R),
+    (0x07F6, 0x07F9, On),
+    (0x07FA, 0x07FA, R),
+    (0x07FD, 0x07FD, Nsm),
+    (0x07FE, 0x0815, R),
+    (0x0816, 0x0819, Nsm),
+    (0x081A, 0x081A, R),
+    (0x081B, 0x0823, Nsm),
+    (0x0824, 0x0824, R),
+    (0x0825, 0x0827, Nsm),
+    (0x0828, 0x0828, R),
+    (0x0829, 0x082D, Nsm),
+    (0x0830, 0x0858, R),
+    (0x0859, 0x085B, Nsm),
+    (0x085E, 0x085E, R),
+    (0x0860, 0x088E, Al),
+    (0x0890, 0x0891, An),
+    (0x0898, 0x089F, Nsm),
+    (0x08A0, 0x08C9, Al),
+    (0x08CA, 0x08E1, Nsm),
+    (0x08E2, 0x08E2, An),
+    (0x08E3, 0x0902, Nsm),
+    (0x093A, 0x093A, Nsm),
+    (0x093C, 0x093C, Nsm),
+    (0x0941, 0x0948, Nsm),
+    (0x094D, 0x094D, Nsm),
+    (0x0951, 0x0957, Nsm),
+    (0x0962, 0x0963, Nsm),
+    (0x0981, 0x0981, Nsm),
+    (0x09BC, 0x09BC, Nsm),
+    (0x09C1, 0x09C4, Nsm),
+    (0x09CD, 0x09CD, Nsm),
+    (0x09E2, 0x09E3, Nsm),
+    (0x09F2, 0x09F3, Et),
+    (0x09FB, 0x09FB, Et),
+    (0x09FE, 0x0A02, Nsm),
+    (0x0A3C, 0x0A3C, Nsm),
+    (0x0A41, 0x0A51, Nsm),
+    (0x0A70, 0x0A71, Nsm),
+    (0x0A75, 0x0A75, Nsm),
+    (0x0A81, 0x0A82, Nsm),
+    (0x0ABC, 0x0ABC, Nsm),
+    (0x0AC1, 0x0AC8, Nsm),
+    (0x0ACD, 0x0ACD, Nsm),
+    (0x0AE2, 0x0AE3, Nsm),
+    (0x0AF1, 0x0AF1, Et),
+    (0x0AFA, 0x0B01, Nsm),
+    (0x0B3C, 0x0B3C, Nsm),
+    (0x0B3F, 0x0B3F, Nsm),
+    (0x0B41, 0x0B44, Nsm),
+    (0x0B4D, 0x0B56, Nsm),
+    (0x0B62, 0x0B63, Nsm),
+    (0x0B82, 0x0B82, Nsm),
+    (0x0BC0, 0x0BC0, Nsm),
+    (0x0BCD, 0x0BCD, Nsm),
+    (0x0BF3, 0x0BF8, On),
+    (0x0BF9, 0x0BF9, Et),
+    (0x0BFA, 0x0BFA, On),
+    (0x0C00, 0x0C00, Nsm),
+    (0x0C04, 0x0C04, Nsm),
+    (0x0C3C, 0x0C3C, Nsm),
+    (0x0C3E, 0x0C40, Nsm),
+    (0x0C46, 0x0C56, Nsm),
+    (0x0C62, 0x0C63, Nsm),
+    (0x0C78, 0x0C7E, On),
+    (0x0C81, 0x0C81, Nsm),
+    (0x0CBC, 0x0CBC, Nsm),
+    (0x0CCC, 0x0CCD, Nsm),
+    (0x0CE2, 0x0CE3, Nsm),
+    (0x0D00, 0x0D01, Nsm),
+    (0x0D3B, 0x0D3C, Nsm),
+    (0x0D41, 0x0D44, Nsm),
+    (0x0D4D, 0x0D4D, Nsm),
+    (0x0D62, 0x0D63, Nsm),
+    (0x0D81, 0x0D81, Nsm),
+    (0x0DCA, 0x0DCA, Nsm),
+    (0x0DD2, 0x0DD6, Nsm),
+    (0x0E31, 0x0E31, Nsm),
+    (0x0E34, 0x0E3A, Nsm),
+    (0x0E3F, 0x0E3F, Et),
+    (0x0E47, 0x0E4E, Nsm),
+    (0x0EB1, 0x0EB1, Nsm),
+    (0x0EB4, 0x0EBC, Nsm),
+    (0x0EC8, 0x0ECD, Nsm),
+    (0x0F18, 0x0F19, Nsm),
+    (0x0F35, 0x0F35, Nsm),
+    (0x0F37, 0x0F37, Nsm),
+    (0x0F39, 0x0F39, Nsm),
+    (0x0F3A, 0x0F3D, On),
+    (0x0F71, 0x0F7E, Nsm),
+    (0x0F80, 0x0F84, Nsm),
+    (0x0F86, 0x0F87, Nsm),
+    (0x0F8D, 0x0FBC, Nsm),
+    (0x0FC6, 0x0FC6, Nsm),
+    (0x102D, 0x1030, Nsm),
+    (0x1032, 0x1037, Nsm),
+    (0x1039, 0x103A, Nsm),
+    (0x103D, 0x103E, Nsm),
+    (0x1058, 0x1059, Nsm),
+    (0x105E, 0x1060, Nsm),
+    (0x1071, 0x1074, Nsm),
+    (0x1082, 0x1082, Nsm),
+    (0x1085, 0x1086, Nsm),
+    (0x108D, 0x108D, Nsm),
+    (0x109D, 0x109D, Nsm),
+    (0x135D, 0x135F, Nsm),
+    (0x1390, 0x1399, On),
+    (0x1400, 0x1400, On),
+    (0x1680, 0x1680, Ws),
+    (0x169B, 0x169C, On),
+    (0x1712, 0x1714, Nsm),
+    (0x1732, 0x1733, Nsm),
+    (0x1752, 0x1753, Nsm),
+    (0x1772, 0x1773, Nsm),
+    (0x17B4, 0x17B5, Nsm),
+    (0x17B7, 0x17BD, Nsm),
+    (0x17C6, 0x17C6, Nsm),
+    (0x17C9, 0x17D3, Nsm),
+    (0x17DB, 0x17DB, Et),
+    (0x17DD, 0x17DD, Nsm),
+    (0x17F0, 0x180A, On),
+    (0x180B, 0x180D, Nsm),
+    (0x180E, 0x180E, Bn),
+    (0x180F, 0x180F, Nsm),
+    (0x1885, 0x1886, Nsm),
+    (0x18A9, 0x18A9, Nsm),
+    (0x1920, 0x1922, Nsm),
+    (0x1927, 0x1928, Nsm),
+    (0x1932, 0x1932, Nsm),
+    (0x1939, 0x193B, Nsm),
+    (0x1940, 0x1945, On),
+    (0x19DE, 0x19FF, On),
+    (0x1A17, 0x1A18, Nsm),
+    (0x1A1B, 0x1A1B, Nsm),
+    (0x1A56, 0x1A56, Nsm),
+    (0x1A58, 0x1A60, Nsm),
+    (0x1A62, 0x1A62, Nsm),
+    (0x1A65, 0x1A6C, Nsm),
+    (0x1A73, 0x1A7F, Nsm),
+    (0x1AB0, 0x1B03, Nsm),
+    (0x1B34, 0x1B34, Nsm),
+    (0x1B36, 0x1B3A, Nsm),
+    (0x1B3C, 0x1B3C, Nsm),
+    (0x1B42, 0x1B42, Nsm),
+    (0x1B6B, 0x1B73, Nsm),
+    (0x1B80, 0x1B81, Nsm),
+    (0x1BA2, 0x1BA5, Nsm),
+    (0x1BA8, 0x1BA9, Nsm),
+    (0x1BAB, 0x1BAD, Nsm),
+    (0x1BE6, 0x1BE6, Nsm),
+    (0x1BE8, 0x1BE9, Nsm),
+    (0x1BED, 0x1BED, Nsm),
+    (0x1BEF, 0x1BF1, Nsm),
+    (0x1C2C, 0x1C33, Nsm),
+    (0x1C36, 0x1C37, Nsm),
+    (0x1CD0, 0x1CD2, Nsm),
+    (0x1CD4, 0x1CE0, Nsm),
+    (0x1CE2, 0x1CE8, Nsm),
+    (0x1CED, 0x1CED, Nsm),
+    (0x1CF4, 0x1CF4, Nsm),
+    (0x1CF8, 0x1CF9, Nsm),
+    (0x1DC0, 0x1DFF, Nsm),
+    (0x1FBD, 0x1FBD, On),
+    (0x1FBF, 0x1FC1, On),
+    (0x1FCD, 0x1FCF, On),
+    (0x1FDD, 0x1FDF, On),
+    (0x1FED, 0x1FEF, On),
+    (0x1FFD, 0x1FFE, On),
+    (0x2000, 0x200A, Ws),
+    (0x200B, 0x200D, Bn),
+    (0x200F, 0x200F, R),
+    (0x2010, 0x2027, On),
+    (0x2028, 0x2028, Ws),
+    (0x2029, 0x2029, B),
+    (0x202A, 0x202A, Lre),
+    (0x202B, 0x202B, Rle),
+    (0x202C, 0x202C, Pdf),
+    (0x202D, 0x202D, Lro),
+    (0x202E, 0x202E, Rlo),
+    (0x202F, 0x202F, Cs),
+    (0x2030, 0x2034, Et),
+    (0x2035, 0x2043, On),
+    (0x2044, 0x2044, Cs),
+    (0x2045, 0x205E, On),
+    (0x205F, 0x205F, Ws),
+    (0x2060, 0x2064, Bn),
+    (0x2066, 0x2066, Lri),
+    (0x2067, 0x2067, Rli),
+    (0x2068, 0x2068, Fsi),
+    (0x2069, 0x2069, Pdi),
+    (0x206A, 0x206F, Bn),
+    (0x2070, 0x2070, En),
+    (0x2074, 0x2079, En),
+    (0x207A, 0x207B, Es),
+    (0x207C, 0x207E, On),
+    (0x2080, 0x2089, En),
+    (0x208A, 0x208B, Es),
+    (0x208C, 0x208E, On),
+    (0x20A0, 0x20C0, Et),
+    (0x20D0, 0x20F0, Nsm),
+    (0x2100, 0x2101, On),
+    (0x2103, 0x2106, On),
+    (0x2108, 0x2109, On),
+    (0x2114, 0x2114, On),
+    (0x2116, 0x2118, On),
+    (0x211E, 0x2123, On),
+    (0x2125, 0x2125, On),
+    (0x2127, 0x2127, On),
+    (0x2129, 0x2129, On),
+    (0x212E, 0x212E, Et),
+    (0x213A, 0x213B, On),
+    (0x2140, 0x2144, On),
+    (0x214A, 0x214D, On),
+    (0x2150, 0x215F, On),
+    (0x2189, 0x2211, On),
+    (0x2212, 0x2212, Es),
+    (0x2213, 0x2213, Et),
+    (0x2214, 0x2335, On),
+    (0x237B, 0x2394, On),
+    (0x2396, 0x2487, On),
+    (0x2488, 0x249B, En),
+    (0x24EA, 0x26AB, On),
+    (0x26AD, 0x27FF, On),
+    (0x2900, 0x2BFF, On),
+    (0x2CE5, 0x2CEA, On),
+    (0x2CEF, 0x2CF1, Nsm),
+    (0x2CF9, 0x2CFF, On),
+    (0x2D7F, 0x2D7F, Nsm),
+    (0x2DE0, 0x2DFF, Nsm),
+    (0x2E00, 0x2FFB, On),
+    (0x3000, 0x3000, Ws),
+    (0x3001, 0x3004, On),
+    (0x3008, 0x3020, On),
+    (0x302A, 0x302D, Nsm),
+    (0x3030, 0x3030, On),
+    (0x3036, 0x3037, On),
+    (0x303D, 0x303F, On),
+    (0x3099, 0x309A, Nsm),
+    (0x309B, 0x309C, On),
+    (0x30A0, 0x30A0, On),
+    (0x30FB, 0x30FB, On),
+    (0x31C0, 0x31E3, On),
+    (0x321D, 0x321E, On),
+    (0x3250, 0x325F, On),
+    (0x327C, 0x327E, On),
+    (0x32B1, 0x32BF, On),
+    (0x32CC, 0x32CF, On),
+    (0x3377, 0x337A, On),
+    (0x33DE, 0x33DF, On),
+    (0x33FF, 0x33FF, On),
+    (0x4DC0, 0x4DFF, On),
+    (0xA490, 0xA4C6, On),
+    (0xA60D, 0xA60F, On),
+    (0xA66F, 0xA672, Nsm),
+    (0xA673, 0xA673, On),
+    (0xA674, 0xA67D, Nsm),
+    (0xA67E, 0xA67F, On),
+    (0xA69E, 0xA69F, Nsm),
+    (0xA6F0, 0xA6F1, Nsm),
+    (0xA700, 0xA721, On),
+    (0xA788, 0xA788, On),
+    (0xA802, 0xA802, Nsm),
+    (0xA806, 0xA806, Nsm),
+    (0xA80B, 0xA80B, Nsm),
+    (0xA825, 0xA826, Nsm),
+    (0xA828, 0xA82B, On),
+    (0xA82C, 0xA82C, Nsm),
+    (0xA838, 0xA839, Et),
+    (0xA874, 0xA877, On),
+    (0xA8C4, 0xA8C5, Nsm),
+    (0xA8E0, 0xA8F1, Nsm),
+    (0xA8FF, 0xA8FF, Nsm),
+    (0xA926, 0xA92D, Nsm),
+    (0xA947, 0xA951, Nsm),
+    (0xA980, 0xA982, Nsm),
+    (0xA9B3, 0xA9B3, Nsm),
+    (0xA9B6, 0xA9B9, Nsm),
+    (0xA9BC, 0xA9BD, Nsm),
+    (0xA9E5, 0xA9E5, Nsm),
+    (0xAA29, 0xAA2E, Nsm),
+    (0xAA31, 0xAA32, Nsm),
+    (0xAA35, 0xAA36, Nsm),
+    (0xAA43, 0xAA43, Nsm),
+    (0xAA4C, 0xAA4C, Nsm),
+    (0xAA7C, 0xAA7C, Nsm),
+    (0xAAB0, 0xAAB0, Nsm),
+    (0xAAB2, 0xAAB4, Nsm),
+    (0xAAB7, 0xAAB8, Nsm),
+    (0xAABE, 0xAABF, Nsm),
+    (0xAAC1, 0xAAC1, Nsm),
+    (0xAAEC, 0xAAED, Nsm),
+    (0xAAF6, 0xAAF6, Nsm),
+    (0xAB6A, 0xAB6B, On),
+    (0xABE5, 0xABE5, Nsm),
+    (0xABE8, 0xABE8, Nsm),
+    (0xABED, 0xABED, Nsm),
+    (0xFB1D, 0xFB1D, R),
+    (0xFB1E, 0xFB1E, Nsm),
+    (0xFB1F, 0xFB28, R),
+    (0xFB29, 0xFB29, Es),
+    (0xFB2A, 0xFB4F, R),
+    (0xFB50, 0xFD3D, Al),
+    (0xFD3E, 0xFD4F, On),
+    (0xFD50, 0xFDC7, Al),
+    (0xFDCF, 0xFDCF, On),
+    (0xFDF0, 0xFDFC, Al),
+    (0xFDFD, 0xFDFF, On),
+    (0xFE00, 0xFE0F, Nsm),
+    (0xFE10, 0xFE19, On),
+    (0xFE20, 0xFE2F, Nsm),
+    (0xFE30, 0xFE4F, On),
+    (0xFE50, 0xFE50, Cs),
+    (0xFE51, 0xFE51, On),
+    (0xFE52, 0xFE52, Cs),
+    (0xFE54, 0xFE54, On),
+    (0xFE55, 0xFE55, Cs),
+    (0xFE56, 0xFE5E, On),
+    (0xFE5F, 0xFE5F, Et),
+    (0xFE60, 0xFE61, On),
+    (0xFE62, 0xFE63, Es),
+    (0xFE64, 0xFE68, On),
+    (0xFE69, 0xFE6A, Et),
+    (0xFE6B, 0xFE6B, On),
+    (0xFE70, 0xFEFC, Al),
+    (0xFEFF, 0xFEFF, Bn),
+    (0xFF01, 0xFF02, On),
+    (0xFF03, 0xFF05, Et),
+    (0xFF06, 0xFF0A, On),
+    (0xFF0B, 0xFF0B, Es),
+    (0xFF0C, 0xFF0C, Cs),
+    (0xFF0D, 0xFF0D, Es),
+    (0xFF0E, 0xFF0F, Cs),
+    (0xFF10, 0xFF19, En),
+    (0xFF1A, 0xFF1A, Cs),
+    (0xFF1B, 0xFF20, On),
+    (0xFF3B, 0xFF40, On),
+    (0xFF5B, 0xFF65, On),
+    (0xFFE0, 0xFFE1, Et),
+    (0xFFE2, 0xFFE4, On),
+    (0xFFE5, 0xFFE6, Et),
+    (0xFFE8, 0xFFFD, On),
+    (0x10101, 0x10101, On),
+    (0x10140, 0x1018C, On),
+    (0x10190, 0x101A0, On),
+    (0x101FD, 0x101FD, Nsm),
+    (0x102E0, 0x102E0, Nsm),
+    (0x102E1, 0x102FB, En),
+    (0x10376, 0x1037A, Nsm),
+    (0x10800, 0x1091B, R),
+    (0x1091F, 0x1091F, On),
+    (0x10920, 0x10A00, R),
+    (0x10A01, 0x10A0F, Nsm),
+    (0x10A10, 0x10A35, R),
+    (0x10A38, 0x10A3F, Nsm),
+    (0x10A40, 0x10AE4, R),
+    (0x10AE5, 0x10AE6, Nsm),
+    (0x10AEB, 0x10B35, R),
+    (0x10B39, 0x10B3F, On),
+    (0x10B40, 0x10CFF, R),
+    (0x10D00, 0x10D23, Al),
+    (0x10D24, 0x10D27, Nsm),
+    (0x10D30, 0x10E7E, An),
+    (0x10E80, 0x10EA9, R),
+    (0x10EAB, 0x10EAC, Nsm),
+    (0x10EAD, 0x10F27, R),
+    (0x10F30, 0x10F45, Al),
+    (0x10F46, 0x10F50, Nsm),
+    (0x10F51, 0x10F59, Al),
+    (0x10F70, 0x10F81, R),
+    (0x10F82, 0x10F85, Nsm),
+    (0x10F86, 0x10FF6, R),
+    (0x11001, 0x11001, Nsm),
+    (0x11038, 0x11046, Nsm),
+    (0x11052, 0x11065, On),
+    (0x11070, 0x11070, Nsm),
+    (0x11073, 0x11074, Nsm),
+    (0x1107F, 0x11081, Nsm),
+    (0x110B3, 0x110B6, Nsm),
+    (0x110B9, 0x110BA, Nsm),
+    (0x110C2, 0x110C2, Nsm),
+    (0x11100, 0x11102, Nsm),
+    (0x11127, 0x1112B, Nsm),
+    (0x1112D, 0x11134, Nsm),
+    (0x11173, 0x11173, Nsm),
+    (0x11180, 0x11181, Nsm),
+    (0x111B6, 0x111BE, Nsm),
+    (0x111C9, 0x111CC, Nsm),
+    (0x111CF, 0x111CF, Nsm),
+    (0x1122F, 0x11231, Nsm),
+    (0x11234, 0x11234, Nsm),
+    (0x11236, 0x11237, Nsm),
+    (0x1123E, 0x1123E, Nsm),
+    (0x112DF, 0x112DF, Nsm),
+    (0x112E3, 0x112EA, Nsm),
+    (0x11300, 0x11301, Nsm),
+    (0x1133B, 0x1133C, Nsm),
+    (0x11340, 0x11340, Nsm),
+    (0x11366, 0x11374, Nsm),
+    (0x11438, 0x1143F, Nsm),
+    (0x11442, 0x11444, Nsm),
+    (0x11446, 0x11446, Nsm),
+    (0x1145E, 0x1145E, Nsm),
+    (0x114B3, 0x114B8, Nsm),
+    (0x114BA, 0x114BA, Nsm),
+    (0x114BF, 0x114C0, Nsm),
+    (0x114C2, 0x114C3, Nsm),
+    (0x115B2, 0x115B5, Nsm),
+    (0x115BC, 0x115BD, Nsm),
+    (0x115BF, 0x115C0, Nsm),
+    (0x115DC, 0x115DD, Nsm),
+    (0x11633, 0x1163A, Nsm),
+    (0x1163D, 0x1163D, Nsm),
+    (0x1163F, 0x11640, Nsm),
+    (0x11660, 0x1166C, On),
+    (0x116AB, 0x116AB, Nsm),
+    (0x116AD, 0x116AD, Nsm),
+    (0x116B0, 0x116B5, Nsm),
+    (0x116B7, 0x116B7, Nsm),
+    (0x1171D, 0x1171F, Nsm),
+    (0x11722, 0x11725, Nsm),
+    (0x11727, 0x1172B, Nsm),
+    (0x1182F, 0x11837, Nsm),
+    (0x11839, 0x1183A, Nsm),
+    (0x1193B, 0x1193C, Nsm),
+    (0x1193E, 0x1193E, Nsm),
+    (0x11943, 0x11943, Nsm),
+    (0x119D4, 0x119DB, Nsm),
+    (0x119E0, 0x119E0, Nsm),
+    (0x11A01, 0x11A06, Nsm),
+    (0x11A09, 0x11A0A, Nsm),
+    (0x11A33, 0x11A38, Nsm),
+    (0x11A3B, 0x11A3E, Nsm),
+    (0x11A47, 0x11A47, Nsm),
+    (0x11A51, 0x11A56, Nsm),
+    (0x11A59, 0x11A5B, Nsm),
+    (0x11A8A, 0x11A96, Nsm),
+    (0x11A98, 0x11A99, Nsm),
+    (0x11C30, 0x11C3D, Nsm),
+    (0x11C92, 0x11CA7, Nsm),
+    (0x11CAA, 0x11CB0, Nsm),
+    (0x11CB2, 0x11CB3, Nsm),
+    (0x11CB5, 0x11CB6, Nsm),
+    (0x11D31, 0x11D45, Nsm),
+    (0x11D47, 0x11D47, Nsm),
+    (0x11D90, 0x11D91, Nsm),
+    (0x11D95, 0x11D95, Nsm),
+    (0x11D97, 0x11D97, Nsm),
+    (0x11EF3, 0x11EF4, Nsm),
+    (0x11FD5, 0x11FDC, On),
+    (0x11FDD, 0x11FE0, Et),
+    (0x11FE1, 0x11FF1, On),
+    (0x16AF0, 0x16AF4, Nsm),
+    (0x16B30, 0x16B36, Nsm),
+    (0x16F4F, 0x16F4F, Nsm),
+    (0x16F8F, 0x16F92, Nsm),
+    (0x16FE2, 0x16FE2, On),
+    (0x16FE4, 0x16FE4, Nsm),
+    (0x1BC9D, 0x1BC9E, Nsm),
+    (0x1BCA0, 0x1BCA3, Bn),
+    (0x1CF00, 0x1CF46, Nsm),
+    (0x1D167, 0x1D169, Nsm),
+    (0x1D173, 0x1D17A, Bn),
+    (0x1D17B, 0x1D182, Nsm),
+    (0x1D185, 0x1D18B, Nsm),
+    (0x1D1AA, 0x1D1AD, Nsm),
+    (0x1D1E9, 0x1D241, On),
+    (0x1D242, 0x1D244, Nsm),
+    (0x1D245, 0x1D245, On),
+    (0x1D300, 0x1D356, On),
+    (0x1D6DB, 0x1D6DB, On),
+    (0x1D715, 0x1D715, On),
+    (0x1D74F, 0x1D74F, On),
+    (0x1D789, 0x1D789, On),
+    (0x1D7C3, 0x1D7C3, On),
+    (0x1D7CE, 0x1D7FF, En),
+    (0x1DA00, 0x1DA36, Nsm),
+    (0x1DA3B, 0x1DA6C, Nsm),
+    (0x1DA75, 0x1DA75, Nsm),
+    (0x1DA84, 0x1DA84, Nsm),
+    (0x1DA9B, 0x1DAAF, Nsm),
+    (0x1E000, 0x1E02A, Nsm),
+    (0x1E130, 0x1E136, Nsm),
+    (0x1E2AE, 0x1E2AE, Nsm),
+    (0x1E2EC, 0x1E2EF, Nsm),
+    (0x1E2FF, 0x1E2FF, Et),
+    (0x1E800, 0x1E8CF, R),
+    (0x1E8D0, 0x1E8D6, Nsm),
+    (0x1E900, 0x1E943, R),
+    (0x1E944, 0x1E94A, Nsm),
+    (0x1E94B, 0x1E95F, R),
+    (0x1EC71, 0x1EEBB, Al),
+    (0x1EEF0, 0x1F0F5, On),
+    (0x1F100, 0x1F10A, En),
+    (0x1F10B, 0x1F10F, On),
+    (0x1F12F, 0x1F12F, On),
+    (0x1F16A, 0x1F16F, On),
+    (0x1F1AD, 0x1F1AD, On),
+    (0x1F260, 0x1FBCA, On),
+    (0x1FBF0, 0x1FBF9, En),
+    (0xE0001, 0xE007F, Bn),
+    (0xE0100, 0xE01EF, Nsm),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIDI_TEST: &str = include_str!("../testdata/BidiTest.txt");
+    const BIDI_CHARACTER_TEST: &str = include_str!("../testdata/BidiCharacterTest.txt");
+
+    // A character of each class, for BidiTest.txt's lists of classes.
+    fn character(class: &str) -> char {
+        match class {
+            "L" => 'a',
+            "R" => '\u{05D0}',
+            "AL" => '\u{0627}',
+            "EN" => '1',
+            "ES" => '+',
+            "ET" => '$',
+            "AN" => '\u{0661}',
+            "CS" => ',',
+            "NSM" => '\u{0300}',
+            "BN" => '\u{00AD}',
+            "B" => '\u{2029}',
+            "S" => '\t',
+            "WS" => ' ',
+            "ON" => '!',
+            "LRE" => '\u{202A}',
+            "LRO" => '\u{202D}',
+            "RLE" => '\u{202B}',
+            "RLO" => '\u{202E}',
+            "PDF" => '\u{202C}',
+            "LRI" => '\u{2066}',
+            "RLI" => '\u{2067}',
+            "FSI" => '\u{2068}',
+            "PDI" => '\u{2069}',
+            class => panic!("unknown bidi class {class}"),
+        }
+    }
+
+    // Levels as the test files write them, with None for removed characters.
+    fn parse_levels(field: &str) -> Vec<Option<u8>> {
+        field
+            .split_whitespace()
+            .map(|level| level.parse().ok())
+            .collect()
+    }
+
+    fn parse_order(field: &str) -> Vec<usize> {
+        field
+            .split_whitespace()
+            .map(|index| index.parse().unwrap())
+            .collect()
+    }
+
+    // Rule L2: from the highest level down to the lowest odd one, reverses
+    // every run of characters at that level or above. Removed characters
+    // are left out.
+    fn visual_order(levels: &[Option<u8>]) -> Vec<usize> {
+        let mut order: Vec<(usize, u8)> = levels
+            .iter()
+            .enumerate()
+            .filter_map(|(index, level)| Some((index, (*level)?)))
+            .collect();
+        let highest = order.iter().map(|&(_, level)| level).max().unwrap_or(0);
+        let lowest_odd = order
+            .iter()
+            .map(|&(_, level)| level)
+            .filter(|level| level % 2 == 1)
+            .min()
+            .unwrap_or(highest + 1);
+        for level in (lowest_odd..=highest).rev() {
+            let mut start = 0;
+            while start < order.len() {
+                if order[start].1 < level {
+                    start += 1;
+                    continue;
+                }
+                let end = order[start..]
+                    .iter()
+                    .position(|&(_, other)| other < level)
+                    .map_or(order.len(), |length| start + length);
+                order[start..end].reverse();
+                start = end;
+            }
+        }
+        order.into_iter().map(|(index, _)| index).collect()
+    }
+
+    // Describes how the levels for 'text' differ from 'expected', if they do.
+    fn check(text: &str, base_level: Option<u8>, expected: &[Option<u8>]) -> Option<String> {
+        let length = text.chars().count();
+        let levels = BidiLevels::new(text, base_level).line_levels(0..length);
+        let matches = levels.len() == expected.len()
+            && levels
+                .iter()
+                .zip(expected)
+                .all(|(level, expected)| expected.is_none_or(|expected| expected == *level));
+        (!matches).then(|| format!("{text:?} at {base_level:?}: {levels:?}, not {expected:?}"))
+    }
+
+    #[test]
+    fn levels_match_bidi_test() {
+        let mut levels = Vec::new();
+        let mut failures = Vec::new();
+        let mut cases = 0;
+        for line in BIDI_TEST.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if let Some(field) = line.strip_prefix("@Levels:") {
+                levels = parse_levels(field);
+                continue;
+            }
+            if let Some(field) = line.strip_prefix("@Reorder:") {
+                assert_eq!(visual_order(&levels), parse_order(field), "{levels:?}");
+                continue;
+            }
+            let Some((classes, bitset)) = line.split_once(';') else {
+                continue;
+            };
+            let text: String = classes.split_whitespace().map(character).collect();
+            let bitset: u8 = bitset.trim().parse().unwrap();
+            for (bit, base_level) in [(1, None), (2, Some(0)), (4, Some(1))] {
+                if bitset & bit != 0 {
+                    cases += 1;
+                    failures.extend(check(&text, base_level, &levels));
+                }
+            }
+        }
+        assert!(cases > 0);
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn levels_match_bidi_character_test() {
+        let mut failures = Vec::new();
+        let mut cases = 0;
+        for line in BIDI_CHARACTER_TEST.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(';').collect();
+            let text: String = fields[0]
+                .split_whitespace()
+                .map(|code| char::from_u32(u32::from_str_radix(code, 16).unwrap()).unwrap())
+                .collect();
+            let level = match fields[1] {
+                "0" => Some(0),
+                "1" => Some(1),
+                _ => None,
+            };
+            let paragraph_level: u8 = fields[2].parse().unwrap();
+            let levels = parse_levels(fields[3]);
+            assert_eq!(visual_order(&levels), parse_order(fields[4]), "{line}");
+
+            cases += 1;
+            if level.is_none() && base_level(&text) != paragraph_level {
+                failures.push(format!("{text:?}: paragraph level isn't {paragraph_level}"));
+            }
+            failures.extend(check(&text, level, &levels));
+        }
+        assert!(cases > 0);
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    ops::Range,
+};
+
+use crate::{
+    emoji::has_color_glyphs,
+    metrics::{Cluster, LineBox, TextMetrics},
+    scene::{Color, TextVisual},
+    text_path::{PathShape, TextPath},
+    typography::{Feature, FeatureTag, resolve_axes, resolve_runs},
+};
+
+// The resolution text is laid out and rasterized at.
+pub const DEFAULT_DPI: f32 = 96.0;
+
+// Axis tags and their values' bits.
+type AxisBits = Vec<(FeatureTag, u32)>;
+
+// Everything a text layout and its rasterized text depend on. Floats are
+// compared by their bits, so only identical values share an entry.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextKey {
+    text: String,
+    font_family: String,
+    locale: String,
+    font_size: u32,
+    max_width: u32,
+    // The resolved direction, so "auto" shares entries with the direction
+    // it resolves to.
+    right_to_left: bool,
+    vertical: bool,
+    // Features and axis values as they apply to each piece of the text,
+    // so runs that resolve the same way share entries. The weight, stretch
+    // and style each piece gets follow from its axis values.
+    features: Vec<(Range<usize>, Vec<Feature>)>,
+    axes: Vec<(Range<usize>, AxisBits)>,
+    path: Option<Vec<u32>>,
+    dpi: u32,
+    // The base color that color text is drawn in, None for text that
+    // gets drawn as a mask.
+    color: Option<Color>,
+}
+
+impl TextKey {
+    // Animated axes count with their static values, so visuals should be
+    // sampled with axes_at first.
+    pub fn new(visual: &TextVisual, dpi: f32) -> Self {
+        let length = visual.text.chars().count();
+        Self {
+            text: visual.text.clone(),
+            font_family: visual.font_family.clone(),
+            locale: visual.locale.clone(),
+            font_size: visual.font_size.to_bits(),
+            max_width: visual.max_width.to_bits(),
+            right_to_left: visual.direction.is_right_to_left(&visual.text),
+            vertical: visual.vertical,
+            features: resolve_runs(&visual.typography, length),
+            axes: resolve_axes(&visual.typography, length)
+                .into_iter()
+                .map(|(range, axes)| {
+                    let axes = axes
+                        .iter()
+                        .map(|axis| (axis.tag, axis.value.to_bits()))
+                        .collect();
+                    (range, axes)
+                })
+                .collect(),
+            path: visual.path.as_ref().map(path_bits),
+            dpi: dpi.to_bits(),
+            color: has_color_glyphs(&visual.text).then_some(visual.color.value),
+        }
+    }
+
+    // The key with the text left out, which is everything that changes how
+    // each piece of the text is drawn. Runs without features or axes are
+    // left out too, since they cover the whole text when it has none and
+    // would change with its length.
+    pub fn without_text(mut self) -> Self {
+        self.text.clear();
+        self.features.retain(|(_, features)| !features.is_empty());
+        self.axes.retain(|(_, axes)| !axes.is_empty());
+        self
+    }
+}
+
+// The path's shape, offset and alignment as bits, tagged with the kind of
+// shape.
+fn path_bits(path: &TextPath) -> Vec<u32> {
+    let (kind, numbers) = match &path.shape {
+        PathShape::Arc {
+            center,
+            radius,
+            start_angle,
+            sweep_angle,
+        } => (
+            0,
+            vec![center.X, center.Y, *radius, *start_angle, *sweep_angle],
+        ),
+        PathShape::Circle {
+            center,
+            radius,
+            start_angle,
+        } => (1, vec![center.X, center.Y, *radius, *start_angle]),
+        PathShape::Bezier(points) => (
+            2,
+            points.iter().flat_map(|point| [point.X, point.Y]).collect(),
+        ),
+    };
+    [kind, path.alignment as u32, path.start_offset.to_bits()]
+        .into_iter()
+        .chain(numbers.iter().map(|number| number.to_bits()))
+        .collect()
+}
+
+// About how much memory a layout of 'text' and its metrics take. Layouts
+// don't report their size, so it's a guess from the text's length.
+pub fn layout_bytes(text: &str, metrics: &TextMetrics) -> usize {
+    text.len() * 64
+        + metrics.lines.len() * size_of::<LineBox>()
+        + metrics.clusters.len() * size_of::<Cluster>()
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+struct CacheEntry<V> {
+    value: V,
+    bytes: usize,
+    last_used: u64,
+}
+
+// Keeps the most recently used values within a budget of entries and of
+// bytes, where each value's size in bytes is given when it's inserted.
+pub struct LruCache<K, V> {
+    max_bytes: usize,
+    max_entries: usize,
+    entries: HashMap<K, CacheEntry<V>>,
+    // Keys by when they were last used, oldest first.
+    order: BTreeMap<u64, K>,
+    uses: u64,
+    stats: CacheStats,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    pub fn new(max_bytes: usize, max_entries: usize) -> Self {
+        Self {
+            max_bytes,
+            max_entries,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            uses: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let Some(entry) = self.entries.get_mut(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.uses += 1;
+        let key = self.order.remove(&entry.last_used).unwrap();
+        self.order.insert(self.uses, key);
+        entry.last_used = self.uses;
+        Some(&entry.value)
+    }
+
+    // Adds a value, evicting the least recently used ones to make room.
+    // Values bigger than the whole budget aren't kept.
+    pub fn insert(&mut self, key: K, value: V, bytes: usize) {
+        self.remove(&key);
+        if bytes > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+        while self.stats.bytes + bytes > self.max_bytes || self.entries.len() >= self.max_entries {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            let entry = self.entries.remove(&oldest).unwrap();
+            self.stats.bytes -= entry.bytes;
+            self.stats.evictions += 1;
+        }
+        self.uses += 1;
+        self.order.insert(self.uses, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                bytes,
+                last_used: self.uses,
+            },
+        );
+        self.stats.bytes += bytes;
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.last_used);
+            self.stats.bytes -= entry.bytes;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scene::Scene,
+        typography::{FontAxis, TypographyRun},
+    };
+
+    fn keys(cache: &LruCache<&'static str, u32>) -> Vec<&'static str> {
+        cache.order.values().copied().collect()
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted_first() {
+        let mut cache = LruCache::new(100, 3);
+        cache.insert("a", 1, 1);
+        cache.insert("b", 2, 1);
+        cache.insert("c", 3, 1);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        cache.insert("d", 4, 1);
+        assert_eq!(keys(&cache), ["c", "a", "d"]);
+        assert_eq!(cache.get(&"b"), None);
+    }
+
+    #[test]
+    fn entries_are_evicted_to_stay_within_the_byte_budget() {
+        let mut cache = LruCache::new(10, 10);
+        cache.insert("a", 1, 4);
+        cache.insert("b", 2, 4);
+        cache.insert("c", 3, 2);
+        assert_eq!(cache.stats().bytes, 10);
+        // Needs both a and b gone.
+        cache.insert("d", 4, 7);
+        assert_eq!(keys(&cache), ["c", "d"]);
+        assert_eq!(cache.stats().bytes, 9);
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn entries_are_evicted_to_stay_within_the_entry_budget() {
+        let mut cache = LruCache::new(100, 2);
+        cache.insert("a", 1, 1);
+        cache.insert("b", 2, 1);
+        cache.insert("c", 3, 1);
+        assert_eq!(keys(&cache), ["b", "c"]);
+        assert_eq!(cache.stats().entries, 2);
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn values_bigger_than_the_budget_arent_kept() {
+        let mut cache = LruCache::new(10, 10);
+        cache.insert("a", 1, 5);
+        cache.insert("b", 2, 11);
+        assert_eq!(keys(&cache), ["a"]);
+        assert_eq!(cache.stats().evictions, 0);
+        cache.insert("c", 3, 10);
+        assert_eq!(keys(&cache), ["c"]);
+
+        let mut empty = LruCache::new(10, 0);
+        empty.insert("a", 1, 1);
+        assert_eq!(empty.get(&"a"), None);
+    }
+
+    #[test]
+    fn replacing_a_value_replaces_its_bytes() {
+        let mut cache = LruCache::new(10, 2);
+        cache.insert("a", 1, 6);
+        cache.insert("b", 2, 2);
+        cache.insert("a", 3, 8);
+        assert_eq!(keys(&cache), ["b", "a"]);
+        assert_eq!(cache.get(&"a"), Some(&3));
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, 10, 0));
+
+        // A replacement that's too big drops the old value too.
+        cache.insert("a", 4, 11);
+        assert_eq!(keys(&cache), ["b"]);
+        assert_eq!(cache.stats().bytes, 2);
+    }
+
+    #[test]
+    fn stats_count_hits_and_misses() {
+        let mut cache = LruCache::new(10, 10);
+        assert_eq!(cache.stats(), CacheStats::default());
+        assert_eq!(cache.stats().hit_rate(), 0.0);
+        cache.insert("a", 1, 3);
+        cache.get(&"a");
+        cache.get(&"a");
+        cache.get(&"a");
+        cache.get(&"b");
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 3,
+                misses: 1,
+                evictions: 0,
+                entries: 1,
+                bytes: 3,
+            }
+        );
+        assert_eq!(cache.stats().hit_rate(), 0.75);
+    }
+
+    #[test]
+    fn keys_without_text_only_differ_in_how_text_is_drawn() {
+        let visual = Scene::demo().visuals.remove(0);
+        let key = |visual: &TextVisual| TextKey::new(visual, DEFAULT_DPI).without_text();
+
+        let mut edited = visual.clone();
+        edited.text.push_str(" and more");
+        assert_eq!(key(&edited), key(&visual));
+        assert_ne!(
+            TextKey::new(&edited, DEFAULT_DPI),
+            TextKey::new(&visual, DEFAULT_DPI)
+        );
+
+        let mut resized = edited.clone();
+        resized.font_size += 1.0;
+        assert_ne!(key(&resized), key(&visual));
+
+        let mut refont = edited.clone();
+        refont.font_family = "Consolas".to_owned();
+        assert_ne!(key(&refont), key(&visual));
+
+        assert_ne!(TextKey::new(&visual, 144.0).without_text(), key(&visual));
+    }
+
+    #[test]
+    fn keys_without_text_keep_runs_that_apply_something() {
+        let mut visual = Scene::demo().visuals.remove(0);
+        visual.typography = vec![TypographyRun {
+            range: 0..5,
+            features: vec![Feature {
+                tag: FeatureTag::SMALL_CAPS,
+                value: 1,
+            }],
+            axes: Vec::new(),
+        }];
+        let key = |visual: &TextVisual| TextKey::new(visual, DEFAULT_DPI).without_text();
+
+        let mut edited = visual.clone();
+        edited.text.push_str(" and more");
+        assert_eq!(key(&edited), key(&visual));
+
+        let mut longer_run = visual.clone();
+        longer_run.typography[0].range = 0..6;
+        assert_ne!(key(&longer_run), key(&visual));
+
+        let mut no_run = visual.clone();
+        no_run.typography.clear();
+        assert_ne!(key(&no_run), key(&visual));
+    }
+
+    #[test]
+    fn keys_differ_by_the_weight_and_style_runs_resolve_to() {
+        let mut visual = Scene::demo().visuals.remove(0);
+        let axis_run = |tag, value| TypographyRun {
+            range: 0..5,
+            features: Vec::new(),
+            axes: vec![FontAxis::new(tag, value)],
+        };
+        visual.typography = vec![axis_run(FeatureTag::WEIGHT, 700.0)];
+        let key = |visual: &TextVisual| TextKey::new(visual, DEFAULT_DPI);
+
+        let mut lighter = visual.clone();
+        lighter.typography[0] = axis_run(FeatureTag::WEIGHT, 300.0);
+        assert_ne!(key(&lighter), key(&visual));
+
+        let mut italic = visual.clone();
+        italic.typography.push(axis_run(FeatureTag::ITALIC, 1.0));
+        assert_ne!(key(&italic), key(&visual));
+
+        // A later run setting the same weight resolves the same way.
+        let mut repeated = visual.clone();
+        repeated
+            .typography
+            .push(axis_run(FeatureTag::WEIGHT, 700.0));
+        assert_eq!(key(&repeated), key(&visual));
+    }
+}
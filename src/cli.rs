@@ -0,0 +1,293 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    export::{ExportFormat, LoopCount, Quantization},
+    logging::LogFormat,
+    metrics::LayoutEngine,
+    overlay::Overlays,
+    svg::TextMode,
+};
+
+pub const USAGE: &str = "\
+Usage: comptextdemo [options]
+
+Options:
+  --scene <file>             Load the scene from a JSON file instead of the
+                             built-in demo
+  --render-frames <dir>      Render frames offscreen to a numbered PNG
+                             sequence instead of opening a window
+  --export-gif <file>        Render the animation offscreen to an animated GIF
+  --export-apng <file>       Render the animation offscreen to an animated PNG
+  --export-y4m <file>        Render the animation offscreen to uncompressed
+                             Y4M video, or to stdout if <file> is '-'
+  --export-svg <file>        Write the scene to SVG, with key frame color and
+                             opacity animations as SMIL
+  --svg-text <mode>          'text' for <text> elements or 'outlines' for
+                             glyph paths (default: text)
+  --dump-metrics             Print the layout metrics of every visual in the
+                             scene as JSON
+  --layout <engine>          Layout engine for '--dump-metrics': 'directwrite'
+                             or 'estimate', the portable approximation
+                             (default: directwrite)
+  --pipe-to <command>        Stream raw RGBA frames to the standard input of
+                             an encoder command; '{width}', '{height}' and
+                             '{fps}' in it are replaced
+  --frames <n>               Number of frames to render
+  --duration <seconds>       Length of the render when '--frames' isn't given
+                             (default: one pass of every animation in the
+                             scene)
+  --fps <n>                  Frame rate to step the clock at (default: 60, or
+                             50 for GIFs, which store delays in hundredths of
+                             a second)
+  --loop-count <n>           Times an exported animation plays, 0 for forever
+                             (default: 0)
+  --colors <n>               Colors in each GIF frame's palette, 2 to 256
+                             (default: 256)
+  --quantizer-speed <n>      GIF palette quality from 1 (best) to 30 (fastest)
+                             (default: 10)
+  --overlays <list>          Debug overlays to draw over the window or
+                             rendered frames, separated by commas: 'bounds',
+                             'lines' (line boxes and baselines), 'overhang',
+                             'surfaces', 'fps' or 'all'. In the window the B,
+                             L, O, S and F keys toggle them.
+  --log-level <filter>       Log events to show on stderr, as a level like
+                             'info' or 'trace' or a RUST_LOG filter like
+                             'comptextdemo::render=debug' (default: RUST_LOG,
+                             or 'warn')
+  --log-format <format>      'text' or 'json', one object per line
+                             (default: text)
+  --debug-layers             Use the Direct3D and Direct2D debug layers when
+                             they're installed and log what they report,
+                             also turned on by setting
+                             COMPTEXTDEMO_DEBUG_LAYERS=1
+  --width <n>                Width of the window or frames, up to 16384
+                             (default: 800)
+  --height <n>               Height of the window or frames, up to 16384
+                             (default: 600)
+  --help                     Print this message";
+
+// The largest window or frame side. Direct3D 11 textures can't be bigger,
+// and a frame that size already takes a gigabyte of RGBA.
+const MAX_SIZE: u32 = 16384;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    Window,
+    RenderFrames { directory: PathBuf },
+    Export { path: PathBuf, format: ExportFormat },
+    Pipe { command: String },
+    Svg { path: PathBuf },
+    DumpMetrics,
+    Help,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub mode: Mode,
+    pub scene: Option<PathBuf>,
+    pub fps: Option<u32>,
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: Option<u64>,
+    pub duration: Option<Duration>,
+    pub loop_count: LoopCount,
+    pub quantization: Quantization,
+    pub svg_text: TextMode,
+    pub overlays: Overlays,
+    pub layout_engine: Option<LayoutEngine>,
+    pub log_filter: Option<String>,
+    pub log_format: LogFormat,
+    pub debug_layers: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Window,
+            scene: None,
+            fps: None,
+            width: 800,
+            height: 600,
+            frame_count: None,
+            duration: None,
+            loop_count: LoopCount::Forever,
+            quantization: Quantization::default(),
+            svg_text: TextMode::Text,
+            overlays: Overlays::default(),
+            layout_engine: None,
+            log_filter: None,
+            log_format: LogFormat::Text,
+            debug_layers: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for '{}'", arg))
+            };
+            let mode = match arg.as_str() {
+                "--render-frames" => Some(Mode::RenderFrames {
+                    directory: PathBuf::from(value()?),
+                }),
+                "--export-gif" => Some(Mode::Export {
+                    path: PathBuf::from(value()?),
+                    format: ExportFormat::Gif,
+                }),
+                "--export-apng" => Some(Mode::Export {
+                    path: PathBuf::from(value()?),
+                    format: ExportFormat::Apng,
+                }),
+                "--export-y4m" => Some(Mode::Export {
+                    path: PathBuf::from(value()?),
+                    format: ExportFormat::Y4m,
+                }),
+                "--pipe-to" => Some(Mode::Pipe { command: value()? }),
+                "--export-svg" => Some(Mode::Svg {
+                    path: PathBuf::from(value()?),
+                }),
+                "--dump-metrics" => Some(Mode::DumpMetrics),
+                "--help" | "-h" => {
+                    options.mode = Mode::Help;
+                    return Ok(options);
+                }
+                _ => None,
+            };
+            if let Some(mode) = mode {
+                if options.mode != Mode::Window {
+                    return Err("Only one output can be given".to_owned());
+                }
+                options.mode = mode;
+                continue;
+            }
+
+            match arg.as_str() {
+                "--scene" => options.scene = Some(PathBuf::from(value()?)),
+                "--frames" => options.frame_count = Some(parse_number(&arg, &value()?)?),
+                "--duration" => {
+                    let value = value()?;
+                    let seconds: f64 = parse_number(&arg, &value)?;
+                    options.duration = Some(
+                        Duration::try_from_secs_f64(seconds)
+                            .map_err(|_| format!("Invalid value '{}' for '{}'", value, arg))?,
+                    );
+                }
+                "--fps" => options.fps = Some(parse_in_range(&arg, &value()?, 1, u16::MAX as u32)?),
+                "--loop-count" => {
+                    options.loop_count = match parse_number(&arg, &value()?)? {
+                        0 => LoopCount::Forever,
+                        plays => LoopCount::Plays(plays),
+                    }
+                }
+                "--colors" => {
+                    options.quantization.colors = parse_in_range(&arg, &value()?, 2, 256)? as u16
+                }
+                "--quantizer-speed" => {
+                    options.quantization.speed = parse_in_range(&arg, &value()?, 1, 30)? as i32
+                }
+                "--svg-text" => {
+                    options.svg_text = match value()?.as_str() {
+                        "text" => TextMode::Text,
+                        "outlines" => TextMode::Outlines,
+                        other => return Err(format!("Invalid value '{}' for '{}'", other, arg)),
+                    }
+                }
+                "--layout" => {
+                    options.layout_engine = Some(match value()?.as_str() {
+                        "directwrite" => LayoutEngine::DirectWrite,
+                        "estimate" => LayoutEngine::Estimate,
+                        other => return Err(format!("Invalid value '{}' for '{}'", other, arg)),
+                    })
+                }
+                "--log-level" => options.log_filter = Some(value()?),
+                "--log-format" => {
+                    options.log_format = match value()?.as_str() {
+                        "text" => LogFormat::Text,
+                        "json" => LogFormat::Json,
+                        other => return Err(format!("Invalid value '{}' for '{}'", other, arg)),
+                    }
+                }
+                "--debug-layers" => options.debug_layers = true,
+                "--overlays" => options.overlays = Overlays::parse(&value()?)?,
+                "--width" => options.width = parse_in_range(&arg, &value()?, 1, MAX_SIZE)?,
+                "--height" => options.height = parse_in_range(&arg, &value()?, 1, MAX_SIZE)?,
+                _ => return Err(format!("Unknown argument '{}'", arg)),
+            }
+        }
+
+        if options.mode == Mode::Window
+            && (options.frame_count.is_some() || options.duration.is_some())
+        {
+            return Err("'--frames' and '--duration' need an offscreen output".to_owned());
+        }
+        if matches!(options.mode, Mode::Svg { .. }) && options.overlays != Overlays::default() {
+            return Err("'--overlays' can't be used with '--export-svg'".to_owned());
+        }
+        if options.mode != Mode::DumpMetrics && options.layout_engine.is_some() {
+            return Err("'--layout' needs '--dump-metrics'".to_owned());
+        }
+        Ok(options)
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps.unwrap_or(match self.mode {
+            Mode::Export {
+                format: ExportFormat::Gif,
+                ..
+            } => 50,
+            _ => 60,
+        })
+    }
+}
+
+pub const DEBUG_LAYERS_VAR: &str = "COMPTEXTDEMO_DEBUG_LAYERS";
+
+// Whether the environment asks for the debug layers, for when the app is
+// started by something that doesn't pass '--debug-layers'. Anything but
+// an empty value or "0" counts.
+pub fn debug_layers_from_env() -> bool {
+    std::env::var_os(DEBUG_LAYERS_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' for '{}'", value, name))
+}
+
+fn parse_in_range(name: &str, value: &str, min: u32, max: u32) -> Result<u32, String> {
+    let number = parse_number(name, value)?;
+    if (min..=max).contains(&number) {
+        Ok(number)
+    } else {
+        Err(format!("'{}' must be between {} and {}", name, min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn sizes_are_limited_to_what_a_texture_can_hold() {
+        let options = parse(&["--width", "16384", "--height", "1"]).unwrap();
+        assert_eq!((options.width, options.height), (16384, 1));
+        for arg in ["--width", "--height"] {
+            for value in ["0", "16385", "4294967295"] {
+                assert_eq!(
+                    parse(&[arg, value]).unwrap_err(),
+                    format!("'{}' must be between 1 and 16384", arg)
+                );
+            }
+        }
+    }
+}
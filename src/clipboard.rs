@@ -0,0 +1,74 @@
+use windows::{
+    Win32::{
+        Foundation::{GlobalFree, HANDLE, HGLOBAL, HWND},
+        System::{
+            DataExchange::{
+                CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable,
+                OpenClipboard, SetClipboardData,
+            },
+            Memory::{GMEM_MOVEABLE, GlobalAlloc, GlobalLock, GlobalUnlock},
+            Ole::CF_UNICODETEXT,
+        },
+    },
+    core::Result,
+};
+
+// The text on the clipboard, if there is any.
+pub fn get_text(window: HWND) -> Result<Option<String>> {
+    unsafe {
+        if IsClipboardFormatAvailable(CF_UNICODETEXT.0 as u32).is_err() {
+            return Ok(None);
+        }
+        OpenClipboard(Some(window))?;
+        let text = read_text();
+        CloseClipboard()?;
+        text
+    }
+}
+
+pub fn set_text(window: HWND, text: &str) -> Result<()> {
+    let mut data: Vec<u16> = text.encode_utf16().collect();
+    data.push(0);
+    unsafe {
+        OpenClipboard(Some(window))?;
+        let written = write_text(&data);
+        CloseClipboard()?;
+        written
+    }
+}
+
+unsafe fn read_text() -> Result<Option<String>> {
+    unsafe {
+        let handle = GetClipboardData(CF_UNICODETEXT.0 as u32)?;
+        let memory = HGLOBAL(handle.0);
+        let data = GlobalLock(memory) as *const u16;
+        if data.is_null() {
+            return Ok(None);
+        }
+        let length = (0..).take_while(|&index| *data.add(index) != 0).count();
+        let text = String::from_utf16_lossy(std::slice::from_raw_parts(data, length));
+        let _ = GlobalUnlock(memory);
+        Ok(Some(text))
+    }
+}
+
+// The clipboard owns the memory once it's been set, and until then it's
+// ours to free.
+unsafe fn write_text(data: &[u16]) -> Result<()> {
+    unsafe {
+        EmptyClipboard()?;
+        let memory = GlobalAlloc(GMEM_MOVEABLE, std::mem::size_of_val(data))?;
+        let destination = GlobalLock(memory) as *mut u16;
+        if destination.is_null() {
+            let _ = GlobalFree(Some(memory));
+            return Err(windows::core::Error::from_win32());
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr(), destination, data.len());
+        let _ = GlobalUnlock(memory);
+        if let Err(error) = SetClipboardData(CF_UNICODETEXT.0 as u32, Some(HANDLE(memory.0))) {
+            let _ = GlobalFree(Some(memory));
+            return Err(error);
+        }
+        Ok(())
+    }
+}
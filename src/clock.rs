@@ -0,0 +1,146 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    epoch: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+}
+
+// Advances only when stepped, one frame at a time at a fixed rate. Frame
+// times are computed from the frame number rather than accumulated so that
+// they don't drift.
+pub struct FrameClock {
+    fps: u32,
+    frame: Cell<u64>,
+}
+
+impl FrameClock {
+    pub fn new(fps: u32) -> Self {
+        assert!(fps > 0, "FrameClock needs a non-zero frame rate");
+        Self {
+            fps,
+            frame: Cell::new(0),
+        }
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame.get()
+    }
+
+    pub fn step(&self) {
+        self.frame.set(self.frame.get() + 1);
+    }
+
+    pub fn seek_to_frame(&self, frame: u64) {
+        self.frame.set(frame);
+    }
+
+    pub fn frame_time(&self, frame: u64) -> Duration {
+        let nanos = frame as u128 * 1_000_000_000 / self.fps as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    // Number of whole frames needed to cover 'duration'.
+    pub fn frame_count(&self, duration: Duration) -> u64 {
+        (duration.as_nanos() * self.fps as u128).div_ceil(1_000_000_000) as u64
+    }
+}
+
+impl Clock for FrameClock {
+    fn now(&self) -> Duration {
+        self.frame_time(self.frame.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_times_round_down_to_whole_nanoseconds() {
+        let clock = FrameClock::new(30);
+        assert_eq!(clock.frame_time(0), Duration::ZERO);
+        assert_eq!(clock.frame_time(1), Duration::from_nanos(33_333_333));
+        assert_eq!(clock.frame_time(2), Duration::from_nanos(66_666_666));
+        assert_eq!(clock.frame_time(3), Duration::from_millis(100));
+        assert_eq!(clock.frame_time(30), Duration::from_secs(1));
+        let clock = FrameClock::new(60);
+        assert_eq!(clock.frame_time(1), Duration::from_nanos(16_666_666));
+        assert_eq!(clock.frame_time(3), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn frame_times_dont_drift() {
+        let clock = FrameClock::new(60);
+        assert_eq!(clock.frame_time(60 * 60 * 10), Duration::from_secs(60 * 10));
+    }
+
+    #[test]
+    fn frame_counts_round_up_to_cover_the_duration() {
+        let clock = FrameClock::new(30);
+        assert_eq!(clock.frame_count(Duration::ZERO), 0);
+        assert_eq!(clock.frame_count(Duration::from_millis(1)), 1);
+        assert_eq!(clock.frame_count(Duration::from_millis(100)), 3);
+        assert_eq!(clock.frame_count(Duration::from_secs(1)), 30);
+        assert_eq!(clock.frame_count(Duration::from_millis(1010)), 31);
+        let clock = FrameClock::new(60);
+        assert_eq!(clock.frame_count(Duration::from_secs(1)), 60);
+        assert_eq!(clock.frame_count(Duration::from_millis(1010)), 61);
+    }
+
+    #[test]
+    fn frame_counts_undo_frame_times() {
+        for fps in [24, 30, 60] {
+            let clock = FrameClock::new(fps);
+            for frame in 0..200 {
+                assert_eq!(clock.frame_count(clock.frame_time(frame)), frame);
+            }
+        }
+    }
+
+    #[test]
+    fn stepping_and_seeking_move_the_clock() {
+        let clock = FrameClock::new(30);
+        assert_eq!(clock.frame(), 0);
+        assert_eq!(clock.now(), Duration::ZERO);
+        clock.step();
+        clock.step();
+        clock.step();
+        assert_eq!(clock.frame(), 3);
+        assert_eq!(clock.now(), Duration::from_millis(100));
+        clock.seek_to_frame(60);
+        assert_eq!(clock.now(), Duration::from_secs(2));
+        clock.seek_to_frame(15);
+        assert_eq!(clock.frame(), 15);
+        assert_eq!(clock.now(), Duration::from_millis(500));
+    }
+
+    #[test]
+    #[should_panic]
+    fn frame_clocks_need_a_frame_rate() {
+        FrameClock::new(0);
+    }
+}
@@ -1,13 +1,27 @@
+use std::collections::HashMap;
+
+use tracing::{debug_span, trace};
 use windows::{
     UI::Composition::{
-        CompositionDrawingSurface, CompositionGraphicsDevice, Compositor, ICompositionSurface,
+        AnimationIterationBehavior, CompositionAnimation, CompositionDrawingSurface,
+        CompositionEasingFunction, CompositionGraphicsDevice, CompositionObject, Compositor,
+        ICompositionSurface, KeyFrameAnimation as CompositionKeyFrameAnimation, Visual,
     },
     Win32::{
         Foundation::{POINT, RECT, SIZE},
         Graphics::{Direct2D::ID2D1Device, Direct3D11::ID3D11Device, Dxgi::IDXGISwapChain1},
         System::WinRT::Composition::{ICompositionDrawingSurfaceInterop, ICompositorInterop},
     },
-    core::{IUnknown, Interface, Result},
+    core::{HSTRING, IUnknown, Interface, Result},
+};
+use windows_numerics::Vector3;
+
+use crate::{
+    animation::{Easing, IterationBehavior, KeyFrameAnimation},
+    numerics::ToColor,
+    scene::{Animation, Color, Property, Transform},
+    storyboard::Storyboard,
+    timeline::Timeline,
 };
 
 pub trait CompositorInterop {
@@ -90,11 +104,159 @@ impl CompositionDrawingSurfaceInterop for CompositionDrawingSurface {
     }
 }
 
+// Only 'update_rect' is drawn when it's given; the rest of the surface
+// keeps what was drawn before.
 pub fn draw_into_surface<UpdateObject: Interface, F: FnOnce(&UpdateObject, &POINT)>(
     surface: &CompositionDrawingSurface,
+    update_rect: Option<&RECT>,
     draw: F,
 ) -> Result<()> {
-    let (update_object, update_offset) = surface.begin_draw(None)?;
+    let _span = debug_span!("draw_into_surface", ?update_rect).entered();
+    let (update_object, update_offset) = surface.begin_draw(update_rect)?;
+    trace!(x = update_offset.x, y = update_offset.y, "Began drawing");
     draw(&update_object, &update_offset);
-    surface.end_draw()
+    surface.end_draw()?;
+    trace!("Ended drawing");
+    Ok(())
+}
+
+pub trait ToCompositionAnimation {
+    fn to_composition_animation(&self, compositor: &Compositor) -> Result<CompositionAnimation>;
+}
+
+impl ToCompositionAnimation for KeyFrameAnimation<f32> {
+    fn to_composition_animation(&self, compositor: &Compositor) -> Result<CompositionAnimation> {
+        let animation = compositor.CreateScalarKeyFrameAnimation()?;
+        for key_frame in self.key_frames() {
+            animation.InsertKeyFrameWithEasingFunction(
+                key_frame.progress,
+                key_frame.value,
+                &create_easing_function(compositor, &key_frame.easing)?,
+            )?;
+        }
+        set_timing(&animation.cast()?, self)?;
+        animation.cast()
+    }
+}
+
+impl ToCompositionAnimation for KeyFrameAnimation<Vector3> {
+    fn to_composition_animation(&self, compositor: &Compositor) -> Result<CompositionAnimation> {
+        let animation = compositor.CreateVector3KeyFrameAnimation()?;
+        for key_frame in self.key_frames() {
+            animation.InsertKeyFrameWithEasingFunction(
+                key_frame.progress,
+                key_frame.value,
+                &create_easing_function(compositor, &key_frame.easing)?,
+            )?;
+        }
+        set_timing(&animation.cast()?, self)?;
+        animation.cast()
+    }
+}
+
+impl ToCompositionAnimation for KeyFrameAnimation<Color> {
+    fn to_composition_animation(&self, compositor: &Compositor) -> Result<CompositionAnimation> {
+        let animation = compositor.CreateColorKeyFrameAnimation()?;
+        for key_frame in self.key_frames() {
+            animation.InsertKeyFrameWithEasingFunction(
+                key_frame.progress,
+                key_frame.value.to_color(),
+                &create_easing_function(compositor, &key_frame.easing)?,
+            )?;
+        }
+        set_timing(&animation.cast()?, self)?;
+        animation.cast()
+    }
+}
+
+fn create_easing_function(
+    compositor: &Compositor,
+    easing: &Easing,
+) -> Result<CompositionEasingFunction> {
+    // Key frames always get an explicit easing function so that Composition
+    // doesn't fall back to its own default curve, which the portable
+    // evaluator wouldn't match.
+    match easing {
+        Easing::Linear => compositor.CreateLinearEasingFunction()?.cast(),
+        Easing::CubicBezier(control_point1, control_point2) => compositor
+            .CreateCubicBezierEasingFunction(*control_point1, *control_point2)?
+            .cast(),
+    }
+}
+
+fn set_timing<T>(
+    animation: &CompositionKeyFrameAnimation,
+    source: &KeyFrameAnimation<T>,
+) -> Result<()> {
+    animation.SetDuration(source.duration.into())?;
+    match source.iteration_behavior {
+        IterationBehavior::Count(count) => {
+            animation.SetIterationBehavior(AnimationIterationBehavior::Count)?;
+            animation.SetIterationCount(count as i32)?;
+        }
+        IterationBehavior::Forever => {
+            animation.SetIterationBehavior(AnimationIterationBehavior::Forever)?;
+        }
+    }
+    Ok(())
+}
+
+// 'references' maps the object names used by expressions to the
+// Composition objects they should be bound to.
+pub fn start_property_animation<T>(
+    compositor: &Compositor,
+    object: &CompositionObject,
+    property_name: &str,
+    property: &Property<T>,
+    references: &HashMap<String, CompositionObject>,
+) -> Result<()>
+where
+    KeyFrameAnimation<T>: ToCompositionAnimation,
+{
+    let animation = match &property.animation {
+        Some(Animation::KeyFrames(animation)) => animation.to_composition_animation(compositor)?,
+        Some(Animation::Expression(expression)) => {
+            let animation = compositor
+                .CreateExpressionAnimationWithExpression(&HSTRING::from(expression.to_string()))?;
+            for reference in expression.references() {
+                if let Some(target) = references.get(reference) {
+                    animation.SetReferenceParameter(&HSTRING::from(reference), target)?;
+                }
+            }
+            animation.cast()?
+        }
+        None => return Ok(()),
+    };
+    trace!(property = property_name, "Starting animation");
+    object.StartAnimation(&HSTRING::from(property_name), &animation)
+}
+
+// Sets the transform's static values and adds its animations to the
+// storyboard, returning the clips for the storyboard's timeline.
+pub fn apply_transform(
+    storyboard: &mut Storyboard,
+    visual: &Visual,
+    transform: &Transform,
+    references: &HashMap<String, CompositionObject>,
+) -> Result<Vec<Timeline>> {
+    visual.SetOffset(transform.offset.value)?;
+    visual.SetScale(transform.scale.value)?;
+    visual.SetRotationAngleInDegrees(transform.rotation_angle_in_degrees.value)?;
+    visual.SetCenterPoint(transform.center_point.value)?;
+    visual.SetOpacity(transform.opacity.value)?;
+
+    let object: CompositionObject = visual.cast()?;
+    let clips = [
+        storyboard.add_property(&object, "Offset", &transform.offset, references)?,
+        storyboard.add_property(&object, "Scale", &transform.scale, references)?,
+        storyboard.add_property(
+            &object,
+            "RotationAngleInDegrees",
+            &transform.rotation_angle_in_degrees,
+            references,
+        )?,
+        storyboard.add_property(&object, "CenterPoint", &transform.center_point, references)?,
+        storyboard.add_property(&object, "Opacity", &transform.opacity, references)?,
+    ];
+    Ok(clips.into_iter().flatten().collect())
 }
@@ -1,19 +1,96 @@
+use tracing::{instrument, warn};
 use windows::Win32::Graphics::{
     Direct2D::{
+        Common::D2D_RECT_F, D2D1_DEBUG_LEVEL_INFORMATION, D2D1_DEBUG_LEVEL_NONE,
         D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1CreateFactory, ID2D1Device,
-        ID2D1Factory1,
+        ID2D1DeviceContext, ID2D1Factory1, ID2D1SolidColorBrush,
     },
     Direct3D11::ID3D11Device,
     Dxgi::IDXGIDevice,
 };
 use windows::core::{Interface, Result};
+use windows_numerics::Vector2;
 
+use crate::{numerics::ToD2DColor, overlay::Shape};
+
+pub fn factory_options(debug_layers: bool) -> D2D1_FACTORY_OPTIONS {
+    D2D1_FACTORY_OPTIONS {
+        debugLevel: if debug_layers {
+            D2D1_DEBUG_LEVEL_INFORMATION
+        } else {
+            D2D1_DEBUG_LEVEL_NONE
+        },
+    }
+}
+
+// Like Direct3D's, the Direct2D debug layer is optional, so a factory that
+// can't get one is created without it. Its messages go to the debugger.
+#[instrument(level = "info", skip_all, fields(debug_level = options.debugLevel.0))]
 pub fn create_d2d_factory(options: D2D1_FACTORY_OPTIONS) -> Result<ID2D1Factory1> {
-    let result = unsafe { D2D1CreateFactory(D2D1_FACTORY_TYPE_SINGLE_THREADED, Some(&options))? };
-    Ok(result)
+    let result = unsafe { D2D1CreateFactory(D2D1_FACTORY_TYPE_SINGLE_THREADED, Some(&options)) };
+    match result {
+        Err(error) if options.debugLevel != D2D1_DEBUG_LEVEL_NONE => {
+            warn!(%error, "The Direct2D debug layer isn't available, continuing without it");
+            let options = D2D1_FACTORY_OPTIONS {
+                debugLevel: D2D1_DEBUG_LEVEL_NONE,
+            };
+            unsafe { D2D1CreateFactory(D2D1_FACTORY_TYPE_SINGLE_THREADED, Some(&options)) }
+        }
+        result => result,
+    }
 }
 
+#[instrument(level = "info", skip_all)]
 pub fn create_d2d_device(factory: &ID2D1Factory1, device: &ID3D11Device) -> Result<ID2D1Device> {
     let dxgi_device: IDXGIDevice = device.cast()?;
     unsafe { factory.CreateDevice(&dxgi_device) }
 }
+
+// Draws overlay shapes with the context's current transform. Both the window
+// and the headless renderer go through here so they look the same.
+pub fn draw_shapes(
+    d2d_context: &ID2D1DeviceContext,
+    d2d_brush: &ID2D1SolidColorBrush,
+    shapes: &[Shape],
+) {
+    let rect = |[x, y, width, height]: [f32; 4]| D2D_RECT_F {
+        left: x,
+        top: y,
+        right: x + width,
+        bottom: y + height,
+    };
+    for shape in shapes {
+        unsafe {
+            match *shape {
+                Shape::Fill(bounds, color) => {
+                    d2d_brush.SetColor(&color.to_d2d_color());
+                    d2d_context.FillRectangle(&rect(bounds), d2d_brush);
+                }
+                Shape::Stroke([x, y, width, height], color) => {
+                    d2d_brush.SetColor(&color.to_d2d_color());
+                    d2d_context.DrawRectangle(
+                        &rect([
+                            x + 0.5,
+                            y + 0.5,
+                            (width - 1.0).max(0.0),
+                            (height - 1.0).max(0.0),
+                        ]),
+                        d2d_brush,
+                        1.0,
+                        None,
+                    );
+                }
+                Shape::Line(from, to, color) => {
+                    d2d_brush.SetColor(&color.to_d2d_color());
+                    d2d_context.DrawLine(
+                        Vector2::new(from.X, from.Y + 0.5),
+                        Vector2::new(to.X, to.Y + 0.5),
+                        d2d_brush,
+                        1.0,
+                        None,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,26 @@
-use windows::Win32::Foundation::HMODULE;
+use std::sync::{Mutex, Once, PoisonError};
+
+use tracing::{debug, error, info, instrument, warn};
+use windows::Win32::Foundation::{DBG_PRINTEXCEPTION_C, HMODULE};
 use windows::Win32::Graphics::Direct3D::{
     D3D_DRIVER_TYPE, D3D_DRIVER_TYPE_HARDWARE, D3D_DRIVER_TYPE_WARP,
 };
 use windows::Win32::Graphics::Direct3D11::{
-    D3D11_CREATE_DEVICE_FLAG, D3D11_SDK_VERSION, D3D11CreateDevice, ID3D11Device,
+    D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_CREATE_DEVICE_DEBUG, D3D11_CREATE_DEVICE_FLAG,
+    D3D11_MESSAGE, D3D11_MESSAGE_SEVERITY_CORRUPTION, D3D11_MESSAGE_SEVERITY_ERROR,
+    D3D11_MESSAGE_SEVERITY_INFO, D3D11_MESSAGE_SEVERITY_WARNING, D3D11_SDK_VERSION,
+    D3D11CreateDevice, ID3D11Device, ID3D11InfoQueue,
+};
+use windows::Win32::Graphics::Dxgi::{
+    DXGI_DEBUG_DXGI, DXGI_ERROR_SDK_COMPONENT_MISSING, DXGI_ERROR_UNSUPPORTED,
+    DXGI_INFO_QUEUE_MESSAGE, DXGI_INFO_QUEUE_MESSAGE_SEVERITY_CORRUPTION,
+    DXGI_INFO_QUEUE_MESSAGE_SEVERITY_ERROR, DXGI_INFO_QUEUE_MESSAGE_SEVERITY_INFO,
+    DXGI_INFO_QUEUE_MESSAGE_SEVERITY_WARNING, DXGIGetDebugInterface1, IDXGIInfoQueue,
 };
-use windows::Win32::Graphics::Dxgi::DXGI_ERROR_UNSUPPORTED;
-use windows::core::Result;
+use windows::Win32::System::Diagnostics::Debug::{
+    AddVectoredExceptionHandler, EXCEPTION_CONTINUE_SEARCH, EXCEPTION_POINTERS,
+};
+use windows::core::{Interface, Result};
 
 fn create_d3d_device_with_type(
     driver_type: D3D_DRIVER_TYPE,
@@ -28,14 +42,190 @@ fn create_d3d_device_with_type(
     }
 }
 
-pub fn create_d3d_device(flags: D3D11_CREATE_DEVICE_FLAG) -> Result<ID3D11Device> {
+// The flags for a device Direct2D can draw with.
+pub fn device_flags(debug_layers: bool) -> D3D11_CREATE_DEVICE_FLAG {
+    if debug_layers {
+        D3D11_CREATE_DEVICE_BGRA_SUPPORT | D3D11_CREATE_DEVICE_DEBUG
+    } else {
+        D3D11_CREATE_DEVICE_BGRA_SUPPORT
+    }
+}
+
+// The debug layer only ships with the SDK or the Graphics Tools optional
+// feature, so without it the device is created without one.
+#[instrument(level = "info", skip_all, fields(flags = flags.0))]
+pub fn create_d3d_device(mut flags: D3D11_CREATE_DEVICE_FLAG) -> Result<ID3D11Device> {
     let mut device = None;
-    let mut result = create_d3d_device_with_type(D3D_DRIVER_TYPE_HARDWARE, flags, &mut device);
+    let mut driver_type = D3D_DRIVER_TYPE_HARDWARE;
+    let mut result = create_d3d_device_with_type(driver_type, flags, &mut device);
+    if let Err(error) = &result {
+        if error.code() == DXGI_ERROR_SDK_COMPONENT_MISSING
+            && flags.contains(D3D11_CREATE_DEVICE_DEBUG)
+        {
+            warn!("The Direct3D debug layer isn't installed, continuing without it");
+            flags &= !D3D11_CREATE_DEVICE_DEBUG;
+            result = create_d3d_device_with_type(driver_type, flags, &mut device);
+        }
+    }
     if let Err(error) = &result {
         if error.code() == DXGI_ERROR_UNSUPPORTED {
-            result = create_d3d_device_with_type(D3D_DRIVER_TYPE_WARP, flags, &mut device);
+            warn!("No hardware device available, falling back to WARP");
+            driver_type = D3D_DRIVER_TYPE_WARP;
+            result = create_d3d_device_with_type(driver_type, flags, &mut device);
         }
     }
     result?;
+    info!(
+        driver_type = driver_type.0,
+        flags = flags.0,
+        "Created Direct3D device"
+    );
     Ok(device.unwrap())
 }
+
+// Forwards what the debug layers report to the log, under the "d3d11",
+// "dxgi" and "d2d1" targets. Direct3D and DXGI keep their messages in info
+// queues until they're drained. Direct2D's debug layer writes its messages
+// with OutputDebugString instead, so they're caught on their way to the
+// debugger, see capture_d2d_message. Under a debugger they only show up
+// there.
+pub struct DebugMessages {
+    d3d11: ID3D11InfoQueue,
+    dxgi: Option<IDXGIInfoQueue>,
+}
+
+static D2D_MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static CAPTURE_D2D_MESSAGES: Once = Once::new();
+
+// OutputDebugString raises DBG_PRINTEXCEPTION_C for a debugger to pick up.
+// Without one attached the exception goes to vectored handlers first, and
+// this one copies out the messages from Direct2D's debug layer, which all
+// start with "D2D DEBUG", and lets the exception carry on.
+unsafe extern "system" fn capture_d2d_message(exception: *mut EXCEPTION_POINTERS) -> i32 {
+    let record = unsafe { &*(*exception).ExceptionRecord };
+    if record.ExceptionCode == DBG_PRINTEXCEPTION_C && record.NumberParameters >= 2 {
+        // The length, counting the terminating null, and the ANSI text.
+        let [length, text, ..] = record.ExceptionInformation;
+        let message = description(text as *const u8, length);
+        if message.starts_with("D2D DEBUG") {
+            D2D_MESSAGES
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(message);
+        }
+    }
+    EXCEPTION_CONTINUE_SEARCH
+}
+
+fn description(text: *const u8, length: usize) -> String {
+    let bytes = unsafe { std::slice::from_raw_parts(text, length) };
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(['\0', '\r', '\n'])
+        .to_owned()
+}
+
+// Calls 'each' with the stored messages of an info queue, which are
+// variable length since each one's description is stored right after it.
+// u64s keep the messages aligned.
+fn for_each_message<M>(
+    count: u64,
+    get_message: impl Fn(u64, Option<*mut M>, &mut usize) -> Result<()>,
+    mut each: impl FnMut(&M),
+) {
+    for index in 0..count {
+        let mut length = 0;
+        if get_message(index, None, &mut length).is_err() {
+            continue;
+        }
+        let mut buffer = vec![0u64; length.div_ceil(8)];
+        let message = buffer.as_mut_ptr() as *mut M;
+        if get_message(index, Some(message), &mut length).is_err() {
+            continue;
+        }
+        each(unsafe { &*message });
+    }
+}
+
+impl DebugMessages {
+    // Returns None if the device doesn't have the debug layer. DXGI's
+    // queue is only there when its debug layer is installed too.
+    pub fn new(device: &ID3D11Device) -> Option<Self> {
+        let d3d11 = device.cast().ok()?;
+        let dxgi = unsafe { DXGIGetDebugInterface1(0) }.ok();
+        CAPTURE_D2D_MESSAGES.call_once(|| unsafe {
+            AddVectoredExceptionHandler(0, Some(capture_d2d_message));
+        });
+        Some(Self { d3d11, dxgi })
+    }
+
+    pub fn drain(&self) {
+        unsafe {
+            for_each_message(
+                self.d3d11.GetNumStoredMessages(),
+                |index, message, length| self.d3d11.GetMessage(index, message, length),
+                |message: &D3D11_MESSAGE| {
+                    let description =
+                        description(message.pDescription, message.DescriptionByteLength);
+                    let id = message.ID.0;
+                    match message.Severity {
+                        D3D11_MESSAGE_SEVERITY_CORRUPTION | D3D11_MESSAGE_SEVERITY_ERROR => {
+                            error!(target: "d3d11", id, "{}", description)
+                        }
+                        D3D11_MESSAGE_SEVERITY_WARNING => {
+                            warn!(target: "d3d11", id, "{}", description)
+                        }
+                        D3D11_MESSAGE_SEVERITY_INFO => {
+                            info!(target: "d3d11", id, "{}", description)
+                        }
+                        _ => debug!(target: "d3d11", id, "{}", description),
+                    }
+                },
+            );
+            self.d3d11.ClearStoredMessages();
+
+            if let Some(dxgi) = &self.dxgi {
+                for_each_message(
+                    dxgi.GetNumStoredMessages(DXGI_DEBUG_DXGI),
+                    |index, message, length| {
+                        dxgi.GetMessage(DXGI_DEBUG_DXGI, index, message, length)
+                    },
+                    |message: &DXGI_INFO_QUEUE_MESSAGE| {
+                        let description =
+                            description(message.pDescription, message.DescriptionByteLength);
+                        let id = message.ID;
+                        match message.Severity {
+                            DXGI_INFO_QUEUE_MESSAGE_SEVERITY_CORRUPTION
+                            | DXGI_INFO_QUEUE_MESSAGE_SEVERITY_ERROR => {
+                                error!(target: "dxgi", id, "{}", description)
+                            }
+                            DXGI_INFO_QUEUE_MESSAGE_SEVERITY_WARNING => {
+                                warn!(target: "dxgi", id, "{}", description)
+                            }
+                            DXGI_INFO_QUEUE_MESSAGE_SEVERITY_INFO => {
+                                info!(target: "dxgi", id, "{}", description)
+                            }
+                            _ => debug!(target: "dxgi", id, "{}", description),
+                        }
+                    },
+                );
+                dxgi.ClearStoredMessages(DXGI_DEBUG_DXGI);
+            }
+        }
+
+        // Messages look like "D2D DEBUG WARNING - The resource ...".
+        let d2d1 =
+            std::mem::take(&mut *D2D_MESSAGES.lock().unwrap_or_else(PoisonError::into_inner));
+        for message in d2d1 {
+            let (severity, description) = message
+                .strip_prefix("D2D DEBUG ")
+                .and_then(|message| message.split_once(" - "))
+                .unwrap_or(("", &message));
+            match severity {
+                "ERROR" => error!(target: "d2d1", "{}", description),
+                "WARNING" => warn!(target: "d2d1", "{}", description),
+                "INFO" => info!(target: "d2d1", "{}", description),
+                _ => debug!(target: "d2d1", "{}", description),
+            }
+        }
+    }
+}
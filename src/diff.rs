@@ -0,0 +1,602 @@
+use crate::{
+    emoji::has_color_glyphs,
+    scene::{Color, Scene, TextVisual},
+};
+
+// What changed about a visual that exists in both scenes. Each flag maps to
+// a different amount of work on the visual tree: 'layout' means the text
+// has to be laid out and rasterized again, the rest only touch properties.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VisualDiff {
+    pub id: String,
+    pub layout: bool,
+    pub placement: bool,
+    pub color: bool,
+    pub transform: bool,
+    pub border: bool,
+}
+
+impl VisualDiff {
+    fn new(old: &TextVisual, new: &TextVisual) -> Self {
+        Self {
+            id: new.id.clone(),
+            layout: old.text != new.text
+                || old.font_family != new.font_family
+                || old.font_size != new.font_size
+                || old.locale != new.locale
+                || old.max_width != new.max_width
+                || old.direction != new.direction
+                || old.vertical != new.vertical
+                || old.typography != new.typography
+                || old.path != new.path
+                // Links are drawn over the text where it's laid out.
+                || old.links != new.links
+                // Color text is drawn in its base color instead of being
+                // colored by a brush.
+                || (has_color_glyphs(&new.text) && old.color.value != new.color.value),
+            placement: old.anchor_point != new.anchor_point
+                || old.relative_offset != new.relative_offset,
+            color: old.color != new.color,
+            transform: old.transform != new.transform,
+            border: old.border != new.border,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.layout || self.placement || self.color || self.transform || self.border)
+    }
+}
+
+// Visuals are matched by id. 'added' is in the new scene's order and
+// 'reordered' is set when visuals in both scenes changed relative order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneDiff {
+    pub background: Option<Color>,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+    pub updated: Vec<VisualDiff>,
+    pub reordered: bool,
+}
+
+impl SceneDiff {
+    // Animations share one timeline and expressions can reference any
+    // visual, so adding, removing or re-animating anything restarts them.
+    pub fn restarts_animations(&self) -> bool {
+        !self.added.is_empty()
+            || !self.removed.is_empty()
+            || self
+                .updated
+                .iter()
+                .any(|update| update.color || update.transform)
+    }
+}
+
+pub fn diff_scenes(old: &Scene, new: &Scene) -> SceneDiff {
+    let background = (old.background != new.background).then_some(new.background);
+    let removed = old
+        .visuals
+        .iter()
+        .filter(|visual| new.visual(&visual.id).is_none())
+        .map(|visual| visual.id.clone())
+        .collect();
+
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    let mut retained = Vec::new();
+    for visual in &new.visuals {
+        match old.visuals.iter().position(|old| old.id == visual.id) {
+            Some(index) => {
+                retained.push(index);
+                let update = VisualDiff::new(&old.visuals[index], visual);
+                if !update.is_empty() {
+                    updated.push(update);
+                }
+            }
+            None => added.push(visual.id.clone()),
+        }
+    }
+    let reordered = retained.windows(2).any(|pair| pair[0] > pair[1]);
+
+    SceneDiff {
+        background,
+        removed,
+        added,
+        updated,
+        reordered,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VisualProperty {
+    Placement,
+    Border,
+}
+
+// One change to the visual tree. Visuals are referred to by id and take
+// their values from the scene being applied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Patch {
+    SetBackground(Color),
+    RemoveVisual(String),
+    // Creates the visual with its placement and border; its surface is
+    // drawn by a following Rasterize.
+    CreateVisual(String),
+    SetProperty(String, VisualProperty),
+    Rasterize(String),
+    // The ids of all visuals, bottom to top.
+    OrderChildren(Vec<String>),
+    RestartAnimations,
+}
+
+// A visual tree built from a scene, like the window's Composition visuals,
+// that patches are applied to one at a time.
+pub trait VisualTree {
+    type Error;
+
+    fn apply(&mut self, scene: &Scene, patch: &Patch) -> Result<(), Self::Error>;
+}
+
+// The patches that turn a visual tree built for 'old' into one for 'new',
+// in the order they should be applied. Animations restart last since
+// expressions can reference any visual.
+pub fn reconcile(old: &Scene, new: &Scene) -> Vec<Patch> {
+    let diff = diff_scenes(old, new);
+    let mut patches = Vec::new();
+    if let Some(background) = diff.background {
+        patches.push(Patch::SetBackground(background));
+    }
+    patches.extend(diff.removed.iter().cloned().map(Patch::RemoveVisual));
+    for id in &diff.added {
+        patches.push(Patch::CreateVisual(id.clone()));
+        patches.push(Patch::Rasterize(id.clone()));
+    }
+    for update in &diff.updated {
+        if update.layout {
+            patches.push(Patch::Rasterize(update.id.clone()));
+        }
+        if update.placement {
+            patches.push(Patch::SetProperty(
+                update.id.clone(),
+                VisualProperty::Placement,
+            ));
+        }
+        if update.border {
+            patches.push(Patch::SetProperty(
+                update.id.clone(),
+                VisualProperty::Border,
+            ));
+        }
+    }
+    if diff.reordered || !diff.added.is_empty() {
+        patches.push(Patch::OrderChildren(
+            new.visuals.iter().map(|visual| visual.id.clone()).collect(),
+        ));
+    }
+    if diff.restarts_animations() {
+        patches.push(Patch::RestartAnimations);
+    }
+    patches
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use windows_numerics::{Vector2, Vector3};
+
+    use super::*;
+    use crate::{
+        links::{Link, LinkTarget},
+        scene::{Border, Property, ReadingDirection},
+        text_path::{PathAlignment, PathShape, TextPath},
+        typography::{Feature, FeatureTag, TypographyRun},
+    };
+
+    fn visual(id: &str) -> TextVisual {
+        let mut visual = Scene::demo().visuals.remove(0);
+        visual.id = id.to_owned();
+        visual.color = Property::new(Color::rgb(0, 0, 0));
+        visual
+    }
+
+    fn scene(ids: &[&str]) -> Scene {
+        Scene {
+            background: Color::rgb(255, 255, 255),
+            visuals: ids.iter().map(|id| visual(id)).collect(),
+        }
+    }
+
+    // The diff of one visual changed by 'change'.
+    fn visual_diff(change: impl FnOnce(&mut TextVisual)) -> Option<VisualDiff> {
+        let old = scene(&["a"]);
+        let mut new = old.clone();
+        change(&mut new.visuals[0]);
+        let mut diff = diff_scenes(&old, &new);
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && !diff.reordered);
+        diff.updated.pop()
+    }
+
+    fn flags(diff: &VisualDiff) -> [bool; 5] {
+        [
+            diff.layout,
+            diff.placement,
+            diff.color,
+            diff.transform,
+            diff.border,
+        ]
+    }
+
+    #[test]
+    fn finds_nothing_between_equal_scenes() {
+        let diff = diff_scenes(&scene(&["a", "b"]), &scene(&["a", "b"]));
+        assert_eq!(diff, SceneDiff::default());
+        assert!(!diff.restarts_animations());
+    }
+
+    #[test]
+    fn finds_added_and_removed_visuals() {
+        let diff = diff_scenes(&scene(&["a", "b", "c"]), &scene(&["d", "b", "e"]));
+        assert_eq!(diff.removed, ["a", "c"]);
+        assert_eq!(diff.added, ["d", "e"]);
+        assert!(diff.updated.is_empty());
+        assert!(!diff.reordered);
+        assert!(diff.restarts_animations());
+    }
+
+    #[test]
+    fn finds_reordered_visuals() {
+        let diff = diff_scenes(&scene(&["a", "b", "c"]), &scene(&["c", "a", "b"]));
+        assert!(diff.reordered);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(!diff.restarts_animations());
+
+        // Removing a visual between others doesn't reorder what's left.
+        let diff = diff_scenes(&scene(&["a", "b", "c"]), &scene(&["a", "x", "c"]));
+        assert!(!diff.reordered);
+    }
+
+    #[test]
+    fn finds_background_changes() {
+        let old = scene(&["a"]);
+        let mut new = old.clone();
+        new.background = Color::rgb(1, 2, 3);
+        let diff = diff_scenes(&old, &new);
+        assert_eq!(diff.background, Some(Color::rgb(1, 2, 3)));
+        assert!(!diff.restarts_animations());
+    }
+
+    #[test]
+    fn flags_layout_changes() {
+        let changes: [fn(&mut TextVisual); 10] = [
+            |visual| visual.text.push('!'),
+            |visual| visual.font_family = "Segoe UI".to_owned(),
+            |visual| visual.font_size += 1.0,
+            |visual| visual.locale = "fr-fr".to_owned(),
+            |visual| visual.max_width += 1.0,
+            |visual| visual.direction = ReadingDirection::RightToLeft,
+            |visual| visual.vertical = true,
+            |visual| {
+                visual.typography.push(TypographyRun {
+                    range: 0..1,
+                    features: vec![Feature {
+                        tag: FeatureTag::SMALL_CAPS,
+                        value: 1,
+                    }],
+                    axes: Vec::new(),
+                })
+            },
+            |visual| {
+                visual.path = Some(TextPath {
+                    shape: PathShape::Circle {
+                        center: Vector2::zero(),
+                        radius: 50.0,
+                        start_angle: 0.0,
+                    },
+                    alignment: PathAlignment::Start,
+                    start_offset: 0.0,
+                })
+            },
+            |visual| {
+                visual.links.push(Link {
+                    range: 0..5,
+                    target: LinkTarget::Action("hello".to_owned()),
+                    color: Color::rgb(0, 0, 255),
+                    hover_color: Color::rgb(0, 0, 128),
+                })
+            },
+        ];
+        for (index, change) in changes.into_iter().enumerate() {
+            let diff = visual_diff(change).unwrap();
+            assert_eq!(
+                flags(&diff),
+                [true, false, false, false, false],
+                "{}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn flags_property_changes() {
+        let diff = visual_diff(|visual| visual.anchor_point = Vector2::zero()).unwrap();
+        assert_eq!(flags(&diff), [false, true, false, false, false]);
+        let diff = visual_diff(|visual| visual.relative_offset = Vector3::zero()).unwrap();
+        assert_eq!(flags(&diff), [false, true, false, false, false]);
+
+        let diff = visual_diff(|visual| visual.color = Property::new(Color::rgb(9, 9, 9))).unwrap();
+        assert_eq!(flags(&diff), [false, false, true, false, false]);
+
+        let diff = visual_diff(|visual| visual.transform.opacity = Property::new(0.5)).unwrap();
+        assert_eq!(flags(&diff), [false, false, false, true, false]);
+
+        let diff = visual_diff(|visual| {
+            visual.border = Some(Border {
+                thickness: 1.0,
+                color: Color::rgb(255, 0, 0),
+            })
+        })
+        .unwrap();
+        assert_eq!(flags(&diff), [false, false, false, false, true]);
+
+        assert_eq!(visual_diff(|visual| visual.editable = true), None);
+    }
+
+    #[test]
+    fn restarts_animations_for_color_and_transform_changes() {
+        let old = scene(&["a"]);
+        let mut new = old.clone();
+        new.visuals[0].transform.scale = Property::new(Vector3::new(2.0, 2.0, 1.0));
+        assert!(diff_scenes(&old, &new).restarts_animations());
+
+        let mut new = old.clone();
+        new.visuals[0].text = "Changed".to_owned();
+        assert!(!diff_scenes(&old, &new).restarts_animations());
+    }
+
+    #[test]
+    fn lays_out_color_glyph_text_again_when_its_color_changes() {
+        let change = |visual: &mut TextVisual| visual.color = Property::new(Color::rgb(0, 128, 0));
+        let old = Scene {
+            visuals: vec![TextVisual {
+                text: "Party 🎉".to_owned(),
+                ..visual("a")
+            }],
+            ..scene(&[])
+        };
+        let mut new = old.clone();
+        change(&mut new.visuals[0]);
+        let diff = diff_scenes(&old, &new);
+        assert_eq!(flags(&diff.updated[0]), [true, false, true, false, false]);
+
+        let diff = visual_diff(change).unwrap();
+        assert_eq!(flags(&diff), [false, false, true, false, false]);
+    }
+
+    // Stands in for the window's visuals: what each visual was last drawn
+    // and placed with, the children in order, and the work patches caused.
+    #[derive(Debug, Default)]
+    struct MockTree {
+        background: Option<Color>,
+        visuals: HashMap<String, MockVisual>,
+        children: Vec<String>,
+        rasterized: Vec<String>,
+        restarts: usize,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct MockVisual {
+        text: Option<String>,
+        anchor_point: Vector2,
+        relative_offset: Vector3,
+        border: Option<Border>,
+    }
+
+    impl VisualTree for MockTree {
+        type Error = String;
+
+        fn apply(&mut self, scene: &Scene, patch: &Patch) -> Result<(), String> {
+            let visual = |id: &str| scene.visual(id).ok_or(format!("no visual '{}'", id));
+            match patch {
+                Patch::SetBackground(background) => self.background = Some(*background),
+                Patch::RemoveVisual(id) => {
+                    self.visuals
+                        .remove(id)
+                        .ok_or(format!("'{}' wasn't created", id))?;
+                    self.children.retain(|child| child != id);
+                }
+                Patch::CreateVisual(id) => {
+                    let text_visual = visual(id)?;
+                    let created = MockVisual {
+                        text: None,
+                        anchor_point: text_visual.anchor_point,
+                        relative_offset: text_visual.relative_offset,
+                        border: text_visual.border,
+                    };
+                    if self.visuals.insert(id.clone(), created).is_some() {
+                        return Err(format!("'{}' was created twice", id));
+                    }
+                }
+                Patch::SetProperty(id, property) => {
+                    let text_visual = visual(id)?;
+                    let hosted = self.visuals.get_mut(id).ok_or(format!("no '{}'", id))?;
+                    match property {
+                        VisualProperty::Placement => {
+                            hosted.anchor_point = text_visual.anchor_point;
+                            hosted.relative_offset = text_visual.relative_offset;
+                        }
+                        VisualProperty::Border => hosted.border = text_visual.border,
+                    }
+                }
+                Patch::Rasterize(id) => {
+                    let text = visual(id)?.text.clone();
+                    let hosted = self.visuals.get_mut(id).ok_or(format!("no '{}'", id))?;
+                    hosted.text = Some(text);
+                    self.rasterized.push(id.clone());
+                }
+                Patch::OrderChildren(ids) => {
+                    if ids.iter().any(|id| !self.visuals.contains_key(id)) {
+                        return Err(format!("can't order {:?}", ids));
+                    }
+                    self.children = ids.clone();
+                }
+                Patch::RestartAnimations => self.restarts += 1,
+            }
+            Ok(())
+        }
+    }
+
+    impl MockTree {
+        fn build(scene: &Scene) -> Self {
+            let mut tree = Self::default();
+            tree.update(
+                &Scene {
+                    visuals: Vec::new(),
+                    ..scene.clone()
+                },
+                scene,
+            );
+            tree.rasterized.clear();
+            tree.restarts = 0;
+            tree
+        }
+
+        fn update(&mut self, old: &Scene, new: &Scene) -> Vec<Patch> {
+            let patches = reconcile(old, new);
+            for patch in &patches {
+                self.apply(new, patch).unwrap();
+            }
+            self.assert_shows(new);
+            patches
+        }
+
+        fn assert_shows(&self, scene: &Scene) {
+            let ids: Vec<_> = scene
+                .visuals
+                .iter()
+                .map(|visual| visual.id.clone())
+                .collect();
+            assert_eq!(self.children, ids);
+            assert_eq!(self.visuals.len(), ids.len());
+            for visual in &scene.visuals {
+                let expected = MockVisual {
+                    text: Some(visual.text.clone()),
+                    anchor_point: visual.anchor_point,
+                    relative_offset: visual.relative_offset,
+                    border: visual.border,
+                };
+                assert_eq!(self.visuals[&visual.id], expected, "{}", visual.id);
+            }
+        }
+    }
+
+    fn id_list(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn reconciles_added_visuals() {
+        let old = scene(&["a", "b"]);
+        let new = scene(&["a", "c", "b"]);
+        let mut tree = MockTree::build(&old);
+        assert_eq!(
+            tree.update(&old, &new),
+            [
+                Patch::CreateVisual("c".to_owned()),
+                Patch::Rasterize("c".to_owned()),
+                Patch::OrderChildren(id_list(&["a", "c", "b"])),
+                Patch::RestartAnimations,
+            ]
+        );
+        assert_eq!(tree.rasterized, ["c"]);
+        assert_eq!(tree.restarts, 1);
+    }
+
+    #[test]
+    fn reconciles_removed_visuals() {
+        let old = scene(&["a", "b", "c"]);
+        let new = scene(&["a", "c"]);
+        let mut tree = MockTree::build(&old);
+        assert_eq!(
+            tree.update(&old, &new),
+            [
+                Patch::RemoveVisual("b".to_owned()),
+                Patch::RestartAnimations,
+            ]
+        );
+        assert!(tree.rasterized.is_empty());
+    }
+
+    #[test]
+    fn reconciles_reordered_visuals() {
+        let old = scene(&["a", "b", "c"]);
+        let new = scene(&["c", "b", "a"]);
+        let mut tree = MockTree::build(&old);
+        assert_eq!(
+            tree.update(&old, &new),
+            [Patch::OrderChildren(id_list(&["c", "b", "a"]))]
+        );
+        assert!(tree.rasterized.is_empty());
+        assert_eq!(tree.restarts, 0);
+    }
+
+    #[test]
+    fn reconciles_text_changes_by_only_rasterizing() {
+        let old = scene(&["a", "b"]);
+        let mut new = old.clone();
+        new.visuals[1].text = "Changed".to_owned();
+        let mut tree = MockTree::build(&old);
+        assert_eq!(tree.update(&old, &new), [Patch::Rasterize("b".to_owned())]);
+        assert_eq!(tree.rasterized, ["b"]);
+        assert_eq!(tree.restarts, 0);
+    }
+
+    #[test]
+    fn reconciles_properties_and_background() {
+        let old = scene(&["a", "b"]);
+        let mut new = old.clone();
+        new.background = Color::rgb(0, 0, 0);
+        new.visuals[0].anchor_point = Vector2::zero();
+        new.visuals[1].border = Some(Border {
+            thickness: 2.0,
+            color: Color::rgb(255, 0, 0),
+        });
+        let mut tree = MockTree::build(&old);
+        assert_eq!(
+            tree.update(&old, &new),
+            [
+                Patch::SetBackground(Color::rgb(0, 0, 0)),
+                Patch::SetProperty("a".to_owned(), VisualProperty::Placement),
+                Patch::SetProperty("b".to_owned(), VisualProperty::Border),
+            ]
+        );
+        assert_eq!(tree.background, Some(Color::rgb(0, 0, 0)));
+        assert!(tree.rasterized.is_empty());
+    }
+
+    #[test]
+    fn reconciles_animation_changes_by_restarting() {
+        let old = scene(&["a"]);
+        let mut new = old.clone();
+        new.visuals[0].transform.opacity = Property::new(0.5);
+        let mut tree = MockTree::build(&old);
+        assert_eq!(tree.update(&old, &new), [Patch::RestartAnimations]);
+        assert_eq!(tree.restarts, 1);
+
+        let mut newer = new.clone();
+        newer.visuals[0].color = Property::new(Color::rgb(0, 0, 255));
+        assert_eq!(tree.update(&new, &newer), [Patch::RestartAnimations]);
+        assert_eq!(tree.restarts, 2);
+    }
+
+    #[test]
+    fn reconciles_a_whole_new_scene() {
+        let old = scene(&["a", "b", "c"]);
+        let mut new = scene(&["d", "c", "a"]);
+        new.visuals[1].text = "Changed".to_owned();
+        let mut tree = MockTree::build(&old);
+        tree.update(&old, &new);
+        assert_eq!(tree.rasterized, ["d", "c"]);
+        assert_eq!(tree.restarts, 1);
+        tree.update(&new, &old);
+    }
+}
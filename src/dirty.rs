@@ -0,0 +1,401 @@
+use crate::metrics::{Cluster, TextMetrics};
+
+// A rectangle of whole pixels in surface coordinates, like a Win32 RECT.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PixelRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl PixelRect {
+    fn intersects(&self, other: &Self) -> bool {
+        self.left < other.right
+            && other.left < self.right
+            && self.top < other.bottom
+            && other.top < self.bottom
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+// What has to be drawn again after a visual's layout changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Redraw {
+    Nothing,
+    // The surface was resized, which throws away what was drawn.
+    Everything,
+    Rects(Vec<PixelRect>),
+}
+
+// A cluster's place on its line and the UTF-16 text it draws.
+#[derive(PartialEq)]
+struct Span<'a> {
+    left: f32,
+    width: f32,
+    text: &'a [u16],
+}
+
+// Compares the layout of 'old_text' with that of 'new_text' line by line
+// and cluster by cluster and returns the parts of the surface that look
+// different. Within a line, clusters that match at the start and at the
+// end are left alone, so typing into a line only redraws from the edit to
+// wherever the rest of the line stops moving. Rects are padded by a
+// quarter of the line height for glyphs that reach past their cluster.
+// Text on a path is always drawn again, since its clusters aren't where
+// its glyphs end up.
+pub fn redraw(old_text: &str, old: &TextMetrics, new_text: &str, new: &TextMetrics) -> Redraw {
+    if old.size != new.size || !old.path.is_empty() || !new.path.is_empty() {
+        return Redraw::Everything;
+    }
+    let old_text: Vec<u16> = old_text.encode_utf16().collect();
+    let new_text: Vec<u16> = new_text.encode_utf16().collect();
+    let (Some(old_lines), Some(new_lines)) =
+        (line_spans(&old_text, old), line_spans(&new_text, new))
+    else {
+        return Redraw::Everything;
+    };
+
+    let width = new.size.X;
+    let mut rects: Vec<PixelRect> = Vec::new();
+    for index in 0..old_lines.len().max(new_lines.len()) {
+        let old_line = old.lines.get(index).zip(old_lines.get(index));
+        let new_line = new.lines.get(index).zip(new_lines.get(index));
+        let (top, bottom, left, right) = match (old_line, new_line) {
+            (Some((old_box, old_spans)), Some((new_box, new_spans))) => {
+                let (top, bottom) = (
+                    old_box.top.min(new_box.top),
+                    (old_box.top + old_box.height).max(new_box.top + new_box.height),
+                );
+                if old_box.top != new_box.top
+                    || old_box.height != new_box.height
+                    || old_box.baseline != new_box.baseline
+                {
+                    (top, bottom, 0.0, width)
+                } else {
+                    match changed_extent(old_spans, new_spans) {
+                        Some((left, right)) => (top, bottom, left, right),
+                        None => continue,
+                    }
+                }
+            }
+            (Some((line, _)), None) | (None, Some((line, _))) => {
+                (line.top, line.top + line.height, 0.0, width)
+            }
+            (None, None) => continue,
+        };
+        let margin = (bottom - top) / 4.0;
+        let rect = PixelRect {
+            left: ((left - margin).floor() as i32).max(0),
+            top: ((top - margin).floor() as i32).max(0),
+            right: ((right + margin).ceil() as i32).min(new.size.X as i32),
+            bottom: ((bottom + margin).ceil() as i32).min(new.size.Y as i32),
+        };
+        if rect.left < rect.right && rect.top < rect.bottom {
+            rects.push(rect);
+        }
+    }
+
+    if rects.is_empty() {
+        return Redraw::Nothing;
+    }
+    Redraw::Rects(merge(rects))
+}
+
+// Splits the clusters into lines and places each one left to right. Returns
+// None when the clusters don't add up to the lines or a line has right to
+// left text, whose clusters aren't laid out in the order they're stored,
+// and for vertical text, whose clusters run down columns.
+fn line_spans<'a>(text: &'a [u16], metrics: &TextMetrics) -> Option<Vec<Vec<Span<'a>>>> {
+    if metrics.is_vertical {
+        return None;
+    }
+    let mut clusters = metrics.clusters.iter();
+    let mut position = 0;
+    let mut lines = Vec::with_capacity(metrics.lines.len());
+    for line in &metrics.lines {
+        let mut spans = Vec::new();
+        let mut left = line.left;
+        let mut length = 0;
+        while length < line.length {
+            let Cluster {
+                width,
+                length: cluster_length,
+                is_right_to_left,
+                ..
+            } = *clusters.next()?;
+            if is_right_to_left {
+                return None;
+            }
+            let end = position + cluster_length as usize;
+            spans.push(Span {
+                left,
+                width,
+                text: text.get(position..end)?,
+            });
+            left += width;
+            length += cluster_length;
+            position = end;
+        }
+        if length != line.length {
+            return None;
+        }
+        lines.push(spans);
+    }
+    Some(lines)
+}
+
+// The horizontal extent of the clusters that differ between two versions
+// of a line, or None if the line looks the same.
+fn changed_extent(old: &[Span], new: &[Span]) -> Option<(f32, f32)> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let changed = old[prefix..old.len() - suffix]
+        .iter()
+        .chain(&new[prefix..new.len() - suffix]);
+    changed.fold(None, |extent, span| {
+        let (left, right) = extent.unwrap_or((f32::MAX, f32::MIN));
+        Some((left.min(span.left), right.max(span.left + span.width)))
+    })
+}
+
+// Merges overlapping rects so no pixel is drawn twice.
+fn merge(mut rects: Vec<PixelRect>) -> Vec<PixelRect> {
+    let mut merged: Vec<PixelRect> = Vec::with_capacity(rects.len());
+    while let Some(mut rect) = rects.pop() {
+        while let Some(index) = merged.iter().position(|other| other.intersects(&rect)) {
+            rect = rect.union(&merged.swap_remove(index));
+        }
+        merged.push(rect);
+    }
+    merged.sort_by_key(|rect| (rect.top, rect.left));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use windows_numerics::Vector2;
+
+    use super::*;
+    use crate::metrics::LineBox;
+
+    // Lays 'text' out in a 200x100 box with every character 10 wide and
+    // every line 20 high, one line per '\n'.
+    fn layout(text: &str) -> TextMetrics {
+        let mut lines = Vec::new();
+        let mut clusters = Vec::new();
+        let count = text.split('\n').count();
+        for (index, line) in text.split('\n').enumerate() {
+            let newline_length = u32::from(index + 1 < count);
+            for character in line.chars() {
+                clusters.push(Cluster {
+                    width: 10.0,
+                    length: character.len_utf16() as u32,
+                    ..Default::default()
+                });
+            }
+            if newline_length == 1 {
+                clusters.push(Cluster {
+                    length: 1,
+                    is_newline: true,
+                    ..Default::default()
+                });
+            }
+            lines.push(LineBox {
+                top: index as f32 * 20.0,
+                width: line.chars().count() as f32 * 10.0,
+                height: 20.0,
+                baseline: 16.0,
+                length: line.encode_utf16().count() as u32 + newline_length,
+                newline_length,
+                ..Default::default()
+            });
+        }
+        let mut metrics = TextMetrics::new(
+            Vector2::new(200.0, 100.0),
+            Default::default(),
+            lines,
+            clusters,
+        );
+        metrics.size = Vector2::new(200.0, 100.0);
+        metrics
+    }
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> PixelRect {
+        PixelRect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    fn redraw_text(old: &str, new: &str) -> Redraw {
+        redraw(old, &layout(old), new, &layout(new))
+    }
+
+    #[test]
+    fn same_layout_redraws_nothing() {
+        assert_eq!(redraw_text("hello\nworld", "hello\nworld"), Redraw::Nothing);
+    }
+
+    #[test]
+    fn editing_the_start_of_a_line_redraws_up_to_where_it_matches_again() {
+        assert_eq!(
+            redraw_text("hello", "jello"),
+            Redraw::Rects(vec![rect(0, 0, 15, 25)])
+        );
+    }
+
+    #[test]
+    fn appending_to_a_line_redraws_only_the_new_text() {
+        assert_eq!(
+            redraw_text("hello", "hello!"),
+            Redraw::Rects(vec![rect(45, 0, 65, 25)])
+        );
+    }
+
+    #[test]
+    fn inserting_into_a_line_redraws_everything_after_it_that_moved() {
+        assert_eq!(
+            redraw_text("hello", "heXllo"),
+            Redraw::Rects(vec![rect(15, 0, 65, 25)])
+        );
+    }
+
+    #[test]
+    fn editing_one_line_leaves_the_others_alone() {
+        assert_eq!(
+            redraw_text("one\ntwo\nsix", "one\ntwo!\nsix"),
+            Redraw::Rects(vec![rect(25, 15, 45, 45)])
+        );
+    }
+
+    #[test]
+    fn a_new_line_is_redrawn_across_the_whole_width() {
+        // The newline added to the first line touches the new second line,
+        // so the two rects are merged.
+        assert_eq!(
+            redraw_text("a", "a\nb"),
+            Redraw::Rects(vec![rect(0, 0, 200, 45)])
+        );
+        assert_eq!(
+            redraw_text("a\nb", "a"),
+            Redraw::Rects(vec![rect(0, 0, 200, 45)])
+        );
+    }
+
+    #[test]
+    fn a_line_that_moves_is_redrawn_across_the_whole_width() {
+        let old = layout("one\ntwo");
+        let mut new = layout("one\ntwo");
+        new.lines[1].top = 30.0;
+        assert_eq!(
+            redraw("one\ntwo", &old, "one\ntwo", &new),
+            Redraw::Rects(vec![rect(0, 12, 200, 58)])
+        );
+    }
+
+    #[test]
+    fn a_new_size_redraws_everything() {
+        let old = layout("hello");
+        let mut new = layout("hello");
+        new.size = Vector2::new(100.0, 100.0);
+        assert_eq!(redraw("hello", &old, "hello", &new), Redraw::Everything);
+    }
+
+    #[test]
+    fn right_to_left_text_redraws_everything() {
+        let old = layout("hello");
+        let mut new = layout("hellx");
+        new.clusters[4].is_right_to_left = true;
+        assert_eq!(redraw("hello", &old, "hellx", &new), Redraw::Everything);
+    }
+
+    #[test]
+    fn vertical_text_redraws_everything() {
+        let mut old = layout("hello");
+        let mut new = layout("hellx");
+        old.is_vertical = true;
+        new.is_vertical = true;
+        assert_eq!(redraw("hello", &old, "hellx", &new), Redraw::Everything);
+    }
+
+    #[test]
+    fn text_on_a_path_redraws_everything() {
+        let old = layout("hello");
+        let mut new = layout("hello");
+        new.path = vec![Vector2::new(0.0, 0.0), Vector2::new(50.0, 0.0)];
+        assert_eq!(redraw("hello", &old, "hello", &new), Redraw::Everything);
+    }
+
+    #[test]
+    fn clusters_that_dont_add_up_to_the_lines_redraw_everything() {
+        let old = layout("hello");
+        let mut new = layout("hellx");
+        new.clusters.pop();
+        assert_eq!(redraw("hello", &old, "hellx", &new), Redraw::Everything);
+    }
+
+    #[test]
+    fn merge_joins_overlapping_rects() {
+        assert_eq!(
+            merge(vec![rect(0, 0, 10, 10), rect(5, 5, 15, 15)]),
+            vec![rect(0, 0, 15, 15)]
+        );
+    }
+
+    #[test]
+    fn merge_joins_rects_that_only_overlap_once_merged() {
+        // The first and last don't overlap until the middle one joins them.
+        assert_eq!(
+            merge(vec![
+                rect(0, 0, 10, 10),
+                rect(20, 20, 30, 30),
+                rect(5, 5, 25, 25),
+            ]),
+            vec![rect(0, 0, 30, 30)]
+        );
+        assert_eq!(
+            merge(vec![
+                rect(0, 0, 10, 10),
+                rect(5, 5, 25, 25),
+                rect(20, 20, 30, 30),
+            ]),
+            vec![rect(0, 0, 30, 30)]
+        );
+    }
+
+    #[test]
+    fn merge_keeps_rects_that_only_touch_apart_in_order() {
+        assert_eq!(
+            merge(vec![
+                rect(10, 10, 20, 20),
+                rect(0, 10, 10, 20),
+                rect(0, 0, 10, 10),
+            ]),
+            vec![
+                rect(0, 0, 10, 10),
+                rect(0, 10, 10, 20),
+                rect(10, 10, 20, 20)
+            ]
+        );
+    }
+}
@@ -0,0 +1,846 @@
+use std::ops::Range;
+
+use crate::unicode::in_ranges;
+
+// Where the caret for a position is drawn: its line, and the top of the
+// caret and its height at 'x' on that line.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Caret {
+    pub line: usize,
+    pub x: f32,
+    pub top: f32,
+    pub height: f32,
+}
+
+// What editing needs from laid out text. Positions are byte offsets into
+// the text and rectangles are (x, y, width, height) in layout coordinates.
+pub trait CaretLayout {
+    fn line_count(&self) -> usize;
+    fn caret(&self, position: usize) -> Caret;
+    // The text on a line, without the line break that ends it.
+    fn line_range(&self, line: usize) -> Range<usize>;
+    // The position on a line whose caret is closest to 'x'.
+    fn position_at(&self, line: usize, x: f32) -> usize;
+    // The boxes covering a range of text, one or more for each line.
+    fn range_rects(&self, range: Range<usize>) -> Vec<[f32; 4]>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Movement {
+    PreviousGrapheme,
+    NextGrapheme,
+    PreviousWord,
+    NextWord,
+    LineStart,
+    LineEnd,
+    PreviousLine,
+    NextLine,
+    TextStart,
+    TextEnd,
+}
+
+// The caret, the selection and the text being composed with an IME, in
+// layout coordinates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EditShapes {
+    pub caret: [f32; 4],
+    pub selection: Vec<[f32; 4]>,
+    pub composition: Vec<[f32; 4]>,
+}
+
+const CARET_WIDTH: f32 = 2.0;
+const COMPOSITION_UNDERLINE: f32 = 1.0;
+
+// A text buffer with a caret and a selection, edited the way a single
+// text box is. The selection runs from 'anchor' to 'caret', both always on
+// grapheme boundaries. Text an IME is composing sits in the buffer like
+// typed text until it's committed or cancelled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEditor {
+    text: String,
+    caret: usize,
+    anchor: usize,
+    composition: Option<Range<usize>>,
+    // Where moving between lines aims for, kept while moving so passing
+    // through a short line doesn't pull the caret over.
+    goal_x: Option<f32>,
+}
+
+impl TextEditor {
+    // Starts with the caret at the end of the text.
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let end = text.len();
+        Self {
+            text,
+            caret: end,
+            anchor: end,
+            composition: None,
+            goal_x: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    pub fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection()]
+    }
+
+    pub fn composition(&self) -> Option<Range<usize>> {
+        self.composition.clone()
+    }
+
+    // Replaces the text when it's changed from elsewhere, keeping the caret
+    // and selection where they were as far as the new text allows.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.composition = None;
+        self.goal_x = None;
+        self.caret = grapheme_floor(&self.text, self.caret);
+        self.anchor = grapheme_floor(&self.text, self.anchor);
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = 0;
+        self.caret = self.text.len();
+        self.goal_x = None;
+    }
+
+    // Puts the caret at the grapheme boundary at or before 'position',
+    // selecting from the anchor if 'extend' is set.
+    pub fn set_caret(&mut self, position: usize, extend: bool) {
+        self.caret = grapheme_floor(&self.text, position);
+        if !extend {
+            self.anchor = self.caret;
+        }
+        self.goal_x = None;
+    }
+
+    // Moving by graphemes without extending collapses a selection to the
+    // edge it's moving towards instead. Graphemes and words are in the
+    // order they're stored, whichever way the text reads.
+    pub fn move_caret(&mut self, movement: Movement, extend: bool, layout: &impl CaretLayout) {
+        let selection = self.selection();
+        let target = match movement {
+            Movement::PreviousGrapheme if !extend && !selection.is_empty() => selection.start,
+            Movement::NextGrapheme if !extend && !selection.is_empty() => selection.end,
+            _ => self.target(movement, layout),
+        };
+        let goal_x = matches!(movement, Movement::PreviousLine | Movement::NextLine)
+            .then(|| self.goal_x.unwrap_or_else(|| layout.caret(self.caret).x));
+        self.set_caret(target, extend);
+        self.goal_x = goal_x;
+    }
+
+    // Replaces the selection with 'text' and puts the caret after it.
+    pub fn insert(&mut self, text: &str) {
+        let selection = self.selection();
+        self.replace(selection, text);
+    }
+
+    // Deletes the selection, or if there isn't one, the text between the
+    // caret and where 'movement' would take it.
+    pub fn delete(&mut self, movement: Movement, layout: &impl CaretLayout) {
+        let mut range = self.selection();
+        if range.is_empty() {
+            let target = self.target(movement, layout);
+            range = self.caret.min(target)..self.caret.max(target);
+        }
+        self.replace(range, "");
+    }
+
+    // Shows what an IME is composing in place of the selection, or of
+    // what it was composing before. 'caret' is a byte offset into 'text'.
+    pub fn set_composition(&mut self, text: &str, caret: usize) {
+        let range = self.composition.take().unwrap_or_else(|| self.selection());
+        let start = range.start;
+        self.replace(range, text);
+        self.composition = (!text.is_empty()).then(|| start..start + text.len());
+        self.caret = start + caret.min(text.len());
+        self.anchor = self.caret;
+    }
+
+    // Replaces the composition with the text the IME settled on.
+    pub fn commit_composition(&mut self, text: &str) {
+        match self.composition.take() {
+            Some(range) => self.replace(range, text),
+            None => self.insert(text),
+        }
+    }
+
+    pub fn cancel_composition(&mut self) {
+        if let Some(range) = self.composition.take() {
+            self.replace(range, "");
+        }
+    }
+
+    pub fn shapes(&self, layout: &impl CaretLayout) -> EditShapes {
+        let caret = layout.caret(self.caret);
+        let selection = self.selection();
+        EditShapes {
+            caret: [
+                caret.x - CARET_WIDTH / 2.0,
+                caret.top,
+                CARET_WIDTH,
+                caret.height,
+            ],
+            selection: if selection.is_empty() {
+                Vec::new()
+            } else {
+                layout.range_rects(selection)
+            },
+            composition: self
+                .composition
+                .clone()
+                .map(|range| {
+                    layout
+                        .range_rects(range)
+                        .into_iter()
+                        .map(|[x, y, width, height]| {
+                            [
+                                x,
+                                y + height - COMPOSITION_UNDERLINE,
+                                width,
+                                COMPOSITION_UNDERLINE,
+                            ]
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn replace(&mut self, range: Range<usize>, text: &str) {
+        self.text.replace_range(range.clone(), text);
+        self.caret = range.start + text.len();
+        self.anchor = self.caret;
+        self.goal_x = None;
+    }
+
+    // Where 'movement' takes the caret.
+    fn target(&self, movement: Movement, layout: &impl CaretLayout) -> usize {
+        let text = &self.text;
+        match movement {
+            Movement::PreviousGrapheme => previous_grapheme(text, self.caret),
+            Movement::NextGrapheme => next_grapheme(text, self.caret),
+            Movement::PreviousWord => previous_word(text, self.caret),
+            Movement::NextWord => next_word(text, self.caret),
+            Movement::LineStart => layout.line_range(layout.caret(self.caret).line).start,
+            Movement::LineEnd => {
+                let line = layout.caret(self.caret).line;
+                let end = layout.line_range(line).end;
+                // A line that wrapped ends where the next one starts, so
+                // stop before its last grapheme to stay on it.
+                if line + 1 < layout.line_count() && end == layout.line_range(line + 1).start {
+                    previous_grapheme(text, end).max(layout.line_range(line).start)
+                } else {
+                    end
+                }
+            }
+            Movement::PreviousLine | Movement::NextLine => {
+                let caret = layout.caret(self.caret);
+                let x = self.goal_x.unwrap_or(caret.x);
+                match movement {
+                    Movement::PreviousLine if caret.line == 0 => 0,
+                    Movement::PreviousLine => layout.position_at(caret.line - 1, x),
+                    _ if caret.line + 1 >= layout.line_count() => text.len(),
+                    _ => layout.position_at(caret.line + 1, x),
+                }
+            }
+            Movement::TextStart => 0,
+            Movement::TextEnd => text.len(),
+        }
+    }
+}
+
+// Marks, joiners, variation selectors, emoji modifiers and tags, which
+// stay with the character before them.
+const EXTENDING: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2),
+    (0x05C4, 0x05C5),
+    (0x05C7, 0x05C7),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06DC),
+    (0x06DF, 0x06E4),
+    (0x06E7, 0x06E8),
+    (0x06EA, 0x06ED),
+    (0x0711, 0x0711),
+    (0x0730, 0x074A),
+    (0x0900, 0x0903),
+    (0x093A, 0x093C),
+    (0x093E, 0x094F),
+    (0x0951, 0x0957),
+    (0x0962, 0x0963),
+    (0x0981, 0x0983),
+    (0x09BC, 0x09BC),
+    (0x09BE, 0x09CD),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200C, 0x200D),
+    (0x20D0, 0x20FF),
+    (0x302A, 0x302F),
+    (0x3099, 0x309A),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0x1F3FB, 0x1F3FF),
+    (0xE0020, 0xE007F),
+    (0xE0100, 0xE01EF),
+];
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+fn is_extending(character: char) -> bool {
+    in_ranges(EXTENDING, character)
+}
+
+fn is_regional_indicator(character: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&character)
+}
+
+// The end of the grapheme starting at 'start': a character with the marks
+// and modifiers after it, CR LF, a pair of regional indicators making a
+// flag, or an emoji sequence held together by zero width joiners. Close
+// enough to Unicode's extended grapheme clusters for moving a caret.
+fn grapheme_end(text: &str, start: usize) -> usize {
+    let mut characters = text[start..]
+        .char_indices()
+        .map(|(index, character)| (start + index, character));
+    let Some((_, first)) = characters.next() else {
+        return start;
+    };
+    let mut characters = characters.peekable();
+    let mut end = start + first.len_utf8();
+    if first == '\r' {
+        if let Some(&(index, '\n')) = characters.peek() {
+            end = index + 1;
+        }
+        return end;
+    }
+    if first == '\n' || first.is_control() {
+        return end;
+    }
+    if is_regional_indicator(first)
+        && let Some(&(index, second)) = characters.peek()
+        && is_regional_indicator(second)
+    {
+        end = index + second.len_utf8();
+        characters.next();
+    }
+    let mut after_joiner = false;
+    for (index, character) in characters {
+        if is_extending(character)
+            || (after_joiner && !character.is_whitespace() && !character.is_control())
+        {
+            after_joiner = character == ZERO_WIDTH_JOINER;
+            end = index + character.len_utf8();
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+fn next_grapheme(text: &str, position: usize) -> usize {
+    grapheme_end(text, grapheme_floor(text, position))
+}
+
+fn previous_grapheme(text: &str, position: usize) -> usize {
+    let mut start = 0;
+    loop {
+        let end = grapheme_end(text, start);
+        if end >= position || end == start {
+            return start;
+        }
+        start = end;
+    }
+}
+
+// The grapheme boundary at or before 'position'.
+fn grapheme_floor(text: &str, position: usize) -> usize {
+    let position = position.min(text.len());
+    let mut start = 0;
+    loop {
+        let end = grapheme_end(text, start);
+        if end > position || end == start {
+            return start;
+        }
+        start = end;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum WordClass {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn word_class(text: &str, position: usize) -> WordClass {
+    match text[position..].chars().next() {
+        Some(character) if character.is_whitespace() => WordClass::Space,
+        Some(character) if character.is_alphanumeric() || character == '_' => WordClass::Word,
+        _ => WordClass::Punctuation,
+    }
+}
+
+// The start of the next word: past the rest of this word or run of
+// punctuation, then past the spaces after it.
+fn next_word(text: &str, position: usize) -> usize {
+    let mut position = grapheme_floor(text, position);
+    if position == text.len() {
+        return position;
+    }
+    let class = word_class(text, position);
+    if class != WordClass::Space {
+        while position < text.len() && word_class(text, position) == class {
+            position = next_grapheme(text, position);
+        }
+    }
+    while position < text.len() && word_class(text, position) == WordClass::Space {
+        position = next_grapheme(text, position);
+    }
+    position
+}
+
+// The start of this word, or of the one before it when already there.
+fn previous_word(text: &str, position: usize) -> usize {
+    let mut position = grapheme_floor(text, position);
+    while position > 0 && word_class(text, previous_grapheme(text, position)) == WordClass::Space {
+        position = previous_grapheme(text, position);
+    }
+    if position == 0 {
+        return 0;
+    }
+    let class = word_class(text, previous_grapheme(text, position));
+    while position > 0 && word_class(text, previous_grapheme(text, position)) == class {
+        position = previous_grapheme(text, position);
+    }
+    position
+}
+
+// Layouts count UTF-16 code units, and the editor bytes.
+pub fn utf16_offset(text: &str, position: usize) -> u32 {
+    text[..position.min(text.len())].encode_utf16().count() as u32
+}
+
+pub fn byte_offset(text: &str, utf16_offset: u32) -> usize {
+    let mut units = 0;
+    for (index, character) in text.char_indices() {
+        if units >= utf16_offset {
+            return index;
+        }
+        units += character.len_utf16() as u32;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character 10 wide and every line 20 high, with lines given as
+    // the byte ranges of their text.
+    struct Grid {
+        text: String,
+        lines: Vec<Range<usize>>,
+    }
+
+    impl Grid {
+        // A line for each line of the text.
+        fn new(text: &str) -> Self {
+            let mut lines = Vec::new();
+            let mut start = 0;
+            for line in text.split('\n') {
+                lines.push(start..start + line.len());
+                start += line.len() + 1;
+            }
+            Self {
+                text: text.to_owned(),
+                lines,
+            }
+        }
+
+        fn x(&self, line: usize, position: usize) -> f32 {
+            self.text[self.lines[line].start..position].chars().count() as f32 * 10.0
+        }
+    }
+
+    impl CaretLayout for Grid {
+        fn line_count(&self) -> usize {
+            self.lines.len()
+        }
+
+        // A position where a line wrapped is at the start of the next one.
+        fn caret(&self, position: usize) -> Caret {
+            let line = (0..self.lines.len())
+                .find(|&line| {
+                    let range = &self.lines[line];
+                    position < range.end
+                        || (position == range.end
+                            && self
+                                .lines
+                                .get(line + 1)
+                                .is_none_or(|next| next.start != position))
+                })
+                .unwrap_or(self.lines.len() - 1);
+            Caret {
+                line,
+                x: self.x(line, position),
+                top: line as f32 * 20.0,
+                height: 20.0,
+            }
+        }
+
+        fn line_range(&self, line: usize) -> Range<usize> {
+            self.lines[line].clone()
+        }
+
+        fn position_at(&self, line: usize, x: f32) -> usize {
+            let range = self.lines[line].clone();
+            let count = (x / 10.0).round() as usize;
+            self.text[range.clone()]
+                .char_indices()
+                .nth(count)
+                .map_or(range.end, |(index, _)| range.start + index)
+        }
+
+        fn range_rects(&self, range: Range<usize>) -> Vec<[f32; 4]> {
+            (0..self.lines.len())
+                .filter_map(|line| {
+                    let start = range.start.max(self.lines[line].start);
+                    let end = range.end.min(self.lines[line].end);
+                    (start < end).then(|| {
+                        let x = self.x(line, start);
+                        [x, line as f32 * 20.0, self.x(line, end) - x, 20.0]
+                    })
+                })
+                .collect()
+        }
+    }
+
+    // Where moving the caret from 'start' over and over takes it.
+    fn stops(text: &str, start: usize, movement: Movement) -> Vec<usize> {
+        let layout = Grid::new(text);
+        let mut editor = TextEditor::new(text);
+        editor.set_caret(start, false);
+        let mut stops = Vec::new();
+        loop {
+            editor.move_caret(movement, false, &layout);
+            if stops.last() == Some(&editor.caret()) || editor.caret() == start {
+                return stops;
+            }
+            stops.push(editor.caret());
+        }
+    }
+
+    const GRAPHEMES: &str =
+        "e\u{301}x\u{1F1EF}\u{1F1F5}\u{1F469}\u{200D}\u{1F469}\u{200D}\u{1F467}\r\nz";
+
+    #[test]
+    fn graphemes_keep_marks_flags_emoji_sequences_and_crlf_together() {
+        assert_eq!(
+            stops(GRAPHEMES, 0, Movement::NextGrapheme),
+            [3, 4, 12, 30, 32, 33]
+        );
+        assert_eq!(
+            stops(GRAPHEMES, GRAPHEMES.len(), Movement::PreviousGrapheme),
+            [32, 30, 12, 4, 3, 0]
+        );
+    }
+
+    #[test]
+    fn the_caret_is_kept_on_grapheme_boundaries() {
+        let mut editor = TextEditor::new(GRAPHEMES);
+        editor.set_caret(2, false);
+        assert_eq!(editor.caret(), 0);
+        editor.set_caret(20, false);
+        assert_eq!(editor.caret(), 12);
+        editor.set_caret(31, false);
+        assert_eq!(editor.caret(), 30);
+        editor.set_caret(100, false);
+        assert_eq!(editor.caret(), GRAPHEMES.len());
+    }
+
+    #[test]
+    fn words_stop_at_the_starts_of_words_and_punctuation() {
+        let text = "hello, world  foo_bar!";
+        assert_eq!(stops(text, 0, Movement::NextWord), [5, 7, 14, 21, 22]);
+        assert_eq!(
+            stops(text, text.len(), Movement::PreviousWord),
+            [21, 14, 7, 5, 0]
+        );
+    }
+
+    #[test]
+    fn moving_between_lines_keeps_aiming_for_the_same_x() {
+        let text = "abcdef\nab\nabcdef";
+        let layout = Grid::new(text);
+        let mut editor = TextEditor::new(text);
+        editor.set_caret(5, false);
+        let mut positions = Vec::new();
+        for movement in [
+            Movement::NextLine,
+            Movement::NextLine,
+            Movement::NextLine,
+            Movement::PreviousLine,
+            Movement::PreviousLine,
+            Movement::PreviousLine,
+            Movement::PreviousLine,
+        ] {
+            editor.move_caret(movement, false, &layout);
+            positions.push(editor.caret());
+        }
+        assert_eq!(positions, [9, 15, 16, 9, 5, 0, 0]);
+    }
+
+    #[test]
+    fn moving_along_a_line_starts_a_new_goal() {
+        let text = "abcdef\nab\nabcdef";
+        let layout = Grid::new(text);
+        let mut editor = TextEditor::new(text);
+        editor.set_caret(5, false);
+        editor.move_caret(Movement::NextLine, false, &layout);
+        editor.move_caret(Movement::PreviousGrapheme, false, &layout);
+        editor.move_caret(Movement::NextLine, false, &layout);
+        assert_eq!(editor.caret(), 11);
+    }
+
+    #[test]
+    fn line_start_and_end_stay_on_the_caret_line() {
+        let text = "abc\ndefg";
+        let layout = Grid::new(text);
+        let mut editor = TextEditor::new(text);
+        editor.set_caret(6, false);
+        editor.move_caret(Movement::LineStart, false, &layout);
+        assert_eq!(editor.caret(), 4);
+        editor.move_caret(Movement::LineEnd, false, &layout);
+        assert_eq!(editor.caret(), 8);
+        editor.move_caret(Movement::TextStart, false, &layout);
+        editor.move_caret(Movement::LineEnd, false, &layout);
+        assert_eq!(editor.caret(), 3);
+        editor.move_caret(Movement::TextEnd, false, &layout);
+        assert_eq!(editor.caret(), 8);
+    }
+
+    #[test]
+    fn line_end_stops_before_where_a_line_wrapped() {
+        let layout = Grid {
+            text: "abcdefgh".to_owned(),
+            lines: vec![0..4, 4..8],
+        };
+        let mut editor = TextEditor::new("abcdefgh");
+        editor.set_caret(1, false);
+        editor.move_caret(Movement::LineEnd, false, &layout);
+        assert_eq!(editor.caret(), 3);
+        editor.set_caret(4, false);
+        editor.move_caret(Movement::LineStart, false, &layout);
+        assert_eq!(editor.caret(), 4);
+        editor.move_caret(Movement::LineEnd, false, &layout);
+        assert_eq!(editor.caret(), 8);
+    }
+
+    #[test]
+    fn extending_selects_from_the_anchor() {
+        let layout = Grid::new("hello world");
+        let mut editor = TextEditor::new("hello world");
+        editor.set_caret(0, false);
+        editor.move_caret(Movement::NextWord, true, &layout);
+        editor.move_caret(Movement::NextGrapheme, true, &layout);
+        assert_eq!(editor.selection(), 0..7);
+        assert_eq!(editor.selected_text(), "hello w");
+
+        editor.set_caret(3, true);
+        assert_eq!(editor.selection(), 0..3);
+        editor.set_caret(9, false);
+        editor.set_caret(6, true);
+        assert_eq!(editor.selection(), 6..9);
+        assert_eq!(editor.caret(), 6);
+    }
+
+    #[test]
+    fn moving_by_graphemes_collapses_a_selection_to_its_edge() {
+        let layout = Grid::new("hello world");
+        let mut editor = TextEditor::new("hello world");
+        editor.set_caret(9, false);
+        editor.set_caret(6, true);
+        editor.move_caret(Movement::NextGrapheme, false, &layout);
+        assert_eq!((editor.caret(), editor.selection()), (9, 9..9));
+
+        editor.select_all();
+        editor.move_caret(Movement::PreviousGrapheme, false, &layout);
+        assert_eq!((editor.caret(), editor.selection()), (0, 0..0));
+
+        // Other movements go from the caret.
+        editor.set_caret(2, false);
+        editor.set_caret(8, true);
+        editor.move_caret(Movement::PreviousWord, false, &layout);
+        assert_eq!((editor.caret(), editor.selection()), (6, 6..6));
+    }
+
+    #[test]
+    fn typing_replaces_the_selection() {
+        let layout = Grid::new("hello world");
+        let mut editor = TextEditor::new("hello world");
+        editor.set_caret(0, false);
+        editor.set_caret(5, true);
+        editor.insert("goodbye");
+        assert_eq!(editor.text(), "goodbye world");
+        assert_eq!((editor.caret(), editor.selection()), (7, 7..7));
+
+        editor.set_caret(8, false);
+        editor.set_caret(13, true);
+        editor.delete(Movement::PreviousGrapheme, &layout);
+        assert_eq!(editor.text(), "goodbye ");
+        assert_eq!(editor.caret(), 8);
+    }
+
+    #[test]
+    fn deleting_without_a_selection_deletes_up_to_the_movement() {
+        let text = "ne\u{301}e new";
+        let layout = Grid::new(text);
+        let mut editor = TextEditor::new(text);
+        editor.set_caret(4, false);
+        editor.delete(Movement::PreviousGrapheme, &layout);
+        assert_eq!(editor.text(), "ne new");
+        assert_eq!(editor.caret(), 1);
+        editor.delete(Movement::NextWord, &layout);
+        assert_eq!(editor.text(), "nnew");
+        editor.move_caret(Movement::TextEnd, false, &layout);
+        editor.delete(Movement::NextGrapheme, &layout);
+        assert_eq!(editor.text(), "nnew");
+    }
+
+    #[test]
+    fn composing_replaces_the_previous_composition() {
+        let mut editor = TextEditor::new("abcd");
+        editor.set_caret(2, false);
+        editor.set_composition("\u{304B}", 3);
+        assert_eq!(editor.text(), "ab\u{304B}cd");
+        assert_eq!(editor.composition(), Some(2..5));
+        assert_eq!(editor.caret(), 5);
+
+        editor.set_composition("\u{304B}\u{3093}", 3);
+        assert_eq!(editor.text(), "ab\u{304B}\u{3093}cd");
+        assert_eq!(editor.composition(), Some(2..8));
+        assert_eq!(editor.caret(), 5);
+
+        editor.commit_composition("\u{6F22}");
+        assert_eq!(editor.text(), "ab\u{6F22}cd");
+        assert_eq!(editor.composition(), None);
+        assert_eq!(editor.caret(), 5);
+    }
+
+    #[test]
+    fn composing_replaces_the_selection() {
+        let mut editor = TextEditor::new("abcd");
+        editor.select_all();
+        editor.set_composition("xy", 1);
+        assert_eq!(editor.text(), "xy");
+        assert_eq!(editor.composition(), Some(0..2));
+        assert_eq!((editor.caret(), editor.selection()), (1, 1..1));
+    }
+
+    #[test]
+    fn cancelling_a_composition_removes_its_text() {
+        let mut editor = TextEditor::new("abcd");
+        editor.set_caret(2, false);
+        editor.set_composition("xyz", 3);
+        editor.cancel_composition();
+        assert_eq!(editor.text(), "abcd");
+        assert_eq!((editor.caret(), editor.composition()), (2, None));
+
+        editor.set_composition("xyz", 3);
+        editor.set_composition("", 0);
+        assert_eq!(editor.text(), "abcd");
+        assert_eq!(editor.composition(), None);
+
+        // Nothing to cancel.
+        editor.cancel_composition();
+        assert_eq!(editor.text(), "abcd");
+    }
+
+    #[test]
+    fn committing_without_a_composition_inserts() {
+        let mut editor = TextEditor::new("abcd");
+        editor.set_caret(1, false);
+        editor.set_caret(3, true);
+        editor.commit_composition("x");
+        assert_eq!(editor.text(), "axd");
+        assert_eq!(editor.caret(), 2);
+    }
+
+    #[test]
+    fn replacing_the_text_keeps_the_caret_on_a_boundary() {
+        let mut editor = TextEditor::new("hello");
+        editor.set_composition("x", 1);
+        editor.set_text("he\u{301}");
+        assert_eq!(editor.caret(), 4);
+        assert_eq!(editor.composition(), None);
+        editor.set_caret(1, false);
+        editor.set_caret(4, true);
+        editor.set_text("he\u{301}llo");
+        assert_eq!(editor.selection(), 1..4);
+        editor.set_text("h");
+        assert_eq!(editor.selection(), 1..1);
+    }
+
+    #[test]
+    fn shapes_cover_the_caret_selection_and_composition() {
+        let text = "abc\ndef";
+        let layout = Grid::new(text);
+        let mut editor = TextEditor::new(text);
+        editor.set_caret(1, false);
+        editor.set_caret(6, true);
+        assert_eq!(
+            editor.shapes(&layout),
+            EditShapes {
+                caret: [19.0, 20.0, 2.0, 20.0],
+                selection: vec![[10.0, 0.0, 20.0, 20.0], [0.0, 20.0, 20.0, 20.0]],
+                composition: Vec::new(),
+            }
+        );
+
+        editor.set_composition("xy", 2);
+        assert_eq!(
+            editor.shapes(&Grid::new(editor.text())),
+            EditShapes {
+                caret: [29.0, 0.0, 2.0, 20.0],
+                selection: Vec::new(),
+                composition: vec![[10.0, 19.0, 20.0, 1.0]],
+            }
+        );
+    }
+
+    #[test]
+    fn offsets_convert_between_bytes_and_utf16() {
+        let text = "a\u{20AC}\u{1F600}b";
+        let boundaries = [(0, 0), (1, 1), (4, 2), (8, 4), (9, 5)];
+        for (bytes, units) in boundaries {
+            assert_eq!(utf16_offset(text, bytes), units);
+            assert_eq!(byte_offset(text, units), bytes);
+        }
+        // Inside a surrogate pair rounds up to the next character.
+        assert_eq!(byte_offset(text, 3), 8);
+        assert_eq!(byte_offset(text, 100), text.len());
+        assert_eq!(utf16_offset(text, 100), 5);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::unicode::in_ranges;
+
+// Characters shown as color emoji unless they're followed by the text
+// presentation selector: Unicode's Emoji_Presentation property as of
+// Unicode 16.
+const EMOJI_PRESENTATION: &[(u32, u32)] = &[
+    (0x231A, 0x231B),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F1E6, 0x1F1FF),
+    (0x1F201, 0x1F201),
+    (0x1F21A, 0x1F21A),
+    (0x1F22F, 0x1F22F),
+    (0x1F232, 0x1F236),
+    (0x1F238, 0x1F23A),
+    (0x1F250, 0x1F251),
+    (0x1F300, 0x1F320),
+    (0x1F32D, 0x1F335),
+    (0x1F337, 0x1F37C),
+    (0x1F37E, 0x1F393),
+    (0x1F3A0, 0x1F3CA),
+    (0x1F3CF, 0x1F3D3),
+    (0x1F3E0, 0x1F3F0),
+    (0x1F3F4, 0x1F3F4),
+    (0x1F3F8, 0x1F43E),
+    (0x1F440, 0x1F440),
+    (0x1F442, 0x1F4FC),
+    (0x1F4FF, 0x1F53D),
+    (0x1F54B, 0x1F54E),
+    (0x1F550, 0x1F567),
+    (0x1F57A, 0x1F57A),
+    (0x1F595, 0x1F596),
+    (0x1F5A4, 0x1F5A4),
+    (0x1F5FB, 0x1F64F),
+    (0x1F680, 0x1F6C5),
+    (0x1F6CC, 0x1F6CC),
+    (0x1F6D0, 0x1F6D2),
+    (0x1F6D5, 0x1F6D7),
+    (0x1F6DC, 0x1F6DF),
+    (0x1F6EB, 0x1F6EC),
+    (0x1F6F4, 0x1F6FC),
+    (0x1F7E0, 0x1F7EB),
+    (0x1F7F0, 0x1F7F0),
+    (0x1F90C, 0x1F93A),
+    (0x1F93C, 0x1F945),
+    (0x1F947, 0x1F9FF),
+    (0x1FA70, 0x1FA7C),
+    (0x1FA80, 0x1FA89),
+    (0x1FA8F, 0x1FAC6),
+    (0x1FACE, 0x1FADC),
+    (0x1FADF, 0x1FAE9),
+    (0x1FAF0, 0x1FAF8),
+];
+
+const TEXT_PRESENTATION_SELECTOR: char = '\u{FE0E}';
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+const COMBINING_ENCLOSING_KEYCAP: char = '\u{20E3}';
+
+fn has_emoji_presentation(character: char) -> bool {
+    in_ranges(EMOJI_PRESENTATION, character)
+}
+
+// Whether the text has characters fonts draw as color emoji: ones that are
+// emoji by default, ones asking for emoji presentation with U+FE0F like
+// "❤️", and keycaps like "1️⃣". Color glyphs in fonts that aren't emoji
+// fonts can't be told from the text alone.
+pub fn has_color_glyphs(text: &str) -> bool {
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        match characters.peek() {
+            Some(&TEXT_PRESENTATION_SELECTOR) => {
+                characters.next();
+            }
+            Some(&EMOJI_PRESENTATION_SELECTOR) => return true,
+            _ if character == COMBINING_ENCLOSING_KEYCAP || has_emoji_presentation(character) => {
+                return true;
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_has_no_color_glyphs() {
+        for text in ["", "hello", "café", "#1*", "\u{2764}", "日本語"] {
+            assert!(!has_color_glyphs(text), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn emoji_presentation_characters_are_color() {
+        for text in ["\u{1F600}", "a \u{231A} b", "\u{2B50}", "\u{1FAF8}"] {
+            assert!(has_color_glyphs(text), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn the_text_presentation_selector_turns_emoji_off() {
+        assert!(!has_color_glyphs("\u{231A}\u{FE0E}"));
+        assert!(!has_color_glyphs("\u{231A}\u{FE0E} and \u{2B50}\u{FE0E}"));
+        assert!(has_color_glyphs("\u{231A}\u{FE0E}\u{1F600}"));
+    }
+
+    #[test]
+    fn the_emoji_presentation_selector_turns_emoji_on() {
+        assert!(has_color_glyphs("\u{2764}\u{FE0F}"));
+        assert!(has_color_glyphs("I \u{2764}\u{FE0F} text"));
+    }
+
+    #[test]
+    fn keycaps_are_color() {
+        assert!(has_color_glyphs("1\u{FE0F}\u{20E3}"));
+        assert!(has_color_glyphs("#\u{20E3}"));
+    }
+
+    #[test]
+    fn flags_are_color() {
+        assert!(has_color_glyphs("\u{1F1EF}\u{1F1F5}"));
+        assert!(has_color_glyphs(
+            "\u{1F3F4}\u{E0067}\u{E0062}\u{E0073}\u{E0063}\u{E0074}\u{E007F}"
+        ));
+    }
+}
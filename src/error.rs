@@ -0,0 +1,290 @@
+use std::{fmt, io, path::PathBuf};
+
+// What the app was doing when a platform call failed, which decides the
+// exit code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Startup,
+    Device,
+    Font,
+    Surface,
+    Render,
+}
+
+// A failed HRESULT along with the system's description of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hresult {
+    pub code: i32,
+    pub message: String,
+}
+
+impl Hresult {
+    pub fn new(code: i32, message: &str) -> Self {
+        Self {
+            code,
+            message: message.trim().to_owned(),
+        }
+    }
+
+    // The name of the constant for codes the app is likely to run into.
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match self.code as u32 {
+            0x80004001 => "E_NOTIMPL",
+            0x80004002 => "E_NOINTERFACE",
+            0x80004003 => "E_POINTER",
+            0x80004004 => "E_ABORT",
+            0x80004005 => "E_FAIL",
+            0x8000FFFF => "E_UNEXPECTED",
+            0x80070002 => "ERROR_FILE_NOT_FOUND",
+            0x80070003 => "ERROR_PATH_NOT_FOUND",
+            0x80070005 => "E_ACCESSDENIED",
+            0x80070006 => "E_HANDLE",
+            0x8007000E => "E_OUTOFMEMORY",
+            0x80070057 => "E_INVALIDARG",
+            0x887A0001 => "DXGI_ERROR_INVALID_CALL",
+            0x887A0004 => "DXGI_ERROR_UNSUPPORTED",
+            0x887A0005 => "DXGI_ERROR_DEVICE_REMOVED",
+            0x887A0006 => "DXGI_ERROR_DEVICE_HUNG",
+            0x887A0007 => "DXGI_ERROR_DEVICE_RESET",
+            0x887A0020 => "DXGI_ERROR_DRIVER_INTERNAL_ERROR",
+            0x887A002D => "DXGI_ERROR_SDK_COMPONENT_MISSING",
+            0x88990001 => "D2DERR_WRONG_STATE",
+            0x88990002 => "D2DERR_NOT_INITIALIZED",
+            0x88990003 => "D2DERR_UNSUPPORTED_OPERATION",
+            0x88990008 => "D2DERR_INTERNAL_ERROR",
+            0x8899000A => "D2DERR_INVALID_CALL",
+            0x8899000B => "D2DERR_NO_HARDWARE_DEVICE",
+            0x8899000C => "D2DERR_RECREATE_TARGET",
+            0x88985000 => "DWRITE_E_FILEFORMAT",
+            0x88985001 => "DWRITE_E_UNEXPECTED",
+            0x88985002 => "DWRITE_E_NOFONT",
+            0x88985003 => "DWRITE_E_FILENOTFOUND",
+            0x88985004 => "DWRITE_E_FILEACCESS",
+            _ => return None,
+        })
+    }
+
+    // The subsystem that reported the code, from its facility bits. The
+    // graphics facilities are past 0x7FF, so they take the reserved bit
+    // above the 11 in the spec too, but not the customer or NTSTATUS bits.
+    pub fn facility(&self) -> Option<&'static str> {
+        Some(match (self.code as u32 >> 16) & 0xFFF {
+            0x007 => "Win32",
+            0x87A => "DXGI",
+            0x87C => "Direct3D 11",
+            0x898 => "DirectWrite",
+            0x899 => "Direct2D",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Hresult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:08X}", self.code as u32)?;
+        match (self.name(), self.facility()) {
+            (Some(name), _) => write!(f, " {}", name)?,
+            (None, Some(facility)) => write!(f, " ({} error)", facility)?,
+            (None, None) => {}
+        }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // 'context' says what was being done, like "create the Direct3D device".
+    Windows {
+        step: Step,
+        context: String,
+        hresult: Hresult,
+    },
+    Scene {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    Io {
+        context: String,
+        error: io::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(context: impl Into<String>, error: io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            error,
+        }
+    }
+
+    // Based on the BSD sysexits codes. 2 is left for usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Windows {
+                step: Step::Device | Step::Font,
+                ..
+            } => 69,
+            Self::Windows { .. } => 70,
+            Self::Scene { .. } => 65,
+            Self::Io { .. } => 74,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Windows {
+                context, hresult, ..
+            } => write!(f, "Failed to {}: {}", context, hresult),
+            Self::Scene {
+                path: Some(path),
+                message,
+            } => write!(f, "{}: {}", path.display(), message),
+            Self::Scene {
+                path: None,
+                message,
+            } => write!(f, "Invalid scene: {}", message),
+            Self::Io { context, error } => write!(f, "Failed to {}: {}", context, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(windows)]
+pub trait Context<T> {
+    fn context(self, step: Step, context: impl Into<String>) -> Result<T>;
+}
+
+#[cfg(windows)]
+impl<T> Context<T> for windows::core::Result<T> {
+    fn context(self, step: Step, context: impl Into<String>) -> Result<T> {
+        self.map_err(|error| Error::Windows {
+            step,
+            context: context.into(),
+            hresult: Hresult::new(error.code().0, &error.message()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows(step: Step, code: u32) -> Error {
+        Error::Windows {
+            step,
+            context: "create the device".to_owned(),
+            hresult: Hresult::new(code as i32, ""),
+        }
+    }
+
+    #[test]
+    fn shows_named_codes() {
+        let hresult = Hresult::new(
+            0x887A0004u32 as i32,
+            "The specified device interface or feature level is not supported on this system.\r\n",
+        );
+        assert_eq!(
+            hresult.to_string(),
+            "0x887A0004 DXGI_ERROR_UNSUPPORTED: The specified device interface or feature level is not supported on this system."
+        );
+        assert_eq!(
+            Hresult::new(0x80070057u32 as i32, "").to_string(),
+            "0x80070057 E_INVALIDARG"
+        );
+    }
+
+    #[test]
+    fn shows_the_facility_of_unnamed_codes() {
+        assert_eq!(
+            Hresult::new(0x88990123u32 as i32, "").to_string(),
+            "0x88990123 (Direct2D error)"
+        );
+        assert_eq!(
+            Hresult::new(0x80070020u32 as i32, "In use").to_string(),
+            "0x80070020 (Win32 error): In use"
+        );
+        assert_eq!(
+            Hresult::new(0x88985009u32 as i32, "").facility(),
+            Some("DirectWrite")
+        );
+        assert_eq!(
+            Hresult::new(0x887C0002u32 as i32, "").facility(),
+            Some("Direct3D 11")
+        );
+    }
+
+    #[test]
+    fn the_customer_and_ntstatus_bits_dont_change_the_facility() {
+        // The customer bit, then the NTSTATUS bit.
+        for code in [0xA0070005u32, 0x90070005] {
+            assert_eq!(Hresult::new(code as i32, "").facility(), Some("Win32"));
+        }
+        assert_eq!(
+            Hresult::new(0xA87A0001u32 as i32, "").to_string(),
+            "0xA87A0001 (DXGI error)"
+        );
+        assert_eq!(
+            Hresult::new(0x98990001u32 as i32, "").facility(),
+            Some("Direct2D")
+        );
+        // Direct3D 10's facility is next to Direct3D 11's.
+        assert_eq!(Hresult::new(0x88790001u32 as i32, "").facility(), None);
+    }
+
+    #[test]
+    fn shows_bare_codes() {
+        assert_eq!(Hresult::new(0x12345678, "  ").to_string(), "0x12345678");
+        assert_eq!(Hresult::new(0x12345678, "x").to_string(), "0x12345678: x");
+    }
+
+    #[test]
+    fn shows_what_was_being_done() {
+        assert_eq!(
+            windows(Step::Device, 0x80004005).to_string(),
+            "Failed to create the device: 0x80004005 E_FAIL"
+        );
+        let error = Error::Scene {
+            path: Some("a.json".into()),
+            message: "visuals[0]: missing 'text'".to_owned(),
+        };
+        assert_eq!(error.to_string(), "a.json: visuals[0]: missing 'text'");
+        let error = Error::Scene {
+            path: None,
+            message: "bad expression".to_owned(),
+        };
+        assert_eq!(error.to_string(), "Invalid scene: bad expression");
+        let error = Error::io("write out.gif", io::Error::other("disk full"));
+        assert_eq!(error.to_string(), "Failed to write out.gif: disk full");
+    }
+
+    #[test]
+    fn maps_errors_to_exit_codes() {
+        assert_eq!(windows(Step::Device, 0x887A0004).exit_code(), 69);
+        assert_eq!(windows(Step::Font, 0x88985002).exit_code(), 69);
+        assert_eq!(windows(Step::Startup, 0x80004005).exit_code(), 70);
+        assert_eq!(windows(Step::Surface, 0x80004005).exit_code(), 70);
+        assert_eq!(windows(Step::Render, 0x80004005).exit_code(), 70);
+        let error = Error::Scene {
+            path: None,
+            message: String::new(),
+        };
+        assert_eq!(error.exit_code(), 65);
+        let error = Error::io("read", io::Error::other("gone"));
+        assert_eq!(error.exit_code(), 74);
+    }
+}
@@ -0,0 +1,698 @@
+use std::{
+    borrow::Cow,
+    io::{self, BufWriter, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use color_quant::NeuQuant;
+
+use crate::frame::Frame;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Gif,
+    Apng,
+    Y4m,
+}
+
+// How many times an exported animation plays. Both formats store this in
+// the file, but GIF counts repeats after the first play and APNG counts
+// plays, so the writers translate it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoopCount {
+    Forever,
+    Plays(u16),
+}
+
+// GIF frames are limited to 256 colors each, so every frame gets its own
+// palette built with NeuQuant. 'speed' trades quality for time, from 1
+// (best) to 30 (fastest).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Quantization {
+    pub colors: u16,
+    pub speed: i32,
+}
+
+impl Default for Quantization {
+    fn default() -> Self {
+        Self {
+            colors: 256,
+            speed: 10,
+        }
+    }
+}
+
+pub trait AnimationWriter {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+pub struct GifWriter<W: Write> {
+    encoder: gif::Encoder<W>,
+    width: u16,
+    height: u16,
+    fps: u32,
+    frame: u64,
+    quantization: Quantization,
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(
+        writer: W,
+        width: u32,
+        height: u32,
+        fps: u32,
+        loop_count: LoopCount,
+        quantization: Quantization,
+    ) -> io::Result<Self> {
+        let width = to_u16(width, "GIF width")?;
+        let height = to_u16(height, "GIF height")?;
+        let mut encoder =
+            gif::Encoder::new(writer, width, height, &[]).map_err(io::Error::other)?;
+        // Without the repeat extension a GIF plays once.
+        let repeat = match loop_count {
+            LoopCount::Forever => Some(gif::Repeat::Infinite),
+            LoopCount::Plays(0 | 1) => None,
+            LoopCount::Plays(plays) => Some(gif::Repeat::Finite(plays - 1)),
+        };
+        if let Some(repeat) = repeat {
+            encoder.set_repeat(repeat).map_err(io::Error::other)?;
+        }
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            fps,
+            frame: 0,
+            quantization,
+        })
+    }
+
+    // GIF delays are whole hundredths of a second. Rounding each frame's
+    // start time rather than its length keeps the total from drifting.
+    fn delay(&self, frame: u64) -> u16 {
+        let centiseconds = |frame: u64| (frame * 100 + self.fps as u64 / 2) / self.fps as u64;
+        (centiseconds(frame + 1) - centiseconds(frame)) as u16
+    }
+}
+
+impl<W: Write> AnimationWriter for GifWriter<W> {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let mut gif_frame = quantize(frame, self.width, self.height, &self.quantization);
+        gif_frame.delay = self.delay(self.frame);
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        self.encoder
+            .write_frame(&gif_frame)
+            .map_err(io::Error::other)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let mut writer = self.encoder.into_inner().map_err(io::Error::other)?;
+        writer.flush()
+    }
+}
+
+// GIF only has 1-bit transparency, so any pixel that isn't fully
+// transparent is made opaque and the transparent ones share one color.
+fn quantize(
+    frame: &Frame,
+    width: u16,
+    height: u16,
+    quantization: &Quantization,
+) -> gif::Frame<'static> {
+    let mut pixels = frame.pixels.clone();
+    let mut transparent: Option<[u8; 4]> = None;
+    for pixel in pixels.chunks_exact_mut(4) {
+        if pixel[3] != 0 {
+            pixel[3] = 255;
+        } else if let Some(color) = transparent {
+            pixel.copy_from_slice(&color);
+        } else {
+            transparent = Some([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        }
+    }
+
+    let colors = quantization.colors.clamp(2, 256) as usize;
+    let quantizer = NeuQuant::new(quantization.speed.clamp(1, 30), colors, &pixels);
+    let indices: Vec<u8> = pixels
+        .chunks_exact(4)
+        .map(|pixel| quantizer.index_of(pixel) as u8)
+        .collect();
+    gif::Frame {
+        width,
+        height,
+        buffer: Cow::Owned(indices),
+        palette: Some(quantizer.color_map_rgb()),
+        transparent: transparent.map(|color| quantizer.index_of(&color) as u8),
+        ..Default::default()
+    }
+}
+
+pub struct ApngWriter<W: Write> {
+    writer: png::Writer<W>,
+}
+
+impl<W: Write> ApngWriter<W> {
+    // APNG declares its frame count up front, so exactly 'frame_count'
+    // frames have to be written before finishing; writing more or fewer
+    // fails rather than leaving a broken file.
+    pub fn new(
+        writer: W,
+        width: u32,
+        height: u32,
+        fps: u32,
+        frame_count: u32,
+        loop_count: LoopCount,
+    ) -> io::Result<Self> {
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let plays = match loop_count {
+            LoopCount::Forever => 0,
+            LoopCount::Plays(plays) => plays.max(1) as u32,
+        };
+        encoder.set_animated(frame_count, plays)?;
+        encoder.set_frame_delay(1, to_u16(fps, "APNG frame rate")?)?;
+        encoder.set_dispose_op(png::DisposeOp::Background)?;
+        encoder.set_blend_op(png::BlendOp::Source)?;
+        encoder.validate_sequence(true);
+        Ok(Self {
+            writer: encoder.write_header()?,
+        })
+    }
+}
+
+impl<W: Write> AnimationWriter for ApngWriter<W> {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.writer.write_image_data(&frame.pixels)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+// Uncompressed video as YUV4MPEG2, which most encoders (ffmpeg, x264,
+// aomenc) read directly. Frames are converted to 4:2:0 BT.601 limited
+// range, the format those tools assume for Y4M input without extra tags.
+// Transparent pixels are composited over black.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut writer: W, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED",
+            width, height, fps
+        )?;
+        Ok(Self {
+            writer,
+            width,
+            height,
+        })
+    }
+}
+
+impl<W: Write> AnimationWriter for Y4mWriter<W> {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let rgb = |x: usize, y: usize| -> [f32; 3] {
+            let pixel = &frame.pixels[(y * width + x) * 4..][..4];
+            let alpha = pixel[3] as f32 / 255.0;
+            [
+                pixel[0] as f32 * alpha,
+                pixel[1] as f32 * alpha,
+                pixel[2] as f32 * alpha,
+            ]
+        };
+
+        let mut luma = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b] = rgb(x, y);
+                luma.push((16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0).round() as u8);
+            }
+        }
+
+        // Each chroma sample averages the (up to) 2x2 block of pixels it
+        // covers; odd sizes round the chroma planes up.
+        let chroma_width = width.div_ceil(2);
+        let chroma_height = height.div_ceil(2);
+        let mut cb = Vec::with_capacity(chroma_width * chroma_height);
+        let mut cr = Vec::with_capacity(chroma_width * chroma_height);
+        for chroma_y in 0..chroma_height {
+            for chroma_x in 0..chroma_width {
+                let mut sum = [0.0; 3];
+                let mut count = 0.0;
+                for y in chroma_y * 2..(chroma_y * 2 + 2).min(height) {
+                    for x in chroma_x * 2..(chroma_x * 2 + 2).min(width) {
+                        let color = rgb(x, y);
+                        for (sum, channel) in sum.iter_mut().zip(color) {
+                            *sum += channel;
+                        }
+                        count += 1.0;
+                    }
+                }
+                let [r, g, b] = sum.map(|sum| sum / count);
+                cb.push((128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0).round() as u8);
+                cr.push((128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0).round() as u8);
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&luma)?;
+        self.writer.write_all(&cb)?;
+        self.writer.write_all(&cr)
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Streams frames as raw, straight alpha RGBA to the standard input of an
+// encoder command run through the shell. "{width}", "{height}" and "{fps}"
+// in the command are replaced so it can describe the stream, e.g.
+//
+//   ffmpeg -f rawvideo -pix_fmt rgba -s {width}x{height} -r {fps} -i - out.mp4
+pub struct PipeWriter {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+}
+
+impl PipeWriter {
+    pub fn spawn(command: &str, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        let command = command
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string())
+            .replace("{fps}", &fps.to_string());
+        let mut child = if cfg!(windows) {
+            Command::new("cmd")
+                .arg("/C")
+                .arg(&command)
+                .stdin(Stdio::piped())
+                .spawn()?
+        } else {
+            Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .stdin(Stdio::piped())
+                .spawn()?
+        };
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("the encoder's standard input isn't available"))?;
+        Ok(Self {
+            child,
+            stdin: Some(BufWriter::new(stdin)),
+        })
+    }
+}
+
+impl AnimationWriter for PipeWriter {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let Some(stdin) = &mut self.stdin else {
+            return Err(io::Error::other("the encoder has already exited"));
+        };
+        let Err(error) = stdin.write_all(&frame.pixels) else {
+            return Ok(());
+        };
+        // Usually the encoder exited early. Reap it rather than leave a
+        // zombie, and say how it exited.
+        self.stdin = None;
+        let status = self.child.wait()?;
+        Err(io::Error::new(
+            error.kind(),
+            format!(
+                "couldn't write to the encoder, which exited with {}: {}",
+                status, error
+            ),
+        ))
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        // Closing stdin tells the encoder the stream has ended. It's waited
+        // for even if flushing fails, so it doesn't outlive the export.
+        let flushed = match self.stdin.take() {
+            Some(stdin) => stdin
+                .into_inner()
+                .map(drop)
+                .map_err(|error| error.into_error()),
+            None => Ok(()),
+        };
+        let status = self.child.wait()?;
+        flushed?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "the encoder exited with {}",
+                status
+            )))
+        }
+    }
+}
+
+// Exports that stop early, e.g. when rendering a frame fails, drop the
+// writer without finishing it. The encoder still sees the end of its input
+// and is waited for.
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+fn to_u16(value: u32, name: &str) -> io::Result<u16> {
+    u16::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} {} is larger than {}", name, value, u16::MAX),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Frame {
+        Frame {
+            width,
+            height,
+            pixels: rgba.repeat(width as usize * height as usize),
+        }
+    }
+
+    fn gif(fps: u32, loop_count: LoopCount, frames: &[Frame]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut writer = Box::new(
+            GifWriter::new(
+                &mut data,
+                frames[0].width,
+                frames[0].height,
+                fps,
+                loop_count,
+                Quantization::default(),
+            )
+            .unwrap(),
+        );
+        for frame in frames {
+            writer.write_frame(frame).unwrap();
+        }
+        writer.finish().unwrap();
+        data
+    }
+
+    fn decode_gif(data: &[u8]) -> (gif::Repeat, Vec<gif::Frame<'static>>) {
+        let mut decoder = gif::DecodeOptions::new().read_info(data).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push(frame.clone());
+        }
+        (decoder.repeat(), frames)
+    }
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn gif_delays_round_each_frame_start_to_centiseconds() {
+        let frames = vec![solid(2, 2, [255, 0, 0, 255]); 6];
+        let delays = |fps| -> Vec<u16> {
+            let (_, frames) = decode_gif(&gif(fps, LoopCount::Forever, &frames));
+            frames.iter().map(|frame| frame.delay).collect()
+        };
+        assert_eq!(delays(50), [2, 2, 2, 2, 2, 2]);
+        assert_eq!(delays(30), [3, 4, 3, 3, 4, 3]);
+        assert_eq!(delays(60), [2, 1, 2, 2, 1, 2]);
+    }
+
+    #[test]
+    fn gif_delays_add_up_without_drifting() {
+        let frames = vec![solid(1, 1, [0, 0, 0, 255]); 60];
+        let (_, frames) = decode_gif(&gif(60, LoopCount::Forever, &frames));
+        let total: u32 = frames.iter().map(|frame| frame.delay as u32).sum();
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn gif_loop_counts_become_repeats_after_the_first_play() {
+        let frames = [solid(1, 1, [0, 0, 0, 255])];
+        let data = gif(50, LoopCount::Forever, &frames);
+        assert_eq!(decode_gif(&data).0, gif::Repeat::Infinite);
+        let data = gif(50, LoopCount::Plays(3), &frames);
+        assert_eq!(decode_gif(&data).0, gif::Repeat::Finite(2));
+        // Playing once needs no repeat extension at all.
+        for plays in [0, 1] {
+            let data = gif(50, LoopCount::Plays(plays), &frames);
+            assert!(!contains(&data, b"NETSCAPE2.0"));
+            assert_eq!(decode_gif(&data).0, gif::Repeat::Finite(0));
+        }
+    }
+
+    #[test]
+    fn gifs_are_limited_to_u16_sizes() {
+        let error = GifWriter::new(
+            Vec::new(),
+            70000,
+            1,
+            50,
+            LoopCount::Forever,
+            Quantization::default(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn transparent_pixels_share_the_transparent_index() {
+        let mut frame = solid(4, 1, [0, 0, 0, 0]);
+        frame.pixels[4..8].copy_from_slice(&[255, 0, 0, 255]);
+        frame.pixels[8..12].copy_from_slice(&[10, 20, 30, 0]);
+        let (_, frames) = decode_gif(&gif(50, LoopCount::Forever, &[frame]));
+        let frame = &frames[0];
+        let transparent = frame.transparent.unwrap();
+        assert_eq!(frame.buffer[0], transparent);
+        assert_eq!(frame.buffer[2], transparent);
+        assert_eq!(frame.buffer[3], transparent);
+        assert_ne!(frame.buffer[1], transparent);
+        assert_eq!(frame.dispose, gif::DisposalMethod::Background);
+    }
+
+    #[test]
+    fn opaque_gifs_have_no_transparent_index() {
+        let (_, frames) = decode_gif(&gif(50, LoopCount::Forever, &[solid(2, 2, [9, 9, 9, 255])]));
+        assert_eq!(frames[0].transparent, None);
+    }
+
+    #[test]
+    fn quantizing_makes_partly_transparent_pixels_opaque() {
+        let mut frame = solid(16, 16, [0, 0, 255, 255]);
+        frame.pixels[..512].copy_from_slice(&[255, 0, 0, 1].repeat(128));
+        let quantized = quantize(&frame, 16, 16, &Quantization::default());
+        assert_eq!(quantized.transparent, None);
+        let palette = quantized.palette.unwrap();
+        let color = |pixel: usize| {
+            let index = quantized.buffer[pixel] as usize * 3;
+            [palette[index], palette[index + 1], palette[index + 2]]
+        };
+        let near = |actual: [u8; 3], expected: [u8; 3]| {
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(&actual, expected)| actual.abs_diff(expected) <= 8)
+        };
+        assert!(near(color(0), [255, 0, 0]), "{:?}", color(0));
+        assert!(near(color(255), [0, 0, 255]), "{:?}", color(255));
+    }
+
+    #[test]
+    fn quantizing_clamps_the_palette_size() {
+        let mut frame = solid(16, 16, [0, 0, 0, 255]);
+        for (index, pixel) in frame.pixels.chunks_exact_mut(4).enumerate() {
+            pixel[0] = index as u8;
+            pixel[1] = 255 - index as u8;
+        }
+        for (colors, expected) in [(0, 2), (16, 16), (1000, 256)] {
+            let quantization = Quantization { colors, speed: 30 };
+            let quantized = quantize(&frame, 16, 16, &quantization);
+            assert_eq!(quantized.palette.unwrap().len(), expected * 3);
+            assert!(
+                quantized
+                    .buffer
+                    .iter()
+                    .all(|&index| (index as usize) < expected)
+            );
+        }
+    }
+
+    #[test]
+    fn apngs_declare_their_frame_count_plays_and_delay() {
+        let mut data = Vec::new();
+        let mut writer =
+            Box::new(ApngWriter::new(&mut data, 2, 2, 30, 3, LoopCount::Plays(2)).unwrap());
+        for color in [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128]] {
+            writer.write_frame(&solid(2, 2, color)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, 2));
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let mut frames = 0;
+        while reader.next_frame(&mut pixels).is_ok() {
+            let frame_control = reader.info().frame_control.unwrap();
+            assert_eq!((frame_control.delay_num, frame_control.delay_den), (1, 30));
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+        assert_eq!(&pixels[..4], [0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn apngs_that_loop_forever_store_zero_plays() {
+        let mut data = Vec::new();
+        let mut writer =
+            Box::new(ApngWriter::new(&mut data, 1, 1, 60, 1, LoopCount::Forever).unwrap());
+        writer.write_frame(&solid(1, 1, [0, 0, 0, 255])).unwrap();
+        writer.finish().unwrap();
+        let reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        assert_eq!(reader.info().animation_control.unwrap().num_plays, 0);
+    }
+
+    #[test]
+    fn apngs_need_exactly_their_frame_count() {
+        let frame = solid(1, 1, [0, 0, 0, 255]);
+        let mut writer =
+            Box::new(ApngWriter::new(Vec::new(), 1, 1, 60, 2, LoopCount::Forever).unwrap());
+        writer.write_frame(&frame).unwrap();
+        assert!(writer.finish().is_err());
+        let mut writer =
+            Box::new(ApngWriter::new(Vec::new(), 1, 1, 60, 1, LoopCount::Forever).unwrap());
+        writer.write_frame(&frame).unwrap();
+        assert!(writer.write_frame(&frame).is_err());
+    }
+
+    fn y4m(frame: &Frame, fps: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut writer =
+            Box::new(Y4mWriter::new(&mut data, frame.width, frame.height, fps).unwrap());
+        writer.write_frame(frame).unwrap();
+        writer.finish().unwrap();
+        data
+    }
+
+    // The Y, Cb and Cr planes of the first frame.
+    fn planes(data: &[u8], width: usize, height: usize) -> (&[u8], &[u8], &[u8]) {
+        let start = data.iter().position(|&byte| byte == b'\n').unwrap() + 1;
+        let frame = data[start..].strip_prefix(b"FRAME\n").unwrap();
+        let chroma = width.div_ceil(2) * height.div_ceil(2);
+        assert_eq!(frame.len(), width * height + chroma * 2);
+        let (luma, chroma_planes) = frame.split_at(width * height);
+        let (cb, cr) = chroma_planes.split_at(chroma);
+        (luma, cb, cr)
+    }
+
+    #[test]
+    fn y4m_headers_describe_limited_range_420() {
+        let data = y4m(&solid(3, 5, [0, 0, 0, 255]), 24);
+        assert!(data.starts_with(b"YUV4MPEG2 W3 H5 F24:1 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED\n"));
+    }
+
+    #[test]
+    fn y4m_uses_bt601_limited_range() {
+        for (rgba, expected) in [
+            ([0, 0, 0, 255], (16, 128, 128)),
+            ([255, 255, 255, 255], (235, 128, 128)),
+            ([255, 0, 0, 255], (81, 90, 240)),
+            ([0, 0, 255, 255], (41, 240, 110)),
+            // Transparent pixels are composited over black.
+            ([255, 255, 255, 0], (16, 128, 128)),
+        ] {
+            let data = y4m(&solid(2, 2, rgba), 30);
+            let (luma, cb, cr) = planes(&data, 2, 2);
+            assert_eq!(luma, [expected.0; 4], "{rgba:?}");
+            assert_eq!((cb[0], cr[0]), (expected.1, expected.2), "{rgba:?}");
+        }
+    }
+
+    #[test]
+    fn y4m_chroma_averages_each_2x2_block() {
+        // Red on the left, black on the right.
+        let mut frame = solid(2, 2, [0, 0, 0, 255]);
+        frame.pixels[0..4].copy_from_slice(&[255, 0, 0, 255]);
+        frame.pixels[8..12].copy_from_slice(&[255, 0, 0, 255]);
+        let data = y4m(&frame, 30);
+        let (luma, cb, cr) = planes(&data, 2, 2);
+        assert_eq!(luma, [81, 16, 81, 16]);
+        assert_eq!((cb, cr), (&[109][..], &[184][..]));
+    }
+
+    #[test]
+    fn y4m_chroma_of_odd_sizes_averages_the_pixels_it_covers() {
+        // Red pixels at the top right, bottom left and bottom right.
+        let mut frame = solid(3, 3, [0, 0, 0, 255]);
+        for index in [2, 6, 8] {
+            frame.pixels[index * 4..][..4].copy_from_slice(&[255, 0, 0, 255]);
+        }
+        let data = y4m(&frame, 30);
+        let (luma, cb, cr) = planes(&data, 3, 3);
+        assert_eq!(luma, [16, 16, 81, 16, 16, 16, 81, 16, 81]);
+        // The edge blocks cover two pixels, one of them red, and the corner
+        // block only the red corner pixel.
+        assert_eq!(cb, [128, 109, 109, 90]);
+        assert_eq!(cr, [128, 184, 184, 240]);
+    }
+
+    #[test]
+    fn y4m_writes_a_frame_header_per_frame() {
+        let mut data = Vec::new();
+        let mut writer = Box::new(Y4mWriter::new(&mut data, 1, 1, 30).unwrap());
+        for _ in 0..3 {
+            writer.write_frame(&solid(1, 1, [0, 0, 0, 255])).unwrap();
+        }
+        writer.finish().unwrap();
+        let header = data.iter().position(|&byte| byte == b'\n').unwrap() + 1;
+        assert_eq!(data[header..], b"FRAME\n\x10\x80\x80".repeat(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_fill_in_the_stream_size_and_rate() {
+        let command = "test {width}x{height}@{fps} = 3x2@25 && cat > /dev/null";
+        let mut writer = Box::new(PipeWriter::spawn(command, 3, 2, 25).unwrap());
+        writer.write_frame(&solid(3, 2, [0, 0, 0, 255])).unwrap();
+        writer.finish().unwrap();
+        let writer = Box::new(PipeWriter::spawn(command, 4, 2, 25).unwrap());
+        assert!(writer.finish().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipes_report_encoders_that_exit_early() {
+        let mut writer = Box::new(PipeWriter::spawn("exit 3", 512, 512, 30).unwrap());
+        let error = writer
+            .write_frame(&solid(512, 512, [0, 0, 0, 255]))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert!(error.to_string().contains("exit status: 3"), "{error}");
+        // The encoder has been waited for and doesn't get more frames.
+        assert!(writer.child.try_wait().unwrap().is_some());
+        assert!(writer.write_frame(&solid(1, 1, [0, 0, 0, 255])).is_err());
+        assert!(writer.finish().is_err());
+    }
+}
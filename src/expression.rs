@@ -0,0 +1,1397 @@
+use std::fmt;
+
+use windows_numerics::{Vector2, Vector3, Vector4};
+
+use crate::{animation::Interpolate, scene::Color};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    Boolean,
+    Scalar,
+    Vector2,
+    Vector3,
+    Vector4,
+    Color,
+}
+
+impl Type {
+    fn component_count(&self) -> Option<usize> {
+        match self {
+            Type::Scalar => Some(1),
+            Type::Vector2 => Some(2),
+            Type::Vector3 => Some(3),
+            Type::Vector4 => Some(4),
+            Type::Boolean | Type::Color => None,
+        }
+    }
+
+    fn with_component_count(count: usize) -> Option<Self> {
+        match count {
+            1 => Some(Type::Scalar),
+            2 => Some(Type::Vector2),
+            3 => Some(Type::Vector3),
+            4 => Some(Type::Vector4),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.component_count().is_some()
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Boolean => "Boolean",
+            Type::Scalar => "Scalar",
+            Type::Vector2 => "Vector2",
+            Type::Vector3 => "Vector3",
+            Type::Vector4 => "Vector4",
+            Type::Color => "Color",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    Scalar(f32),
+    Vector2(Vector2),
+    Vector3(Vector3),
+    Vector4(Vector4),
+    Color(Color),
+}
+
+impl Value {
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Boolean(_) => Type::Boolean,
+            Value::Scalar(_) => Type::Scalar,
+            Value::Vector2(_) => Type::Vector2,
+            Value::Vector3(_) => Type::Vector3,
+            Value::Vector4(_) => Type::Vector4,
+            Value::Color(_) => Type::Color,
+        }
+    }
+
+    fn components(&self) -> Option<Vec<f32>> {
+        match self {
+            Value::Scalar(value) => Some(vec![*value]),
+            Value::Vector2(value) => Some(vec![value.X, value.Y]),
+            Value::Vector3(value) => Some(vec![value.X, value.Y, value.Z]),
+            Value::Vector4(value) => Some(vec![value.X, value.Y, value.Z, value.W]),
+            Value::Boolean(_) | Value::Color(_) => None,
+        }
+    }
+
+    fn from_components(components: &[f32]) -> Option<Self> {
+        match *components {
+            [x] => Some(Value::Scalar(x)),
+            [x, y] => Some(Value::Vector2(Vector2::new(x, y))),
+            [x, y, z] => Some(Value::Vector3(Vector3::new(x, y, z))),
+            [x, y, z, w] => Some(Value::Vector4(Vector4::new(x, y, z, w))),
+            _ => None,
+        }
+    }
+}
+
+pub trait ExpressionValue: Sized {
+    const TYPE: Type;
+    fn from_value(value: Value) -> Option<Self>;
+    fn to_value(&self) -> Value;
+}
+
+impl ExpressionValue for f32 {
+    const TYPE: Type = Type::Scalar;
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Scalar(value) => Some(value),
+            _ => None,
+        }
+    }
+    fn to_value(&self) -> Value {
+        Value::Scalar(*self)
+    }
+}
+
+impl ExpressionValue for Vector2 {
+    const TYPE: Type = Type::Vector2;
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Vector2(value) => Some(value),
+            _ => None,
+        }
+    }
+    fn to_value(&self) -> Value {
+        Value::Vector2(*self)
+    }
+}
+
+impl ExpressionValue for Vector3 {
+    const TYPE: Type = Type::Vector3;
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Vector3(value) => Some(value),
+            _ => None,
+        }
+    }
+    fn to_value(&self) -> Value {
+        Value::Vector3(*self)
+    }
+}
+
+impl ExpressionValue for Color {
+    const TYPE: Type = Type::Color;
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Color(value) => Some(value),
+            _ => None,
+        }
+    }
+    fn to_value(&self) -> Value {
+        Value::Color(*self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpressionError {
+    Syntax { position: usize, message: String },
+    Type(String),
+    Evaluation(String),
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::Syntax { position, message } => {
+                write!(f, "syntax error at position {}: {}", position, message)
+            }
+            ExpressionError::Type(message) => write!(f, "type error: {}", message),
+            ExpressionError::Evaluation(message) => write!(f, "evaluation error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+// Objects are either visuals referenced by name, or "this" for the animation
+// itself ("this.StartingValue", "this.CurrentValue") and "this.Target" for
+// the object being animated.
+pub trait Environment {
+    fn property_type(&self, object: &str, property: &str) -> Option<Type>;
+    fn property(&self, object: &str, property: &str) -> Option<Value>;
+}
+
+pub fn visual_property_type(property: &str) -> Option<Type> {
+    match property {
+        "Offset" | "Scale" | "CenterPoint" | "RelativeOffsetAdjustment" => Some(Type::Vector3),
+        "Size" | "AnchorPoint" | "RelativeSizeAdjustment" => Some(Type::Vector2),
+        "Opacity" | "RotationAngle" | "RotationAngleInDegrees" => Some(Type::Scalar),
+        _ => None,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Number(f32),
+    Boolean(bool),
+    Reference { object: String, property: String },
+    Swizzle(Box<Expression>, String),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    Call(String, Vec<Expression>),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end: source.len(),
+        };
+        let expression = parser.parse_conditional()?;
+        match parser.peek() {
+            Some(token) => Err(ExpressionError::Syntax {
+                position: token.position,
+                message: format!("unexpected {}", token.kind),
+            }),
+            None => Ok(expression),
+        }
+    }
+
+    // Names of the objects this expression reads from, which on Windows
+    // become the ExpressionAnimation's reference parameters.
+    pub fn references(&self) -> Vec<&str> {
+        let mut references = Vec::new();
+        self.collect_references(&mut references);
+        references
+    }
+
+    fn collect_references<'a>(&'a self, references: &mut Vec<&'a str>) {
+        match self {
+            Expression::Number(_) | Expression::Boolean(_) => {}
+            Expression::Reference { object, .. } => {
+                if !object.starts_with("this") && !references.contains(&object.as_str()) {
+                    references.push(object);
+                }
+            }
+            Expression::Swizzle(inner, _) | Expression::Unary(_, inner) => {
+                inner.collect_references(references)
+            }
+            Expression::Binary(_, left, right) => {
+                left.collect_references(references);
+                right.collect_references(references);
+            }
+            Expression::Conditional(condition, when_true, when_false) => {
+                condition.collect_references(references);
+                when_true.collect_references(references);
+                when_false.collect_references(references);
+            }
+            Expression::Call(_, arguments) => {
+                for argument in arguments {
+                    argument.collect_references(references);
+                }
+            }
+        }
+    }
+
+    pub fn check(&self, environment: &dyn Environment) -> Result<Type, ExpressionError> {
+        match self {
+            Expression::Number(_) => Ok(Type::Scalar),
+            Expression::Boolean(_) => Ok(Type::Boolean),
+            Expression::Reference { object, property } => {
+                environment.property_type(object, property).ok_or_else(|| {
+                    ExpressionError::Type(format!("unknown property '{}.{}'", object, property))
+                })
+            }
+            Expression::Swizzle(inner, components) => {
+                let ty = inner.check(environment)?;
+                swizzle_indices(ty, components)?;
+                Ok(Type::with_component_count(components.len()).unwrap())
+            }
+            Expression::Unary(operator, operand) => {
+                let ty = operand.check(environment)?;
+                match (operator, ty) {
+                    (UnaryOperator::Negate, ty) if ty.is_numeric() => Ok(ty),
+                    (UnaryOperator::Not, Type::Boolean) => Ok(Type::Boolean),
+                    _ => Err(ExpressionError::Type(format!(
+                        "operator '{}' can't be applied to {}",
+                        operator.symbol(),
+                        ty
+                    ))),
+                }
+            }
+            Expression::Binary(operator, left, right) => {
+                let left = left.check(environment)?;
+                let right = right.check(environment)?;
+                binary_type(*operator, left, right)
+            }
+            Expression::Conditional(condition, when_true, when_false) => {
+                let condition = condition.check(environment)?;
+                if condition != Type::Boolean {
+                    return Err(ExpressionError::Type(format!(
+                        "condition must be Boolean, found {}",
+                        condition
+                    )));
+                }
+                let when_true = when_true.check(environment)?;
+                let when_false = when_false.check(environment)?;
+                if when_true != when_false {
+                    return Err(ExpressionError::Type(format!(
+                        "conditional branches differ: {} and {}",
+                        when_true, when_false
+                    )));
+                }
+                Ok(when_true)
+            }
+            Expression::Call(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.check(environment))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function_type(name, &arguments)
+            }
+        }
+    }
+
+    pub fn evaluate(&self, environment: &dyn Environment) -> Result<Value, ExpressionError> {
+        match self {
+            Expression::Number(value) => Ok(Value::Scalar(*value)),
+            Expression::Boolean(value) => Ok(Value::Boolean(*value)),
+            Expression::Reference { object, property } => {
+                environment.property(object, property).ok_or_else(|| {
+                    ExpressionError::Evaluation(format!("'{}.{}' has no value", object, property))
+                })
+            }
+            Expression::Swizzle(inner, components) => {
+                let value = inner.evaluate(environment)?;
+                let indices = swizzle_indices(value.ty(), components)?;
+                let source = value.components().unwrap();
+                let swizzled: Vec<_> = indices.iter().map(|index| source[*index]).collect();
+                Ok(Value::from_components(&swizzled).unwrap())
+            }
+            Expression::Unary(operator, operand) => {
+                let value = operand.evaluate(environment)?;
+                match (operator, value) {
+                    (UnaryOperator::Not, Value::Boolean(value)) => Ok(Value::Boolean(!value)),
+                    (UnaryOperator::Negate, value) => map_components(value, |x| -x),
+                    _ => Err(ExpressionError::Type(format!(
+                        "operator '{}' can't be applied to {}",
+                        operator.symbol(),
+                        value.ty()
+                    ))),
+                }
+            }
+            Expression::Binary(BinaryOperator::And, left, right) => Ok(Value::Boolean(
+                as_boolean(left.evaluate(environment)?)?
+                    && as_boolean(right.evaluate(environment)?)?,
+            )),
+            Expression::Binary(BinaryOperator::Or, left, right) => Ok(Value::Boolean(
+                as_boolean(left.evaluate(environment)?)?
+                    || as_boolean(right.evaluate(environment)?)?,
+            )),
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(environment)?;
+                let right = right.evaluate(environment)?;
+                evaluate_binary(*operator, left, right)
+            }
+            Expression::Conditional(condition, when_true, when_false) => {
+                if as_boolean(condition.evaluate(environment)?)? {
+                    when_true.evaluate(environment)
+                } else {
+                    when_false.evaluate(environment)
+                }
+            }
+            Expression::Call(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(environment))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call_function(name, &arguments)
+            }
+        }
+    }
+}
+
+impl UnaryOperator {
+    fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+        }
+    }
+}
+
+// Emits the expression in the Composition expression syntax, fully
+// parenthesized so that precedence never depends on the printer.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Number(value) => write!(f, "{}", value),
+            Expression::Boolean(value) => write!(f, "{}", value),
+            Expression::Reference { object, property } => write!(f, "{}.{}", object, property),
+            Expression::Swizzle(inner, components) => write!(f, "{}.{}", inner, components),
+            Expression::Unary(operator, operand) => write!(f, "{}({})", operator.symbol(), operand),
+            Expression::Binary(operator, left, right) => {
+                write!(f, "({} {} {})", left, operator.symbol(), right)
+            }
+            Expression::Conditional(condition, when_true, when_false) => {
+                write!(f, "({} ? {} : {})", condition, when_true, when_false)
+            }
+            Expression::Call(name, arguments) => {
+                write!(f, "{}(", name)?;
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Number(f32),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(value) => write!(f, "number {}", value),
+            TokenKind::Identifier(name) => write!(f, "'{}'", name),
+            TokenKind::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+const SYMBOLS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "+", "-", "*", "/", "%", "<", ">", "!", "?", ":", "(", ")",
+    ",", ".",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit()
+            || (c == '.'
+                && source[position + 1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|next| next.is_ascii_digit()))
+        {
+            let mut end = position;
+            let mut seen_exponent = false;
+            while let Some(&(index, c)) = chars.peek() {
+                let previous = source[..index].chars().last();
+                let is_exponent_sign =
+                    (c == '+' || c == '-') && matches!(previous, Some('e') | Some('E'));
+                if c.is_ascii_digit() || c == '.' || is_exponent_sign {
+                    end = index + c.len_utf8();
+                    chars.next();
+                } else if (c == 'e' || c == 'E') && !seen_exponent {
+                    seen_exponent = true;
+                    end = index + 1;
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let text = &source[position..end];
+            let value = text.parse::<f32>().map_err(|_| ExpressionError::Syntax {
+                position,
+                message: format!("invalid number '{}'", text),
+            })?;
+            tokens.push(Token {
+                kind: TokenKind::Number(value),
+                position,
+            });
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = position;
+            while let Some(&(index, c)) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    end = index + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token {
+                kind: TokenKind::Identifier(source[position..end].to_owned()),
+                position,
+            });
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| source[position..].starts_with(**symbol))
+                .ok_or_else(|| ExpressionError::Syntax {
+                    position,
+                    message: format!("unexpected character '{}'", c),
+                })?;
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push(Token {
+                kind: TokenKind::Symbol(symbol),
+                position,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn current_position(&self) -> usize {
+        self.peek().map(|token| token.position).unwrap_or(self.end)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ExpressionError> {
+        Err(ExpressionError::Syntax {
+            position: self.current_position(),
+            message: message.into(),
+        })
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token { kind: TokenKind::Symbol(s), .. }) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ExpressionError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", symbol))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, ExpressionError> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => self.error("expected a name"),
+        }
+    }
+
+    fn parse_conditional(&mut self) -> Result<Expression, ExpressionError> {
+        let condition = self.parse_binary(0)?;
+        if self.eat("?") {
+            let when_true = self.parse_conditional()?;
+            self.expect(":")?;
+            let when_false = self.parse_conditional()?;
+            Ok(Expression::Conditional(
+                Box::new(condition),
+                Box::new(when_true),
+                Box::new(when_false),
+            ))
+        } else {
+            Ok(condition)
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expression, ExpressionError> {
+        const LEVELS: &[&[BinaryOperator]] = &[
+            &[BinaryOperator::Or],
+            &[BinaryOperator::And],
+            &[BinaryOperator::Equal, BinaryOperator::NotEqual],
+            &[
+                BinaryOperator::Less,
+                BinaryOperator::LessOrEqual,
+                BinaryOperator::Greater,
+                BinaryOperator::GreaterOrEqual,
+            ],
+            &[BinaryOperator::Add, BinaryOperator::Subtract],
+            &[
+                BinaryOperator::Multiply,
+                BinaryOperator::Divide,
+                BinaryOperator::Modulo,
+            ],
+        ];
+        if level == LEVELS.len() {
+            return self.parse_unary();
+        }
+        let mut left = self.parse_binary(level + 1)?;
+        'outer: loop {
+            for operator in LEVELS[level] {
+                if self.eat(operator.symbol()) {
+                    let right = self.parse_binary(level + 1)?;
+                    left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.eat("-") {
+            let operand = self.parse_unary()?;
+            Ok(match operand {
+                Expression::Number(value) => Expression::Number(-value),
+                operand => Expression::Unary(UnaryOperator::Negate, Box::new(operand)),
+            })
+        } else if self.eat("!") {
+            let operand = self.parse_unary()?;
+            Ok(Expression::Unary(UnaryOperator::Not, Box::new(operand)))
+        } else if self.eat("+") {
+            self.parse_unary()
+        } else {
+            self.parse_postfix()
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expression = self.parse_primary()?;
+        while self.eat(".") {
+            let components = self.expect_identifier()?;
+            expression = Expression::Swizzle(Box::new(expression), components);
+        }
+        Ok(expression)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ExpressionError> {
+        let position = self.current_position();
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Number(value),
+                ..
+            }) => Ok(Expression::Number(value)),
+            Some(Token {
+                kind: TokenKind::Symbol("("),
+                ..
+            }) => {
+                let expression = self.parse_conditional()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => {
+                if name.eq_ignore_ascii_case("true") {
+                    return Ok(Expression::Boolean(true));
+                }
+                if name.eq_ignore_ascii_case("false") {
+                    return Ok(Expression::Boolean(false));
+                }
+                if self.eat("(") {
+                    let mut arguments = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            arguments.push(self.parse_conditional()?);
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    return Ok(Expression::Call(name, arguments));
+                }
+                self.expect(".")?;
+                let mut object = name;
+                let mut property = self.expect_identifier()?;
+                if object == "this" && property == "Target" {
+                    self.expect(".")?;
+                    object = "this.Target".to_owned();
+                    property = self.expect_identifier()?;
+                }
+                Ok(Expression::Reference { object, property })
+            }
+            Some(token) => Err(ExpressionError::Syntax {
+                position,
+                message: format!("unexpected {}", token.kind),
+            }),
+            None => Err(ExpressionError::Syntax {
+                position,
+                message: "unexpected end of expression".to_owned(),
+            }),
+        }
+    }
+}
+
+fn swizzle_indices(ty: Type, components: &str) -> Result<Vec<usize>, ExpressionError> {
+    let count = ty
+        .component_count()
+        .ok_or_else(|| ExpressionError::Type(format!("{} has no components", ty)))?;
+    if components.is_empty() || components.len() > 4 {
+        return Err(ExpressionError::Type(format!(
+            "invalid swizzle '{}'",
+            components
+        )));
+    }
+    components
+        .chars()
+        .map(|component| {
+            let index = match component {
+                'X' | 'x' => 0,
+                'Y' | 'y' => 1,
+                'Z' | 'z' => 2,
+                'W' | 'w' => 3,
+                _ => usize::MAX,
+            };
+            if index < count {
+                Ok(index)
+            } else {
+                Err(ExpressionError::Type(format!(
+                    "{} has no component '{}'",
+                    ty, component
+                )))
+            }
+        })
+        .collect()
+}
+
+fn binary_type(operator: BinaryOperator, left: Type, right: Type) -> Result<Type, ExpressionError> {
+    let result = match operator {
+        BinaryOperator::Add | BinaryOperator::Subtract if left == right && left.is_numeric() => {
+            Some(left)
+        }
+        BinaryOperator::Multiply | BinaryOperator::Divide
+            if left.is_numeric() && right.is_numeric() =>
+        {
+            if left == right || right == Type::Scalar {
+                Some(left)
+            } else if left == Type::Scalar {
+                Some(right)
+            } else {
+                None
+            }
+        }
+        BinaryOperator::Modulo if left == Type::Scalar && right == Type::Scalar => {
+            Some(Type::Scalar)
+        }
+        BinaryOperator::Less
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterOrEqual
+            if left == Type::Scalar && right == Type::Scalar =>
+        {
+            Some(Type::Boolean)
+        }
+        BinaryOperator::Equal | BinaryOperator::NotEqual if left == right => Some(Type::Boolean),
+        BinaryOperator::And | BinaryOperator::Or
+            if left == Type::Boolean && right == Type::Boolean =>
+        {
+            Some(Type::Boolean)
+        }
+        _ => None,
+    };
+    result.ok_or_else(|| {
+        ExpressionError::Type(format!(
+            "operator '{}' can't be applied to {} and {}",
+            operator.symbol(),
+            left,
+            right
+        ))
+    })
+}
+
+fn as_boolean(value: Value) -> Result<bool, ExpressionError> {
+    match value {
+        Value::Boolean(value) => Ok(value),
+        value => Err(ExpressionError::Type(format!(
+            "expected Boolean, found {}",
+            value.ty()
+        ))),
+    }
+}
+
+fn as_scalar(value: Value) -> Result<f32, ExpressionError> {
+    match value {
+        Value::Scalar(value) => Ok(value),
+        value => Err(ExpressionError::Type(format!(
+            "expected Scalar, found {}",
+            value.ty()
+        ))),
+    }
+}
+
+fn map_components(value: Value, f: impl Fn(f32) -> f32) -> Result<Value, ExpressionError> {
+    let components = value
+        .components()
+        .ok_or_else(|| ExpressionError::Type(format!("{} isn't numeric", value.ty())))?;
+    let mapped: Vec<_> = components.into_iter().map(f).collect();
+    Ok(Value::from_components(&mapped).unwrap())
+}
+
+// Combines two numeric values component-wise, broadcasting a scalar operand
+// across the other operand's components.
+fn zip_components(
+    left: Value,
+    right: Value,
+    f: impl Fn(f32, f32) -> f32,
+) -> Result<Value, ExpressionError> {
+    let mismatch =
+        || ExpressionError::Type(format!("can't combine {} and {}", left.ty(), right.ty()));
+    let left_components = left.components().ok_or_else(mismatch)?;
+    let right_components = right.components().ok_or_else(mismatch)?;
+    let zipped: Vec<_> = match (left_components.len(), right_components.len()) {
+        (a, b) if a == b => left_components
+            .iter()
+            .zip(&right_components)
+            .map(|(l, r)| f(*l, *r))
+            .collect(),
+        (_, 1) => left_components
+            .iter()
+            .map(|l| f(*l, right_components[0]))
+            .collect(),
+        (1, _) => right_components
+            .iter()
+            .map(|r| f(left_components[0], *r))
+            .collect(),
+        _ => return Err(mismatch()),
+    };
+    Ok(Value::from_components(&zipped).unwrap())
+}
+
+fn evaluate_binary(
+    operator: BinaryOperator,
+    left: Value,
+    right: Value,
+) -> Result<Value, ExpressionError> {
+    binary_type(operator, left.ty(), right.ty())?;
+    match operator {
+        BinaryOperator::Add => zip_components(left, right, |l, r| l + r),
+        BinaryOperator::Subtract => zip_components(left, right, |l, r| l - r),
+        BinaryOperator::Multiply => zip_components(left, right, |l, r| l * r),
+        BinaryOperator::Divide => zip_components(left, right, |l, r| l / r),
+        BinaryOperator::Modulo => zip_components(left, right, |l, r| l % r),
+        BinaryOperator::Less => Ok(Value::Boolean(as_scalar(left)? < as_scalar(right)?)),
+        BinaryOperator::LessOrEqual => Ok(Value::Boolean(as_scalar(left)? <= as_scalar(right)?)),
+        BinaryOperator::Greater => Ok(Value::Boolean(as_scalar(left)? > as_scalar(right)?)),
+        BinaryOperator::GreaterOrEqual => Ok(Value::Boolean(as_scalar(left)? >= as_scalar(right)?)),
+        BinaryOperator::Equal => Ok(Value::Boolean(left == right)),
+        BinaryOperator::NotEqual => Ok(Value::Boolean(left != right)),
+        BinaryOperator::And => Ok(Value::Boolean(as_boolean(left)? && as_boolean(right)?)),
+        BinaryOperator::Or => Ok(Value::Boolean(as_boolean(left)? || as_boolean(right)?)),
+    }
+}
+
+fn function_type(name: &str, arguments: &[Type]) -> Result<Type, ExpressionError> {
+    let numeric = |ty: &Type| ty.is_numeric();
+    let vector = |ty: &Type| ty.is_numeric() && *ty != Type::Scalar;
+    let result = match (name, arguments) {
+        ("Abs" | "Ceil" | "Floor" | "Round" | "Sqrt" | "Square", [argument])
+            if numeric(argument) =>
+        {
+            Some(*argument)
+        }
+        (
+            "Sin" | "Cos" | "Tan" | "Asin" | "Acos" | "Atan" | "Ln" | "Log10" | "Exp" | "ToRadians"
+            | "ToDegrees",
+            [Type::Scalar],
+        ) => Some(Type::Scalar),
+        ("Pow" | "Mod" | "Atan2", [Type::Scalar, Type::Scalar]) => Some(Type::Scalar),
+        ("Min" | "Max", [a, b]) if a == b && numeric(a) => Some(*a),
+        ("Clamp", [value, min, max]) if value == min && value == max && numeric(value) => {
+            Some(*value)
+        }
+        ("Lerp", [a, b, Type::Scalar]) if a == b && numeric(a) => Some(*a),
+        ("Length" | "LengthSquared", [argument]) if vector(argument) => Some(Type::Scalar),
+        ("Normalize", [argument]) if vector(argument) => Some(*argument),
+        ("Distance" | "DistanceSquared", [a, b]) if a == b && vector(a) => Some(Type::Scalar),
+        ("Scale", [value, Type::Scalar]) if numeric(value) => Some(*value),
+        ("Vector2", [Type::Scalar, Type::Scalar]) => Some(Type::Vector2),
+        ("Vector3", [Type::Scalar, Type::Scalar, Type::Scalar]) => Some(Type::Vector3),
+        ("Vector4", [Type::Scalar, Type::Scalar, Type::Scalar, Type::Scalar]) => {
+            Some(Type::Vector4)
+        }
+        ("ColorRGB", [Type::Scalar, Type::Scalar, Type::Scalar, Type::Scalar]) => Some(Type::Color),
+        ("ColorLerp", [Type::Color, Type::Color, Type::Scalar]) => Some(Type::Color),
+        _ => None,
+    };
+    result.ok_or_else(|| {
+        let arguments: Vec<_> = arguments.iter().map(|ty| ty.to_string()).collect();
+        ExpressionError::Type(format!("no function {}({})", name, arguments.join(", ")))
+    })
+}
+
+fn call_function(name: &str, arguments: &[Value]) -> Result<Value, ExpressionError> {
+    let types: Vec<_> = arguments.iter().map(|argument| argument.ty()).collect();
+    function_type(name, &types)?;
+    let scalar = |index: usize| as_scalar(arguments[index]);
+    match name {
+        "Abs" => map_components(arguments[0], f32::abs),
+        "Ceil" => map_components(arguments[0], f32::ceil),
+        "Floor" => map_components(arguments[0], f32::floor),
+        "Round" => map_components(arguments[0], f32::round),
+        "Sqrt" => map_components(arguments[0], f32::sqrt),
+        "Square" => map_components(arguments[0], |x| x * x),
+        "Sin" => Ok(Value::Scalar(scalar(0)?.sin())),
+        "Cos" => Ok(Value::Scalar(scalar(0)?.cos())),
+        "Tan" => Ok(Value::Scalar(scalar(0)?.tan())),
+        "Asin" => Ok(Value::Scalar(scalar(0)?.asin())),
+        "Acos" => Ok(Value::Scalar(scalar(0)?.acos())),
+        "Atan" => Ok(Value::Scalar(scalar(0)?.atan())),
+        "Atan2" => Ok(Value::Scalar(scalar(0)?.atan2(scalar(1)?))),
+        "Ln" => Ok(Value::Scalar(scalar(0)?.ln())),
+        "Log10" => Ok(Value::Scalar(scalar(0)?.log10())),
+        "Exp" => Ok(Value::Scalar(scalar(0)?.exp())),
+        "ToRadians" => Ok(Value::Scalar(scalar(0)?.to_radians())),
+        "ToDegrees" => Ok(Value::Scalar(scalar(0)?.to_degrees())),
+        "Pow" => Ok(Value::Scalar(scalar(0)?.powf(scalar(1)?))),
+        "Mod" => Ok(Value::Scalar(scalar(0)? % scalar(1)?)),
+        "Min" => zip_components(arguments[0], arguments[1], f32::min),
+        "Max" => zip_components(arguments[0], arguments[1], f32::max),
+        "Clamp" => {
+            let lower = zip_components(arguments[0], arguments[1], f32::max)?;
+            zip_components(lower, arguments[2], f32::min)
+        }
+        "Lerp" => {
+            let t = scalar(2)?;
+            zip_components(arguments[0], arguments[1], |a, b| a.interpolate(&b, t))
+        }
+        "Length" => Ok(Value::Scalar(length_squared(arguments[0]).sqrt())),
+        "LengthSquared" => Ok(Value::Scalar(length_squared(arguments[0]))),
+        "Normalize" => {
+            let length = length_squared(arguments[0]).sqrt();
+            map_components(
+                arguments[0],
+                |x| if length > 0.0 { x / length } else { 0.0 },
+            )
+        }
+        "Distance" | "DistanceSquared" => {
+            let difference = zip_components(arguments[0], arguments[1], |a, b| a - b)?;
+            let squared = length_squared(difference);
+            Ok(Value::Scalar(if name == "Distance" {
+                squared.sqrt()
+            } else {
+                squared
+            }))
+        }
+        "Scale" => zip_components(arguments[0], arguments[1], |a, b| a * b),
+        "Vector2" | "Vector3" | "Vector4" => {
+            let components = (0..arguments.len())
+                .map(scalar)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Value::from_components(&components).unwrap())
+        }
+        "ColorRGB" => {
+            let channel = |index: usize| -> Result<u8, ExpressionError> {
+                Ok(scalar(index)?.round().clamp(0.0, 255.0) as u8)
+            };
+            Ok(Value::Color(Color {
+                a: channel(0)?,
+                r: channel(1)?,
+                g: channel(2)?,
+                b: channel(3)?,
+            }))
+        }
+        "ColorLerp" => match (arguments[0], arguments[1]) {
+            (Value::Color(from), Value::Color(to)) => {
+                Ok(Value::Color(from.interpolate(&to, scalar(2)?)))
+            }
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn length_squared(value: Value) -> f32 {
+    value
+        .components()
+        .map(|components| components.iter().map(|x| x * x).sum())
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // "a" is a visual and "this" the animation, with a few properties set.
+    struct TestEnvironment(HashMap<(&'static str, &'static str), Value>);
+
+    impl TestEnvironment {
+        fn new() -> Self {
+            Self(HashMap::from([
+                (("a", "Offset"), Value::Vector3(Vector3::new(1.0, 2.0, 3.0))),
+                (("a", "Size"), Value::Vector2(Vector2::new(4.0, 3.0))),
+                (("a", "Opacity"), Value::Scalar(0.5)),
+                (("this", "StartingValue"), Value::Scalar(10.0)),
+                (
+                    ("this.Target", "Color"),
+                    Value::Color(Color {
+                        a: 255,
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                    }),
+                ),
+            ]))
+        }
+    }
+
+    impl Environment for TestEnvironment {
+        fn property_type(&self, object: &str, property: &str) -> Option<Type> {
+            self.property(object, property).map(|value| value.ty())
+        }
+
+        fn property(&self, object: &str, property: &str) -> Option<Value> {
+            self.0
+                .iter()
+                .find(|((o, p), _)| *o == object && *p == property)
+                .map(|(_, value)| *value)
+        }
+    }
+
+    fn evaluate(source: &str) -> Value {
+        let expression = Expression::parse(source).unwrap();
+        let environment = TestEnvironment::new();
+        let ty = expression.check(&environment).unwrap();
+        let value = expression.evaluate(&environment).unwrap();
+        assert_eq!(value.ty(), ty, "{}", source);
+        value
+    }
+
+    fn scalar(source: &str) -> f32 {
+        match evaluate(source) {
+            Value::Scalar(value) => value,
+            value => panic!("{} is {:?}", source, value),
+        }
+    }
+
+    fn check_error(source: &str) -> String {
+        let expression = Expression::parse(source).unwrap();
+        match expression.check(&TestEnvironment::new()) {
+            Err(ExpressionError::Type(message)) => message,
+            result => panic!("{} checked as {:?}", source, result),
+        }
+    }
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_numbers_names_and_symbols() {
+        assert_eq!(
+            kinds("a.Offset.x*2.5 >= .5e1"),
+            [
+                TokenKind::Identifier("a".to_owned()),
+                TokenKind::Symbol("."),
+                TokenKind::Identifier("Offset".to_owned()),
+                TokenKind::Symbol("."),
+                TokenKind::Identifier("x".to_owned()),
+                TokenKind::Symbol("*"),
+                TokenKind::Number(2.5),
+                TokenKind::Symbol(">="),
+                TokenKind::Number(5.0),
+            ]
+        );
+        assert_eq!(
+            kinds("1e-2+3"),
+            [
+                TokenKind::Number(0.01),
+                TokenKind::Symbol("+"),
+                TokenKind::Number(3.0),
+            ]
+        );
+        let positions: Vec<_> = tokenize("a && !b")
+            .unwrap()
+            .iter()
+            .map(|token| token.position)
+            .collect();
+        assert_eq!(positions, [0, 2, 5, 6]);
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_positions() {
+        assert_eq!(
+            tokenize("1 # 2").unwrap_err(),
+            ExpressionError::Syntax {
+                position: 2,
+                message: "unexpected character '#'".to_owned(),
+            }
+        );
+        assert!(matches!(
+            Expression::parse("1.2.3"),
+            Err(ExpressionError::Syntax { position: 0, .. })
+        ));
+        assert!(matches!(
+            Expression::parse("(1 + 2"),
+            Err(ExpressionError::Syntax { position: 6, .. })
+        ));
+        assert!(matches!(
+            Expression::parse("1 2"),
+            Err(ExpressionError::Syntax { position: 2, .. })
+        ));
+        assert!(matches!(
+            Expression::parse(""),
+            Err(ExpressionError::Syntax { position: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn parses_with_precedence() {
+        let printed = |source: &str| Expression::parse(source).unwrap().to_string();
+        assert_eq!(printed("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(printed("1 - 2 - 3"), "((1 - 2) - 3)");
+        assert_eq!(printed("(1 + 2) * 3"), "((1 + 2) * 3)");
+        assert_eq!(printed("1 < 2 == 3 > 4"), "((1 < 2) == (3 > 4))");
+        assert_eq!(
+            printed("true || false && !true"),
+            "(true || (false && !(true)))"
+        );
+        assert_eq!(printed("-a.Opacity * 2"), "(-(a.Opacity) * 2)");
+        assert_eq!(printed("-2 % 3"), "(-2 % 3)");
+        assert_eq!(
+            printed("true ? false ? 1 : 2 : 3"),
+            "(true ? (false ? 1 : 2) : 3)"
+        );
+        assert_eq!(scalar("2 + 3 * 4 - 6 / 2"), 11.0);
+        assert_eq!(scalar("7 % 4 * 2"), 6.0);
+        assert_eq!(scalar("1 < 2 && 3 < 2 ? 1 : 0"), 0.0);
+    }
+
+    #[test]
+    fn parses_references() {
+        assert_eq!(
+            Expression::parse("this.Target.Color").unwrap(),
+            Expression::Reference {
+                object: "this.Target".to_owned(),
+                property: "Color".to_owned(),
+            }
+        );
+        let expression = Expression::parse("a.Opacity + b.Opacity * this.StartingValue").unwrap();
+        assert_eq!(expression.references(), ["a", "b"]);
+    }
+
+    #[test]
+    fn swizzles_components() {
+        assert_eq!(evaluate("a.Offset.x"), Value::Scalar(1.0));
+        assert_eq!(
+            evaluate("a.Offset.zyx"),
+            Value::Vector3(Vector3::new(3.0, 2.0, 1.0))
+        );
+        assert_eq!(
+            evaluate("a.Size.YXXY"),
+            Value::Vector4(Vector4::new(3.0, 4.0, 4.0, 3.0))
+        );
+        assert_eq!(evaluate("a.Offset.xy.y"), Value::Scalar(2.0));
+        assert_eq!(check_error("a.Size.z"), "Vector2 has no component 'z'");
+        assert_eq!(check_error("a.Offset.xyzwx"), "invalid swizzle 'xyzwx'");
+        assert_eq!(check_error("(1 < 2).x"), "Boolean has no components");
+    }
+
+    #[test]
+    fn broadcasts_scalars_over_vectors() {
+        assert_eq!(
+            evaluate("a.Offset * 2"),
+            Value::Vector3(Vector3::new(2.0, 4.0, 6.0))
+        );
+        assert_eq!(
+            evaluate("12 / a.Size"),
+            Value::Vector2(Vector2::new(3.0, 4.0))
+        );
+        assert_eq!(
+            evaluate("a.Offset - a.Offset"),
+            Value::Vector3(Vector3::new(0.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            evaluate("-a.Size"),
+            Value::Vector2(Vector2::new(-4.0, -3.0))
+        );
+        assert_eq!(evaluate("a.Size == Vector2(4, 3)"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        assert_eq!(
+            check_error("a.Offset + a.Size"),
+            "operator '+' can't be applied to Vector3 and Vector2"
+        );
+        assert_eq!(
+            check_error("a.Offset * a.Size"),
+            "operator '*' can't be applied to Vector3 and Vector2"
+        );
+        assert_eq!(
+            check_error("a.Size < 1"),
+            "operator '<' can't be applied to Vector2 and Scalar"
+        );
+        assert_eq!(
+            check_error("1 && true"),
+            "operator '&&' can't be applied to Scalar and Boolean"
+        );
+        assert_eq!(check_error("!1"), "operator '!' can't be applied to Scalar");
+        assert_eq!(
+            check_error("-true"),
+            "operator '-' can't be applied to Boolean"
+        );
+        assert_eq!(
+            check_error("1 ? 2 : 3"),
+            "condition must be Boolean, found Scalar"
+        );
+        assert_eq!(
+            check_error("true ? 2 : a.Size"),
+            "conditional branches differ: Scalar and Vector2"
+        );
+        assert_eq!(check_error("b.Opacity"), "unknown property 'b.Opacity'");
+        assert_eq!(check_error("Sin(a.Size)"), "no function Sin(Vector2)");
+        assert_eq!(check_error("Length(1)"), "no function Length(Scalar)");
+        assert_eq!(check_error("Nope()"), "no function Nope()");
+    }
+
+    #[test]
+    fn evaluates_references_to_missing_values() {
+        let expression = Expression::parse("b.Opacity").unwrap();
+        assert_eq!(
+            expression.evaluate(&TestEnvironment::new()),
+            Err(ExpressionError::Evaluation(
+                "'b.Opacity' has no value".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn evaluates_scalar_functions() {
+        let close = |source: &str, expected: f32| {
+            let value = scalar(source);
+            assert!((value - expected).abs() < 1e-5, "{} = {}", source, value);
+        };
+        close("Abs(-2)", 2.0);
+        close("Ceil(1.2)", 2.0);
+        close("Floor(1.8)", 1.0);
+        close("Round(1.5)", 2.0);
+        close("Sqrt(9)", 3.0);
+        close("Square(3)", 9.0);
+        close("Sin(ToRadians(90))", 1.0);
+        close("Cos(0)", 1.0);
+        close("Tan(ToRadians(45))", 1.0);
+        close("ToDegrees(Asin(1))", 90.0);
+        close("Acos(1)", 0.0);
+        close("ToDegrees(Atan(1))", 45.0);
+        close("ToDegrees(Atan2(1, 0))", 90.0);
+        close("Ln(Exp(2))", 2.0);
+        close("Log10(1000)", 3.0);
+        close("Pow(2, 10)", 1024.0);
+        close("Mod(7, 3)", 1.0);
+        close("Min(2, 3)", 2.0);
+        close("Max(2, 3)", 3.0);
+        close("Clamp(5, 0, 1)", 1.0);
+        close("Clamp(-5, 0, 1)", 0.0);
+        close("Lerp(10, 20, 0.25)", 12.5);
+        close("Scale(2, 4)", 8.0);
+        close("Length(a.Size)", 5.0);
+        close("LengthSquared(a.Size)", 25.0);
+        close("Distance(a.Size, Vector2(1, -1))", 5.0);
+        close("DistanceSquared(a.Size, Vector2(1, -1))", 25.0);
+    }
+
+    #[test]
+    fn evaluates_vector_functions() {
+        assert_eq!(
+            evaluate("Abs(Vector2(-1, 2))"),
+            Value::Vector2(Vector2::new(1.0, 2.0))
+        );
+        assert_eq!(
+            evaluate("Min(a.Offset, Vector3(2, 2, 2))"),
+            Value::Vector3(Vector3::new(1.0, 2.0, 2.0))
+        );
+        assert_eq!(
+            evaluate("Max(a.Size, Vector2(5, 1))"),
+            Value::Vector2(Vector2::new(5.0, 3.0))
+        );
+        assert_eq!(
+            evaluate("Clamp(a.Offset, Vector3(2, 0, 0), Vector3(9, 9, 2))"),
+            Value::Vector3(Vector3::new(2.0, 2.0, 2.0))
+        );
+        assert_eq!(
+            evaluate("Lerp(Vector2(0, 0), a.Size, 0.5)"),
+            Value::Vector2(Vector2::new(2.0, 1.5))
+        );
+        assert_eq!(
+            evaluate("Normalize(a.Size)"),
+            Value::Vector2(Vector2::new(0.8, 0.6))
+        );
+        assert_eq!(
+            evaluate("Normalize(Vector2(0, 0))"),
+            Value::Vector2(Vector2::new(0.0, 0.0))
+        );
+        assert_eq!(
+            evaluate("Scale(a.Size, 2)"),
+            Value::Vector2(Vector2::new(8.0, 6.0))
+        );
+        assert_eq!(
+            evaluate("Vector3(1, 2, 3)"),
+            Value::Vector3(Vector3::new(1.0, 2.0, 3.0))
+        );
+        assert_eq!(
+            evaluate("Vector4(1, 2, 3, 4)"),
+            Value::Vector4(Vector4::new(1.0, 2.0, 3.0, 4.0))
+        );
+    }
+
+    #[test]
+    fn evaluates_color_functions() {
+        assert_eq!(
+            evaluate("ColorRGB(255, 300, 127.6, -4)"),
+            Value::Color(Color {
+                a: 255,
+                r: 255,
+                g: 128,
+                b: 0,
+            })
+        );
+        assert_eq!(
+            evaluate("ColorLerp(this.Target.Color, ColorRGB(255, 200, 100, 50), 0.5)"),
+            Value::Color(Color {
+                a: 255,
+                r: 100,
+                g: 50,
+                b: 25,
+            })
+        );
+    }
+}
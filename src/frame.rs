@@ -0,0 +1,117 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+// A rendered image with straight (not premultiplied) RGBA8 pixels, rows
+// stored top to bottom without padding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    // Converts rows of premultiplied BGRA8, as D2D and DXGI produce them,
+    // with 'pitch' bytes between the start of each row.
+    pub fn from_premultiplied_bgra(width: u32, height: u32, pitch: usize, data: &[u8]) -> Self {
+        let mut frame = Self::new(width, height);
+        let row_length = width as usize * 4;
+        for (y, row) in frame.pixels.chunks_exact_mut(row_length).enumerate() {
+            let source = &data[y * pitch..y * pitch + row_length];
+            for (pixel, bgra) in row.chunks_exact_mut(4).zip(source.chunks_exact(4)) {
+                let alpha = bgra[3];
+                let unpremultiply = |channel: u8| -> u8 {
+                    if alpha == 0 {
+                        0
+                    } else {
+                        ((channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+                    }
+                };
+                pixel[0] = unpremultiply(bgra[2]);
+                pixel[1] = unpremultiply(bgra[1]);
+                pixel[2] = unpremultiply(bgra[0]);
+                pixel[3] = alpha;
+            }
+        }
+        frame
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opaque_pixels_only_swap_to_rgba() {
+        let frame = Frame::from_premultiplied_bgra(2, 1, 8, &[1, 2, 3, 255, 40, 50, 60, 255]);
+        assert_eq!(frame.pixels, [3, 2, 1, 255, 60, 50, 40, 255]);
+    }
+
+    #[test]
+    fn transparent_pixels_become_transparent_black() {
+        let frame = Frame::from_premultiplied_bgra(1, 1, 4, &[10, 20, 30, 0]);
+        assert_eq!(frame.pixels, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn partly_transparent_pixels_are_unpremultiplied_with_rounding() {
+        let frame = Frame::from_premultiplied_bgra(1, 1, 4, &[0, 64, 128, 128]);
+        assert_eq!(frame.pixels, [255, 128, 0, 128]);
+        let frame = Frame::from_premultiplied_bgra(1, 1, 4, &[1, 2, 3, 3]);
+        assert_eq!(frame.pixels, [255, 170, 85, 3]);
+    }
+
+    #[test]
+    fn channels_brighter_than_alpha_are_clamped() {
+        let frame = Frame::from_premultiplied_bgra(1, 1, 4, &[200, 200, 200, 100]);
+        assert_eq!(frame.pixels, [255, 255, 255, 100]);
+    }
+
+    #[test]
+    fn padding_past_each_row_is_skipped() {
+        let data = [
+            1, 2, 3, 255, 9, 9, 9, 9, //
+            4, 5, 6, 255, 9, 9, 9, 9,
+        ];
+        let frame = Frame::from_premultiplied_bgra(1, 2, 8, &data);
+        assert_eq!(frame.pixels, [3, 2, 1, 255, 6, 5, 4, 255]);
+    }
+
+    #[test]
+    fn pngs_decode_to_the_same_pixels() {
+        let frame = Frame::from_premultiplied_bgra(2, 1, 8, &[1, 2, 3, 255, 0, 64, 128, 128]);
+        let mut png = Vec::new();
+        frame.write_png(&mut png).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(pixels, frame.pixels);
+    }
+}
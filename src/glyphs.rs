@@ -0,0 +1,211 @@
+use std::{cell::RefCell, mem::ManuallyDrop};
+
+use windows::{
+    Win32::Graphics::{
+        Direct2D::{ID2D1Brush, ID2D1DeviceContext},
+        DirectWrite::{
+            DWRITE_GLYPH_OFFSET, DWRITE_GLYPH_RUN, DWRITE_GLYPH_RUN_DESCRIPTION, DWRITE_MATRIX,
+            DWRITE_MEASURING_MODE, DWRITE_MEASURING_MODE_NATURAL, DWRITE_STRIKETHROUGH,
+            DWRITE_UNDERLINE, IDWriteFontFace, IDWriteInlineObject, IDWritePixelSnapping_Impl,
+            IDWriteTextLayout, IDWriteTextRenderer, IDWriteTextRenderer_Impl,
+        },
+    },
+    core::{BOOL, ComObject, IUnknown, Ref, Result, implement},
+};
+use windows_numerics::{Matrix3x2, Vector2};
+
+use crate::text_path::TextPath;
+
+// A single glyph from a layout's glyph runs. 'x' is its left edge on the
+// line, whichever way its run reads.
+struct Glyph {
+    font_face: IDWriteFontFace,
+    em_size: f32,
+    index: u16,
+    advance: f32,
+    offset: DWRITE_GLYPH_OFFSET,
+    x: f32,
+    is_sideways: bool,
+    is_right_to_left: bool,
+}
+
+// Collects the glyphs DirectWrite would draw for a layout, in the order it
+// draws them. Underlines, strikethroughs and inline objects are dropped.
+#[implement(IDWriteTextRenderer)]
+struct GlyphCollector {
+    glyphs: RefCell<Vec<Glyph>>,
+}
+
+impl IDWritePixelSnapping_Impl for GlyphCollector_Impl {
+    fn IsPixelSnappingDisabled(&self, _context: *const core::ffi::c_void) -> Result<BOOL> {
+        Ok(true.into())
+    }
+
+    fn GetCurrentTransform(
+        &self,
+        _context: *const core::ffi::c_void,
+        transform: *mut DWRITE_MATRIX,
+    ) -> Result<()> {
+        unsafe {
+            transform.write(DWRITE_MATRIX {
+                m11: 1.0,
+                m22: 1.0,
+                ..Default::default()
+            })
+        };
+        Ok(())
+    }
+
+    fn GetPixelsPerDip(&self, _context: *const core::ffi::c_void) -> Result<f32> {
+        Ok(1.0)
+    }
+}
+
+impl IDWriteTextRenderer_Impl for GlyphCollector_Impl {
+    fn DrawGlyphRun(
+        &self,
+        _context: *const core::ffi::c_void,
+        baseline_origin_x: f32,
+        _baseline_origin_y: f32,
+        _measuring_mode: DWRITE_MEASURING_MODE,
+        glyph_run: *const DWRITE_GLYPH_RUN,
+        _glyph_run_description: *const DWRITE_GLYPH_RUN_DESCRIPTION,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        let glyph_run = unsafe { &*glyph_run };
+        let Some(font_face) = glyph_run.fontFace.as_ref() else {
+            return Ok(());
+        };
+        let count = glyph_run.glyphCount as usize;
+        let (indices, advances, offsets) = unsafe {
+            (
+                std::slice::from_raw_parts(glyph_run.glyphIndices, count),
+                std::slice::from_raw_parts(glyph_run.glyphAdvances, count),
+                (!glyph_run.glyphOffsets.is_null())
+                    .then(|| std::slice::from_raw_parts(glyph_run.glyphOffsets, count)),
+            )
+        };
+        // Right-to-left runs are drawn leftwards from their origin.
+        let is_right_to_left = glyph_run.bidiLevel % 2 == 1;
+        let mut pen = 0.0;
+        let mut glyphs = self.glyphs.borrow_mut();
+        for (index, (&glyph_index, &advance)) in indices.iter().zip(advances).enumerate() {
+            let x = if is_right_to_left {
+                baseline_origin_x - pen - advance
+            } else {
+                baseline_origin_x + pen
+            };
+            pen += advance;
+            glyphs.push(Glyph {
+                font_face: font_face.clone(),
+                em_size: glyph_run.fontEmSize,
+                index: glyph_index,
+                advance,
+                offset: offsets.map_or_else(Default::default, |offsets| offsets[index]),
+                x,
+                is_sideways: glyph_run.isSideways.as_bool(),
+                is_right_to_left,
+            });
+        }
+        Ok(())
+    }
+
+    fn DrawUnderline(
+        &self,
+        _context: *const core::ffi::c_void,
+        _baseline_origin_x: f32,
+        _baseline_origin_y: f32,
+        _underline: *const DWRITE_UNDERLINE,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn DrawStrikethrough(
+        &self,
+        _context: *const core::ffi::c_void,
+        _baseline_origin_x: f32,
+        _baseline_origin_y: f32,
+        _strikethrough: *const DWRITE_STRIKETHROUGH,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn DrawInlineObject(
+        &self,
+        _context: *const core::ffi::c_void,
+        _origin_x: f32,
+        _origin_y: f32,
+        _inline_object: Ref<'_, IDWriteInlineObject>,
+        _is_sideways: BOOL,
+        _is_right_to_left: BOOL,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Draws the layout's single line of glyphs one at a time along the path,
+// whose flattened 'points' are in the same coordinates as the context's
+// current transform. Color glyphs are drawn in the brush's color.
+pub fn draw_text_on_path(
+    d2d_context: &ID2D1DeviceContext,
+    text_layout: &IDWriteTextLayout,
+    path: &TextPath,
+    points: &[Vector2],
+    brush: &ID2D1Brush,
+) -> Result<()> {
+    let collector = ComObject::new(GlyphCollector {
+        glyphs: RefCell::new(Vec::new()),
+    });
+    let renderer: IDWriteTextRenderer = collector.to_interface();
+    unsafe { text_layout.Draw(None, &renderer, 0.0, 0.0)? };
+    let glyphs = collector.glyphs.take();
+
+    let spans: Vec<_> = glyphs
+        .iter()
+        .map(|glyph| (glyph.x, glyph.advance))
+        .collect();
+    let placements = path.place_glyphs(points, &spans);
+    let mut transform = Matrix3x2::default();
+    unsafe {
+        d2d_context.GetTransform(&mut transform);
+        for (glyph, placement) in glyphs.iter().zip(placements) {
+            let Some(placement) = placement else {
+                continue;
+            };
+            // Each glyph is drawn as a left-to-right run of one, so offsets
+            // that pushed it leftwards in its run still do.
+            let offset = if glyph.is_right_to_left {
+                DWRITE_GLYPH_OFFSET {
+                    advanceOffset: -glyph.offset.advanceOffset,
+                    ..glyph.offset
+                }
+            } else {
+                glyph.offset
+            };
+            let glyph_run = DWRITE_GLYPH_RUN {
+                fontFace: ManuallyDrop::new(Some(glyph.font_face.clone())),
+                fontEmSize: glyph.em_size,
+                glyphCount: 1,
+                glyphIndices: &glyph.index,
+                glyphAdvances: &glyph.advance,
+                glyphOffsets: &offset,
+                isSideways: glyph.is_sideways.into(),
+                bidiLevel: 0,
+            };
+            d2d_context.SetTransform(&(placement.matrix() * transform));
+            d2d_context.DrawGlyphRun(
+                Vector2::zero(),
+                &glyph_run,
+                None,
+                brush,
+                DWRITE_MEASURING_MODE_NATURAL,
+            );
+            drop(ManuallyDrop::into_inner(glyph_run.fontFace));
+        }
+        d2d_context.SetTransform(&transform);
+    }
+    Ok(())
+}
@@ -0,0 +1,574 @@
+use std::time::Duration;
+
+use windows_numerics::{Matrix3x2, Vector2};
+
+use crate::{
+    expression::Environment,
+    metrics::{Cluster, LineBox, TextMetrics},
+    scene::{Scene, SceneEnvironment, TextVisual},
+};
+
+// Where a point lands in a visual's text: the cluster under it, starting
+// at 'character' and 'length' characters long, whether the point is over
+// its trailing half, and the line it's on. Points outside the text hit the
+// nearest cluster and aren't 'is_inside'. Characters are counted like
+// typography runs are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextHit {
+    pub character: usize,
+    pub length: usize,
+    pub is_trailing: bool,
+    pub line: usize,
+    pub is_inside: bool,
+}
+
+impl TextHit {
+    // Where a caret placed at the point goes, in characters.
+    pub fn caret(&self) -> usize {
+        if self.is_trailing {
+            self.character + self.length
+        } else {
+            self.character
+        }
+    }
+}
+
+// A cluster in the order it's shown on its line. 'start' counts UTF-16
+// code units, like the metrics.
+pub struct ShownCluster<'a> {
+    pub start: u32,
+    pub cluster: &'a Cluster,
+}
+
+// The topmost visual with text under a point in the window, and where in
+// its text the point is. Visuals are sized to the text they're showing,
+// from 'metrics', and expressions are resolved at 'time'.
+pub fn hit_test_scene<'m>(
+    scene: &Scene,
+    metrics: impl Fn(&str) -> Option<&'m TextMetrics>,
+    point: Vector2,
+    time: Duration,
+    window_size: Vector2,
+) -> Option<(String, TextHit)> {
+    let environment = scene_environment(scene, &metrics, time, window_size);
+    scene.visuals.iter().rev().find_map(|visual| {
+        let hit = hit_test(
+            visual,
+            metrics(&visual.id)?,
+            point,
+            time,
+            window_size,
+            &environment,
+        )?;
+        hit.is_inside.then(|| (visual.id.clone(), hit))
+    })
+}
+
+// Where a point in the window is in one visual's text, even when it's
+// outside it or under another visual, as it is while dragging out a
+// selection.
+pub fn hit_test_scene_visual<'m>(
+    scene: &Scene,
+    metrics: impl Fn(&str) -> Option<&'m TextMetrics>,
+    id: &str,
+    point: Vector2,
+    time: Duration,
+    window_size: Vector2,
+) -> Option<TextHit> {
+    let environment = scene_environment(scene, &metrics, time, window_size);
+    hit_test(
+        scene.visual(id)?,
+        metrics(id)?,
+        point,
+        time,
+        window_size,
+        &environment,
+    )
+}
+
+fn scene_environment<'s, 'm>(
+    scene: &'s Scene,
+    metrics: &impl Fn(&str) -> Option<&'m TextMetrics>,
+    time: Duration,
+    window_size: Vector2,
+) -> SceneEnvironment<'s> {
+    let mut environment = SceneEnvironment::new(scene, time, window_size);
+    for visual in &scene.visuals {
+        if let Some(metrics) = metrics(&visual.id) {
+            environment.set_size(&visual.id, metrics.size);
+        }
+    }
+    environment
+}
+
+// Hit tests a point in the parent's coordinates, such as the window's for
+// visuals under the root, by undoing the visual's anchor point, relative
+// offset and transform at 'time'.
+pub fn hit_test(
+    visual: &TextVisual,
+    metrics: &TextMetrics,
+    point: Vector2,
+    time: Duration,
+    parent_size: Vector2,
+    environment: &dyn Environment,
+) -> Option<TextHit> {
+    let matrix = visual.matrix_at(time, metrics.size, parent_size, environment);
+    let point = inverse_transform(&matrix, point)?;
+    hit_test_layout(&visual.layout_text(), metrics, point)
+}
+
+// The point that 'matrix' takes to 'point', unless it flattens everything
+// onto a line, like a zero scale does.
+pub fn inverse_transform(matrix: &Matrix3x2, point: Vector2) -> Option<Vector2> {
+    let determinant = matrix.M11 * matrix.M22 - matrix.M12 * matrix.M21;
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let x = point.X - matrix.M31;
+    let y = point.Y - matrix.M32;
+    Some(Vector2::new(
+        (x * matrix.M22 - y * matrix.M21) / determinant,
+        (y * matrix.M11 - x * matrix.M12) / determinant,
+    ))
+}
+
+// Hit tests a point in layout coordinates against the lines and clusters
+// of 'text'. Clusters run along each line in the order they're stored,
+// except that runs of right to left clusters are reversed, which is how
+// single level BiDi text is shown. Vertical columns run down from their
+// top. Text on a path can't be hit tested.
+pub fn hit_test_layout(text: &str, metrics: &TextMetrics, point: Vector2) -> Option<TextHit> {
+    if !metrics.path.is_empty() {
+        return None;
+    }
+    // Where lines sit across the direction text runs in, and where a
+    // point is along it and across it.
+    let across_line = |line: &LineBox| {
+        if metrics.is_vertical {
+            (line.left, line.width)
+        } else {
+            (line.top, line.height)
+        }
+    };
+    let (along, across) = if metrics.is_vertical {
+        (point.Y, point.X)
+    } else {
+        (point.X, point.Y)
+    };
+
+    let distance = |line: &LineBox| {
+        let (start, size) = across_line(line);
+        (start - across).max(across - (start + size)).max(0.0)
+    };
+    let (line_index, line) = metrics
+        .lines
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))?;
+    let line_start: u32 = metrics.lines[..line_index]
+        .iter()
+        .map(|line| line.length)
+        .sum();
+
+    let shown = shown_clusters(&metrics.clusters, line_start, line.length);
+    let line_origin = if metrics.is_vertical {
+        line.top
+    } else {
+        line.left
+    };
+    let line_end = line_origin
+        + shown
+            .iter()
+            .map(|shown_cluster| shown_cluster.cluster.width)
+            .sum::<f32>();
+    // Points before the line hit the leading edge of what's shown first,
+    // and points after it the trailing edge of what's shown last.
+    let mut edge = line_origin;
+    let hit = shown
+        .iter()
+        .find_map(|shown_cluster| {
+            let width = shown_cluster.cluster.width;
+            let is_right_half = along >= edge + width / 2.0;
+            edge += width;
+            (along < edge).then_some((
+                shown_cluster,
+                is_right_half != shown_cluster.cluster.is_right_to_left,
+            ))
+        })
+        .or_else(|| {
+            shown
+                .last()
+                .map(|last| (last, !last.cluster.is_right_to_left))
+        });
+    let Some((shown_cluster, is_trailing)) = hit else {
+        // Lines without clusters are empty or only a line break.
+        return Some(TextHit {
+            character: char_index(text, line_start),
+            length: 0,
+            is_trailing: false,
+            line: line_index,
+            is_inside: false,
+        });
+    };
+    let character = char_index(text, shown_cluster.start);
+    let end = char_index(text, shown_cluster.start + shown_cluster.cluster.length);
+    Some(TextHit {
+        character,
+        length: end - character,
+        is_trailing,
+        line: line_index,
+        is_inside: distance(line) == 0.0 && along >= line_origin && along < line_end,
+    })
+}
+
+// The line's clusters in the order they're shown, leaving out the line
+// break that ends it.
+pub fn shown_clusters(
+    clusters: &[Cluster],
+    line_start: u32,
+    line_length: u32,
+) -> Vec<ShownCluster<'_>> {
+    let mut shown = Vec::new();
+    let mut start = 0;
+    for cluster in clusters {
+        if start >= line_start + line_length {
+            break;
+        }
+        if start >= line_start && !cluster.is_newline {
+            shown.push(ShownCluster { start, cluster });
+        }
+        start += cluster.length;
+    }
+    let mut run_start = 0;
+    while run_start < shown.len() {
+        let is_right_to_left = shown[run_start].cluster.is_right_to_left;
+        let run_end = shown[run_start..]
+            .iter()
+            .position(|shown_cluster| shown_cluster.cluster.is_right_to_left != is_right_to_left)
+            .map_or(shown.len(), |length| run_start + length);
+        if is_right_to_left {
+            shown[run_start..run_end].reverse();
+        }
+        run_start = run_end;
+    }
+    shown
+}
+
+// The character that starts at a UTF-16 offset into 'text'.
+pub fn char_index(text: &str, utf16_offset: u32) -> usize {
+    let mut units = 0;
+    for (index, character) in text.chars().enumerate() {
+        if units >= utf16_offset {
+            return index;
+        }
+        units += character.len_utf16() as u32;
+    }
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use windows_numerics::Vector3;
+
+    use super::*;
+    use crate::scene::{Property, Transform};
+
+    // Lines of clusters 10 wide and 20 high, one cluster per character.
+    fn metrics(lines: &[&str]) -> TextMetrics {
+        let mut line_boxes = Vec::new();
+        let mut clusters = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            let mut length = 0;
+            for character in line.chars() {
+                clusters.push(Cluster {
+                    width: if character == '\n' { 0.0 } else { 10.0 },
+                    length: character.len_utf16() as u32,
+                    is_newline: character == '\n',
+                    ..Default::default()
+                });
+                length += character.len_utf16() as u32;
+            }
+            line_boxes.push(LineBox {
+                top: index as f32 * 20.0,
+                width: line.trim_end_matches('\n').chars().count() as f32 * 10.0,
+                height: 20.0,
+                baseline: 16.0,
+                length,
+                ..Default::default()
+            });
+        }
+        let width = line_boxes.iter().map(|line| line.width).fold(0.0, f32::max);
+        TextMetrics::new(
+            Vector2::new(width, lines.len() as f32 * 20.0),
+            Default::default(),
+            line_boxes,
+            clusters,
+        )
+    }
+
+    fn hit(character: usize, is_trailing: bool, line: usize, is_inside: bool) -> TextHit {
+        TextHit {
+            character,
+            length: 1,
+            is_trailing,
+            line,
+            is_inside,
+        }
+    }
+
+    fn rotation(degrees: f32) -> Matrix3x2 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Matrix3x2 {
+            M11: cos,
+            M12: sin,
+            M21: -sin,
+            M22: cos,
+            M31: 0.0,
+            M32: 0.0,
+        }
+    }
+
+    fn transform(matrix: &Matrix3x2, point: Vector2) -> Vector2 {
+        Vector2::new(
+            point.X * matrix.M11 + point.Y * matrix.M21 + matrix.M31,
+            point.X * matrix.M12 + point.Y * matrix.M22 + matrix.M32,
+        )
+    }
+
+    fn assert_near(actual: Vector2, expected: Vector2) {
+        assert!(
+            (actual.X - expected.X).abs() < 1e-4 && (actual.Y - expected.Y).abs() < 1e-4,
+            "{actual:?} isn't {expected:?}"
+        );
+    }
+
+    #[test]
+    fn inverse_transform_undoes_a_matrix() {
+        let point = Vector2::new(3.0, -7.0);
+        for matrix in [
+            Matrix3x2::identity(),
+            Matrix3x2::translation(10.0, 20.0),
+            Matrix3x2::scale(2.0, 0.5),
+            rotation(90.0),
+            rotation(-30.0),
+            Matrix3x2::translation(-5.0, 5.0) * Matrix3x2::scale(3.0, 1.0) * rotation(45.0),
+        ] {
+            assert_near(
+                inverse_transform(&matrix, transform(&matrix, point)).unwrap(),
+                point,
+            );
+        }
+        assert_near(
+            inverse_transform(&rotation(90.0), Vector2::new(0.0, 1.0)).unwrap(),
+            Vector2::new(1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn inverse_transform_fails_for_a_flattening_matrix() {
+        let point = Vector2::new(1.0, 1.0);
+        assert_eq!(inverse_transform(&Matrix3x2::scale(0.0, 1.0), point), None);
+        assert_eq!(inverse_transform(&Matrix3x2::scale(1.0, 0.0), point), None);
+        let projection = Matrix3x2 {
+            M11: 1.0,
+            M12: 2.0,
+            M21: 2.0,
+            M22: 4.0,
+            M31: 0.0,
+            M32: 0.0,
+        };
+        assert_eq!(inverse_transform(&projection, point), None);
+    }
+
+    #[test]
+    fn points_hit_the_half_of_the_cluster_under_them() {
+        let metrics = metrics(&["hello\n", "world"]);
+        let text = "hello\nworld";
+        let at = |x, y| hit_test_layout(text, &metrics, Vector2::new(x, y)).unwrap();
+        assert_eq!(at(2.0, 5.0), hit(0, false, 0, true));
+        assert_eq!(at(17.0, 5.0), hit(1, true, 0, true));
+        assert_eq!(at(49.0, 19.0), hit(4, true, 0, true));
+        assert_eq!(at(21.0, 25.0), hit(8, false, 1, true));
+        assert_eq!(hit(8, false, 1, true).caret(), 8);
+        assert_eq!(hit(8, true, 1, true).caret(), 9);
+    }
+
+    #[test]
+    fn points_outside_the_text_hit_the_nearest_cluster() {
+        let metrics = metrics(&["hello\n", "hi"]);
+        let text = "hello\nhi";
+        let at = |x, y| hit_test_layout(text, &metrics, Vector2::new(x, y)).unwrap();
+        assert_eq!(at(-5.0, 5.0), hit(0, false, 0, false));
+        assert_eq!(at(60.0, 5.0), hit(4, true, 0, false));
+        assert_eq!(at(35.0, 25.0), hit(7, true, 1, false));
+        assert_eq!(at(2.0, -10.0), hit(0, false, 0, false));
+        assert_eq!(at(15.0, 100.0), hit(7, true, 1, false));
+    }
+
+    #[test]
+    fn right_to_left_clusters_are_hit_in_the_order_theyre_shown() {
+        let mut metrics = metrics(&["ab\u{5D0}\u{5D1}\u{5D2}"]);
+        for cluster in &mut metrics.clusters[2..] {
+            cluster.is_right_to_left = true;
+        }
+        let text = "ab\u{5D0}\u{5D1}\u{5D2}";
+        let at = |x| hit_test_layout(text, &metrics, Vector2::new(x, 5.0)).unwrap();
+        // Shown as "ab" then gimel, bet, alef, whose leading edges are on
+        // their right.
+        assert_eq!(at(22.0), hit(4, true, 0, true));
+        assert_eq!(at(28.0), hit(4, false, 0, true));
+        assert_eq!(at(41.0), hit(2, true, 0, true));
+        assert_eq!(at(60.0), hit(2, false, 0, false));
+        let order: Vec<u32> = shown_clusters(&metrics.clusters, 0, 5)
+            .iter()
+            .map(|shown| shown.start)
+            .collect();
+        assert_eq!(order, [0, 1, 4, 3, 2]);
+    }
+
+    #[test]
+    fn characters_are_counted_in_chars_not_utf16() {
+        let metrics = metrics(&["\u{1F600}a"]);
+        let text = "\u{1F600}a";
+        let at = |x| hit_test_layout(text, &metrics, Vector2::new(x, 5.0)).unwrap();
+        assert_eq!(at(2.0), hit(0, false, 0, true));
+        assert_eq!(at(12.0), hit(1, false, 0, true));
+        assert_eq!(char_index(text, 2), 1);
+        assert_eq!(char_index(text, 10), 2);
+    }
+
+    #[test]
+    fn empty_lines_hit_their_start() {
+        let metrics = metrics(&["a\n", "\n", "b"]);
+        let text = "a\n\nb";
+        assert_eq!(
+            hit_test_layout(text, &metrics, Vector2::new(5.0, 25.0)),
+            Some(TextHit {
+                character: 2,
+                length: 0,
+                is_trailing: false,
+                line: 1,
+                is_inside: false,
+            })
+        );
+    }
+
+    #[test]
+    fn vertical_columns_run_down() {
+        let mut metrics = metrics(&["ab"]);
+        metrics.is_vertical = true;
+        metrics.lines[0] = LineBox {
+            left: 0.0,
+            top: 0.0,
+            width: 20.0,
+            height: 20.0,
+            length: 2,
+            ..Default::default()
+        };
+        let at = |x, y| hit_test_layout("ab", &metrics, Vector2::new(x, y)).unwrap();
+        assert_eq!(at(5.0, 2.0), hit(0, false, 0, true));
+        assert_eq!(at(5.0, 17.0), hit(1, true, 0, true));
+    }
+
+    #[test]
+    fn text_on_a_path_isnt_hit() {
+        let mut metrics = metrics(&["ab"]);
+        metrics.path = vec![Vector2::zero(), Vector2::new(20.0, 0.0)];
+        assert_eq!(
+            hit_test_layout("ab", &metrics, Vector2::new(5.0, 5.0)),
+            None
+        );
+    }
+
+    // A 50x20 visual of "hello" centered in a 200x100 window.
+    fn centered(id: &str) -> TextVisual {
+        let mut visual = Scene::demo().visuals.remove(0);
+        visual.id = id.to_owned();
+        visual.text = "hello".to_owned();
+        visual.path = None;
+        visual.anchor_point = Vector2::new(0.5, 0.5);
+        visual.relative_offset = Vector3::new(0.5, 0.5, 0.0);
+        visual.transform = Transform::default();
+        visual
+    }
+
+    const WINDOW: Vector2 = Vector2 { X: 200.0, Y: 100.0 };
+
+    fn hit_centered(visual: &TextVisual, point: Vector2) -> Option<TextHit> {
+        let scene = Scene {
+            visuals: vec![visual.clone()],
+            ..Scene::demo()
+        };
+        let metrics = metrics(&["hello"]);
+        hit_test_scene_visual(
+            &scene,
+            |_| Some(&metrics),
+            &visual.id,
+            point,
+            Duration::ZERO,
+            WINDOW,
+        )
+    }
+
+    #[test]
+    fn hit_testing_undoes_the_anchor_and_relative_offset() {
+        let visual = centered("a");
+        assert_eq!(
+            hit_centered(&visual, Vector2::new(78.0, 50.0)),
+            Some(hit(0, false, 0, true))
+        );
+        assert_eq!(
+            hit_centered(&visual, Vector2::new(122.0, 45.0)),
+            Some(hit(4, true, 0, true))
+        );
+    }
+
+    #[test]
+    fn hit_testing_undoes_the_transform() {
+        let mut visual = centered("a");
+        visual.transform.offset = Property::new(Vector3::new(10.0, 0.0, 0.0));
+        visual.transform.rotation_angle_in_degrees = Property::new(90.0);
+        visual.transform.center_point = Property::new(Vector3::new(25.0, 10.0, 0.0));
+        // (47, 5) in the layout is (22, -5) from the center point, which
+        // turns to (5, 22) and lands at (75 + 25 + 10, 40 + 10) plus that.
+        assert_eq!(
+            hit_centered(&visual, Vector2::new(115.0, 72.0)),
+            Some(hit(4, true, 0, true))
+        );
+
+        visual.transform.rotation_angle_in_degrees = Property::new(0.0);
+        visual.transform.scale = Property::new(Vector3::new(2.0, 2.0, 1.0));
+        // Scaled around the center point, now at (110, 50), so (120, 60)
+        // is (30, 15) in the layout.
+        assert_eq!(
+            hit_centered(&visual, Vector2::new(120.0, 60.0)),
+            Some(hit(3, false, 0, true))
+        );
+    }
+
+    #[test]
+    fn visuals_scaled_to_nothing_cant_be_hit() {
+        let mut visual = centered("a");
+        visual.transform.scale = Property::new(Vector3::new(0.0, 1.0, 1.0));
+        assert_eq!(hit_centered(&visual, Vector2::new(100.0, 50.0)), None);
+    }
+
+    #[test]
+    fn the_topmost_visual_with_text_under_the_point_is_hit() {
+        let mut below = centered("below");
+        below.relative_offset = Vector3::new(0.4, 0.5, 0.0);
+        let scene = Scene {
+            visuals: vec![below, centered("above"), centered("unmeasured")],
+            ..Scene::demo()
+        };
+        let metrics = metrics(&["hello"]);
+        let measured = |id: &str| (id != "unmeasured").then_some(&metrics);
+        let at = |x, y| {
+            hit_test_scene(&scene, measured, Vector2::new(x, y), Duration::ZERO, WINDOW)
+                .map(|(id, hit)| (id, hit.character))
+        };
+        assert_eq!(at(80.0, 50.0), Some(("above".to_owned(), 0)));
+        assert_eq!(at(60.0, 50.0), Some(("below".to_owned(), 0)));
+        assert_eq!(at(100.0, 90.0), None);
+    }
+}
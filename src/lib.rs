@@ -0,0 +1,27 @@
+pub mod animation;
+pub mod atlas;
+pub mod bidi;
+pub mod cache;
+pub mod cli;
+pub mod clock;
+pub mod diff;
+pub mod dirty;
+pub mod editing;
+pub mod emoji;
+pub mod error;
+pub mod export;
+pub mod expression;
+pub mod frame;
+pub mod hit_test;
+pub mod links;
+pub mod logging;
+pub mod metrics;
+pub mod overlay;
+pub mod scene;
+pub mod scene_file;
+pub mod svg;
+pub mod text_path;
+pub mod timeline;
+pub mod typography;
+pub mod unicode;
+pub mod watch;
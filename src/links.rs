@@ -0,0 +1,357 @@
+use std::ops::Range;
+
+use crate::{
+    hit_test::{TextHit, char_index, shown_clusters},
+    metrics::{LineBox, TextMetrics},
+    scene::{Color, Scene},
+};
+
+// What clicking a link does: open a URL in the browser, or hand an action
+// id to the app.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    Url(String),
+    Action(String),
+}
+
+// A range of a visual's text, in chars, that can be clicked. Links are
+// shown in 'color' and change to 'hover_color' and get underlined while
+// the mouse is over them. Where links overlap, the later one is clicked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub range: Range<usize>,
+    pub target: LinkTarget,
+    pub color: Color,
+    pub hover_color: Color,
+}
+
+// Where a link is drawn in its visual: a rectangle over each piece of the
+// link on a line, and an underline under each.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkShapes {
+    pub text: Vec<[f32; 4]>,
+    pub underlines: Vec<[f32; 4]>,
+}
+
+// Links are only set in horizontal text that isn't on a path, so lines run
+// left to right down the layout. Clusters next to each other on a line are
+// joined into one rectangle, which a line's trailing whitespace is left out
+// of.
+pub fn link_shapes(text: &str, metrics: &TextMetrics, range: Range<usize>) -> LinkShapes {
+    let mut shapes = LinkShapes::default();
+    let mut line_start = 0;
+    for line in &metrics.lines {
+        let shown_end = line_start + line.length - line.trailing_whitespace_length;
+        let mut edge = line.left;
+        let mut piece: Option<[f32; 2]> = None;
+        for shown in shown_clusters(&metrics.clusters, line_start, line.length) {
+            let width = shown.cluster.width;
+            let is_linked =
+                shown.start < shown_end && range.contains(&char_index(text, shown.start));
+            match (&mut piece, is_linked) {
+                (Some([_, end]), true) => *end = edge + width,
+                (None, true) => piece = Some([edge, edge + width]),
+                (Some([left, right]), false) => {
+                    shapes.push_piece(*left, *right, line);
+                    piece = None;
+                }
+                (None, false) => {}
+            }
+            edge += width;
+        }
+        if let Some([left, right]) = piece {
+            shapes.push_piece(left, right, line);
+        }
+        line_start += line.length;
+    }
+    shapes
+}
+
+impl LinkShapes {
+    // Underlines sit their own thickness below the baseline.
+    fn push_piece(&mut self, left: f32, right: f32, line: &LineBox) {
+        let thickness = (line.height / 16.0).round().max(1.0);
+        let width = right - left;
+        self.text.push([left, line.top, width, line.height]);
+        self.underlines
+            .push([left, line.top + line.baseline + thickness, width, thickness]);
+    }
+}
+
+// The link under a hit, if the hit is inside its visual's text.
+pub fn link_at(scene: &Scene, hit: Option<&(String, TextHit)>) -> Option<(String, usize)> {
+    let (id, hit) = hit?;
+    let links = &scene.visual(id)?.links;
+    links
+        .iter()
+        .rposition(|link| hit.is_inside && link.range.contains(&hit.character))
+        .map(|index| (id.clone(), index))
+}
+
+// What the mouse did to a link, which is named by its visual's id and its
+// index in the visual's links.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkEvent {
+    Enter(String, usize),
+    Leave(String, usize),
+    Click(String, usize),
+}
+
+// Tracks the link under the mouse from what's under it at each mouse
+// message. A link is clicked when the button goes down and comes back up
+// over it.
+#[derive(Default)]
+pub struct LinkHover {
+    hovered: Option<(String, usize)>,
+    pressed: Option<(String, usize)>,
+}
+
+impl LinkHover {
+    pub fn mouse_move(&mut self, link: Option<(String, usize)>) -> Vec<LinkEvent> {
+        if link == self.hovered {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        if let Some((id, index)) = self.hovered.take() {
+            events.push(LinkEvent::Leave(id, index));
+        }
+        if let Some((id, index)) = &link {
+            events.push(LinkEvent::Enter(id.clone(), *index));
+        }
+        self.hovered = link;
+        events
+    }
+
+    pub fn mouse_down(&mut self, link: Option<(String, usize)>) -> Vec<LinkEvent> {
+        let events = self.mouse_move(link.clone());
+        self.pressed = link;
+        events
+    }
+
+    pub fn mouse_up(&mut self, link: Option<(String, usize)>) -> Vec<LinkEvent> {
+        let mut events = self.mouse_move(link.clone());
+        if let Some(link) = link
+            && self.pressed.take().as_ref() == Some(&link)
+        {
+            events.push(LinkEvent::Click(link.0, link.1));
+        }
+        self.pressed = None;
+        events
+    }
+
+    // The mouse left the window, or the links under it may have changed
+    // with the scene, so nothing stays hovered or pressed.
+    pub fn reset(&mut self) -> Vec<LinkEvent> {
+        self.pressed = None;
+        self.mouse_move(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hit_test::TextHit, metrics::Cluster};
+
+    // Lines of clusters 10 wide and 16 high, one cluster per character,
+    // with trailing spaces counted as trailing whitespace.
+    fn metrics(lines: &[&str]) -> TextMetrics {
+        let mut line_boxes = Vec::new();
+        let mut clusters = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            for character in line.chars() {
+                clusters.push(Cluster {
+                    width: 10.0,
+                    length: character.len_utf16() as u32,
+                    is_whitespace: character == ' ',
+                    is_right_to_left: ('\u{5D0}'..='\u{5EA}').contains(&character),
+                    ..Default::default()
+                });
+            }
+            let length = line.encode_utf16().count() as u32;
+            line_boxes.push(LineBox {
+                top: index as f32 * 16.0,
+                width: line.trim_end().chars().count() as f32 * 10.0,
+                height: 16.0,
+                baseline: 12.0,
+                length,
+                trailing_whitespace_length: length - line.trim_end().encode_utf16().count() as u32,
+                ..Default::default()
+            });
+        }
+        TextMetrics {
+            lines: line_boxes,
+            clusters,
+            ..Default::default()
+        }
+    }
+
+    fn shapes(lines: &[&str], range: Range<usize>) -> LinkShapes {
+        link_shapes(&lines.concat(), &metrics(lines), range)
+    }
+
+    // A piece of a link from 'left' to 'right' on line 'line', and its
+    // underline.
+    fn piece(left: f32, right: f32, line: usize) -> ([f32; 4], [f32; 4]) {
+        let top = line as f32 * 16.0;
+        (
+            [left, top, right - left, 16.0],
+            [left, top + 13.0, right - left, 1.0],
+        )
+    }
+
+    fn pieces(pieces: &[([f32; 4], [f32; 4])]) -> LinkShapes {
+        LinkShapes {
+            text: pieces.iter().map(|(text, _)| *text).collect(),
+            underlines: pieces.iter().map(|(_, underline)| *underline).collect(),
+        }
+    }
+
+    #[test]
+    fn a_link_on_one_line_is_one_piece() {
+        assert_eq!(
+            shapes(&["Read the docs now"], 9..13),
+            pieces(&[piece(90.0, 130.0, 0)])
+        );
+    }
+
+    #[test]
+    fn a_link_across_lines_is_a_piece_on_each_without_trailing_whitespace() {
+        assert_eq!(
+            shapes(&["hello ", "world"], 3..8),
+            pieces(&[piece(30.0, 50.0, 0), piece(0.0, 20.0, 1)])
+        );
+    }
+
+    #[test]
+    fn right_to_left_links_are_joined_where_theyre_shown_together() {
+        // Shown as "ab", gimel, bet, alef.
+        let line = ["ab\u{5D0}\u{5D1}\u{5D2}"];
+        assert_eq!(shapes(&line, 2..4), pieces(&[piece(30.0, 50.0, 0)]));
+        assert_eq!(
+            shapes(&line, 1..3),
+            pieces(&[piece(10.0, 20.0, 0), piece(40.0, 50.0, 0)])
+        );
+    }
+
+    #[test]
+    fn links_outside_the_text_have_no_pieces() {
+        assert_eq!(shapes(&["hello"], 5..usize::MAX), LinkShapes::default());
+        assert_eq!(
+            shapes(&["hello"], 0..usize::MAX),
+            pieces(&[piece(0.0, 50.0, 0)])
+        );
+    }
+
+    #[test]
+    fn the_last_of_overlapping_links_is_under_a_hit() {
+        let scene = crate::scene_file::parse_scene(
+            r#"{ "visuals": [{ "id": "a", "text": "hello world", "links": [
+                { "start": 0, "length": 4, "url": "https://example.com" },
+                { "start": 2, "length": 4, "action": "b" }
+            ] }] }"#,
+        )
+        .unwrap();
+        let at = |id: &str, character, is_inside| {
+            let hit = TextHit {
+                character,
+                length: 1,
+                is_trailing: false,
+                line: 0,
+                is_inside,
+            };
+            link_at(&scene, Some(&(id.to_owned(), hit)))
+        };
+        assert_eq!(at("a", 0, true), Some(("a".to_owned(), 0)));
+        assert_eq!(at("a", 3, true), Some(("a".to_owned(), 1)));
+        assert_eq!(at("a", 7, true), None);
+        assert_eq!(at("a", 0, false), None);
+        assert_eq!(at("b", 0, true), None);
+        assert_eq!(link_at(&scene, None), None);
+    }
+
+    fn link(index: usize) -> Option<(String, usize)> {
+        Some(("a".to_owned(), index))
+    }
+
+    #[test]
+    fn moving_over_links_enters_and_leaves_them() {
+        let mut hover = LinkHover::default();
+        assert_eq!(
+            hover.mouse_move(link(0)),
+            [LinkEvent::Enter("a".to_owned(), 0)]
+        );
+        assert_eq!(hover.mouse_move(link(0)), []);
+        assert_eq!(
+            hover.mouse_move(link(1)),
+            [
+                LinkEvent::Leave("a".to_owned(), 0),
+                LinkEvent::Enter("a".to_owned(), 1)
+            ]
+        );
+        assert_eq!(
+            hover.mouse_move(None),
+            [LinkEvent::Leave("a".to_owned(), 1)]
+        );
+        assert_eq!(hover.mouse_move(None), []);
+    }
+
+    #[test]
+    fn pressing_and_releasing_over_a_link_clicks_it() {
+        let mut hover = LinkHover::default();
+        hover.mouse_move(link(0));
+        assert_eq!(hover.mouse_down(link(0)), []);
+        assert_eq!(
+            hover.mouse_up(link(0)),
+            [LinkEvent::Click("a".to_owned(), 0)]
+        );
+
+        // Leaving and coming back while pressed still clicks.
+        hover.mouse_down(link(0));
+        hover.mouse_move(None);
+        hover.mouse_move(link(0));
+        assert_eq!(
+            hover.mouse_up(link(0)),
+            [LinkEvent::Click("a".to_owned(), 0)]
+        );
+    }
+
+    #[test]
+    fn releasing_somewhere_else_doesnt_click() {
+        let mut hover = LinkHover::default();
+        hover.mouse_down(link(0));
+        assert_eq!(
+            hover.mouse_up(link(1)),
+            [
+                LinkEvent::Leave("a".to_owned(), 0),
+                LinkEvent::Enter("a".to_owned(), 1)
+            ]
+        );
+
+        hover.mouse_down(link(1));
+        assert_eq!(hover.mouse_up(None), [LinkEvent::Leave("a".to_owned(), 1)]);
+        // The press was used up.
+        assert_eq!(
+            hover.mouse_up(link(1)),
+            [LinkEvent::Enter("a".to_owned(), 1)]
+        );
+
+        // Pressing outside a link and releasing over one doesn't either.
+        hover.mouse_down(None);
+        assert_eq!(
+            hover.mouse_up(link(1)),
+            [LinkEvent::Enter("a".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn resetting_leaves_the_hovered_link_and_forgets_the_press() {
+        let mut hover = LinkHover::default();
+        hover.mouse_down(link(0));
+        assert_eq!(hover.reset(), [LinkEvent::Leave("a".to_owned(), 0)]);
+        assert_eq!(hover.reset(), []);
+        assert_eq!(
+            hover.mouse_up(link(0)),
+            [LinkEvent::Enter("a".to_owned(), 0)]
+        );
+    }
+}
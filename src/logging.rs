@@ -0,0 +1,35 @@
+use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    // One JSON object per line, for collecting logs from the field.
+    Json,
+}
+
+pub const DEFAULT_LOG_LEVEL: &str = "warn";
+
+// Sends log events to stderr, since stdout can carry Y4M video or JSON
+// metrics. 'filter' uses the RUST_LOG syntax, like "debug" or
+// "comptextdemo::render=trace"; without one, RUST_LOG is used if it's set.
+pub fn init_logging(filter: Option<&str>, format: LogFormat) -> Result<(), String> {
+    let filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter),
+        None => {
+            EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(DEFAULT_LOG_LEVEL))
+        }
+    }
+    .map_err(|error| format!("Invalid log filter: {}", error))?;
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        // Closing a span logs how long it took, which is what makes the
+        // spans around device creation and drawing useful.
+        .with_span_events(FmtSpan::CLOSE);
+    match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    }
+    .map_err(|error| error.to_string())
+}
@@ -0,0 +1,712 @@
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use windows_numerics::Vector2;
+
+use crate::{
+    bidi::BidiLevels,
+    scene::{Scene, TextVisual},
+    typography::{
+        FeatureTag, advance_scale, is_enabled, ligature_length, resolve_axes, resolve_runs,
+    },
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayoutEngine {
+    DirectWrite,
+    // TextMetrics::estimate, which is what's available on other platforms.
+    Estimate,
+}
+
+impl LayoutEngine {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DirectWrite => "directwrite",
+            Self::Estimate => "estimate",
+        }
+    }
+}
+
+// A line of laid out text, relative to the top left of the layout box.
+// 'left' and 'width' cover the line's text without trailing whitespace.
+// Lengths are in UTF-16 code units, like DirectWrite's. In vertical text
+// lines are columns, 'top' and 'height' cover the column's text and the
+// baseline is measured from the column's right edge.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LineBox {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub baseline: f32,
+    pub length: u32,
+    pub trailing_whitespace_length: u32,
+    pub newline_length: u32,
+    pub is_trimmed: bool,
+}
+
+// The smallest run of text that can't be split, usually a single glyph.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Cluster {
+    pub width: f32,
+    pub length: u32,
+    pub can_wrap_line_after: bool,
+    pub is_whitespace: bool,
+    pub is_newline: bool,
+    pub is_soft_hyphen: bool,
+    pub is_right_to_left: bool,
+}
+
+// How far the ink extends past each edge of the layout box, positive when
+// it's outside.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Overhang {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+// Where a visual's text ended up. 'text_rect' is what the visual is sized
+// from, and 'size', the size of the visual and of the surface its text is
+// drawn into, is that rounded down to whole pixels. Text on a path keeps
+// the lines and clusters of the line it was laid out on, and 'path' is the
+// flattened path in the visual's coordinates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    pub size: Vector2,
+    pub text_rect: [f32; 4],
+    pub layout_size: Vector2,
+    pub overhang: Overhang,
+    pub lines: Vec<LineBox>,
+    pub clusters: Vec<Cluster>,
+    pub is_vertical: bool,
+    pub path: Vec<Vector2>,
+}
+
+impl TextMetrics {
+    // Builds metrics from the layout box and what the layout engine
+    // reported, sizing the visual the same way for every engine.
+    pub fn new(
+        layout_size: Vector2,
+        overhang: Overhang,
+        lines: Vec<LineBox>,
+        clusters: Vec<Cluster>,
+    ) -> Self {
+        let text_rect = [
+            0.0,
+            0.0,
+            overhang.right + layout_size.X + -overhang.left,
+            overhang.bottom + layout_size.Y + -overhang.top,
+        ];
+        Self {
+            size: Vector2::new(text_rect[2].trunc(), text_rect[3].trunc()),
+            text_rect,
+            layout_size,
+            overhang,
+            lines,
+            clusters,
+            is_vertical: false,
+            path: Vec::new(),
+        }
+    }
+
+    // Sizes the visual to the frame around its path, if it has one.
+    pub fn on_path(self, visual: &TextVisual) -> Self {
+        let Some(path) = &visual.path else {
+            return self;
+        };
+        let (points, size) = path.frame(visual.font_size);
+        Self {
+            size: Vector2::new(size.X.trunc(), size.Y.trunc()),
+            text_rect: [0.0, 0.0, size.X, size.Y],
+            layout_size: size,
+            overhang: Overhang::default(),
+            path: points,
+            ..self
+        }
+    }
+
+    // A rough layout for when no text engine is around: every character is
+    // a cluster half as wide as the font size, which is about right for
+    // proportional Latin fonts, and lines wrap after whitespace. Right to
+    // left text is sized to its widest line and lines are aligned to the
+    // right, the way DirectWrite's layouts are; vertical text is estimated
+    // as if it were horizontal. Ligatures make one cluster of their letters
+    // and small caps are a little wider than lowercase letters; there's no
+    // kerning and figures are always tabular. Font axes widen or narrow
+    // characters by their width and weight. Text on a path is one line.
+    pub fn estimate(visual: &TextVisual) -> Self {
+        let right_to_left = visual.direction.is_right_to_left(&visual.text);
+        let max_width = if visual.path.is_some() {
+            f32::INFINITY
+        } else {
+            visual.max_width
+        };
+        let advance = visual.font_size * 0.5;
+        let line_height = visual.font_size * 1.25;
+        let characters: Vec<char> = visual.layout_text().chars().collect();
+        let mut clusters = Vec::with_capacity(characters.len());
+        // The index of each cluster's first character.
+        let mut cluster_starts = Vec::with_capacity(characters.len());
+        let mut scales = vec![1.0; characters.len()];
+        for (range, axes) in resolve_axes(&visual.typography, characters.len()) {
+            scales[range].fill(advance_scale(&axes));
+        }
+        for (range, features) in resolve_runs(&visual.typography, characters.len()) {
+            let mut index = range.start;
+            while index < range.end {
+                // CRLF is one line break, like it is to DirectWrite.
+                let length = if characters[index..range.end].starts_with(&['\r', '\n']) {
+                    2
+                } else {
+                    ligature_length(&characters[index..range.end], &features)
+                };
+                let character = characters[index];
+                let is_newline = matches!(character, '\n' | '\r' | '\u{2028}' | '\u{2029}');
+                let is_whitespace = character.is_whitespace();
+                let small_caps =
+                    character.is_lowercase() && is_enabled(&features, FeatureTag::SMALL_CAPS);
+                let width = match (is_newline, small_caps) {
+                    (true, _) => 0.0,
+                    (false, true) => advance * length as f32 * 1.2,
+                    (false, false) => advance * length as f32,
+                } * scales[index];
+                clusters.push(Cluster {
+                    width,
+                    length: characters[index..index + length]
+                        .iter()
+                        .map(|character| character.len_utf16() as u32)
+                        .sum(),
+                    can_wrap_line_after: is_whitespace || character == '-',
+                    is_whitespace,
+                    is_newline,
+                    is_soft_hyphen: character == '\u{AD}',
+                    is_right_to_left: false,
+                });
+                cluster_starts.push(index);
+                index += length;
+            }
+        }
+
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        while line_start < clusters.len() || lines.is_empty() {
+            let line_end = line_end(&clusters[line_start..], max_width) + line_start;
+            let line = &clusters[line_start..line_end];
+            let trailing = line
+                .iter()
+                .rev()
+                .take_while(|cluster| cluster.is_whitespace)
+                .count();
+            let content = &line[..line.len() - trailing];
+            let newline_length = line
+                .last()
+                .filter(|cluster| cluster.is_newline)
+                .map_or(0, |cluster| cluster.length);
+            lines.push(LineBox {
+                left: 0.0,
+                top: lines.len() as f32 * line_height,
+                width: content
+                    .iter()
+                    .fold(0.0, |width, cluster| width + cluster.width),
+                height: line_height,
+                baseline: visual.font_size,
+                length: line.iter().map(|cluster| cluster.length).sum(),
+                trailing_whitespace_length: line[content.len()..]
+                    .iter()
+                    .map(|cluster| cluster.length)
+                    .sum(),
+                newline_length,
+                is_trimmed: false,
+            });
+            line_start = line_end;
+            // Text ending in a newline gets an empty last line.
+            if line_start == clusters.len() && newline_length > 0 {
+                lines.push(LineBox {
+                    top: lines.len() as f32 * line_height,
+                    height: line_height,
+                    baseline: visual.font_size,
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Each cluster reads in the direction of its first character.
+        let bidi = BidiLevels::new(&visual.text, Some(right_to_left as u8));
+        let mut start = 0;
+        for line in &lines {
+            let (mut end, mut length) = (start, 0);
+            while length < line.length {
+                length += clusters[end].length;
+                end += 1;
+            }
+            let first_character = cluster_starts
+                .get(start)
+                .copied()
+                .unwrap_or(characters.len());
+            let end_character = cluster_starts.get(end).copied().unwrap_or(characters.len());
+            let levels = bidi.line_levels(first_character..end_character);
+            for (cluster, cluster_start) in clusters[start..end]
+                .iter_mut()
+                .zip(&cluster_starts[start..])
+            {
+                cluster.is_right_to_left = levels[cluster_start - first_character] % 2 == 1;
+            }
+            start = end;
+        }
+
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+        let height = lines.len() as f32 * line_height;
+        let layout_width = if right_to_left {
+            for line in &mut lines {
+                line.left = width - line.width;
+            }
+            width
+        } else {
+            visual.max_width
+        };
+        Self::new(
+            Vector2::new(layout_width, 0.0),
+            Overhang {
+                left: 0.0,
+                top: 0.0,
+                right: width - layout_width,
+                bottom: height,
+            },
+            lines,
+            clusters,
+        )
+        .on_path(visual)
+    }
+
+    // The rectangle covered by ink, as (x, y, width, height).
+    pub fn ink_rect(&self) -> [f32; 4] {
+        let overhang = &self.overhang;
+        [
+            -overhang.left,
+            -overhang.top,
+            self.layout_size.X + overhang.left + overhang.right,
+            self.layout_size.Y + overhang.top + overhang.bottom,
+        ]
+    }
+
+    pub fn to_json(&self) -> Value {
+        let [x, y, width, height] = self.text_rect;
+        json!({
+            "textRect": { "x": x, "y": y, "width": width, "height": height },
+            "textSize": { "width": self.size.X as i32, "height": self.size.Y as i32 },
+            "surfaceSize": { "width": self.size.X as i32, "height": self.size.Y as i32 },
+            "layoutSize": { "width": self.layout_size.X, "height": self.layout_size.Y },
+            "overhang": {
+                "left": self.overhang.left,
+                "top": self.overhang.top,
+                "right": self.overhang.right,
+                "bottom": self.overhang.bottom,
+            },
+            "lines": self.lines.iter().map(|line| json!({
+                "left": line.left,
+                "top": line.top,
+                "width": line.width,
+                "height": line.height,
+                "baseline": line.baseline,
+                "length": line.length,
+                "trailingWhitespaceLength": line.trailing_whitespace_length,
+                "newlineLength": line.newline_length,
+                "isTrimmed": line.is_trimmed,
+            })).collect::<Vec<_>>(),
+            "isVertical": self.is_vertical,
+            "path": self.path.iter().map(|point| [point.X, point.Y]).collect::<Vec<_>>(),
+            "clusters": self.clusters.iter().map(|cluster| json!({
+                "width": cluster.width,
+                "length": cluster.length,
+                "canWrapLineAfter": cluster.can_wrap_line_after,
+                "isWhitespace": cluster.is_whitespace,
+                "isNewline": cluster.is_newline,
+                "isSoftHyphen": cluster.is_soft_hyphen,
+                "isRightToLeft": cluster.is_right_to_left,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+// Where the line starting with 'clusters' ends: after a newline, or after
+// the last wrap opportunity before the text gets wider than 'max_width'.
+// Whitespace can hang past the edge and a line always takes at least one
+// cluster.
+fn line_end(clusters: &[Cluster], max_width: f32) -> usize {
+    let mut width = 0.0;
+    let mut wrap = None;
+    for (index, cluster) in clusters.iter().enumerate() {
+        if cluster.is_newline {
+            return index + 1;
+        }
+        width += cluster.width;
+        if !cluster.is_whitespace && width > max_width && index > 0 {
+            return wrap.unwrap_or(index);
+        }
+        if cluster.can_wrap_line_after {
+            wrap = Some(index + 1);
+        }
+    }
+    clusters.len()
+}
+
+// The metrics report for every visual in a scene, in scene order.
+pub fn metrics_report(engine: LayoutEngine, visuals: &[(&TextVisual, TextMetrics)]) -> Value {
+    json!({
+        "engine": engine.name(),
+        "visuals": visuals.iter().map(|(visual, metrics)| {
+            let right_to_left = visual.direction.is_right_to_left(&visual.text);
+            let mut report = json!({
+                "id": visual.id,
+                "text": visual.text,
+                "fontFamily": visual.font_family,
+                "fontSize": visual.font_size,
+                "maxWidth": visual.max_width,
+                "direction": if right_to_left { "rtl" } else { "ltr" },
+                "vertical": visual.vertical,
+            });
+            if let (Value::Object(report), Value::Object(metrics)) = (&mut report, metrics.to_json()) {
+                report.extend(metrics);
+            }
+            report
+        }).collect::<Vec<_>>(),
+    })
+}
+
+// The report of the portable estimate for every visual in 'scene' as it is
+// at time zero, which is all '--dump-metrics' can do without DirectWrite.
+pub fn estimate_report(scene: &Scene) -> Value {
+    let visuals: Vec<_> = scene
+        .visuals
+        .iter()
+        .map(|visual| {
+            (
+                visual,
+                TextMetrics::estimate(&visual.axes_at(Duration::ZERO)),
+            )
+        })
+        .collect();
+    metrics_report(LayoutEngine::Estimate, &visuals)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use windows_numerics::Vector3;
+
+    use super::*;
+    use crate::{
+        scene::{Color, Property, ReadingDirection, Transform},
+        text_path::{PathShape, TextPath},
+    };
+
+    // At a font size of 20 every character is 10 wide and lines are 25
+    // high.
+    fn visual(text: &str, max_width: f32) -> TextVisual {
+        TextVisual {
+            id: "text".to_owned(),
+            text: text.to_owned(),
+            font_family: "Segoe UI".to_owned(),
+            font_size: 20.0,
+            locale: "en-us".to_owned(),
+            max_width,
+            direction: ReadingDirection::Auto,
+            vertical: false,
+            typography: Vec::new(),
+            path: None,
+            editable: false,
+            links: Vec::new(),
+            anchor_point: Vector2::zero(),
+            relative_offset: Vector3::zero(),
+            color: Property::new(Color::rgb(0, 0, 0)),
+            transform: Transform::default(),
+            border: None,
+        }
+    }
+
+    fn cluster(width: f32, character: char) -> Cluster {
+        Cluster {
+            width,
+            length: 1,
+            can_wrap_line_after: character == ' ',
+            is_whitespace: character == ' ',
+            is_newline: character == '\n',
+            ..Default::default()
+        }
+    }
+
+    fn clusters(text: &str) -> Vec<Cluster> {
+        text.chars()
+            .map(|character| cluster(10.0, character))
+            .collect()
+    }
+
+    // (left, top, width, length) of each line.
+    fn lines(metrics: &TextMetrics) -> Vec<(f32, f32, f32, u32)> {
+        metrics
+            .lines
+            .iter()
+            .map(|line| (line.left, line.top, line.width, line.length))
+            .collect()
+    }
+
+    fn keys(value: &Value) -> BTreeSet<&str> {
+        value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn lines_end_at_the_last_wrap_before_the_edge() {
+        assert_eq!(line_end(&clusters("aaa bbb ccc"), 75.0), 8);
+        assert_eq!(line_end(&clusters("aaa bbb ccc"), 110.0), 11);
+        assert_eq!(line_end(&[], 100.0), 0);
+    }
+
+    #[test]
+    fn whitespace_hangs_past_the_edge() {
+        assert_eq!(line_end(&clusters("aa   b"), 20.0), 5);
+    }
+
+    #[test]
+    fn lines_end_after_newlines() {
+        assert_eq!(line_end(&clusters("ab\ncd"), 100.0), 3);
+        assert_eq!(line_end(&clusters("\nab"), 100.0), 1);
+    }
+
+    #[test]
+    fn words_wider_than_a_line_are_split() {
+        assert_eq!(line_end(&clusters("abcdefghij"), 35.0), 3);
+        // But every line takes at least one cluster.
+        assert_eq!(line_end(&[cluster(50.0, 'a'), cluster(10.0, 'b')], 10.0), 1);
+    }
+
+    #[test]
+    fn estimates_give_characters_half_the_font_size() {
+        let metrics = TextMetrics::estimate(&visual("hello", 200.0));
+        assert_eq!(lines(&metrics), [(0.0, 0.0, 50.0, 5)]);
+        assert_eq!(metrics.lines[0].height, 25.0);
+        assert_eq!(metrics.lines[0].baseline, 20.0);
+        assert_eq!(metrics.clusters, clusters("hello"));
+        assert_eq!(metrics.layout_size, Vector2::new(200.0, 0.0));
+        assert_eq!(metrics.text_rect, [0.0, 0.0, 50.0, 25.0]);
+        assert_eq!(metrics.size, Vector2::new(50.0, 25.0));
+        assert!(metrics.path.is_empty());
+    }
+
+    #[test]
+    fn estimates_wrap_after_whitespace_and_hyphens() {
+        let metrics = TextMetrics::estimate(&visual("aaa bbb well-known", 75.0));
+        assert_eq!(
+            lines(&metrics),
+            [
+                (0.0, 0.0, 70.0, 8),
+                (0.0, 25.0, 50.0, 5),
+                (0.0, 50.0, 50.0, 5)
+            ]
+        );
+        assert_eq!(metrics.lines[0].trailing_whitespace_length, 1);
+        assert_eq!(metrics.size, Vector2::new(70.0, 75.0));
+    }
+
+    #[test]
+    fn estimates_break_lines_at_newlines() {
+        let metrics = TextMetrics::estimate(&visual("ab\r\ncd\n", 200.0));
+        assert_eq!(
+            lines(&metrics),
+            [
+                (0.0, 0.0, 20.0, 4),
+                (0.0, 25.0, 20.0, 3),
+                (0.0, 50.0, 0.0, 0)
+            ]
+        );
+        assert_eq!(metrics.lines[0].newline_length, 2);
+        assert_eq!(metrics.lines[1].newline_length, 1);
+        // CRLF is a single cluster with no width.
+        assert_eq!(metrics.clusters.len(), 6);
+        assert_eq!(metrics.clusters[2].length, 2);
+        assert!(metrics.clusters[2].is_newline && metrics.clusters[2].width == 0.0);
+    }
+
+    #[test]
+    fn empty_text_has_one_empty_line() {
+        let metrics = TextMetrics::estimate(&visual("", 200.0));
+        assert_eq!(lines(&metrics), [(0.0, 0.0, 0.0, 0)]);
+        assert_eq!(metrics.size, Vector2::new(0.0, 25.0));
+    }
+
+    #[test]
+    fn right_to_left_estimates_align_lines_to_the_right() {
+        let metrics = TextMetrics::estimate(&visual("שלום עולם טוב", 100.0));
+        assert_eq!(
+            lines(&metrics),
+            [(0.0, 0.0, 90.0, 10), (60.0, 25.0, 30.0, 3)]
+        );
+        // Right to left layouts are as wide as their widest line.
+        assert_eq!(metrics.layout_size, Vector2::new(90.0, 0.0));
+        assert_eq!(metrics.size, Vector2::new(90.0, 50.0));
+        assert!(
+            metrics
+                .clusters
+                .iter()
+                .all(|cluster| cluster.is_right_to_left)
+        );
+    }
+
+    #[test]
+    fn left_to_right_runs_in_right_to_left_text_stay_left_to_right() {
+        let mut visual = visual("abc אבג", 200.0);
+        visual.direction = ReadingDirection::RightToLeft;
+        let metrics = TextMetrics::estimate(&visual);
+        let right_to_left: Vec<bool> = metrics
+            .clusters
+            .iter()
+            .map(|cluster| cluster.is_right_to_left)
+            .collect();
+        assert_eq!(right_to_left, [false, false, false, true, true, true, true]);
+        assert_eq!(lines(&metrics), [(0.0, 0.0, 70.0, 7)]);
+    }
+
+    #[test]
+    fn text_on_a_path_is_one_line_sized_from_the_path() {
+        let mut visual = visual("a long line of text", 50.0);
+        let path = TextPath {
+            shape: PathShape::Bezier(vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(100.0, 0.0),
+                Vector2::new(200.0, 0.0),
+                Vector2::new(300.0, 0.0),
+            ]),
+            start_offset: 0.0,
+            alignment: Default::default(),
+        };
+        let (points, size) = path.frame(visual.font_size);
+        visual.path = Some(path);
+        let metrics = TextMetrics::estimate(&visual);
+        assert_eq!(metrics.lines.len(), 1);
+        assert_eq!(metrics.path, points);
+        assert_eq!(metrics.layout_size, size);
+        assert_eq!(metrics.size, Vector2::new(size.X.trunc(), size.Y.trunc()));
+    }
+
+    #[test]
+    fn metrics_json_has_every_field() {
+        let metrics = TextMetrics::estimate(&visual("hi there", 200.0));
+        let json = metrics.to_json();
+        assert_eq!(
+            keys(&json),
+            BTreeSet::from([
+                "clusters",
+                "isVertical",
+                "layoutSize",
+                "lines",
+                "overhang",
+                "path",
+                "surfaceSize",
+                "textRect",
+                "textSize",
+            ])
+        );
+        assert_eq!(
+            json["textRect"],
+            json!({ "x": 0.0, "y": 0.0, "width": 80.0, "height": 25.0 })
+        );
+        assert_eq!(json["textSize"], json!({ "width": 80, "height": 25 }));
+        assert_eq!(json["surfaceSize"], json["textSize"]);
+        assert_eq!(
+            json["overhang"],
+            json!({ "left": 0.0, "top": 0.0, "right": -120.0, "bottom": 25.0 })
+        );
+        assert_eq!(
+            json["lines"],
+            json!([{
+                "left": 0.0,
+                "top": 0.0,
+                "width": 80.0,
+                "height": 25.0,
+                "baseline": 20.0,
+                "length": 8,
+                "trailingWhitespaceLength": 0,
+                "newlineLength": 0,
+                "isTrimmed": false,
+            }])
+        );
+        assert_eq!(json["clusters"].as_array().unwrap().len(), 8);
+        assert_eq!(
+            json["clusters"][2],
+            json!({
+                "width": 10.0,
+                "length": 1,
+                "canWrapLineAfter": true,
+                "isWhitespace": true,
+                "isNewline": false,
+                "isSoftHyphen": false,
+                "isRightToLeft": false,
+            })
+        );
+        assert_eq!(json["path"], json!([]));
+    }
+
+    #[test]
+    fn reports_add_each_visual_to_its_metrics() {
+        let hebrew = TextVisual {
+            id: "hebrew".to_owned(),
+            ..visual("שלום", 100.0)
+        };
+        let latin = visual("hello", 200.0);
+        let visuals = [
+            (&latin, TextMetrics::estimate(&latin)),
+            (&hebrew, TextMetrics::estimate(&hebrew)),
+        ];
+        let report = metrics_report(LayoutEngine::Estimate, &visuals);
+        assert_eq!(keys(&report), BTreeSet::from(["engine", "visuals"]));
+        assert_eq!(report["engine"], "estimate");
+        let reported = report["visuals"].as_array().unwrap();
+        assert_eq!(reported.len(), 2);
+        let metrics = visuals[0].1.to_json();
+        let mut expected = keys(&metrics);
+        expected.extend([
+            "id",
+            "text",
+            "fontFamily",
+            "fontSize",
+            "maxWidth",
+            "direction",
+            "vertical",
+        ]);
+        assert_eq!(keys(&reported[0]), expected);
+        assert_eq!(reported[0]["id"], "text");
+        assert_eq!(reported[0]["text"], "hello");
+        assert_eq!(reported[0]["fontSize"], 20.0);
+        assert_eq!(reported[0]["direction"], "ltr");
+        assert_eq!(reported[0]["vertical"], false);
+        assert_eq!(reported[1]["id"], "hebrew");
+        assert_eq!(reported[1]["direction"], "rtl");
+        assert_eq!(reported[1]["lines"], visuals[1].1.to_json()["lines"]);
+    }
+
+    #[test]
+    fn estimate_reports_cover_every_visual_in_the_scene() {
+        let scene = Scene::demo();
+        let report = estimate_report(&scene);
+        assert_eq!(report["engine"], "estimate");
+        let ids: Vec<&str> = report["visuals"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|visual| visual["id"].as_str().unwrap())
+            .collect();
+        let expected: Vec<&str> = scene
+            .visuals
+            .iter()
+            .map(|visual| visual.id.as_str())
+            .collect();
+        assert_eq!(ids, expected);
+    }
+}
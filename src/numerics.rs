@@ -1,6 +1,8 @@
-use windows::Graphics::SizeInt32;
+use windows::{Graphics::SizeInt32, UI::Color, Win32::Graphics::Direct2D::Common::D2D1_COLOR_F};
 use windows_numerics::Vector2;
 
+use crate::scene;
+
 pub trait ToVector2 {
     fn to_vector2(&self) -> Vector2;
 }
@@ -13,3 +15,33 @@ impl ToVector2 for SizeInt32 {
         }
     }
 }
+
+pub trait ToColor {
+    fn to_color(&self) -> Color;
+}
+
+impl ToColor for scene::Color {
+    fn to_color(&self) -> Color {
+        Color {
+            A: self.a,
+            R: self.r,
+            G: self.g,
+            B: self.b,
+        }
+    }
+}
+
+pub trait ToD2DColor {
+    fn to_d2d_color(&self) -> D2D1_COLOR_F;
+}
+
+impl ToD2DColor for scene::Color {
+    fn to_d2d_color(&self) -> D2D1_COLOR_F {
+        D2D1_COLOR_F {
+            r: self.r as f32 / 255.0,
+            g: self.g as f32 / 255.0,
+            b: self.b as f32 / 255.0,
+            a: self.a as f32 / 255.0,
+        }
+    }
+}
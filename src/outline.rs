@@ -0,0 +1,200 @@
+use std::cell::{Cell, RefCell};
+
+use windows::{
+    Win32::Graphics::{
+        Direct2D::Common::{
+            D2D1_BEZIER_SEGMENT, D2D1_FIGURE_BEGIN, D2D1_FIGURE_END, D2D1_FIGURE_END_CLOSED,
+            D2D1_FILL_MODE, D2D1_PATH_SEGMENT, ID2D1SimplifiedGeometrySink,
+            ID2D1SimplifiedGeometrySink_Impl,
+        },
+        DirectWrite::{
+            DWRITE_GLYPH_RUN, DWRITE_GLYPH_RUN_DESCRIPTION, DWRITE_MATRIX, DWRITE_MEASURING_MODE,
+            DWRITE_STRIKETHROUGH, DWRITE_UNDERLINE, IDWriteInlineObject, IDWritePixelSnapping_Impl,
+            IDWriteTextLayout, IDWriteTextRenderer, IDWriteTextRenderer_Impl,
+        },
+    },
+    core::{BOOL, ComObject, IUnknown, Ref, Result, implement},
+};
+use windows_numerics::Vector2;
+
+use crate::svg::PathBuilder;
+
+// Collects glyph outlines from DirectWrite as SVG path data. Glyph run
+// outlines are relative to their baseline origin, so the sink offsets every
+// point by the origin of the run being drawn.
+#[implement(ID2D1SimplifiedGeometrySink)]
+struct PathSink {
+    path: RefCell<PathBuilder>,
+    origin: Cell<Vector2>,
+}
+
+impl PathSink {
+    fn point(&self, point: &Vector2) -> Vector2 {
+        let origin = self.origin.get();
+        Vector2::new(point.X + origin.X, point.Y + origin.Y)
+    }
+}
+
+impl ID2D1SimplifiedGeometrySink_Impl for PathSink_Impl {
+    fn SetFillMode(&self, _fill_mode: D2D1_FILL_MODE) {}
+
+    fn SetSegmentFlags(&self, _vertex_flags: D2D1_PATH_SEGMENT) {}
+
+    fn BeginFigure(&self, start_point: &Vector2, _figure_begin: D2D1_FIGURE_BEGIN) {
+        self.path.borrow_mut().move_to(self.point(start_point));
+    }
+
+    fn AddLines(&self, points: *const Vector2, points_count: u32) {
+        let points = unsafe { std::slice::from_raw_parts(points, points_count as usize) };
+        let mut path = self.path.borrow_mut();
+        for point in points {
+            path.line_to(self.point(point));
+        }
+    }
+
+    fn AddBeziers(&self, beziers: *const D2D1_BEZIER_SEGMENT, beziers_count: u32) {
+        let beziers = unsafe { std::slice::from_raw_parts(beziers, beziers_count as usize) };
+        let mut path = self.path.borrow_mut();
+        for bezier in beziers {
+            path.cubic_to(
+                self.point(&bezier.point1),
+                self.point(&bezier.point2),
+                self.point(&bezier.point3),
+            );
+        }
+    }
+
+    fn EndFigure(&self, figure_end: D2D1_FIGURE_END) {
+        if figure_end == D2D1_FIGURE_END_CLOSED {
+            self.path.borrow_mut().close();
+        }
+    }
+
+    fn Close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[implement(IDWriteTextRenderer)]
+struct OutlineRenderer {
+    sink: ComObject<PathSink>,
+}
+
+impl IDWritePixelSnapping_Impl for OutlineRenderer_Impl {
+    fn IsPixelSnappingDisabled(&self, _context: *const core::ffi::c_void) -> Result<BOOL> {
+        Ok(true.into())
+    }
+
+    fn GetCurrentTransform(
+        &self,
+        _context: *const core::ffi::c_void,
+        transform: *mut DWRITE_MATRIX,
+    ) -> Result<()> {
+        unsafe {
+            transform.write(DWRITE_MATRIX {
+                m11: 1.0,
+                m22: 1.0,
+                ..Default::default()
+            })
+        };
+        Ok(())
+    }
+
+    fn GetPixelsPerDip(&self, _context: *const core::ffi::c_void) -> Result<f32> {
+        Ok(1.0)
+    }
+}
+
+impl IDWriteTextRenderer_Impl for OutlineRenderer_Impl {
+    fn DrawGlyphRun(
+        &self,
+        _context: *const core::ffi::c_void,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        _measuring_mode: DWRITE_MEASURING_MODE,
+        glyph_run: *const DWRITE_GLYPH_RUN,
+        _glyph_run_description: *const DWRITE_GLYPH_RUN_DESCRIPTION,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        let glyph_run = unsafe { &*glyph_run };
+        let Some(font_face) = glyph_run.fontFace.as_ref() else {
+            return Ok(());
+        };
+        self.sink
+            .origin
+            .set(Vector2::new(baseline_origin_x, baseline_origin_y));
+        let sink: ID2D1SimplifiedGeometrySink = self.sink.to_interface();
+        unsafe {
+            font_face.GetGlyphRunOutline(
+                glyph_run.fontEmSize,
+                glyph_run.glyphIndices,
+                (!glyph_run.glyphAdvances.is_null()).then_some(glyph_run.glyphAdvances),
+                (!glyph_run.glyphOffsets.is_null()).then_some(glyph_run.glyphOffsets),
+                glyph_run.glyphCount,
+                glyph_run.isSideways.as_bool(),
+                glyph_run.bidiLevel % 2 == 1,
+                &sink,
+            )
+        }
+    }
+
+    fn DrawUnderline(
+        &self,
+        _context: *const core::ffi::c_void,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        underline: *const DWRITE_UNDERLINE,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        let underline = unsafe { &*underline };
+        self.sink.path.borrow_mut().rect(
+            baseline_origin_x,
+            baseline_origin_y + underline.offset,
+            underline.width,
+            underline.thickness,
+        );
+        Ok(())
+    }
+
+    fn DrawStrikethrough(
+        &self,
+        _context: *const core::ffi::c_void,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        strikethrough: *const DWRITE_STRIKETHROUGH,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        let strikethrough = unsafe { &*strikethrough };
+        self.sink.path.borrow_mut().rect(
+            baseline_origin_x,
+            baseline_origin_y + strikethrough.offset,
+            strikethrough.width,
+            strikethrough.thickness,
+        );
+        Ok(())
+    }
+
+    fn DrawInlineObject(
+        &self,
+        _context: *const core::ffi::c_void,
+        _origin_x: f32,
+        _origin_y: f32,
+        _inline_object: Ref<'_, IDWriteInlineObject>,
+        _is_sideways: BOOL,
+        _is_right_to_left: BOOL,
+        _client_drawing_effect: Ref<'_, IUnknown>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+// The glyphs of a text layout drawn at the origin, as SVG path data.
+pub fn text_outline(text_layout: &IDWriteTextLayout) -> Result<String> {
+    let sink = ComObject::new(PathSink {
+        path: RefCell::new(PathBuilder::default()),
+        origin: Cell::new(Vector2::zero()),
+    });
+    let renderer: IDWriteTextRenderer = OutlineRenderer { sink: sink.clone() }.into();
+    unsafe { text_layout.Draw(None, &renderer, 0.0, 0.0)? };
+    Ok(sink.path.take().finish())
+}
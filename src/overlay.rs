@@ -0,0 +1,522 @@
+use std::time::Duration;
+
+use windows_numerics::{Vector2, Vector3};
+
+use crate::{
+    metrics::TextMetrics,
+    scene::{Border, Color, Property, ReadingDirection, TextVisual, Transform},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    // The visual's bounds, drawn as the red border the demo always had.
+    Bounds,
+    // Each line's box and baseline, or the path that text on a path follows.
+    Lines,
+    // The layout box and the ink overhanging it.
+    Overhang,
+    // The bounds of the surface the text is drawn into.
+    Surfaces,
+    // Frame rate and frame number in the top left corner.
+    Counter,
+}
+
+impl Overlay {
+    pub const ALL: [Self; 5] = [
+        Self::Bounds,
+        Self::Lines,
+        Self::Overhang,
+        Self::Surfaces,
+        Self::Counter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bounds => "bounds",
+            Self::Lines => "lines",
+            Self::Overhang => "overhang",
+            Self::Surfaces => "surfaces",
+            Self::Counter => "fps",
+        }
+    }
+
+    // The key that toggles the overlay in the window.
+    pub fn key(self) -> char {
+        match self {
+            Self::Bounds => 'B',
+            Self::Lines => 'L',
+            Self::Overhang => 'O',
+            Self::Surfaces => 'S',
+            Self::Counter => 'F',
+        }
+    }
+
+    pub fn from_key(key: char) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|overlay| overlay.key() == key.to_ascii_uppercase())
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Overlays(u8);
+
+impl Overlays {
+    // A comma separated list of overlay names, or "all". An empty list
+    // turns them all off.
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut overlays = Self::default();
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if name == "all" {
+                Overlay::ALL
+                    .into_iter()
+                    .for_each(|overlay| overlays.insert(overlay));
+                continue;
+            }
+            let overlay = Overlay::ALL
+                .into_iter()
+                .find(|overlay| overlay.name() == name)
+                .ok_or_else(|| format!("Unknown overlay '{}'", name))?;
+            overlays.insert(overlay);
+        }
+        Ok(overlays)
+    }
+
+    pub fn contains(self, overlay: Overlay) -> bool {
+        self.0 & overlay.bit() != 0
+    }
+
+    pub fn insert(&mut self, overlay: Overlay) {
+        self.0 |= overlay.bit();
+    }
+
+    pub fn toggle(&mut self, overlay: Overlay) {
+        self.0 ^= overlay.bit();
+    }
+}
+
+// Rectangles are (x, y, width, height). Strokes are a pixel wide and drawn
+// just inside the rectangle so they stay crisp.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Fill([f32; 4], Color),
+    Stroke([f32; 4], Color),
+    Line(Vector2, Vector2, Color),
+}
+
+impl Shape {
+    fn bounds(&self) -> [f32; 4] {
+        match *self {
+            Self::Fill(rect, _) | Self::Stroke(rect, _) => rect,
+            Self::Line(from, to, _) => [
+                from.X.min(to.X),
+                from.Y.min(to.Y),
+                (to.X - from.X).abs().max(1.0),
+                (to.Y - from.Y).abs().max(1.0),
+            ],
+        }
+    }
+}
+
+const BOUNDS: Border = Border {
+    thickness: 2.0,
+    color: Color::rgb(255, 0, 0),
+};
+const LINE_COLOR: Color = Color::rgb(0, 120, 215);
+const BASELINE_COLOR: Color = Color::rgb(0, 160, 0);
+const LAYOUT_BOX_COLOR: Color = Color::rgb(255, 140, 0);
+const INK_COLOR: Color = Color {
+    a: 64,
+    r: 255,
+    g: 140,
+    b: 0,
+};
+const SURFACE_COLOR: Color = Color::rgb(200, 0, 200);
+
+// The shapes drawn over a visual, in the visual's own coordinates.
+pub fn visual_shapes(overlays: Overlays, metrics: &TextMetrics) -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    if overlays.contains(Overlay::Overhang) {
+        shapes.push(Shape::Fill(metrics.ink_rect(), INK_COLOR));
+        shapes.push(Shape::Stroke(
+            [0.0, 0.0, metrics.layout_size.X, metrics.layout_size.Y],
+            LAYOUT_BOX_COLOR,
+        ));
+    }
+    if overlays.contains(Overlay::Lines) && !metrics.path.is_empty() {
+        for segment in metrics.path.windows(2) {
+            shapes.push(Shape::Line(segment[0], segment[1], BASELINE_COLOR));
+        }
+    } else if overlays.contains(Overlay::Lines) {
+        for line in &metrics.lines {
+            shapes.push(Shape::Stroke(
+                [line.left, line.top, line.width, line.height],
+                LINE_COLOR,
+            ));
+            let (start, end) = if metrics.is_vertical {
+                let x = line.left + line.width - line.baseline;
+                (
+                    Vector2::new(x, line.top),
+                    Vector2::new(x, line.top + line.height),
+                )
+            } else {
+                let y = line.top + line.baseline;
+                (
+                    Vector2::new(line.left, y),
+                    Vector2::new(line.left + line.width, y),
+                )
+            };
+            shapes.push(Shape::Line(start, end, BASELINE_COLOR));
+        }
+    }
+    if overlays.contains(Overlay::Surfaces) {
+        shapes.push(Shape::Stroke(
+            [0.0, 0.0, metrics.size.X, metrics.size.Y],
+            SURFACE_COLOR,
+        ));
+    }
+    if overlays.contains(Overlay::Bounds) {
+        for rect in BOUNDS.bands(metrics.size) {
+            shapes.push(Shape::Fill(rect, BOUNDS.color));
+        }
+    }
+    shapes
+}
+
+// The smallest whole pixel rectangle holding all of 'shapes'.
+pub fn shapes_bounds(shapes: &[Shape]) -> Option<[f32; 4]> {
+    let (left, top, right, bottom) = shapes.iter().map(Shape::bounds).fold(
+        (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+        |(left, top, right, bottom), [x, y, width, height]| {
+            (
+                left.min(x),
+                top.min(y),
+                right.max(x + width),
+                bottom.max(y + height),
+            )
+        },
+    );
+    (left < right && top < bottom).then(|| {
+        let (left, top) = (left.floor(), top.floor());
+        [left, top, right.ceil() - left, bottom.ceil() - top]
+    })
+}
+
+// The counter's text is laid out like any other visual's, at this offset
+// from the top left of the window with a backdrop padded around it.
+pub const COUNTER_OFFSET: Vector2 = Vector2 { X: 12.0, Y: 12.0 };
+const COUNTER_PADDING: f32 = 4.0;
+const COUNTER_BACKDROP: Color = Color {
+    a: 192,
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+pub fn counter_visual(fps: f32, frame: u64) -> TextVisual {
+    TextVisual {
+        id: "counter".to_owned(),
+        text: format!("{:.1} fps  frame {}", fps, frame),
+        font_family: "Consolas".to_owned(),
+        font_size: 14.0,
+        locale: "en-us".to_owned(),
+        max_width: 400.0,
+        direction: ReadingDirection::LeftToRight,
+        vertical: false,
+        typography: Vec::new(),
+        path: None,
+        editable: false,
+        links: Vec::new(),
+        anchor_point: Vector2::zero(),
+        relative_offset: Vector3::zero(),
+        color: Property::new(Color::rgb(0, 0, 0)),
+        transform: Transform::default(),
+        border: None,
+    }
+}
+
+// The backdrop behind counter text of 'size', relative to the text.
+pub fn counter_shapes(size: Vector2) -> Vec<Shape> {
+    vec![Shape::Fill(
+        [
+            -COUNTER_PADDING,
+            -COUNTER_PADDING,
+            size.X + COUNTER_PADDING * 2.0,
+            size.Y + COUNTER_PADDING * 2.0,
+        ],
+        COUNTER_BACKDROP,
+    )]
+}
+
+// Averages the frame rate between samples of a running frame count.
+#[derive(Clone, Debug, Default)]
+pub struct FrameRate {
+    first_frame: Option<u64>,
+    last: Option<(Duration, u64)>,
+    fps: f32,
+}
+
+impl FrameRate {
+    // Returns the frame rate since the previous sample and the number of
+    // frames since the first.
+    pub fn sample(&mut self, time: Duration, frame: u64) -> (f32, u64) {
+        let first_frame = *self.first_frame.get_or_insert(frame);
+        if let Some((last_time, last_frame)) = self.last {
+            let elapsed = time.saturating_sub(last_time).as_secs_f32();
+            if elapsed > 0.0 {
+                self.fps = frame.saturating_sub(last_frame) as f32 / elapsed;
+            }
+        }
+        self.last = Some((time, frame));
+        (self.fps, frame.saturating_sub(first_frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{LineBox, Overhang};
+
+    fn only(overlay: Overlay) -> Overlays {
+        let mut overlays = Overlays::default();
+        overlays.insert(overlay);
+        overlays
+    }
+
+    // Two lines in a 100x40 layout box whose ink overhangs it.
+    fn metrics() -> TextMetrics {
+        let line = |top: f32, width: f32| LineBox {
+            left: 0.0,
+            top,
+            width,
+            height: 20.0,
+            baseline: 16.0,
+            ..Default::default()
+        };
+        TextMetrics::new(
+            Vector2::new(100.0, 40.0),
+            Overhang {
+                left: 2.0,
+                top: 1.0,
+                right: 3.0,
+                bottom: 4.0,
+            },
+            vec![line(0.0, 100.0), line(20.0, 60.0)],
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn overlays_parse_from_a_list_of_names() {
+        let overlays = Overlays::parse("bounds, fps ,lines").unwrap();
+        for overlay in Overlay::ALL {
+            assert_eq!(
+                overlays.contains(overlay),
+                matches!(overlay, Overlay::Bounds | Overlay::Counter | Overlay::Lines),
+                "{overlay:?}"
+            );
+        }
+        let all = Overlays::parse("all").unwrap();
+        assert!(
+            Overlay::ALL
+                .into_iter()
+                .all(|overlay| all.contains(overlay))
+        );
+        assert_eq!(Overlays::parse("surfaces,all").unwrap(), all);
+    }
+
+    #[test]
+    fn empty_overlay_lists_turn_them_off() {
+        assert_eq!(Overlays::parse("").unwrap(), Overlays::default());
+        assert_eq!(Overlays::parse(" , ").unwrap(), Overlays::default());
+        assert_eq!(Overlays::parse("lines,").unwrap(), only(Overlay::Lines));
+    }
+
+    #[test]
+    fn unknown_overlays_are_errors() {
+        assert_eq!(
+            Overlays::parse("bounds,grid").unwrap_err(),
+            "Unknown overlay 'grid'"
+        );
+        // Names are matched exactly, and the counter's name is "fps".
+        assert!(Overlays::parse("Bounds").is_err());
+        assert!(Overlays::parse("counter").is_err());
+    }
+
+    #[test]
+    fn toggling_flips_one_overlay() {
+        let mut overlays = Overlays::parse("bounds,surfaces").unwrap();
+        overlays.toggle(Overlay::Lines);
+        overlays.toggle(Overlay::Bounds);
+        assert_eq!(overlays, Overlays::parse("lines,surfaces").unwrap());
+        overlays.toggle(Overlay::Lines);
+        overlays.toggle(Overlay::Bounds);
+        assert_eq!(overlays, Overlays::parse("bounds,surfaces").unwrap());
+    }
+
+    #[test]
+    fn keys_pick_overlays_in_either_case() {
+        assert_eq!(Overlay::from_key('f'), Some(Overlay::Counter));
+        assert_eq!(Overlay::from_key('O'), Some(Overlay::Overhang));
+        assert_eq!(Overlay::from_key('x'), None);
+    }
+
+    #[test]
+    fn no_overlays_draw_nothing() {
+        assert!(visual_shapes(Overlays::default(), &metrics()).is_empty());
+        // The counter isn't drawn per visual.
+        assert!(visual_shapes(only(Overlay::Counter), &metrics()).is_empty());
+        assert_eq!(shapes_bounds(&[]), None);
+    }
+
+    #[test]
+    fn overhang_shows_the_ink_and_the_layout_box() {
+        assert_eq!(
+            visual_shapes(only(Overlay::Overhang), &metrics()),
+            [
+                Shape::Fill([-2.0, -1.0, 105.0, 45.0], INK_COLOR),
+                Shape::Stroke([0.0, 0.0, 100.0, 40.0], LAYOUT_BOX_COLOR),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_show_each_line_box_and_baseline() {
+        assert_eq!(
+            visual_shapes(only(Overlay::Lines), &metrics()),
+            [
+                Shape::Stroke([0.0, 0.0, 100.0, 20.0], LINE_COLOR),
+                Shape::Line(
+                    Vector2::new(0.0, 16.0),
+                    Vector2::new(100.0, 16.0),
+                    BASELINE_COLOR
+                ),
+                Shape::Stroke([0.0, 20.0, 60.0, 20.0], LINE_COLOR),
+                Shape::Line(
+                    Vector2::new(0.0, 36.0),
+                    Vector2::new(60.0, 36.0),
+                    BASELINE_COLOR
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn vertical_baselines_run_down_their_column() {
+        let metrics = TextMetrics {
+            is_vertical: true,
+            lines: vec![LineBox {
+                left: 10.0,
+                top: 0.0,
+                width: 20.0,
+                height: 50.0,
+                baseline: 6.0,
+                ..Default::default()
+            }],
+            ..metrics()
+        };
+        assert_eq!(
+            visual_shapes(only(Overlay::Lines), &metrics)[1],
+            Shape::Line(
+                Vector2::new(24.0, 0.0),
+                Vector2::new(24.0, 50.0),
+                BASELINE_COLOR
+            )
+        );
+    }
+
+    #[test]
+    fn lines_of_text_on_a_path_show_the_path() {
+        let points = [
+            Vector2::new(0.0, 10.0),
+            Vector2::new(5.0, 5.0),
+            Vector2::new(10.0, 10.0),
+        ];
+        let metrics = TextMetrics {
+            path: points.to_vec(),
+            ..metrics()
+        };
+        assert_eq!(
+            visual_shapes(only(Overlay::Lines), &metrics),
+            [
+                Shape::Line(points[0], points[1], BASELINE_COLOR),
+                Shape::Line(points[1], points[2], BASELINE_COLOR),
+            ]
+        );
+    }
+
+    #[test]
+    fn surfaces_and_bounds_follow_the_visual_size() {
+        let metrics = metrics();
+        assert_eq!(metrics.size, Vector2::new(101.0, 43.0));
+        assert_eq!(
+            visual_shapes(only(Overlay::Surfaces), &metrics),
+            [Shape::Stroke([0.0, 0.0, 101.0, 43.0], SURFACE_COLOR)]
+        );
+        let bounds = visual_shapes(only(Overlay::Bounds), &metrics);
+        assert_eq!(bounds.len(), 4);
+        assert_eq!(
+            bounds[0],
+            Shape::Fill([-2.0, -2.0, 105.0, 2.0], BOUNDS.color)
+        );
+        assert_eq!(shapes_bounds(&bounds), Some([-2.0, -2.0, 105.0, 47.0]));
+    }
+
+    #[test]
+    fn every_overlay_draws_in_order() {
+        let shapes = visual_shapes(Overlays::parse("all").unwrap(), &metrics());
+        assert_eq!(shapes.len(), 2 + 4 + 1 + 4);
+        assert!(matches!(shapes[0], Shape::Fill(_, INK_COLOR)));
+        assert!(matches!(shapes[6], Shape::Stroke(_, SURFACE_COLOR)));
+        assert_eq!(shapes_bounds(&shapes), Some([-2.0, -2.0, 105.0, 47.0]));
+    }
+
+    #[test]
+    fn shape_bounds_round_out_to_whole_pixels() {
+        let shapes = [
+            Shape::Fill([0.5, 1.25, 10.0, 5.0], INK_COLOR),
+            Shape::Stroke([-3.5, 2.0, 4.0, 4.0], LINE_COLOR),
+        ];
+        assert_eq!(shapes_bounds(&shapes), Some([-4.0, 1.0, 15.0, 6.0]));
+    }
+
+    #[test]
+    fn straight_lines_are_at_least_a_pixel_wide() {
+        let line = Shape::Line(
+            Vector2::new(20.0, 5.0),
+            Vector2::new(10.0, 5.0),
+            BASELINE_COLOR,
+        );
+        assert_eq!(shapes_bounds(&[line]), Some([10.0, 5.0, 10.0, 1.0]));
+    }
+
+    #[test]
+    fn the_counter_backdrop_pads_its_text() {
+        assert_eq!(
+            counter_shapes(Vector2::new(100.0, 20.0)),
+            [Shape::Fill([-4.0, -4.0, 108.0, 28.0], COUNTER_BACKDROP)]
+        );
+        assert_eq!(counter_visual(59.94, 120).text, "59.9 fps  frame 120");
+    }
+
+    #[test]
+    fn frame_rates_average_between_samples() {
+        let mut rate = FrameRate::default();
+        let ms = Duration::from_millis;
+        assert_eq!(rate.sample(ms(1000), 100), (0.0, 0));
+        assert_eq!(rate.sample(ms(1500), 130), (60.0, 30));
+        assert_eq!(rate.sample(ms(2500), 160), (30.0, 60));
+        // Samples at the same time keep the last rate.
+        assert_eq!(rate.sample(ms(2500), 161), (30.0, 61));
+        // A clock that goes backwards doesn't make the rate negative.
+        assert_eq!(rate.sample(ms(2000), 150), (30.0, 50));
+    }
+}
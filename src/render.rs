@@ -0,0 +1,410 @@
+use std::{cell::RefCell, path::Path};
+
+use tracing::{info, instrument};
+use windows::{
+    Win32::Graphics::{
+        Direct2D::{
+            Common::{
+                D2D_RECT_F, D2D_SIZE_U, D2D1_ALPHA_MODE_PREMULTIPLIED, D2D1_COLOR_F,
+                D2D1_PIXEL_FORMAT,
+            },
+            D2D1_ANTIALIAS_MODE_ALIASED, D2D1_ANTIALIAS_MODE_PER_PRIMITIVE,
+            D2D1_BITMAP_OPTIONS_CANNOT_DRAW, D2D1_BITMAP_OPTIONS_CPU_READ,
+            D2D1_BITMAP_OPTIONS_TARGET, D2D1_BITMAP_PROPERTIES1, D2D1_DEVICE_CONTEXT_OPTIONS_NONE,
+            D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT, D2D1_DRAW_TEXT_OPTIONS_NONE,
+            D2D1_INTERPOLATION_MODE_LINEAR, D2D1_MAP_OPTIONS_READ,
+            D2D1_TEXT_ANTIALIAS_MODE_GRAYSCALE, ID2D1Bitmap1, ID2D1DeviceContext,
+        },
+        DirectWrite::IDWriteTextLayout,
+        Dxgi::Common::{DXGI_FORMAT_A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM},
+    },
+    core::Result,
+};
+use windows_numerics::{Matrix3x2, Vector2};
+
+use crate::{
+    cache::{CacheStats, DEFAULT_DPI, LruCache, TextKey, layout_bytes},
+    clock::{Clock, FrameClock},
+    d2d::{create_d2d_device, create_d2d_factory, draw_shapes, factory_options},
+    d3d::{DebugMessages, create_d3d_device, device_flags},
+    emoji::has_color_glyphs,
+    error::{self, Context, Error, Step},
+    export::AnimationWriter,
+    frame::Frame,
+    glyphs::draw_text_on_path,
+    metrics::TextMetrics,
+    numerics::ToD2DColor,
+    overlay::{COUNTER_OFFSET, Overlay, Overlays, counter_shapes, counter_visual, visual_shapes},
+    scene::{Color, Scene, SceneEnvironment, TextVisual},
+    text::{TextFactory, text_metrics},
+};
+
+// Text is kept rasterized across frames within these limits.
+const TEXT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+const TEXT_CACHE_ENTRIES: usize = 1024;
+
+// A layout's metrics along with its text rasterized into a bitmap the size
+// of the visual: an alpha mask, or a color bitmap for text with color glyphs.
+// Text with nothing to draw has no bitmap.
+#[derive(Clone)]
+struct CachedText {
+    metrics: TextMetrics,
+    bitmap: Option<ID2D1Bitmap1>,
+    is_color: bool,
+}
+
+// Draws a scene with D2D into an offscreen bitmap, evaluating animations
+// with the portable evaluator instead of the compositor. Each text visual is
+// drawn the way Composition would show it: its text rasterized into a mask
+// the size of the visual, colored by the color brush and placed by its
+// transform. Color text is drawn in its base color, like in the window.
+// Overlays are drawn on top the same way the window draws them.
+pub struct HeadlessRenderer {
+    d2d_context: ID2D1DeviceContext,
+    target: ID2D1Bitmap1,
+    readback: ID2D1Bitmap1,
+    text_factory: TextFactory,
+    width: u32,
+    height: u32,
+    overlays: Overlays,
+    debug_messages: Option<DebugMessages>,
+    text_cache: RefCell<LruCache<TextKey, CachedText>>,
+}
+
+impl HeadlessRenderer {
+    #[instrument(level = "info", skip(overlays))]
+    pub fn new(width: u32, height: u32, overlays: Overlays, debug_layers: bool) -> Result<Self> {
+        let d3d_device = create_d3d_device(device_flags(debug_layers))?;
+        let d2d_factory = create_d2d_factory(factory_options(debug_layers))?;
+        let d2d_device = create_d2d_device(&d2d_factory, &d3d_device)?;
+        let d2d_context =
+            unsafe { d2d_device.CreateDeviceContext(D2D1_DEVICE_CONTEXT_OPTIONS_NONE)? };
+
+        let size = D2D_SIZE_U { width, height };
+        let pixel_format = D2D1_PIXEL_FORMAT {
+            format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            alphaMode: D2D1_ALPHA_MODE_PREMULTIPLIED,
+        };
+        let target = unsafe {
+            d2d_context.CreateBitmap(
+                size,
+                None,
+                0,
+                &D2D1_BITMAP_PROPERTIES1 {
+                    pixelFormat: pixel_format,
+                    dpiX: 96.0,
+                    dpiY: 96.0,
+                    bitmapOptions: D2D1_BITMAP_OPTIONS_TARGET | D2D1_BITMAP_OPTIONS_CANNOT_DRAW,
+                    ..Default::default()
+                },
+            )?
+        };
+        let readback = unsafe {
+            d2d_context.CreateBitmap(
+                size,
+                None,
+                0,
+                &D2D1_BITMAP_PROPERTIES1 {
+                    pixelFormat: pixel_format,
+                    dpiX: 96.0,
+                    dpiY: 96.0,
+                    bitmapOptions: D2D1_BITMAP_OPTIONS_CPU_READ | D2D1_BITMAP_OPTIONS_CANNOT_DRAW,
+                    ..Default::default()
+                },
+            )?
+        };
+        unsafe {
+            d2d_context.SetTarget(&target);
+            // Match the grayscale antialiasing text gets on the A8 mask surface.
+            d2d_context.SetTextAntialiasMode(D2D1_TEXT_ANTIALIAS_MODE_GRAYSCALE);
+        }
+
+        Ok(Self {
+            d2d_context,
+            target,
+            readback,
+            text_factory: TextFactory::new()?,
+            width,
+            height,
+            overlays,
+            debug_messages: DebugMessages::new(&d3d_device),
+            text_cache: RefCell::new(LruCache::new(TEXT_CACHE_BYTES, TEXT_CACHE_ENTRIES)),
+        })
+    }
+
+    // Renders the scene at the clock's current time.
+    #[instrument(level = "debug", skip_all, fields(frame = clock.frame()))]
+    pub fn render(&self, scene: &Scene, clock: &FrameClock) -> Result<Frame> {
+        let time = clock.now();
+        let root_size = Vector2::new(self.width as f32, self.height as f32);
+        let mut texts = Vec::with_capacity(scene.visuals.len());
+        // Text with animated font axes is laid out again at each frame's
+        // axis values; repeated values come from the cache.
+        for visual in &scene.visuals {
+            texts.push((visual, self.text(&visual.axes_at(time))?));
+        }
+        let mut environment = SceneEnvironment::new(scene, time, root_size);
+        for (visual, text) in &texts {
+            environment.set_size(&visual.id, text.metrics.size);
+        }
+
+        let d2d_context = &self.d2d_context;
+        unsafe {
+            d2d_context.BeginDraw();
+            d2d_context.SetTransform(&Matrix3x2::identity());
+            d2d_context.Clear(Some(&scene.background.to_d2d_color()));
+            let d2d_brush =
+                d2d_context.CreateSolidColorBrush(&scene.background.to_d2d_color(), None)?;
+            for (visual, text) in &texts {
+                let size = &text.metrics.size;
+                let transform = visual.transform_at(time, &environment);
+                let color = visual.color_at(time, &environment);
+                d2d_brush.SetColor(&color.to_d2d_color());
+                d2d_brush.SetOpacity(transform.opacity);
+                d2d_context.SetTransform(&transform.matrix(visual.position(*size, root_size)));
+                match &text.bitmap {
+                    Some(bitmap) if text.is_color => d2d_context.DrawBitmap(
+                        bitmap,
+                        None,
+                        transform.opacity,
+                        D2D1_INTERPOLATION_MODE_LINEAR,
+                        None,
+                        None,
+                    ),
+                    Some(mask) => {
+                        // Opacity masks can only be drawn without antialiasing.
+                        d2d_context.SetAntialiasMode(D2D1_ANTIALIAS_MODE_ALIASED);
+                        d2d_context.FillOpacityMask(mask, &d2d_brush, None, None);
+                        d2d_context.SetAntialiasMode(D2D1_ANTIALIAS_MODE_PER_PRIMITIVE);
+                    }
+                    None => {}
+                }
+                if let Some(border) = &visual.border {
+                    d2d_brush.SetColor(&border.color.to_d2d_color());
+                    for [x, y, width, height] in border.bands(*size) {
+                        d2d_context.FillRectangle(
+                            &D2D_RECT_F {
+                                left: x,
+                                top: y,
+                                right: x + width,
+                                bottom: y + height,
+                            },
+                            &d2d_brush,
+                        );
+                    }
+                }
+                draw_shapes(
+                    d2d_context,
+                    &d2d_brush,
+                    &visual_shapes(self.overlays, &text.metrics),
+                );
+            }
+            if self.overlays.contains(Overlay::Counter) {
+                let counter = counter_visual(clock.fps() as f32, clock.frame());
+                let text_layout = self.text_factory.create_text_layout(&counter)?;
+                let size = text_metrics(&text_layout)?.size;
+                d2d_brush.SetOpacity(1.0);
+                d2d_context
+                    .SetTransform(&Matrix3x2::translation(COUNTER_OFFSET.X, COUNTER_OFFSET.Y));
+                draw_shapes(d2d_context, &d2d_brush, &counter_shapes(size));
+                d2d_brush.SetColor(&counter.color.value.to_d2d_color());
+                d2d_context.DrawTextLayout(
+                    Vector2 { X: 0.0, Y: 0.0 },
+                    &text_layout,
+                    &d2d_brush,
+                    D2D1_DRAW_TEXT_OPTIONS_NONE,
+                );
+            }
+            d2d_context.EndDraw(None, None)?;
+        }
+
+        let frame = self.read_frame();
+        if let Some(debug_messages) = &self.debug_messages {
+            debug_messages.drain();
+        }
+        frame
+    }
+
+    pub fn text_cache_stats(&self) -> CacheStats {
+        self.text_cache.borrow().stats()
+    }
+
+    // Lays out and rasterizes the visual's text, or reuses what was done
+    // for identical text in an earlier frame.
+    fn text(&self, visual: &TextVisual) -> Result<CachedText> {
+        let key = TextKey::new(visual, DEFAULT_DPI);
+        if let Some(text) = self.text_cache.borrow_mut().get(&key) {
+            return Ok(text.clone());
+        }
+        let layout = self.text_factory.create_text_layout(visual)?;
+        let metrics = text_metrics(&layout)?.on_path(visual);
+        let color = has_color_glyphs(&visual.text).then_some(visual.color.value);
+        let bitmap = self.rasterize(&layout, visual, &metrics, color)?;
+        let pixel_bytes = if color.is_some() { 4 } else { 1 };
+        let bytes = metrics.size.X as usize * metrics.size.Y as usize * pixel_bytes
+            + layout_bytes(&visual.text, &metrics);
+        let text = CachedText {
+            metrics,
+            bitmap,
+            is_color: color.is_some(),
+        };
+        self.text_cache
+            .borrow_mut()
+            .insert(key, text.clone(), bytes);
+        Ok(text)
+    }
+
+    // Draws the layout into a new bitmap the size of the text, in 'color'
+    // with its color glyphs if one is given, otherwise as an A8 mask.
+    fn rasterize(
+        &self,
+        text_layout: &IDWriteTextLayout,
+        visual: &TextVisual,
+        metrics: &TextMetrics,
+        color: Option<Color>,
+    ) -> Result<Option<ID2D1Bitmap1>> {
+        let size = metrics.size;
+        if size.X < 1.0 || size.Y < 1.0 {
+            return Ok(None);
+        }
+        let d2d_context = &self.d2d_context;
+        unsafe {
+            let bitmap = d2d_context.CreateBitmap(
+                D2D_SIZE_U {
+                    width: size.X as u32,
+                    height: size.Y as u32,
+                },
+                None,
+                0,
+                &D2D1_BITMAP_PROPERTIES1 {
+                    pixelFormat: D2D1_PIXEL_FORMAT {
+                        format: if color.is_some() {
+                            DXGI_FORMAT_B8G8R8A8_UNORM
+                        } else {
+                            DXGI_FORMAT_A8_UNORM
+                        },
+                        alphaMode: D2D1_ALPHA_MODE_PREMULTIPLIED,
+                    },
+                    dpiX: DEFAULT_DPI,
+                    dpiY: DEFAULT_DPI,
+                    bitmapOptions: D2D1_BITMAP_OPTIONS_TARGET,
+                    ..Default::default()
+                },
+            )?;
+            d2d_context.SetTarget(&bitmap);
+            d2d_context.BeginDraw();
+            d2d_context.SetTransform(&Matrix3x2::identity());
+            d2d_context.Clear(Some(&D2D1_COLOR_F::default()));
+            let d2d_brush = d2d_context.CreateSolidColorBrush(
+                &color.unwrap_or(Color::rgb(0, 0, 0)).to_d2d_color(),
+                None,
+            )?;
+            // Finish drawing even if the glyphs couldn't be laid along the
+            // path, so the context is left ready for the next frame.
+            let drawn = if let Some(path) = &visual.path {
+                draw_text_on_path(d2d_context, text_layout, path, &metrics.path, &d2d_brush)
+            } else {
+                d2d_context.DrawTextLayout(
+                    Vector2 { X: 0.0, Y: 0.0 },
+                    text_layout,
+                    &d2d_brush,
+                    if color.is_some() {
+                        D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT
+                    } else {
+                        D2D1_DRAW_TEXT_OPTIONS_NONE
+                    },
+                );
+                Ok(())
+            };
+            let result = d2d_context.EndDraw(None, None);
+            d2d_context.SetTarget(&self.target);
+            drawn?;
+            result?;
+            Ok(Some(bitmap))
+        }
+    }
+
+    fn read_frame(&self) -> Result<Frame> {
+        unsafe {
+            self.readback.CopyFromBitmap(None, &self.target, None)?;
+            let mapped = self.readback.Map(D2D1_MAP_OPTIONS_READ)?;
+            let data = std::slice::from_raw_parts(
+                mapped.bits,
+                mapped.pitch as usize * self.height as usize,
+            );
+            let frame = Frame::from_premultiplied_bgra(
+                self.width,
+                self.height,
+                mapped.pitch as usize,
+                data,
+            );
+            self.readback.Unmap()?;
+            Ok(frame)
+        }
+    }
+}
+
+// Renders 'frame_count' frames, stepping 'clock' after each one.
+pub fn render_sequence<F: FnMut(u64, Frame) -> error::Result<()>>(
+    renderer: &HeadlessRenderer,
+    scene: &Scene,
+    clock: &FrameClock,
+    frame_count: u64,
+    mut write_frame: F,
+) -> error::Result<()> {
+    info!(frame_count, fps = clock.fps(), "Rendering frames");
+    for _ in 0..frame_count {
+        let frame = renderer
+            .render(scene, clock)
+            .context(Step::Render, format!("render frame {}", clock.frame()))?;
+        write_frame(clock.frame(), frame)?;
+        clock.step();
+    }
+    let stats = renderer.text_cache_stats();
+    info!(
+        hits = stats.hits,
+        misses = stats.misses,
+        evictions = stats.evictions,
+        entries = stats.entries,
+        bytes = stats.bytes,
+        hit_rate = stats.hit_rate(),
+        "Text cache"
+    );
+    Ok(())
+}
+
+// Renders 'frame_count' frames stepped by 'clock' into
+// 'directory/frame_00000.png', 'directory/frame_00001.png' and so on.
+pub fn render_frames(
+    renderer: &HeadlessRenderer,
+    scene: &Scene,
+    clock: &FrameClock,
+    frame_count: u64,
+    directory: &Path,
+) -> error::Result<()> {
+    std::fs::create_dir_all(directory)
+        .map_err(|error| Error::io(format!("create {}", directory.display()), error))?;
+    render_sequence(renderer, scene, clock, frame_count, |index, frame| {
+        let path = directory.join(format!("frame_{:05}.png", index));
+        frame
+            .save_png(&path)
+            .map_err(|error| Error::io(format!("write {}", path.display()), error))
+    })
+}
+
+pub fn export_animation(
+    renderer: &HeadlessRenderer,
+    scene: &Scene,
+    clock: &FrameClock,
+    frame_count: u64,
+    mut writer: Box<dyn AnimationWriter>,
+) -> error::Result<()> {
+    render_sequence(renderer, scene, clock, frame_count, |index, frame| {
+        writer
+            .write_frame(&frame)
+            .map_err(|error| Error::io(format!("write frame {}", index), error))
+    })?;
+    writer
+        .finish()
+        .map_err(|error| Error::io("finish writing the animation", error))
+}
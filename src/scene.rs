@@ -0,0 +1,708 @@
+use std::{borrow::Cow, cell::Cell, collections::HashMap, time::Duration};
+
+use windows_numerics::{Matrix3x2, Vector2, Vector3};
+
+use crate::{
+    animation::{Interpolate, IterationBehavior, KeyFrameAnimation},
+    bidi::base_level,
+    expression::{
+        Environment, Expression, ExpressionError, ExpressionValue, Type, Value,
+        visual_property_type,
+    },
+    links::Link,
+    text_path::TextPath,
+    typography::TypographyRun,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub a: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { a: 255, r, g, b }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Animation<T> {
+    KeyFrames(KeyFrameAnimation<T>),
+    Expression(Expression),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property<T> {
+    pub value: T,
+    pub animation: Option<Animation<T>>,
+}
+
+impl<T: Interpolate + ExpressionValue> Property<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            animation: None,
+        }
+    }
+
+    pub fn animated(value: T, animation: KeyFrameAnimation<T>) -> Self {
+        Self {
+            value,
+            animation: Some(Animation::KeyFrames(animation)),
+        }
+    }
+
+    pub fn expression(value: T, expression: Expression) -> Self {
+        Self {
+            value,
+            animation: Some(Animation::Expression(expression)),
+        }
+    }
+
+    pub fn check(&self, environment: &dyn Environment) -> Result<(), ExpressionError> {
+        if let Some(Animation::Expression(expression)) = &self.animation {
+            let environment = ThisEnvironment {
+                parent: environment,
+                starting_value: self.value.to_value(),
+            };
+            let ty = expression.check(&environment)?;
+            if ty != T::TYPE {
+                return Err(ExpressionError::Type(format!(
+                    "expected {}, found {} in '{}'",
+                    T::TYPE,
+                    ty,
+                    expression
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Expressions that fail to evaluate (e.g. a reference cycle) leave the
+    // property at its static value.
+    pub fn value_at(&self, time: Duration, environment: &dyn Environment) -> T {
+        match &self.animation {
+            Some(Animation::KeyFrames(animation)) => animation.value_at(time, self.value),
+            Some(Animation::Expression(expression)) => {
+                let environment = ThisEnvironment {
+                    parent: environment,
+                    starting_value: self.value.to_value(),
+                };
+                expression
+                    .evaluate(&environment)
+                    .ok()
+                    .and_then(T::from_value)
+                    .unwrap_or(self.value)
+            }
+            None => self.value,
+        }
+    }
+}
+
+impl<T: Interpolate> Property<T> {
+    // How long the property's key frames take to play through once.
+    pub fn key_frame_duration(&self) -> Option<Duration> {
+        match &self.animation {
+            Some(Animation::KeyFrames(animation)) => Some(animation.total_duration()),
+            _ => None,
+        }
+    }
+}
+
+// Composition's "this.CurrentValue" is the value from the previous frame;
+// without frame history both it and "this.StartingValue" resolve to the
+// property's static value.
+struct ThisEnvironment<'a> {
+    parent: &'a dyn Environment,
+    starting_value: Value,
+}
+
+impl Environment for ThisEnvironment<'_> {
+    fn property_type(&self, object: &str, property: &str) -> Option<Type> {
+        match (object, property) {
+            ("this", "StartingValue" | "CurrentValue") => Some(self.starting_value.ty()),
+            _ => self.parent.property_type(object, property),
+        }
+    }
+
+    fn property(&self, object: &str, property: &str) -> Option<Value> {
+        match (object, property) {
+            ("this", "StartingValue" | "CurrentValue") => Some(self.starting_value),
+            _ => self.parent.property(object, property),
+        }
+    }
+}
+
+struct TargetEnvironment<'a> {
+    parent: &'a dyn Environment,
+    target: &'a str,
+}
+
+impl Environment for TargetEnvironment<'_> {
+    fn property_type(&self, object: &str, property: &str) -> Option<Type> {
+        let object = if object == "this.Target" {
+            self.target
+        } else {
+            object
+        };
+        self.parent.property_type(object, property)
+    }
+
+    fn property(&self, object: &str, property: &str) -> Option<Value> {
+        let object = if object == "this.Target" {
+            self.target
+        } else {
+            object
+        };
+        self.parent.property(object, property)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub offset: Property<Vector3>,
+    pub scale: Property<Vector3>,
+    pub rotation_angle_in_degrees: Property<f32>,
+    pub center_point: Property<Vector3>,
+    pub opacity: Property<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            offset: Property::new(Vector3::zero()),
+            scale: Property::new(Vector3::one()),
+            rotation_angle_in_degrees: Property::new(0.0),
+            center_point: Property::new(Vector3::zero()),
+            opacity: Property::new(1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn evaluate(&self, time: Duration, environment: &dyn Environment) -> TransformValues {
+        TransformValues {
+            offset: self.offset.value_at(time, environment),
+            scale: self.scale.value_at(time, environment),
+            rotation_angle_in_degrees: self.rotation_angle_in_degrees.value_at(time, environment),
+            center_point: self.center_point.value_at(time, environment),
+            opacity: self.opacity.value_at(time, environment).clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn key_frame_durations(&self) -> [Option<Duration>; 5] {
+        [
+            self.offset.key_frame_duration(),
+            self.scale.key_frame_duration(),
+            self.rotation_angle_in_degrees.key_frame_duration(),
+            self.center_point.key_frame_duration(),
+            self.opacity.key_frame_duration(),
+        ]
+    }
+
+    pub fn check(&self, environment: &dyn Environment) -> Result<(), ExpressionError> {
+        self.offset.check(environment)?;
+        self.scale.check(environment)?;
+        self.rotation_angle_in_degrees.check(environment)?;
+        self.center_point.check(environment)?;
+        self.opacity.check(environment)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransformValues {
+    pub offset: Vector3,
+    pub scale: Vector3,
+    pub rotation_angle_in_degrees: f32,
+    pub center_point: Vector3,
+    pub opacity: f32,
+}
+
+impl TransformValues {
+    // Mirrors how Composition places a visual: scale and rotate around the
+    // center point, then move the anchor point to the (relative) offset.
+    pub fn matrix(&self, position: Vector2) -> Matrix3x2 {
+        let center = Vector2::new(self.center_point.X, self.center_point.Y);
+        let (sin, cos) = self.rotation_angle_in_degrees.to_radians().sin_cos();
+        let rotation = Matrix3x2 {
+            M11: cos,
+            M12: sin,
+            M21: -sin,
+            M22: cos,
+            M31: 0.0,
+            M32: 0.0,
+        };
+        Matrix3x2::translation(-center.X, -center.Y)
+            * Matrix3x2::scale(self.scale.X, self.scale.Y)
+            * rotation
+            * Matrix3x2::translation(
+                center.X + self.offset.X + position.X,
+                center.Y + self.offset.Y + position.Y,
+            )
+    }
+}
+
+// A hollow nine-grid drawn just outside the visual's bounds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Border {
+    pub thickness: f32,
+    pub color: Color,
+}
+
+impl Border {
+    // The four bands, as (x, y, width, height), that the nine-grid covers
+    // around a visual of 'size'.
+    pub fn bands(&self, size: Vector2) -> [[f32; 4]; 4] {
+        let thickness = self.thickness;
+        [
+            [-thickness, -thickness, size.X + thickness * 2.0, thickness],
+            [-thickness, size.Y, size.X + thickness * 2.0, thickness],
+            [-thickness, 0.0, thickness, size.Y],
+            [size.X, 0.0, thickness, size.Y],
+        ]
+    }
+}
+
+// The direction text is read in. Auto takes it from the first strong
+// character, so Arabic or Hebrew text reads right to left.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ReadingDirection {
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl ReadingDirection {
+    pub fn is_right_to_left(self, text: &str) -> bool {
+        match self {
+            ReadingDirection::Auto => base_level(text) == 1,
+            ReadingDirection::LeftToRight => false,
+            ReadingDirection::RightToLeft => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextVisual {
+    pub id: String,
+    pub text: String,
+    pub font_family: String,
+    pub font_size: f32,
+    pub locale: String,
+    // For vertical text this is the length of a column.
+    pub max_width: f32,
+    pub direction: ReadingDirection,
+    // Lays the text out top to bottom in columns that go right to left,
+    // as CJK text is often set.
+    pub vertical: bool,
+    // Font axes can't be animated by Composition, so text with animated
+    // axes is laid out and rasterized again as they change.
+    pub typography: Vec<TypographyRun>,
+    // Sets the text on a single line along a path. 'max_width' doesn't
+    // apply and line breaks become spaces.
+    pub path: Option<TextPath>,
+    // Can be typed into in the window.
+    pub editable: bool,
+    pub links: Vec<Link>,
+    pub anchor_point: Vector2,
+    pub relative_offset: Vector3,
+    pub color: Property<Color>,
+    pub transform: Transform,
+    pub border: Option<Border>,
+}
+
+impl TextVisual {
+    // Where the visual's origin lands in its parent before the transform's
+    // own offset is applied.
+    pub fn position(&self, size: Vector2, parent_size: Vector2) -> Vector2 {
+        Vector2::new(
+            self.relative_offset.X * parent_size.X - self.anchor_point.X * size.X,
+            self.relative_offset.Y * parent_size.Y - self.anchor_point.Y * size.Y,
+        )
+    }
+
+    pub fn transform_at(&self, time: Duration, environment: &dyn Environment) -> TransformValues {
+        let environment = TargetEnvironment {
+            parent: environment,
+            target: &self.id,
+        };
+        self.transform.evaluate(time, &environment)
+    }
+
+    pub fn color_at(&self, time: Duration, environment: &dyn Environment) -> Color {
+        let environment = TargetEnvironment {
+            parent: environment,
+            target: &self.id,
+        };
+        self.color.value_at(time, &environment)
+    }
+
+    pub fn matrix_at(
+        &self,
+        time: Duration,
+        size: Vector2,
+        parent_size: Vector2,
+        environment: &dyn Environment,
+    ) -> Matrix3x2 {
+        self.transform_at(time, environment)
+            .matrix(self.position(size, parent_size))
+    }
+
+    // The text as it's laid out. Line breaks become spaces on a path,
+    // keeping every character where it was.
+    pub fn layout_text(&self) -> Cow<'_, str> {
+        let is_line_break = |character| matches!(character, '\n' | '\r' | '\u{2028}' | '\u{2029}');
+        if self.path.is_none() || !self.text.contains(is_line_break) {
+            return Cow::Borrowed(&self.text);
+        }
+        Cow::Owned(
+            self.text
+                .chars()
+                .map(|character| {
+                    if is_line_break(character) {
+                        ' '
+                    } else {
+                        character
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    pub fn has_animated_axes(&self) -> bool {
+        self.typography.iter().any(TypographyRun::has_animated_axes)
+    }
+
+    // The visual with its font axes fixed at their values at 'time', which
+    // is what gets laid out.
+    pub fn axes_at(&self, time: Duration) -> Cow<'_, TextVisual> {
+        if !self.has_animated_axes() {
+            return Cow::Borrowed(self);
+        }
+        Cow::Owned(TextVisual {
+            typography: self.typography.iter().map(|run| run.at(time)).collect(),
+            ..self.clone()
+        })
+    }
+
+    pub fn check(&self, environment: &dyn Environment) -> Result<(), ExpressionError> {
+        let environment = TargetEnvironment {
+            parent: environment,
+            target: &self.id,
+        };
+        self.color.check(&environment)?;
+        self.transform.check(&environment)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub background: Color,
+    pub visuals: Vec<TextVisual>,
+}
+
+impl Scene {
+    pub fn visual(&self, id: &str) -> Option<&TextVisual> {
+        self.visuals.iter().find(|visual| visual.id == id)
+    }
+
+    // Long enough for every key frame animation in the scene to play through
+    // once, which makes a sensible default length for offline renders.
+    pub fn preview_duration(&self) -> Duration {
+        self.visuals
+            .iter()
+            .flat_map(|visual| {
+                let mut durations = visual.transform.key_frame_durations().to_vec();
+                durations.push(visual.color.key_frame_duration());
+                durations.extend(
+                    visual
+                        .typography
+                        .iter()
+                        .flat_map(|run| &run.axes)
+                        .map(|axis| {
+                            axis.animation
+                                .as_ref()
+                                .map(KeyFrameAnimation::total_duration)
+                        }),
+                );
+                durations
+            })
+            .flatten()
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn check_expressions(&self) -> Result<(), ExpressionError> {
+        let environment = SceneEnvironment::new(self, Duration::ZERO, Vector2::zero());
+        for visual in &self.visuals {
+            visual.check(&environment)?;
+        }
+        Ok(())
+    }
+
+    pub fn demo() -> Self {
+        let mut color_animation = KeyFrameAnimation::new(Duration::from_secs(3));
+        color_animation.insert_key_frame(0.0, Color::rgb(255, 0, 0));
+        color_animation.insert_key_frame(0.25, Color::rgb(0, 255, 0));
+        color_animation.insert_key_frame(0.5, Color::rgb(0, 0, 255));
+        color_animation.insert_key_frame(0.75, Color::rgb(255, 255, 0));
+        color_animation.insert_key_frame(1.0, Color::rgb(255, 0, 0));
+        color_animation.iteration_behavior = IterationBehavior::Forever;
+
+        Self {
+            background: Color::rgb(255, 255, 255),
+            visuals: vec![TextVisual {
+                id: "text".to_owned(),
+                text: "Hello, World!".to_owned(),
+                font_family: "Comic Sans MS".to_owned(),
+                font_size: 36.0,
+                locale: "en-us".to_owned(),
+                max_width: 400.0,
+                direction: ReadingDirection::Auto,
+                vertical: false,
+                typography: Vec::new(),
+                path: None,
+                editable: false,
+                links: Vec::new(),
+                anchor_point: Vector2::new(0.5, 0.5),
+                relative_offset: Vector3::new(0.5, 0.5, 0.0),
+                color: Property::animated(Color::rgb(255, 0, 0), color_animation),
+                transform: Transform::default(),
+                border: None,
+            }],
+        }
+    }
+}
+
+// Resolves expression references against the scene at a point in time.
+// "root" is the visual hosting the scene, sized to the window.
+pub struct SceneEnvironment<'a> {
+    scene: &'a Scene,
+    time: Duration,
+    root_size: Vector2,
+    sizes: HashMap<String, Vector2>,
+    depth: Cell<u32>,
+}
+
+impl<'a> SceneEnvironment<'a> {
+    const MAX_DEPTH: u32 = 16;
+
+    pub fn new(scene: &'a Scene, time: Duration, root_size: Vector2) -> Self {
+        Self {
+            scene,
+            time,
+            root_size,
+            sizes: HashMap::new(),
+            depth: Cell::new(0),
+        }
+    }
+
+    pub fn set_size(&mut self, id: &str, size: Vector2) {
+        self.sizes.insert(id.to_owned(), size);
+    }
+
+    fn visual_property(&self, visual: &TextVisual, property: &str) -> Option<Value> {
+        let depth = self.depth.get();
+        if depth >= Self::MAX_DEPTH {
+            return None;
+        }
+        self.depth.set(depth + 1);
+        let environment = TargetEnvironment {
+            parent: self,
+            target: &visual.id,
+        };
+        let transform = &visual.transform;
+        let value = match property {
+            "Offset" => Some(Value::Vector3(
+                transform.offset.value_at(self.time, &environment),
+            )),
+            "Scale" => Some(Value::Vector3(
+                transform.scale.value_at(self.time, &environment),
+            )),
+            "CenterPoint" => Some(Value::Vector3(
+                transform.center_point.value_at(self.time, &environment),
+            )),
+            "Opacity" => Some(Value::Scalar(
+                transform.opacity.value_at(self.time, &environment),
+            )),
+            "RotationAngleInDegrees" => Some(Value::Scalar(
+                transform
+                    .rotation_angle_in_degrees
+                    .value_at(self.time, &environment),
+            )),
+            "RotationAngle" => Some(Value::Scalar(
+                transform
+                    .rotation_angle_in_degrees
+                    .value_at(self.time, &environment)
+                    .to_radians(),
+            )),
+            "AnchorPoint" => Some(Value::Vector2(visual.anchor_point)),
+            "RelativeOffsetAdjustment" => Some(Value::Vector3(visual.relative_offset)),
+            "RelativeSizeAdjustment" => Some(Value::Vector2(Vector2::zero())),
+            "Size" => self.sizes.get(&visual.id).copied().map(Value::Vector2),
+            _ => None,
+        };
+        self.depth.set(depth);
+        value
+    }
+}
+
+impl Environment for SceneEnvironment<'_> {
+    fn property_type(&self, object: &str, property: &str) -> Option<Type> {
+        if object == "root" || self.scene.visual(object).is_some() {
+            visual_property_type(property)
+        } else {
+            None
+        }
+    }
+
+    fn property(&self, object: &str, property: &str) -> Option<Value> {
+        if object == "root" {
+            return match property {
+                "Size" => Some(Value::Vector2(self.root_size)),
+                "Offset" | "CenterPoint" | "RelativeOffsetAdjustment" => {
+                    Some(Value::Vector3(Vector3::zero()))
+                }
+                "Scale" => Some(Value::Vector3(Vector3::one())),
+                "Opacity" => Some(Value::Scalar(1.0)),
+                "RotationAngle" | "RotationAngleInDegrees" => Some(Value::Scalar(0.0)),
+                "AnchorPoint" => Some(Value::Vector2(Vector2::zero())),
+                "RelativeSizeAdjustment" => Some(Value::Vector2(Vector2::one())),
+                _ => None,
+            };
+        }
+        let visual = self.scene.visual(object)?;
+        self.visual_property(visual, property)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Built by hand, since Matrix3x2::rotation calls into d2d1.
+    fn rotation(degrees: f32) -> Matrix3x2 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Matrix3x2 {
+            M11: cos,
+            M12: sin,
+            M21: -sin,
+            M22: cos,
+            M31: 0.0,
+            M32: 0.0,
+        }
+    }
+
+    fn transform(matrix: &Matrix3x2, point: Vector2) -> Vector2 {
+        Vector2::new(
+            point.X * matrix.M11 + point.Y * matrix.M21 + matrix.M31,
+            point.X * matrix.M12 + point.Y * matrix.M22 + matrix.M32,
+        )
+    }
+
+    fn assert_near(actual: Vector2, expected: Vector2) {
+        assert!(
+            (actual.X - expected.X).abs() < 1e-4 && (actual.Y - expected.Y).abs() < 1e-4,
+            "{actual:?} isn't {expected:?}"
+        );
+    }
+
+    fn values() -> TransformValues {
+        TransformValues {
+            offset: Vector3::new(0.0, 0.0, 0.0),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            rotation_angle_in_degrees: 0.0,
+            center_point: Vector3::new(0.0, 0.0, 0.0),
+            opacity: 1.0,
+        }
+    }
+
+    #[test]
+    fn an_untransformed_visual_only_moves_to_its_position() {
+        let matrix = values().matrix(Vector2::new(30.0, 40.0));
+        assert_near(
+            transform(&matrix, Vector2::new(0.0, 0.0)),
+            Vector2::new(30.0, 40.0),
+        );
+        assert_near(
+            transform(&matrix, Vector2::new(5.0, 6.0)),
+            Vector2::new(35.0, 46.0),
+        );
+    }
+
+    #[test]
+    fn the_offset_adds_to_the_position() {
+        let matrix = TransformValues {
+            offset: Vector3::new(5.0, -10.0, 3.0),
+            ..values()
+        }
+        .matrix(Vector2::new(30.0, 40.0));
+        assert_near(
+            transform(&matrix, Vector2::new(0.0, 0.0)),
+            Vector2::new(35.0, 30.0),
+        );
+    }
+
+    #[test]
+    fn scale_and_rotation_keep_the_center_point_in_place() {
+        let matrix = TransformValues {
+            scale: Vector3::new(2.0, 3.0, 1.0),
+            rotation_angle_in_degrees: 90.0,
+            center_point: Vector3::new(10.0, 20.0, 0.0),
+            ..values()
+        }
+        .matrix(Vector2::new(0.0, 0.0));
+        assert_near(
+            transform(&matrix, Vector2::new(10.0, 20.0)),
+            Vector2::new(10.0, 20.0),
+        );
+    }
+
+    #[test]
+    fn visuals_scale_then_rotate_around_the_center_then_move() {
+        let values = TransformValues {
+            offset: Vector3::new(5.0, 0.0, 0.0),
+            scale: Vector3::new(2.0, 3.0, 1.0),
+            rotation_angle_in_degrees: 90.0,
+            center_point: Vector3::new(10.0, 20.0, 0.0),
+            ..values()
+        };
+        let position = Vector2::new(100.0, 50.0);
+        let matrix = values.matrix(position);
+        // Scaled to (2, 0) from the center, then turned clockwise onto y.
+        assert_near(
+            transform(&matrix, Vector2::new(11.0, 20.0)),
+            Vector2::new(115.0, 72.0),
+        );
+        // Scaled to (0, 3), then turned onto -x.
+        assert_near(
+            transform(&matrix, Vector2::new(10.0, 21.0)),
+            Vector2::new(112.0, 70.0),
+        );
+        let expected = Matrix3x2::translation(-10.0, -20.0)
+            * Matrix3x2::scale(2.0, 3.0)
+            * rotation(90.0)
+            * Matrix3x2::translation(115.0, 70.0);
+        for point in [Vector2::new(0.0, 0.0), Vector2::new(-7.0, 13.0)] {
+            assert_near(transform(&matrix, point), transform(&expected, point));
+        }
+    }
+
+    #[test]
+    fn rotation_turns_clockwise_on_screen() {
+        let matrix = TransformValues {
+            rotation_angle_in_degrees: 30.0,
+            ..values()
+        }
+        .matrix(Vector2::new(0.0, 0.0));
+        let (sin, cos) = 30.0f32.to_radians().sin_cos();
+        assert_near(
+            transform(&matrix, Vector2::new(10.0, 0.0)),
+            Vector2::new(10.0 * cos, 10.0 * sin),
+        );
+    }
+}
@@ -0,0 +1,827 @@
+use std::{fmt, io, path::Path, time::Duration};
+
+use serde_json::{Map, Value as Json};
+use windows_numerics::{Vector2, Vector3};
+
+use crate::{
+    animation::{Easing, Interpolate, IterationBehavior, KeyFrameAnimation},
+    error::{self, Error},
+    expression::{Expression, ExpressionValue},
+    links::{Link, LinkTarget},
+    scene::{Border, Color, Property, ReadingDirection, Scene, TextVisual, Transform},
+    text_path::{PathAlignment, PathShape, TextPath},
+    typography::{Feature, FeatureTag, FontAxis, TypographyRun},
+};
+
+// Scenes are stored as JSON. Colors are "#RRGGBB" or "#AARRGGBB" strings,
+// vectors are arrays and durations are in seconds. Any property can be a
+// plain value or an object with a "value" and either "keyFrames" or an
+// "expression":
+//
+//   "color": {
+//     "value": "#FF0000",
+//     "keyFrames": {
+//       "duration": 3,
+//       "iterations": "forever",
+//       "frames": [
+//         { "progress": 0, "value": "#FF0000" },
+//         { "progress": 1, "value": "#0000FF", "easing": [0.4, 0, 0.2, 1] }
+//       ]
+//     }
+//   }
+//
+// OpenType features are set for runs of a visual's text, counted in
+// characters. A run without a "start" starts at the beginning and one
+// without a "length" goes to the end. Features are turned on with true,
+// off with false, or given a number to pick an alternate:
+//
+//   "typography": [
+//     { "features": { "liga": false, "tnum": true } },
+//     { "start": 6, "length": 5, "features": { "smcp": true, "ss01": true } }
+//   ]
+//
+// Runs can also set variable font axes, like "wght", "wdth", "slnt",
+// "opsz" or a font's own axes, to a number or to key frames:
+//
+//   { "axes": { "wdth": 75, "wght": { "keyFrames": { ... } } } }
+//
+// Text can be set along a path instead of in lines. The path is an
+// "arc", a "circle" or a cubic "bezier" given as a start point followed by
+// two control points and an end point for each segment, in the visual's
+// coordinates before it's sized. Angles are in degrees:
+//
+//   "path": {
+//     "arc": { "center": [0, 0], "radius": 120, "startAngle": 180, "sweepAngle": 180 },
+//     "alignment": "center",
+//     "startOffset": 10
+//   }
+//
+// Visuals with "editable": true can be typed into in the window. Tab moves
+// between them. Editable text can't be vertical or set along a path.
+//
+// Runs of text can be links to a "url", opened in the browser when they're
+// clicked, or to an "action" id the app is told about. URLs have to be
+// http, https or mailto ones, since anything else the shell would run. Links are counted
+// like typography runs and shown in "color", changing to "hoverColor" and
+// underlined while the mouse is over them. Text with links can't be
+// editable, vertical or set along a path:
+//
+//   "links": [
+//     { "start": 0, "length": 4, "url": "https://example.com" },
+//     { "start": 9, "length": 5, "action": "replay", "hoverColor": "#C00000" }
+//   ]
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Invalid { path: String, message: String },
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io(error) => write!(f, "{}", error),
+            SceneFileError::Json(error) => write!(f, "{}", error),
+            SceneFileError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+pub fn load_scene(path: &Path) -> Result<Scene, SceneFileError> {
+    let source = std::fs::read_to_string(path).map_err(SceneFileError::Io)?;
+    parse_scene(&source)
+}
+
+// The scene in the file at 'path', or the built-in demo without one, with
+// its expressions checked.
+pub fn load_checked_scene(path: Option<&Path>) -> error::Result<Scene> {
+    let scene = match path {
+        Some(path) => load_scene(path).map_err(|error| Error::Scene {
+            path: Some(path.to_owned()),
+            message: error.to_string(),
+        })?,
+        None => Scene::demo(),
+    };
+    scene.check_expressions().map_err(|error| Error::Scene {
+        path: path.map(Path::to_owned),
+        message: error.to_string(),
+    })?;
+    Ok(scene)
+}
+
+pub fn parse_scene(source: &str) -> Result<Scene, SceneFileError> {
+    let json: Json = serde_json::from_str(source).map_err(SceneFileError::Json)?;
+    let object = as_object(&json, "scene")?;
+    check_keys(object, "scene", &["background", "visuals"])?;
+
+    let background = match object.get("background") {
+        Some(background) => Color::from_json(background, "background")?,
+        None => Color::rgb(255, 255, 255),
+    };
+    let mut visuals: Vec<TextVisual> = Vec::new();
+    if let Some(json) = object.get("visuals") {
+        let Json::Array(array) = json else {
+            return Err(invalid("visuals", "expected an array"));
+        };
+        for (index, json) in array.iter().enumerate() {
+            let path = format!("visuals[{}]", index);
+            let visual = text_visual(json, &path)?;
+            if visuals.iter().any(|existing| existing.id == visual.id) {
+                return Err(invalid(
+                    &path,
+                    format!("duplicate visual id '{}'", visual.id),
+                ));
+            }
+            visuals.push(visual);
+        }
+    }
+    Ok(Scene {
+        background,
+        visuals,
+    })
+}
+
+fn text_visual(json: &Json, path: &str) -> Result<TextVisual, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(
+        object,
+        path,
+        &[
+            "id",
+            "text",
+            "fontFamily",
+            "fontSize",
+            "locale",
+            "maxWidth",
+            "direction",
+            "vertical",
+            "typography",
+            "path",
+            "editable",
+            "links",
+            "anchorPoint",
+            "relativeOffset",
+            "color",
+            "transform",
+            "border",
+        ],
+    )?;
+
+    let id = string(required(object, path, "id")?, &field(path, "id"))?;
+    if id == "root" || id.starts_with("this") {
+        return Err(invalid(
+            &field(path, "id"),
+            format!("'{}' is reserved for expressions", id),
+        ));
+    }
+    let transform = match object.get("transform") {
+        Some(json) => transform(json, &field(path, "transform"))?,
+        None => Transform::default(),
+    };
+    let editable = optional(object, path, "editable", boolean)?.unwrap_or(false);
+    let vertical = optional(object, path, "vertical", boolean)?.unwrap_or(false);
+    let text_path = optional(object, path, "path", text_path)?;
+    if editable && (vertical || text_path.is_some()) {
+        return Err(invalid(
+            &field(path, "editable"),
+            "editable text can't be vertical or on a path",
+        ));
+    }
+    let links = optional(object, path, "links", links)?.unwrap_or_default();
+    if !links.is_empty() && (editable || vertical || text_path.is_some()) {
+        return Err(invalid(
+            &field(path, "links"),
+            "links can't be in editable or vertical text or on a path",
+        ));
+    }
+    Ok(TextVisual {
+        id,
+        text: string(required(object, path, "text")?, &field(path, "text"))?,
+        font_family: optional(object, path, "fontFamily", string)?
+            .unwrap_or_else(|| "Segoe UI".to_owned()),
+        font_size: optional(object, path, "fontSize", f32::from_json)?.unwrap_or(24.0),
+        locale: optional(object, path, "locale", string)?.unwrap_or_else(|| "en-us".to_owned()),
+        max_width: optional(object, path, "maxWidth", f32::from_json)?.unwrap_or(400.0),
+        direction: optional(object, path, "direction", reading_direction)?.unwrap_or_default(),
+        vertical,
+        typography: optional(object, path, "typography", typography)?.unwrap_or_default(),
+        path: text_path,
+        editable,
+        links,
+        anchor_point: optional(object, path, "anchorPoint", Vector2::from_json)?
+            .unwrap_or_else(Vector2::zero),
+        relative_offset: optional(object, path, "relativeOffset", Vector3::from_json)?
+            .unwrap_or_else(Vector3::zero),
+        color: optional(object, path, "color", |json, path| {
+            property(json, path, Color::rgb(0, 0, 0))
+        })?
+        .unwrap_or_else(|| Property::new(Color::rgb(0, 0, 0))),
+        transform,
+        border: optional(object, path, "border", border)?,
+    })
+}
+
+fn reading_direction(json: &Json, path: &str) -> Result<ReadingDirection, SceneFileError> {
+    match json.as_str() {
+        Some("auto") => Ok(ReadingDirection::Auto),
+        Some("ltr") => Ok(ReadingDirection::LeftToRight),
+        Some("rtl") => Ok(ReadingDirection::RightToLeft),
+        _ => Err(invalid(path, "expected \"auto\", \"ltr\" or \"rtl\"")),
+    }
+}
+
+fn typography(json: &Json, path: &str) -> Result<Vec<TypographyRun>, SceneFileError> {
+    let Json::Array(array) = json else {
+        return Err(invalid(path, "expected an array"));
+    };
+    array
+        .iter()
+        .enumerate()
+        .map(|(index, json)| typography_run(json, &format!("{}[{}]", path, index)))
+        .collect()
+}
+
+fn typography_run(json: &Json, path: &str) -> Result<TypographyRun, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(object, path, &["start", "length", "features", "axes"])?;
+    if !object.contains_key("features") && !object.contains_key("axes") {
+        return Err(invalid(path, "expected 'features' or 'axes'"));
+    }
+    let start = optional(object, path, "start", count)?.unwrap_or(0);
+    let end = match optional(object, path, "length", count)? {
+        Some(length) => start.saturating_add(length),
+        None => usize::MAX,
+    };
+    Ok(TypographyRun {
+        range: start..end,
+        features: optional(object, path, "features", features)?.unwrap_or_default(),
+        axes: optional(object, path, "axes", font_axes)?.unwrap_or_default(),
+    })
+}
+
+// Links default to the blues Windows uses for them.
+fn links(json: &Json, path: &str) -> Result<Vec<Link>, SceneFileError> {
+    let Json::Array(array) = json else {
+        return Err(invalid(path, "expected an array"));
+    };
+    array
+        .iter()
+        .enumerate()
+        .map(|(index, json)| link(json, &format!("{}[{}]", path, index)))
+        .collect()
+}
+
+fn link(json: &Json, path: &str) -> Result<Link, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(
+        object,
+        path,
+        &["start", "length", "url", "action", "color", "hoverColor"],
+    )?;
+    let target = match (object.get("url"), object.get("action")) {
+        (Some(json), None) => LinkTarget::Url(url(json, &field(path, "url"))?),
+        (None, Some(action)) => LinkTarget::Action(string(action, &field(path, "action"))?),
+        _ => return Err(invalid(path, "expected one of 'url' or 'action'")),
+    };
+    let start = optional(object, path, "start", count)?.unwrap_or(0);
+    let end = match optional(object, path, "length", count)? {
+        Some(length) => start.saturating_add(length),
+        None => usize::MAX,
+    };
+    Ok(Link {
+        range: start..end,
+        target,
+        color: optional(object, path, "color", Color::from_json)?
+            .unwrap_or(Color::rgb(0, 103, 192)),
+        hover_color: optional(object, path, "hoverColor", Color::from_json)?
+            .unwrap_or(Color::rgb(0, 62, 146)),
+    })
+}
+
+// Clicking a link hands its URL to ShellExecuteW, which would just as well
+// run a program or open a file, so only web and mail links are allowed.
+fn url(json: &Json, path: &str) -> Result<String, SceneFileError> {
+    let url = string(json, path)?;
+    let scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    let is_allowed = match scheme.as_deref() {
+        Some("http" | "https") => url
+            .split_once("://")
+            .is_some_and(|(_, rest)| !rest.is_empty() && !rest.starts_with('/')),
+        Some("mailto") => url.len() > "mailto:".len(),
+        _ => false,
+    };
+    if !is_allowed {
+        return Err(invalid(path, "expected an http, https or mailto URL"));
+    }
+    Ok(url)
+}
+
+fn features(json: &Json, path: &str) -> Result<Vec<Feature>, SceneFileError> {
+    as_object(json, path)?
+        .iter()
+        .map(|(tag, json)| {
+            let path = field(path, tag);
+            let tag = FeatureTag::new(tag)
+                .ok_or_else(|| invalid(&path, "expected a four character feature tag"))?;
+            let value = match json {
+                Json::Bool(on) => *on as u32,
+                _ => json
+                    .as_u64()
+                    .and_then(|value| u32::try_from(value).ok())
+                    .ok_or_else(|| invalid(&path, "expected true, false or a number"))?,
+            };
+            Ok(Feature { tag, value })
+        })
+        .collect()
+}
+
+// Axes are like properties, except Composition can't run expressions on
+// them.
+fn font_axes(json: &Json, path: &str) -> Result<Vec<FontAxis>, SceneFileError> {
+    as_object(json, path)?
+        .iter()
+        .map(|(tag, json)| {
+            let path = field(path, tag);
+            let tag = FeatureTag::new(tag)
+                .ok_or_else(|| invalid(&path, "expected a four character axis tag"))?;
+            let Json::Object(object) = json else {
+                return Ok(FontAxis::new(tag, f32::from_json(json, &path)?));
+            };
+            check_keys(object, &path, &["value", "keyFrames"])?;
+            let value = optional(object, &path, "value", f32::from_json)?;
+            let animation = optional(object, &path, "keyFrames", key_frame_animation)?;
+            let value = value
+                .or_else(|| {
+                    let key_frames = animation.as_ref()?.key_frames();
+                    key_frames.first().map(|key_frame| key_frame.value)
+                })
+                .ok_or_else(|| invalid(&path, "missing 'value' or 'keyFrames'"))?;
+            Ok(FontAxis {
+                tag,
+                value,
+                animation,
+            })
+        })
+        .collect()
+}
+
+fn text_path(json: &Json, path: &str) -> Result<TextPath, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(
+        object,
+        path,
+        &["arc", "circle", "bezier", "startOffset", "alignment"],
+    )?;
+    let shapes: Vec<_> = ["arc", "circle", "bezier"]
+        .into_iter()
+        .filter(|key| object.contains_key(*key))
+        .collect();
+    let shape = match shapes[..] {
+        ["arc"] => arc(&object["arc"], &field(path, "arc"))?,
+        ["circle"] => circle(&object["circle"], &field(path, "circle"))?,
+        ["bezier"] => bezier(&object["bezier"], &field(path, "bezier"))?,
+        _ => {
+            return Err(invalid(path, "expected one of 'arc', 'circle' or 'bezier'"));
+        }
+    };
+    Ok(TextPath {
+        shape,
+        start_offset: optional(object, path, "startOffset", f32::from_json)?.unwrap_or(0.0),
+        alignment: optional(object, path, "alignment", path_alignment)?.unwrap_or_default(),
+    })
+}
+
+fn arc(json: &Json, path: &str) -> Result<PathShape, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(
+        object,
+        path,
+        &["center", "radius", "startAngle", "sweepAngle"],
+    )?;
+    let number = |key| f32::from_json(required(object, path, key)?, &field(path, key));
+    Ok(PathShape::Arc {
+        center: Vector2::from_json(required(object, path, "center")?, &field(path, "center"))?,
+        radius: number("radius")?,
+        start_angle: number("startAngle")?,
+        sweep_angle: number("sweepAngle")?,
+    })
+}
+
+fn circle(json: &Json, path: &str) -> Result<PathShape, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(object, path, &["center", "radius", "startAngle"])?;
+    Ok(PathShape::Circle {
+        center: Vector2::from_json(required(object, path, "center")?, &field(path, "center"))?,
+        radius: f32::from_json(required(object, path, "radius")?, &field(path, "radius"))?,
+        start_angle: optional(object, path, "startAngle", f32::from_json)?.unwrap_or(0.0),
+    })
+}
+
+fn bezier(json: &Json, path: &str) -> Result<PathShape, SceneFileError> {
+    let Json::Array(array) = json else {
+        return Err(invalid(path, "expected an array of points"));
+    };
+    if array.len() < 4 || !(array.len() - 1).is_multiple_of(3) {
+        return Err(invalid(
+            path,
+            "expected a start point and three points for each segment",
+        ));
+    }
+    array
+        .iter()
+        .enumerate()
+        .map(|(index, json)| Vector2::from_json(json, &format!("{}[{}]", path, index)))
+        .collect::<Result<_, _>>()
+        .map(PathShape::Bezier)
+}
+
+fn path_alignment(json: &Json, path: &str) -> Result<PathAlignment, SceneFileError> {
+    match json.as_str() {
+        Some("start") => Ok(PathAlignment::Start),
+        Some("center") => Ok(PathAlignment::Center),
+        Some("end") => Ok(PathAlignment::End),
+        _ => Err(invalid(path, "expected \"start\", \"center\" or \"end\"")),
+    }
+}
+
+fn border(json: &Json, path: &str) -> Result<Border, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(object, path, &["thickness", "color"])?;
+    Ok(Border {
+        thickness: f32::from_json(
+            required(object, path, "thickness")?,
+            &field(path, "thickness"),
+        )?,
+        color: Color::from_json(required(object, path, "color")?, &field(path, "color"))?,
+    })
+}
+
+fn transform(json: &Json, path: &str) -> Result<Transform, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(
+        object,
+        path,
+        &[
+            "offset",
+            "scale",
+            "rotationAngleInDegrees",
+            "centerPoint",
+            "opacity",
+        ],
+    )?;
+    let defaults = Transform::default();
+    Ok(Transform {
+        offset: property_field(object, path, "offset", defaults.offset)?,
+        scale: property_field(object, path, "scale", defaults.scale)?,
+        rotation_angle_in_degrees: property_field(
+            object,
+            path,
+            "rotationAngleInDegrees",
+            defaults.rotation_angle_in_degrees,
+        )?,
+        center_point: property_field(object, path, "centerPoint", defaults.center_point)?,
+        opacity: property_field(object, path, "opacity", defaults.opacity)?,
+    })
+}
+
+fn property_field<T: FromJson>(
+    object: &Map<String, Json>,
+    path: &str,
+    key: &str,
+    default: Property<T>,
+) -> Result<Property<T>, SceneFileError> {
+    match object.get(key) {
+        Some(json) => property(json, &field(path, key), default.value),
+        None => Ok(default),
+    }
+}
+
+fn property<T: FromJson>(
+    json: &Json,
+    path: &str,
+    default: T,
+) -> Result<Property<T>, SceneFileError> {
+    let Json::Object(object) = json else {
+        return Ok(Property::new(T::from_json(json, path)?));
+    };
+    check_keys(object, path, &["value", "keyFrames", "expression"])?;
+    let value = optional(object, path, "value", T::from_json)?;
+    match (object.get("keyFrames"), object.get("expression")) {
+        (Some(_), Some(_)) => Err(invalid(
+            path,
+            "a property can't have both 'keyFrames' and an 'expression'",
+        )),
+        (Some(json), None) => {
+            let animation = key_frame_animation(json, &field(path, "keyFrames"))?;
+            // Without a static value, start from the first key frame.
+            let value = value
+                .or_else(|| {
+                    animation
+                        .key_frames()
+                        .first()
+                        .map(|key_frame| key_frame.value)
+                })
+                .unwrap_or(default);
+            Ok(Property::animated(value, animation))
+        }
+        (None, Some(json)) => {
+            let path = field(path, "expression");
+            let source = string(json, &path)?;
+            let expression =
+                Expression::parse(&source).map_err(|error| invalid(&path, error.to_string()))?;
+            Ok(Property::expression(value.unwrap_or(default), expression))
+        }
+        (None, None) => Ok(Property::new(value.unwrap_or(default))),
+    }
+}
+
+fn key_frame_animation<T: FromJson>(
+    json: &Json,
+    path: &str,
+) -> Result<KeyFrameAnimation<T>, SceneFileError> {
+    let object = as_object(json, path)?;
+    check_keys(object, path, &["duration", "iterations", "frames"])?;
+    let duration = duration(
+        required(object, path, "duration")?,
+        &field(path, "duration"),
+    )?;
+    let mut animation = KeyFrameAnimation::new(duration);
+    if let Some(json) = object.get("iterations") {
+        animation.iteration_behavior = iteration_behavior(json, &field(path, "iterations"))?;
+    }
+
+    let frames_path = field(path, "frames");
+    let Json::Array(frames) = required(object, path, "frames")? else {
+        return Err(invalid(&frames_path, "expected an array"));
+    };
+    if frames.is_empty() {
+        return Err(invalid(&frames_path, "expected at least one key frame"));
+    }
+    for (index, json) in frames.iter().enumerate() {
+        let path = format!("{}[{}]", frames_path, index);
+        let object = as_object(json, &path)?;
+        check_keys(object, &path, &["progress", "value", "easing"])?;
+        let progress = f32::from_json(
+            required(object, &path, "progress")?,
+            &field(&path, "progress"),
+        )?;
+        if !(0.0..=1.0).contains(&progress) {
+            return Err(invalid(
+                &field(&path, "progress"),
+                "expected a number between 0 and 1",
+            ));
+        }
+        let value = T::from_json(required(object, &path, "value")?, &field(&path, "value"))?;
+        let easing = optional(object, &path, "easing", easing)?.unwrap_or(Easing::Linear);
+        animation.insert_key_frame_with_easing(progress, value, easing);
+    }
+    Ok(animation)
+}
+
+fn easing(json: &Json, path: &str) -> Result<Easing, SceneFileError> {
+    match json {
+        Json::String(name) if name == "linear" => Ok(Easing::Linear),
+        Json::Array(_) => {
+            let [x1, y1, x2, y2] = numbers::<4>(json, path)?;
+            Ok(Easing::CubicBezier(
+                Vector2::new(x1, y1),
+                Vector2::new(x2, y2),
+            ))
+        }
+        _ => Err(invalid(
+            path,
+            "expected \"linear\" or cubic bezier control points [x1, y1, x2, y2]",
+        )),
+    }
+}
+
+fn iteration_behavior(json: &Json, path: &str) -> Result<IterationBehavior, SceneFileError> {
+    match json {
+        Json::String(name) if name == "forever" => Ok(IterationBehavior::Forever),
+        Json::Number(number) => match number.as_u64().and_then(|count| u32::try_from(count).ok()) {
+            Some(count) if count > 0 => Ok(IterationBehavior::Count(count)),
+            _ => Err(invalid(path, "expected a positive whole number")),
+        },
+        _ => Err(invalid(path, "expected a count or \"forever\"")),
+    }
+}
+
+fn duration(json: &Json, path: &str) -> Result<Duration, SceneFileError> {
+    json.as_f64()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| invalid(path, "expected a non-negative number of seconds"))
+}
+
+trait FromJson: Interpolate + ExpressionValue + Sized {
+    fn from_json(json: &Json, path: &str) -> Result<Self, SceneFileError>;
+}
+
+impl FromJson for f32 {
+    fn from_json(json: &Json, path: &str) -> Result<Self, SceneFileError> {
+        json.as_f64()
+            .map(|number| number as f32)
+            .ok_or_else(|| invalid(path, "expected a number"))
+    }
+}
+
+impl FromJson for Vector2 {
+    fn from_json(json: &Json, path: &str) -> Result<Self, SceneFileError> {
+        let [x, y] = numbers::<2>(json, path)?;
+        Ok(Vector2::new(x, y))
+    }
+}
+
+impl FromJson for Vector3 {
+    fn from_json(json: &Json, path: &str) -> Result<Self, SceneFileError> {
+        let [x, y, z] = numbers::<3>(json, path)?;
+        Ok(Vector3::new(x, y, z))
+    }
+}
+
+impl FromJson for Color {
+    fn from_json(json: &Json, path: &str) -> Result<Self, SceneFileError> {
+        let error = || invalid(path, "expected a \"#RRGGBB\" or \"#AARRGGBB\" color");
+        let hex = json
+            .as_str()
+            .and_then(|text| text.strip_prefix('#'))
+            .ok_or_else(error)?;
+        let channels = u32::from_str_radix(hex, 16).map_err(|_| error())?;
+        let [a, r, g, b] = channels.to_be_bytes();
+        match hex.len() {
+            6 => Ok(Color::rgb(r, g, b)),
+            8 => Ok(Color { a, r, g, b }),
+            _ => Err(error()),
+        }
+    }
+}
+
+fn numbers<const N: usize>(json: &Json, path: &str) -> Result<[f32; N], SceneFileError> {
+    let error = || invalid(path, format!("expected an array of {} numbers", N));
+    let Json::Array(array) = json else {
+        return Err(error());
+    };
+    if array.len() != N {
+        return Err(error());
+    }
+    let mut numbers = [0.0; N];
+    for (number, json) in numbers.iter_mut().zip(array) {
+        *number = json.as_f64().ok_or_else(error)? as f32;
+    }
+    Ok(numbers)
+}
+
+fn string(json: &Json, path: &str) -> Result<String, SceneFileError> {
+    json.as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| invalid(path, "expected a string"))
+}
+
+fn count(json: &Json, path: &str) -> Result<usize, SceneFileError> {
+    json.as_u64()
+        .and_then(|count| usize::try_from(count).ok())
+        .ok_or_else(|| invalid(path, "expected a whole number"))
+}
+
+fn boolean(json: &Json, path: &str) -> Result<bool, SceneFileError> {
+    json.as_bool()
+        .ok_or_else(|| invalid(path, "expected true or false"))
+}
+
+fn as_object<'a>(json: &'a Json, path: &str) -> Result<&'a Map<String, Json>, SceneFileError> {
+    json.as_object()
+        .ok_or_else(|| invalid(path, "expected an object"))
+}
+
+// Unknown keys are almost always typos, so they're reported rather than
+// silently ignored.
+fn check_keys(object: &Map<String, Json>, path: &str, keys: &[&str]) -> Result<(), SceneFileError> {
+    match object.keys().find(|key| !keys.contains(&key.as_str())) {
+        Some(key) => Err(invalid(path, format!("unknown key '{}'", key))),
+        None => Ok(()),
+    }
+}
+
+fn required<'a>(
+    object: &'a Map<String, Json>,
+    path: &str,
+    key: &str,
+) -> Result<&'a Json, SceneFileError> {
+    object
+        .get(key)
+        .ok_or_else(|| invalid(path, format!("missing '{}'", key)))
+}
+
+fn optional<T>(
+    object: &Map<String, Json>,
+    path: &str,
+    key: &str,
+    parse: impl FnOnce(&Json, &str) -> Result<T, SceneFileError>,
+) -> Result<Option<T>, SceneFileError> {
+    object
+        .get(key)
+        .map(|json| parse(json, &field(path, key)))
+        .transpose()
+}
+
+fn field(path: &str, key: &str) -> String {
+    format!("{}.{}", path, key)
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> SceneFileError {
+    SceneFileError::Invalid {
+        path: path.to_owned(),
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link_target(url: &str) -> Result<LinkTarget, String> {
+        let source = format!(
+            r#"{{ "visuals": [{{ "id": "a", "text": "link", "links": [{{ "url": {} }}] }}] }}"#,
+            Json::from(url)
+        );
+        parse_scene(&source)
+            .map(|mut scene| scene.visuals.remove(0).links.remove(0).target)
+            .map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn web_and_mail_links_are_allowed() {
+        for url in [
+            "https://example.com",
+            "http://example.com/path?query#fragment",
+            "HTTPS://EXAMPLE.COM",
+            "mailto:someone@example.com",
+        ] {
+            assert_eq!(link_target(url), Ok(LinkTarget::Url(url.to_owned())));
+        }
+    }
+
+    #[test]
+    fn links_the_shell_would_run_are_rejected() {
+        for url in [
+            "C:\\Windows\\System32\\calc.exe",
+            "calc.exe",
+            "\\\\server\\share\\setup.exe",
+            "file:///C:/Windows/System32/calc.exe",
+            "ms-settings:",
+            "javascript:alert(1)",
+            "shell:startup",
+            "https:",
+            "https://",
+            "https:///C:/Windows",
+            "https:example.com",
+            "mailto:",
+            "",
+        ] {
+            assert_eq!(
+                link_target(url),
+                Err("visuals[0].links[0].url: expected an http, https or mailto URL".to_owned()),
+                "{url:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn urls_have_to_be_strings() {
+        let source = r#"{ "visuals": [{ "id": "a", "text": "link", "links": [{ "url": 1 }] }] }"#;
+        assert_eq!(
+            parse_scene(source).unwrap_err().to_string(),
+            "visuals[0].links[0].url: expected a string"
+        );
+    }
+
+    #[test]
+    fn without_a_file_the_demo_is_loaded() {
+        assert_eq!(load_checked_scene(None).unwrap(), Scene::demo());
+    }
+
+    #[test]
+    fn scene_files_that_fail_to_load_or_check_are_scene_errors() {
+        let missing = Path::new("no such directory/scene.json");
+        let Err(Error::Scene { path, .. }) = load_checked_scene(Some(missing)) else {
+            panic!("a missing scene file loaded");
+        };
+        assert_eq!(path.as_deref(), Some(missing));
+
+        let path = std::env::temp_dir().join(format!("unchecked-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{ "visuals": [{ "id": "a", "text": "a", "opacity": { "value": 1, "expression": "this.Target.Offset" } }] }"#,
+        )
+        .unwrap();
+        let result = load_checked_scene(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+        let Err(error @ Error::Scene { .. }) = result else {
+            panic!("a scene with a mistyped expression loaded");
+        };
+        assert_eq!(error.exit_code(), 65);
+    }
+}
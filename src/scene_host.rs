@@ -0,0 +1,1008 @@
+use std::{collections::HashMap, rc::Rc, time::Duration};
+
+use tracing::{debug, info, instrument, trace};
+use windows::{
+    Graphics::{
+        DirectX::{DirectXAlphaMode, DirectXPixelFormat},
+        SizeInt32,
+    },
+    UI::Composition::{
+        AnimationIterationBehavior, CompositionBrush, CompositionColorBrush,
+        CompositionDrawingSurface, CompositionGraphicsDevice, CompositionMaskBrush,
+        CompositionObject, CompositionStretch, CompositionSurfaceBrush, Compositor,
+        ContainerVisual, SpriteVisual,
+    },
+    Win32::{
+        Foundation::{RECT, SIZE},
+        Graphics::{
+            Direct2D::{
+                Common::{D2D_RECT_F, D2D1_COLOR_F},
+                D2D1_ANTIALIAS_MODE_ALIASED, D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT,
+                D2D1_DRAW_TEXT_OPTIONS_NONE, ID2D1DeviceContext, ID2D1SolidColorBrush,
+            },
+            DirectWrite::IDWriteTextLayout,
+        },
+        UI::WindowsAndMessaging::GetCaretBlinkTime,
+    },
+    core::{Error, HSTRING, Interface, Result},
+};
+use windows_numerics::{Matrix3x2, Vector2, Vector3};
+
+use crate::{
+    atlas::{Allocation, Atlas, AtlasRect},
+    cache::{DEFAULT_DPI, LruCache, TextKey, layout_bytes},
+    clock::Clock,
+    composition::{CompositionDrawingSurfaceInterop, apply_transform, draw_into_surface},
+    d2d::draw_shapes,
+    diff::{Patch, VisualProperty, VisualTree, reconcile},
+    dirty::{PixelRect, Redraw, redraw},
+    editing::EditShapes,
+    emoji::has_color_glyphs,
+    glyphs::draw_text_on_path,
+    hit_test::{TextHit, hit_test_scene, hit_test_scene_visual},
+    links::link_shapes,
+    metrics::TextMetrics,
+    numerics::{ToColor, ToD2DColor},
+    overlay::{
+        COUNTER_OFFSET, Overlay, Overlays, Shape, counter_shapes, counter_visual, shapes_bounds,
+        visual_shapes,
+    },
+    scene::{Border, Color, Scene, TextVisual},
+    storyboard::Storyboard,
+    text::{TextFactory, text_metrics},
+    text_path::TextPath,
+    timeline::Timeline,
+};
+
+// Text masks are packed into shared pages so scenes with many labels don't
+// need a surface for each one.
+const ATLAS_PAGE_SIZE: u32 = 2048;
+const ATLAS_MAX_PAGES: usize = 8;
+const ATLAS_PADDING: u32 = 2;
+
+// Layouts are kept for text that's shown again, like labels repeated
+// across a dashboard or edits that are undone.
+const LAYOUT_CACHE_BYTES: usize = 16 * 1024 * 1024;
+const LAYOUT_CACHE_ENTRIES: usize = 1024;
+
+// Selected text is tinted with the system's usual blue.
+const SELECTION_COLOR: Color = Color {
+    a: 96,
+    r: 0,
+    g: 120,
+    b: 215,
+};
+
+// How long links take to change color and show or hide their underline.
+const LINK_FADE: Duration = Duration::from_millis(150);
+
+// Where a visual's text is drawn. Masks go in a slot in one of the atlas
+// pages, or a surface of their own for text too big for a page or when the
+// atlas is full. Text with color glyphs is drawn in color, in 'color', on a
+// surface the visual shows directly.
+enum TextSurface {
+    Atlas {
+        page: usize,
+        rect: AtlasRect,
+    },
+    Own(CompositionDrawingSurface),
+    Color {
+        surface: CompositionDrawingSurface,
+        brush: CompositionSurfaceBrush,
+        color: Color,
+    },
+}
+
+struct HostedVisual {
+    visual: SpriteVisual,
+    // Colors the mask with 'color_brush'.
+    mask_brush: CompositionMaskBrush,
+    // Shows the visual's part of its mask surface.
+    mask: CompositionSurfaceBrush,
+    // None until there's text to draw.
+    text_surface: Option<TextSurface>,
+    color_brush: CompositionColorBrush,
+    border: Option<SpriteVisual>,
+    // The text drawn into the surface, the rest of its layout's key and its
+    // layout, to work out what needs drawing when it changes.
+    text: String,
+    style: Option<TextKey>,
+    metrics: TextMetrics,
+    overlay: OverlayVisual,
+    // The caret, selection and composition while the text is being edited.
+    edit: Option<ContainerVisual>,
+    links: Vec<HostedLink>,
+    hovered_link: Option<usize>,
+}
+
+// A link's text drawn again over the visual's in the link's color, and its
+// underline, which is only shown on hover.
+struct HostedLink {
+    visual: ContainerVisual,
+    brush: CompositionColorBrush,
+    underline: ContainerVisual,
+}
+
+// A visual sized to fit the overlay shapes drawn into its surface.
+struct OverlayVisual {
+    visual: SpriteVisual,
+    surface: CompositionDrawingSurface,
+}
+
+impl OverlayVisual {
+    // Draws 'shapes', then any 'text' at the origin, with the shapes'
+    // coordinates relative to 'origin' in the parent visual.
+    fn draw(
+        &self,
+        shapes: &[Shape],
+        text: Option<(&IDWriteTextLayout, Color)>,
+        origin: Vector2,
+    ) -> Result<()> {
+        let Some([x, y, width, height]) = shapes_bounds(shapes) else {
+            return self.visual.SetIsVisible(false);
+        };
+        self.surface.resize(&SIZE {
+            cx: width as i32,
+            cy: height as i32,
+        })?;
+        self.visual
+            .SetOffset(Vector3::new(origin.X + x, origin.Y + y, 0.0))?;
+        self.visual.SetSize(Vector2::new(width, height))?;
+        self.visual.SetIsVisible(true)?;
+        draw_into_surface(
+            &self.surface,
+            None,
+            |d2d_context: &ID2D1DeviceContext, offset| unsafe {
+                d2d_context.SetTransform(&Matrix3x2::translation(
+                    offset.x as f32 - x,
+                    offset.y as f32 - y,
+                ));
+                d2d_context.Clear(Some(&D2D1_COLOR_F::default()));
+                let d2d_brush: ID2D1SolidColorBrush = d2d_context
+                    .CreateSolidColorBrush(&D2D1_COLOR_F::default(), None)
+                    .expect("Failed to create color brush!");
+                draw_shapes(d2d_context, &d2d_brush, shapes);
+                if let Some((text_layout, color)) = text {
+                    d2d_brush.SetColor(&color.to_d2d_color());
+                    d2d_context.DrawTextLayout(
+                        Vector2 { X: 0.0, Y: 0.0 },
+                        text_layout,
+                        &d2d_brush,
+                        D2D1_DRAW_TEXT_OPTIONS_NONE,
+                    );
+                }
+            },
+        )
+    }
+}
+
+// Keeps a visual tree under 'root' in sync with a scene. Updating to a new
+// scene applies the patches from reconcile, so the window and any visuals
+// that didn't change are left alone.
+pub struct SceneHost {
+    compositor: Compositor,
+    root: SpriteVisual,
+    root_brush: CompositionColorBrush,
+    comp_graphics: CompositionGraphicsDevice,
+    text_factory: TextFactory,
+    clock: Rc<dyn Clock>,
+    scene: Scene,
+    visuals: HashMap<String, HostedVisual>,
+    atlas: Atlas<String>,
+    atlas_pages: Vec<CompositionDrawingSurface>,
+    layouts: LruCache<TextKey, (IDWriteTextLayout, TextMetrics)>,
+    storyboard: Storyboard,
+    overlays: Overlays,
+    counter: Option<OverlayVisual>,
+}
+
+impl SceneHost {
+    pub fn new(
+        compositor: &Compositor,
+        root: &SpriteVisual,
+        comp_graphics: &CompositionGraphicsDevice,
+        clock: Rc<dyn Clock>,
+        scene: Scene,
+        overlays: Overlays,
+    ) -> Result<Self> {
+        let root_brush = compositor.CreateColorBrushWithColor(scene.background.to_color())?;
+        root.SetBrush(&root_brush)?;
+
+        let mut host = Self {
+            compositor: compositor.clone(),
+            root: root.clone(),
+            root_brush,
+            comp_graphics: comp_graphics.clone(),
+            text_factory: TextFactory::new()?,
+            storyboard: Storyboard::new(compositor, clock.clone()),
+            clock,
+            scene: Scene {
+                background: scene.background,
+                visuals: Vec::new(),
+            },
+            visuals: HashMap::new(),
+            atlas: Atlas::new(ATLAS_PAGE_SIZE, ATLAS_MAX_PAGES, ATLAS_PADDING),
+            atlas_pages: Vec::new(),
+            layouts: LruCache::new(LAYOUT_CACHE_BYTES, LAYOUT_CACHE_ENTRIES),
+            overlays: Overlays::default(),
+            counter: None,
+        };
+        host.update(scene)?;
+        host.set_overlays(overlays)?;
+        Ok(host)
+    }
+
+    // Pauses or resumes every key frame animation, returning whether they're
+    // now paused.
+    pub fn toggle_paused(&mut self) -> Result<bool> {
+        if self.storyboard.is_paused() {
+            self.storyboard.resume()?;
+        } else {
+            self.storyboard.pause()?;
+        }
+        Ok(self.storyboard.is_paused())
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    // Replaces a visual's text, as typing into it does.
+    pub fn set_text(&mut self, id: &str, text: &str) -> Result<()> {
+        let mut scene = self.scene.clone();
+        if let Some(text_visual) = scene.visuals.iter_mut().find(|visual| visual.id == id) {
+            text_visual.text = text.to_owned();
+        }
+        self.update(scene)
+    }
+
+    // The layout the visual's text is drawn from.
+    pub fn text_layout(&mut self, id: &str) -> Result<Option<(IDWriteTextLayout, TextMetrics)>> {
+        let Some(text_visual) = self.scene.visual(id) else {
+            return Ok(None);
+        };
+        let text_visual = text_visual.axes_at(self.storyboard.position()).into_owned();
+        self.layout(&text_visual).map(Some)
+    }
+
+    // The topmost visual with text under a point in the window, and where
+    // in its text the point is. 'window_size' is the root's size.
+    pub fn hit_test(&self, point: Vector2, window_size: Vector2) -> Option<(String, TextHit)> {
+        hit_test_scene(
+            &self.scene,
+            |id| self.visuals.get(id).map(|hosted| &hosted.metrics),
+            point,
+            self.storyboard.position(),
+            window_size,
+        )
+    }
+
+    // Where a point in the window is in one visual's text, even when it's
+    // outside it, as it is while dragging out a selection.
+    pub fn hit_test_text(&self, id: &str, point: Vector2, window_size: Vector2) -> Option<TextHit> {
+        hit_test_scene_visual(
+            &self.scene,
+            |id| self.visuals.get(id).map(|hosted| &hosted.metrics),
+            id,
+            point,
+            self.storyboard.position(),
+            window_size,
+        )
+    }
+
+    // Animates a link to its hover color and shows its underline, or back.
+    pub fn set_link_hovered(&mut self, id: &str, index: usize, is_hovered: bool) -> Result<()> {
+        let (Some(hosted), Some(link)) = (
+            self.visuals.get_mut(id),
+            self.scene
+                .visual(id)
+                .and_then(|visual| visual.links.get(index)),
+        ) else {
+            return Ok(());
+        };
+        let Some(hosted_link) = hosted.links.get(index) else {
+            return Ok(());
+        };
+        hosted.hovered_link = is_hovered.then_some(index);
+        let (color, opacity) = if is_hovered {
+            (link.hover_color, 1.0)
+        } else {
+            (link.color, 0.0)
+        };
+        let color_animation = self.compositor.CreateColorKeyFrameAnimation()?;
+        color_animation.InsertKeyFrame(1.0, color.to_color())?;
+        color_animation.SetDuration(LINK_FADE.into())?;
+        hosted_link
+            .brush
+            .StartAnimation(&HSTRING::from("Color"), &color_animation)?;
+        let fade = self.compositor.CreateScalarKeyFrameAnimation()?;
+        fade.InsertKeyFrame(1.0, opacity)?;
+        fade.SetDuration(LINK_FADE.into())?;
+        hosted_link
+            .underline
+            .StartAnimation(&HSTRING::from("Opacity"), &fade)
+    }
+
+    // Shows the caret, selection and composition over a visual's text, or
+    // hides them. The caret blinks at the system's rate, starting out shown
+    // so it's seen wherever it moves.
+    pub fn show_edit_shapes(&mut self, id: &str, shapes: Option<&EditShapes>) -> Result<()> {
+        let Some(hosted) = self.visuals.get_mut(id) else {
+            return Ok(());
+        };
+        if let Some(edit) = hosted.edit.take() {
+            hosted.visual.Children()?.Remove(&edit)?;
+        }
+        let Some(shapes) = shapes else {
+            return Ok(());
+        };
+        let edit = self.compositor.CreateContainerVisual()?;
+        let children = edit.Children()?;
+        let selection_brush: CompositionBrush = self
+            .compositor
+            .CreateColorBrushWithColor(SELECTION_COLOR.to_color())?
+            .cast()?;
+        let text_brush: CompositionBrush = hosted.color_brush.cast()?;
+        for rect in &shapes.selection {
+            children.InsertAtTop(&rect_visual(&self.compositor, *rect, &selection_brush)?)?;
+        }
+        for rect in &shapes.composition {
+            children.InsertAtTop(&rect_visual(&self.compositor, *rect, &text_brush)?)?;
+        }
+        let caret = rect_visual(&self.compositor, shapes.caret, &text_brush)?;
+        let blink_time = unsafe { GetCaretBlinkTime() };
+        if blink_time != 0 && blink_time != u32::MAX {
+            let blink = self.compositor.CreateScalarKeyFrameAnimation()?;
+            blink.InsertKeyFrame(0.0, 1.0)?;
+            blink.InsertKeyFrame(0.5, 1.0)?;
+            blink.InsertKeyFrame(0.501, 0.0)?;
+            blink.InsertKeyFrame(1.0, 0.0)?;
+            blink.SetDuration(Duration::from_millis(2 * blink_time as u64).into())?;
+            blink.SetIterationBehavior(AnimationIterationBehavior::Forever)?;
+            caret.StartAnimation(&HSTRING::from("Opacity"), &blink)?;
+        }
+        children.InsertAtTop(&caret)?;
+        // Below the overlay, which is always on top.
+        hosted
+            .visual
+            .Children()?
+            .InsertBelow(&edit, &hosted.overlay.visual)?;
+        hosted.edit = Some(edit);
+        Ok(())
+    }
+
+    pub fn overlays(&self) -> Overlays {
+        self.overlays
+    }
+
+    pub fn set_overlays(&mut self, overlays: Overlays) -> Result<()> {
+        self.overlays = overlays;
+        for hosted in self.visuals.values() {
+            hosted.overlay.draw(
+                &visual_shapes(overlays, &hosted.metrics),
+                None,
+                Vector2::zero(),
+            )?;
+        }
+        match (overlays.contains(Overlay::Counter), &self.counter) {
+            (true, None) => {
+                let counter = self.create_overlay()?;
+                self.root.Children()?.InsertAtTop(&counter.visual)?;
+                self.counter = Some(counter);
+                self.update_counter(0.0, 0)?;
+            }
+            (false, Some(counter)) => {
+                self.root.Children()?.Remove(&counter.visual)?;
+                self.counter = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn update_counter(&self, fps: f32, frame: u64) -> Result<()> {
+        let Some(counter) = &self.counter else {
+            return Ok(());
+        };
+        let text_visual = counter_visual(fps, frame);
+        let text_layout = self.text_factory.create_text_layout(&text_visual)?;
+        let size = text_metrics(&text_layout)?.size;
+        counter.draw(
+            &counter_shapes(size),
+            Some((&text_layout, text_visual.color.value)),
+            COUNTER_OFFSET,
+        )
+    }
+
+    pub fn update(&mut self, scene: Scene) -> Result<()> {
+        let patches = reconcile(&self.scene, &scene);
+        debug!(patches = patches.len(), "Updating scene");
+        for patch in patches {
+            trace!(?patch, "Applying patch");
+            self.apply(&scene, &patch)?;
+        }
+        self.scene = scene;
+        let stats = self.layouts.stats();
+        debug!(
+            hits = stats.hits,
+            misses = stats.misses,
+            entries = stats.entries,
+            bytes = stats.bytes,
+            "Layout cache"
+        );
+        Ok(())
+    }
+
+    // Composition can't animate font axes, so visuals with animated axes are
+    // laid out and drawn again whenever their values at the storyboard's
+    // position differ from the ones last drawn.
+    pub fn update_font_axes(&mut self) -> Result<()> {
+        let time = self.storyboard.position();
+        let animated: Vec<TextVisual> = self
+            .scene
+            .visuals
+            .iter()
+            .filter(|text_visual| text_visual.has_animated_axes())
+            .map(|text_visual| text_visual.axes_at(time).into_owned())
+            .collect();
+        for text_visual in animated {
+            let style = TextKey::new(&text_visual, DEFAULT_DPI).without_text();
+            if self.visuals[&text_visual.id].style.as_ref() != Some(&style) {
+                trace!(id = text_visual.id.as_str(), "Font axes changed");
+                self.rasterize(&text_visual.id, &text_visual)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_visual(&self, text_visual: &TextVisual) -> Result<HostedVisual> {
+        let visual = self.compositor.CreateSpriteVisual()?;
+        visual.SetAnchorPoint(text_visual.anchor_point)?;
+        visual.SetRelativeOffsetAdjustment(text_visual.relative_offset)?;
+
+        // The mask surface is drawn at its natural size and shifted so the
+        // visual's slot lines up with the visual, which clips the rest.
+        let mask = self.compositor.CreateSurfaceBrush()?;
+        mask.SetStretch(CompositionStretch::None)?;
+        mask.SetHorizontalAlignmentRatio(0.0)?;
+        mask.SetVerticalAlignmentRatio(0.0)?;
+
+        // Create our mask brush
+        let mask_brush = self.compositor.CreateMaskBrush()?;
+        let color_brush = self
+            .compositor
+            .CreateColorBrushWithColor(text_visual.color.value.to_color())?;
+        mask_brush.SetSource(&color_brush)?;
+        mask_brush.SetMask(&mask)?;
+        visual.SetBrush(&mask_brush)?;
+
+        let overlay = self.create_overlay()?;
+        visual.Children()?.InsertAtTop(&overlay.visual)?;
+        Ok(HostedVisual {
+            border: self.create_border(&visual, text_visual.border.as_ref())?,
+            visual,
+            mask_brush,
+            mask,
+            text_surface: None,
+            color_brush,
+            text: String::new(),
+            style: None,
+            metrics: TextMetrics::default(),
+            overlay,
+            edit: None,
+            links: Vec::new(),
+            hovered_link: None,
+        })
+    }
+
+    fn create_overlay(&self) -> Result<OverlayVisual> {
+        let surface = self.comp_graphics.CreateDrawingSurface2(
+            Default::default(),
+            DirectXPixelFormat::B8G8R8A8UIntNormalized,
+            DirectXAlphaMode::Premultiplied,
+        )?;
+        let visual = self.compositor.CreateSpriteVisual()?;
+        visual.SetBrush(&self.compositor.CreateSurfaceBrushWithSurface(&surface)?)?;
+        visual.SetIsVisible(false)?;
+        Ok(OverlayVisual { visual, surface })
+    }
+
+    // Lays out the visual's text, sizes the visual to fit and draws the text
+    // into its mask. When the size stays the same only the lines and
+    // clusters that changed are drawn, unless something other than the text
+    // changed, like the font or its axes, which changes the glyphs
+    // themselves.
+    fn rasterize(&mut self, id: &str, text_visual: &TextVisual) -> Result<()> {
+        let (text_layout, metrics) = self.layout(text_visual)?;
+        let color = has_color_glyphs(&text_visual.text).then_some(text_visual.color.value);
+        let style = TextKey::new(text_visual, DEFAULT_DPI).without_text();
+        let hosted = &self.visuals[id];
+        let drawn_color = match &hosted.text_surface {
+            Some(TextSurface::Color { color, .. }) => Some(*color),
+            _ => None,
+        };
+        let changes = if color == drawn_color && hosted.style.as_ref() == Some(&style) {
+            redraw(&hosted.text, &hosted.metrics, &text_visual.text, &metrics)
+        } else {
+            Redraw::Everything
+        };
+        if changes == Redraw::Everything {
+            self.place_text(id, metrics.size, color)?;
+        }
+
+        let hosted = &self.visuals[id];
+        if let Some(text_surface) = &hosted.text_surface {
+            let (surface, origin, full_rect) = match text_surface {
+                TextSurface::Atlas { page, rect } => {
+                    // Clear the padding too, since new pages start out
+                    // with whatever was in memory.
+                    let padding = self.atlas.padding() as i32;
+                    (
+                        &self.atlas_pages[*page],
+                        (rect.x as i32, rect.y as i32),
+                        PixelRect {
+                            left: -padding,
+                            top: -padding,
+                            right: rect.width as i32 + padding,
+                            bottom: rect.height as i32 + padding,
+                        },
+                    )
+                }
+                TextSurface::Own(surface) | TextSurface::Color { surface, .. } => (
+                    surface,
+                    (0, 0),
+                    PixelRect {
+                        left: 0,
+                        top: 0,
+                        right: metrics.size.X as i32,
+                        bottom: metrics.size.Y as i32,
+                    },
+                ),
+            };
+            let rects = match changes {
+                Redraw::Nothing => {
+                    debug!(id, "Layout changed without changing any pixels");
+                    Vec::new()
+                }
+                Redraw::Everything => vec![full_rect],
+                Redraw::Rects(rects) => {
+                    debug!(id, rects = rects.len(), "Drawing part of the text");
+                    rects
+                }
+            };
+            let path = text_visual
+                .path
+                .as_ref()
+                .map(|path| (path, metrics.path.as_slice()));
+            for rect in rects {
+                draw_text(surface, origin, &text_layout, path, color, rect)?;
+            }
+        }
+
+        let hosted = self.visuals.get_mut(id).unwrap();
+        hosted.overlay.draw(
+            &visual_shapes(self.overlays, &metrics),
+            None,
+            Vector2::zero(),
+        )?;
+        hosted.text = text_visual.text.clone();
+        hosted.style = Some(style);
+        hosted.metrics = metrics;
+        self.show_links(id, text_visual)
+    }
+
+    // Links are tinted by masking their color with the visual's own mask,
+    // clipped to each piece of the link. Color text can only be underlined.
+    fn show_links(&mut self, id: &str, text_visual: &TextVisual) -> Result<()> {
+        let hosted = self.visuals.get_mut(id).unwrap();
+        let children = hosted.visual.Children()?;
+        for link in hosted.links.drain(..) {
+            children.Remove(&link.visual)?;
+        }
+        hosted.hovered_link = hosted
+            .hovered_link
+            .filter(|index| *index < text_visual.links.len());
+        let is_color = matches!(hosted.text_surface, Some(TextSurface::Color { .. }));
+        let size = hosted.metrics.size;
+        for (index, link) in text_visual.links.iter().enumerate() {
+            let shapes = link_shapes(&text_visual.text, &hosted.metrics, link.range.clone());
+            let is_hovered = hosted.hovered_link == Some(index);
+            let color = if is_hovered {
+                link.hover_color
+            } else {
+                link.color
+            };
+            let brush = self
+                .compositor
+                .CreateColorBrushWithColor(color.to_color())?;
+            let visual = self.compositor.CreateContainerVisual()?;
+            let link_children = visual.Children()?;
+            if !is_color {
+                let tint = self.compositor.CreateMaskBrush()?;
+                tint.SetSource(&brush)?;
+                tint.SetMask(&hosted.mask)?;
+                for [x, y, width, height] in &shapes.text {
+                    let piece = self.compositor.CreateSpriteVisual()?;
+                    piece.SetSize(size)?;
+                    piece.SetBrush(&tint)?;
+                    piece.SetClip(&self.compositor.CreateInsetClipWithInsets(
+                        *x,
+                        *y,
+                        size.X - x - width,
+                        size.Y - y - height,
+                    )?)?;
+                    link_children.InsertAtTop(&piece)?;
+                }
+            }
+            let underline = self.compositor.CreateContainerVisual()?;
+            let underline_brush: CompositionBrush = brush.cast()?;
+            for rect in &shapes.underlines {
+                underline.Children()?.InsertAtTop(&rect_visual(
+                    &self.compositor,
+                    *rect,
+                    &underline_brush,
+                )?)?;
+            }
+            underline.SetOpacity(if is_hovered { 1.0 } else { 0.0 })?;
+            link_children.InsertAtTop(&underline)?;
+            // Below the overlay, which is always on top.
+            children.InsertBelow(&visual, &hosted.overlay.visual)?;
+            hosted.links.push(HostedLink {
+                visual,
+                brush,
+                underline,
+            });
+        }
+        Ok(())
+    }
+
+    fn layout(&mut self, text_visual: &TextVisual) -> Result<(IDWriteTextLayout, TextMetrics)> {
+        let key = TextKey::new(text_visual, DEFAULT_DPI);
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(layout.clone());
+        }
+        let text_layout = self.text_factory.create_text_layout(text_visual)?;
+        let metrics = text_metrics(&text_layout)?.on_path(text_visual);
+        let bytes = layout_bytes(&text_visual.text, &metrics);
+        self.layouts
+            .insert(key, (text_layout.clone(), metrics.clone()), bytes);
+        Ok((text_layout, metrics))
+    }
+
+    // Sizes the visual and finds it a surface of that size: a color one if
+    // 'color' is given, otherwise a mask in the atlas if there's room.
+    fn place_text(&mut self, id: &str, size: Vector2, color: Option<Color>) -> Result<()> {
+        let hosted = self.visuals.get_mut(id).unwrap();
+        hosted.visual.SetSize(size)?;
+        let (width, height) = (size.X as u32, size.Y as u32);
+        if width == 0 || height == 0 {
+            self.atlas.release(&id.to_owned());
+            hosted.text_surface = None;
+            return Ok(());
+        }
+
+        if let Some(color) = color {
+            self.atlas.release(&id.to_owned());
+            let (surface, brush) = match hosted.text_surface.take() {
+                Some(TextSurface::Color { surface, brush, .. }) => (surface, brush),
+                _ => {
+                    let surface = self.comp_graphics.CreateDrawingSurface2(
+                        Default::default(),
+                        DirectXPixelFormat::B8G8R8A8UIntNormalized,
+                        DirectXAlphaMode::Premultiplied,
+                    )?;
+                    let brush = self.compositor.CreateSurfaceBrushWithSurface(&surface)?;
+                    (surface, brush)
+                }
+            };
+            surface.resize(&SIZE {
+                cx: width as i32,
+                cy: height as i32,
+            })?;
+            hosted.visual.SetBrush(&brush)?;
+            debug!(id, "Drawing color text");
+            hosted.text_surface = Some(TextSurface::Color {
+                surface,
+                brush,
+                color,
+            });
+            return Ok(());
+        }
+        hosted.visual.SetBrush(&hosted.mask_brush)?;
+
+        match self.atlas.acquire(id.to_owned(), width, height) {
+            Ok(acquired) => {
+                if !acquired.evicted.is_empty() {
+                    debug!(evicted = ?acquired.evicted, "Evicted released atlas slots");
+                }
+                let Allocation { page, rect } = acquired.allocation;
+                debug!(
+                    id,
+                    page,
+                    reused = !acquired.is_new,
+                    "Placed text in the atlas"
+                );
+                while self.atlas_pages.len() <= page {
+                    let page_size = self.atlas.page_size() as i32;
+                    self.atlas_pages
+                        .push(self.comp_graphics.CreateDrawingSurface2(
+                            SizeInt32 {
+                                Width: page_size,
+                                Height: page_size,
+                            },
+                            DirectXPixelFormat::A8UIntNormalized,
+                            DirectXAlphaMode::Premultiplied,
+                        )?);
+                    debug!(pages = self.atlas_pages.len(), "Added an atlas page");
+                }
+                hosted.mask.SetSurface(&self.atlas_pages[page])?;
+                hosted
+                    .mask
+                    .SetOffset(Vector2::new(-(rect.x as f32), -(rect.y as f32)))?;
+                hosted.text_surface = Some(TextSurface::Atlas { page, rect });
+            }
+            Err(evicted) => {
+                if !evicted.is_empty() {
+                    debug!(?evicted, "Evicted released atlas slots");
+                }
+                debug!(id, width, height, "Text doesn't fit in the atlas");
+                let surface = match &hosted.text_surface {
+                    Some(TextSurface::Own(surface)) => surface.clone(),
+                    _ => self.comp_graphics.CreateDrawingSurface2(
+                        Default::default(),
+                        DirectXPixelFormat::A8UIntNormalized,
+                        DirectXAlphaMode::Premultiplied,
+                    )?,
+                };
+                surface.resize(&SIZE {
+                    cx: width as i32,
+                    cy: height as i32,
+                })?;
+                hosted.mask.SetSurface(&surface)?;
+                hosted.mask.SetOffset(Vector2::zero())?;
+                hosted.text_surface = Some(TextSurface::Own(surface));
+            }
+        }
+        Ok(())
+    }
+
+    fn create_border(
+        &self,
+        visual: &SpriteVisual,
+        border: Option<&Border>,
+    ) -> Result<Option<SpriteVisual>> {
+        let Some(text_border) = border else {
+            return Ok(None);
+        };
+        let border = self.compositor.CreateSpriteVisual()?;
+        let border_size = text_border.thickness;
+        border.SetSize(Vector2::new(border_size * 2.0, border_size * 2.0))?;
+        border.SetRelativeSizeAdjustment(Vector2::one())?;
+        border.SetOffset(Vector3::new(-border_size, -border_size, 0.0))?;
+        let border_brush = self.compositor.CreateNineGridBrush()?;
+        border.SetBrush(&border_brush)?;
+        border_brush.SetInsets(border_size)?;
+        border_brush.SetIsCenterHollow(true)?;
+        border_brush.SetSource(
+            &self
+                .compositor
+                .CreateColorBrushWithColor(text_border.color.to_color())?,
+        )?;
+        // Below the overlay, which is always on top.
+        visual.Children()?.InsertAtBottom(&border)?;
+        Ok(Some(border))
+    }
+
+    // Replaces the storyboard with one for 'scene', picking up at
+    // the old storyboard's position so reloads don't visibly restart
+    // animations, paused if they were. Every visual has to exist first since
+    // expressions can reference any of them.
+    #[instrument(level = "debug", skip_all)]
+    fn start_animations(&mut self, scene: &Scene) -> Result<()> {
+        let position = self.storyboard.position();
+        let is_paused = self.storyboard.is_paused();
+        debug!(?position, is_paused, "Restarting animations");
+        self.storyboard.stop()?;
+        self.storyboard = Storyboard::new(&self.compositor, self.clock.clone());
+        self.storyboard
+            .set_completed(|| info!("Key frame animations completed"));
+
+        let mut references = HashMap::from([("root".to_owned(), self.root.cast()?)]);
+        for (id, hosted) in &self.visuals {
+            references.insert(id.clone(), hosted.visual.cast()?);
+        }
+        let mut clips = Vec::new();
+        for text_visual in &scene.visuals {
+            let hosted = &self.visuals[&text_visual.id];
+            let color_brush: CompositionObject = hosted.color_brush.cast()?;
+            let visual: CompositionObject = hosted.visual.cast()?;
+            // Expressions aren't part of the storyboard, so stop anything
+            // still running from the previous scene.
+            color_brush.StopAnimation(&HSTRING::from("Color"))?;
+            for property in [
+                "Offset",
+                "Scale",
+                "RotationAngleInDegrees",
+                "CenterPoint",
+                "Opacity",
+            ] {
+                visual.StopAnimation(&HSTRING::from(property))?;
+            }
+
+            hosted
+                .color_brush
+                .SetColor(text_visual.color.value.to_color())?;
+            clips.extend(self.storyboard.add_property(
+                &color_brush,
+                "Color",
+                &text_visual.color,
+                &references,
+            )?);
+            clips.extend(apply_transform(
+                &mut self.storyboard,
+                &visual.cast()?,
+                &text_visual.transform,
+                &references,
+            )?);
+        }
+        self.storyboard.set_timeline(Timeline::parallel(clips));
+        if position.is_zero() {
+            self.storyboard.start()?;
+        } else {
+            self.storyboard.seek(position)?;
+        }
+        if is_paused {
+            self.storyboard.pause()?;
+        }
+        Ok(())
+    }
+}
+
+impl VisualTree for SceneHost {
+    type Error = Error;
+
+    fn apply(&mut self, scene: &Scene, patch: &Patch) -> Result<()> {
+        match patch {
+            Patch::SetBackground(background) => self.root_brush.SetColor(background.to_color()),
+            Patch::RemoveVisual(id) => match self.visuals.remove(id) {
+                Some(hosted) => {
+                    self.atlas.release(id);
+                    self.root.Children()?.Remove(&hosted.visual)
+                }
+                None => Ok(()),
+            },
+            Patch::CreateVisual(id) => {
+                let hosted = self.create_visual(scene.visual(id).unwrap())?;
+                self.visuals.insert(id.clone(), hosted);
+                Ok(())
+            }
+            Patch::SetProperty(id, VisualProperty::Placement) => {
+                let text_visual = scene.visual(id).unwrap();
+                let visual = &self.visuals[id].visual;
+                visual.SetAnchorPoint(text_visual.anchor_point)?;
+                visual.SetRelativeOffsetAdjustment(text_visual.relative_offset)
+            }
+            Patch::SetProperty(id, VisualProperty::Border) => {
+                let text_visual = scene.visual(id).unwrap();
+                let border =
+                    self.create_border(&self.visuals[id].visual, text_visual.border.as_ref())?;
+                let hosted = self.visuals.get_mut(id).unwrap();
+                match std::mem::replace(&mut hosted.border, border) {
+                    Some(old_border) => hosted.visual.Children()?.Remove(&old_border),
+                    None => Ok(()),
+                }
+            }
+            Patch::Rasterize(id) => {
+                let text_visual = scene.visual(id).unwrap();
+                self.rasterize(id, &text_visual.axes_at(self.storyboard.position()))
+            }
+            // Children are kept in scene order, last on top, with the
+            // counter above them all.
+            Patch::OrderChildren(ids) => {
+                let children = self.root.Children()?;
+                children.RemoveAll()?;
+                for id in ids {
+                    children.InsertAtTop(&self.visuals[id].visual)?;
+                }
+                if let Some(counter) = &self.counter {
+                    children.InsertAtTop(&counter.visual)?;
+                }
+                Ok(())
+            }
+            Patch::RestartAnimations => self.start_animations(scene),
+        }
+    }
+}
+
+fn rect_visual(
+    compositor: &Compositor,
+    [x, y, width, height]: [f32; 4],
+    brush: &CompositionBrush,
+) -> Result<SpriteVisual> {
+    let visual = compositor.CreateSpriteVisual()?;
+    visual.SetOffset(Vector3::new(x, y, 0.0))?;
+    visual.SetSize(Vector2::new(width, height))?;
+    visual.SetBrush(brush)?;
+    Ok(visual)
+}
+
+// Draws the part of the text in 'rect' into a surface that has the text's
+// top left corner at 'origin'. The text is drawn in 'color' with its color
+// glyphs if one is given, otherwise as a mask, and along 'path' and its
+// flattened points if it has one.
+fn draw_text(
+    surface: &CompositionDrawingSurface,
+    origin: (i32, i32),
+    text_layout: &IDWriteTextLayout,
+    path: Option<(&TextPath, &[Vector2])>,
+    color: Option<Color>,
+    rect: PixelRect,
+) -> Result<()> {
+    let mut drawn = Ok(());
+    let update_rect = RECT {
+        left: origin.0 + rect.left,
+        top: origin.1 + rect.top,
+        right: origin.0 + rect.right,
+        bottom: origin.1 + rect.bottom,
+    };
+    draw_into_surface(
+        surface,
+        Some(&update_rect),
+        |d2d_context: &ID2D1DeviceContext, offset| unsafe {
+            // The offset is where the update rect starts, so shift the drawing
+            // back by the rect's position in the text.
+            d2d_context.SetTransform(&Matrix3x2::translation(
+                (offset.x - rect.left) as f32,
+                (offset.y - rect.top) as f32,
+            ));
+            d2d_context.PushAxisAlignedClip(
+                &D2D_RECT_F {
+                    left: rect.left as f32,
+                    top: rect.top as f32,
+                    right: rect.right as f32,
+                    bottom: rect.bottom as f32,
+                },
+                D2D1_ANTIALIAS_MODE_ALIASED,
+            );
+
+            let d2d_brush = {
+                let color = match color {
+                    Some(color) => color.to_d2d_color(),
+                    None => D2D1_COLOR_F {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 1.0,
+                    },
+                };
+                d2d_context
+                    .CreateSolidColorBrush(&color, None)
+                    .expect("Failed to create color brush!")
+            };
+            let options = if color.is_some() {
+                D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT
+            } else {
+                D2D1_DRAW_TEXT_OPTIONS_NONE
+            };
+
+            d2d_context.Clear(Some(&D2D1_COLOR_F {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+            }));
+            if let Some((path, points)) = path {
+                drawn = draw_text_on_path(d2d_context, text_layout, path, points, &d2d_brush);
+            } else {
+                d2d_context.DrawTextLayout(
+                    Vector2 { X: 0.0, Y: 0.0 },
+                    text_layout,
+                    &d2d_brush,
+                    options,
+                );
+            }
+            d2d_context.PopAxisAlignedClip();
+        },
+    )?;
+    drawn
+}
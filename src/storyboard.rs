@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use tracing::instrument;
+use windows::{
+    Foundation::TypedEventHandler,
+    UI::Composition::{
+        AnimationIterationBehavior, CompositionBatchTypes, CompositionObject,
+        CompositionScopedBatch, Compositor, KeyFrameAnimation as CompositionKeyFrameAnimation,
+    },
+    core::{HSTRING, Interface, Result},
+};
+
+use crate::{
+    animation::{IterationBehavior, KeyFrameAnimation},
+    clock::Clock,
+    composition::{ToCompositionAnimation, start_property_animation},
+    scene::{Animation, Property},
+    timeline::{ClipState, Playhead, Timeline},
+};
+
+struct Entry {
+    object: CompositionObject,
+    property_name: HSTRING,
+    animation: CompositionKeyFrameAnimation,
+    iteration_behavior: IterationBehavior,
+}
+
+// Groups key frame animations under a timeline so they can be started,
+// paused, resumed and seeked together. Completion is reported through a
+// CompositionScopedBatch covering every animation the storyboard starts.
+// The clock only drives the playhead; Composition still runs the animations
+// on its own clock.
+pub struct Storyboard {
+    compositor: Compositor,
+    entries: Vec<Entry>,
+    timeline: Timeline,
+    clock: Rc<dyn Clock>,
+    playhead: Option<Playhead>,
+    batch: Option<CompositionScopedBatch>,
+    generation: Arc<AtomicU64>,
+    completed: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl Storyboard {
+    pub fn new(compositor: &Compositor, clock: Rc<dyn Clock>) -> Self {
+        Self {
+            compositor: compositor.clone(),
+            entries: Vec::new(),
+            timeline: Timeline::parallel(Vec::new()),
+            clock,
+            playhead: None,
+            batch: None,
+            generation: Arc::new(AtomicU64::new(0)),
+            completed: None,
+        }
+    }
+
+    pub fn add_animation<T>(
+        &mut self,
+        object: &CompositionObject,
+        property_name: &str,
+        animation: &KeyFrameAnimation<T>,
+    ) -> Result<Timeline>
+    where
+        KeyFrameAnimation<T>: ToCompositionAnimation,
+    {
+        let id = self.entries.len();
+        self.entries.push(Entry {
+            object: object.clone(),
+            property_name: HSTRING::from(property_name),
+            animation: animation
+                .to_composition_animation(&self.compositor)?
+                .cast()?,
+            iteration_behavior: animation.iteration_behavior,
+        });
+        Ok(Timeline::clip(
+            id,
+            animation.duration,
+            animation.iteration_behavior,
+        ))
+    }
+
+    // Key frame animations join the storyboard and are returned as clips for
+    // its timeline. Expressions aren't timed, so they start right away.
+    pub fn add_property<T>(
+        &mut self,
+        object: &CompositionObject,
+        property_name: &str,
+        property: &Property<T>,
+        references: &HashMap<String, CompositionObject>,
+    ) -> Result<Option<Timeline>>
+    where
+        KeyFrameAnimation<T>: ToCompositionAnimation,
+    {
+        match &property.animation {
+            Some(Animation::KeyFrames(animation)) => self
+                .add_animation(object, property_name, animation)
+                .map(Some),
+            Some(Animation::Expression(_)) => {
+                start_property_animation(
+                    &self.compositor,
+                    object,
+                    property_name,
+                    property,
+                    references,
+                )?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_timeline(&mut self, timeline: Timeline) {
+        self.timeline = timeline;
+    }
+
+    pub fn set_completed<F: Fn() + Send + Sync + 'static>(&mut self, completed: F) {
+        self.completed = Some(Arc::new(completed));
+    }
+
+    pub fn position(&self) -> Duration {
+        self.playhead
+            .map(|playhead| playhead.position(self.now()))
+            .unwrap_or_default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.playhead.is_some_and(|playhead| playhead.is_paused())
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        self.playhead = Some(Playhead::new(self.now()));
+        self.start_at(Duration::ZERO)
+    }
+
+    pub fn pause(&mut self) -> Result<()> {
+        let now = self.now();
+        let Some(playhead) = &mut self.playhead else {
+            return Ok(());
+        };
+        if playhead.is_paused() {
+            return Ok(());
+        }
+        let position = playhead.position(now);
+        playhead.pause(now);
+        self.pause_animations_at(position)
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        let now = self.now();
+        let Some(playhead) = &mut self.playhead else {
+            return Ok(());
+        };
+        if !playhead.is_paused() {
+            return Ok(());
+        }
+        let position = playhead.position(now);
+        playhead.resume(now);
+        self.start_at(position)
+    }
+
+    pub fn seek(&mut self, position: Duration) -> Result<()> {
+        let now = self.now();
+        let playhead = self.playhead.get_or_insert_with(|| Playhead::new(now));
+        playhead.seek(now, position);
+        if playhead.is_paused() {
+            self.start_at(position)?;
+            self.pause_animations_at(position)
+        } else {
+            self.start_at(position)
+        }
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        for entry in &self.entries {
+            entry.object.StopAnimation(&entry.property_name)?;
+        }
+        self.playhead = None;
+        self.batch = None;
+        Ok(())
+    }
+
+    fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    #[instrument(level = "debug", skip(self), fields(clips = self.entries.len()))]
+    fn start_at(&mut self, position: Duration) -> Result<()> {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let batch = self
+            .compositor
+            .CreateScopedBatch(CompositionBatchTypes::Animation)?;
+        for clip in self.timeline.schedule() {
+            let entry = &self.entries[clip.id];
+            entry.object.StopAnimation(&entry.property_name)?;
+            match clip.state_at(position) {
+                ClipState::Pending { starts_in } => {
+                    entry.animation.SetDelayTime(starts_in.into())?;
+                    set_remaining_iterations(entry, 0)?;
+                    entry
+                        .object
+                        .StartAnimation(&entry.property_name, &entry.animation)?;
+                }
+                ClipState::Active {
+                    iteration,
+                    progress,
+                    ..
+                } => {
+                    entry.animation.SetDelayTime(Duration::ZERO.into())?;
+                    set_remaining_iterations(entry, iteration)?;
+                    entry
+                        .object
+                        .StartAnimation(&entry.property_name, &entry.animation)?;
+                    entry
+                        .object
+                        .TryGetAnimationController(&entry.property_name)?
+                        .SetProgress(progress)?;
+                }
+                ClipState::Completed => {
+                    // Run the last iteration to its end so the property is
+                    // left holding the final key frame's value.
+                    entry.animation.SetDelayTime(Duration::ZERO.into())?;
+                    entry
+                        .animation
+                        .SetIterationBehavior(AnimationIterationBehavior::Count)?;
+                    entry.animation.SetIterationCount(1)?;
+                    entry
+                        .object
+                        .StartAnimation(&entry.property_name, &entry.animation)?;
+                    entry
+                        .object
+                        .TryGetAnimationController(&entry.property_name)?
+                        .SetProgress(1.0)?;
+                }
+            }
+        }
+        batch.End()?;
+
+        if let Some(completed) = &self.completed {
+            let completed = completed.clone();
+            let current_generation = self.generation.clone();
+            batch.Completed(&TypedEventHandler::new(move |_, _| {
+                if current_generation.load(Ordering::SeqCst) == generation {
+                    completed();
+                }
+                Ok(())
+            }))?;
+        }
+        self.batch = Some(batch);
+        Ok(())
+    }
+
+    fn pause_animations_at(&mut self, position: Duration) -> Result<()> {
+        // Stopping the pending animations would complete the batch, so make
+        // sure the completion handler ignores it.
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        for clip in self.timeline.schedule() {
+            let entry = &self.entries[clip.id];
+            match clip.state_at(position) {
+                ClipState::Active { .. } => {
+                    entry
+                        .object
+                        .TryGetAnimationController(&entry.property_name)?
+                        .Pause()?;
+                }
+                ClipState::Pending { .. } => entry.object.StopAnimation(&entry.property_name)?,
+                ClipState::Completed => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_remaining_iterations(entry: &Entry, completed_iterations: u32) -> Result<()> {
+    match entry.iteration_behavior {
+        IterationBehavior::Count(count) => {
+            entry
+                .animation
+                .SetIterationBehavior(AnimationIterationBehavior::Count)?;
+            entry
+                .animation
+                .SetIterationCount(count.saturating_sub(completed_iterations).max(1) as i32)
+        }
+        IterationBehavior::Forever => entry
+            .animation
+            .SetIterationBehavior(AnimationIterationBehavior::Forever),
+    }
+}
@@ -0,0 +1,731 @@
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use windows_numerics::{Matrix3x2, Vector2};
+
+use crate::{
+    animation::{Easing, Interpolate, IterationBehavior, KeyFrameAnimation},
+    scene::{Animation, Color, Property, Scene, SceneEnvironment, TextVisual},
+    text_path::{PathAlignment, TextPath},
+    typography::{resolve_axes, resolve_runs},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextMode {
+    // Selectable, editable text that depends on the viewer having the font.
+    Text,
+    // Glyph outlines that look the same everywhere.
+    Outlines,
+}
+
+// Where a line of text starts. Right to left lines start at their right
+// edge, and vertical lines are columns whose baseline runs down from
+// ('x', 'baseline').
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLine {
+    pub text: String,
+    pub x: f32,
+    pub baseline: f32,
+}
+
+// What the exporter needs to know about a visual's laid out text. On
+// Windows this comes from DirectWrite; 'outline' is SVG path data for the
+// glyphs in the visual's own coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextBlock {
+    pub size: Vector2,
+    pub lines: Vec<TextLine>,
+    pub outline: Option<String>,
+}
+
+impl TextBlock {
+    // A single line of text as wide as the layout, with its baseline roughly
+    // where most fonts put it. Good enough when no text engine is around.
+    // Text on a path is sized from the path.
+    pub fn estimate(visual: &TextVisual) -> Self {
+        let line_height = visual.font_size * 1.25;
+        let (size, x, baseline) = if let Some(path) = &visual.path {
+            (path.frame(visual.font_size).1, 0.0, 0.0)
+        } else if visual.vertical {
+            (
+                Vector2::new(line_height, visual.max_width),
+                line_height / 2.0,
+                0.0,
+            )
+        } else if visual.direction.is_right_to_left(&visual.text) {
+            (
+                Vector2::new(visual.max_width, line_height),
+                visual.max_width,
+                visual.font_size,
+            )
+        } else {
+            (
+                Vector2::new(visual.max_width, line_height),
+                0.0,
+                visual.font_size,
+            )
+        };
+        Self {
+            size,
+            lines: vec![TextLine {
+                text: visual.layout_text().into_owned(),
+                x,
+                baseline,
+            }],
+            outline: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    pub width: u32,
+    pub height: u32,
+    pub text_mode: TextMode,
+}
+
+// Builds SVG path data, keeping numbers short.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathBuilder {
+    data: String,
+}
+
+impl PathBuilder {
+    pub fn move_to(&mut self, point: Vector2) {
+        self.command('M', &[point]);
+    }
+
+    pub fn line_to(&mut self, point: Vector2) {
+        self.command('L', &[point]);
+    }
+
+    pub fn cubic_to(&mut self, control_point1: Vector2, control_point2: Vector2, point: Vector2) {
+        self.command('C', &[control_point1, control_point2, point]);
+    }
+
+    pub fn close(&mut self) {
+        self.data.push('Z');
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.move_to(Vector2::new(x, y));
+        self.line_to(Vector2::new(x + width, y));
+        self.line_to(Vector2::new(x + width, y + height));
+        self.line_to(Vector2::new(x, y + height));
+        self.close();
+    }
+
+    pub fn finish(self) -> String {
+        self.data
+    }
+
+    fn command(&mut self, command: char, points: &[Vector2]) {
+        self.data.push(command);
+        for (index, point) in points.iter().enumerate() {
+            if index > 0 {
+                self.data.push(' ');
+            }
+            let _ = write!(self.data, "{} {}", number(point.X), number(point.Y));
+        }
+    }
+}
+
+// Writes the scene as it looks at time zero. Key frame color and opacity
+// animations become SMIL <animate> elements; expressions and transform
+// animations can't be expressed that way and keep their starting values.
+// Visuals without a text block are laid out with TextBlock::estimate.
+pub fn scene_to_svg(
+    scene: &Scene,
+    blocks: &HashMap<String, TextBlock>,
+    options: &SvgOptions,
+) -> String {
+    let estimates: HashMap<&str, TextBlock> = scene
+        .visuals
+        .iter()
+        .filter(|visual| !blocks.contains_key(&visual.id))
+        .map(|visual| {
+            let visual_at_zero = visual.axes_at(Duration::ZERO);
+            (visual.id.as_str(), TextBlock::estimate(&visual_at_zero))
+        })
+        .collect();
+    let block = |visual: &TextVisual| -> &TextBlock {
+        blocks
+            .get(&visual.id)
+            .unwrap_or_else(|| &estimates[visual.id.as_str()])
+    };
+
+    let root_size = Vector2::new(options.width as f32, options.height as f32);
+    let mut environment = SceneEnvironment::new(scene, Duration::ZERO, root_size);
+    for visual in &scene.visuals {
+        environment.set_size(&visual.id, block(visual).size);
+    }
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        options.width, options.height
+    );
+    let _ = writeln!(
+        svg,
+        r#"  <rect width="{}" height="{}"{}/>"#,
+        options.width,
+        options.height,
+        fill(scene.background)
+    );
+    for visual in &scene.visuals {
+        let block = block(visual);
+        let transform = visual.transform_at(Duration::ZERO, &environment);
+        let matrix = transform.matrix(visual.position(block.size, root_size));
+        let color = visual.color_at(Duration::ZERO, &environment);
+
+        let _ = writeln!(
+            svg,
+            r#"  <g id="{}" transform="{}" opacity="{}">"#,
+            escape(&visual.id),
+            svg_matrix(&matrix),
+            number(transform.opacity)
+        );
+        if let Some(animation) = key_frames(&visual.transform.opacity) {
+            svg.push_str("    ");
+            svg.push_str(&animate(
+                "opacity",
+                animation,
+                visual.transform.opacity.value,
+                |value| number(value.clamp(0.0, 1.0)),
+            ));
+            svg.push('\n');
+        }
+
+        let color_animations = key_frames(&visual.color)
+            .map(|animation| color_animations(animation, visual.color.value))
+            .unwrap_or_default();
+        match (&block.outline, options.text_mode, &visual.path) {
+            (Some(outline), TextMode::Outlines, _) => {
+                let _ = writeln!(svg, r#"    <path d="{}"{}>"#, outline, fill(color));
+                svg.push_str(&color_animations);
+                svg.push_str("    </path>\n");
+            }
+            (_, _, Some(path)) => {
+                write_text_path(&mut svg, visual, path, color);
+                svg.push_str(&color_animations);
+                svg.push_str("    </text>\n");
+            }
+            _ => {
+                let direction = if visual.vertical {
+                    r#" writing-mode="vertical-rl""#
+                } else if visual.direction.is_right_to_left(&visual.text) {
+                    r#" direction="rtl""#
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    svg,
+                    r#"    <text font-family="{}" font-size="{}" xml:lang="{}" xml:space="preserve"{}{}{}>"#,
+                    escape(&visual.font_family),
+                    number(visual.font_size),
+                    escape(&visual.locale),
+                    direction,
+                    font_settings(visual),
+                    fill(color)
+                );
+                for line in &block.lines {
+                    let _ = writeln!(
+                        svg,
+                        r#"      <tspan x="{}" y="{}">{}</tspan>"#,
+                        number(line.x),
+                        number(line.baseline),
+                        escape(&line.text)
+                    );
+                }
+                svg.push_str(&color_animations);
+                svg.push_str("    </text>\n");
+            }
+        }
+
+        if let Some(border) = &visual.border {
+            let mut path = PathBuilder::default();
+            for [x, y, width, height] in border.bands(block.size) {
+                path.rect(x, y, width, height);
+            }
+            let _ = writeln!(
+                svg,
+                r#"    <path d="{}"{}/>"#,
+                path.finish(),
+                fill(border.color)
+            );
+        }
+        svg.push_str("  </g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn key_frames<T>(property: &Property<T>) -> Option<&KeyFrameAnimation<T>> {
+    match &property.animation {
+        Some(Animation::KeyFrames(animation)) if !animation.duration.is_zero() => Some(animation),
+        _ => None,
+    }
+}
+
+fn color_animations(animation: &KeyFrameAnimation<Color>, initial: Color) -> String {
+    let mut animations = format!("      {}\n", animate("fill", animation, initial, hex));
+    // SVG colors have no alpha, so it's animated separately when it changes.
+    let translucent = initial.a != 255
+        || animation
+            .key_frames()
+            .iter()
+            .any(|key_frame| key_frame.value.a != 255);
+    if translucent {
+        let _ = writeln!(
+            animations,
+            "      {}",
+            animate("fill-opacity", animation, initial, |color| {
+                number(color.a as f32 / 255.0)
+            })
+        );
+    }
+    animations
+}
+
+// SMIL wants values at both ends of the animation, so 'initial' fills in
+// before the first key frame, the same way Composition starts from the
+// property's current value, and the last key frame holds to the end.
+fn animate<T: Interpolate>(
+    attribute: &str,
+    animation: &KeyFrameAnimation<T>,
+    initial: T,
+    format: impl Fn(T) -> String,
+) -> String {
+    let mut stops = Vec::new();
+    match animation.key_frames().first() {
+        Some(first) if first.progress > 0.0 => stops.push((0.0, initial, Easing::Linear)),
+        None => stops.push((0.0, initial, Easing::Linear)),
+        _ => {}
+    }
+    stops.extend(
+        animation
+            .key_frames()
+            .iter()
+            .map(|key_frame| (key_frame.progress, key_frame.value, key_frame.easing)),
+    );
+    if let Some(&(progress, value, _)) = stops.last()
+        && progress < 1.0
+    {
+        stops.push((1.0, value, Easing::Linear));
+    }
+
+    let join = |values: Vec<String>| values.join(";");
+    let key_times = join(stops.iter().map(|stop| number(stop.0)).collect());
+    let values = join(stops.iter().map(|stop| format(stop.1)).collect());
+    let repeat_count = match animation.iteration_behavior {
+        IterationBehavior::Count(count) => count.to_string(),
+        IterationBehavior::Forever => "indefinite".to_owned(),
+    };
+    let mut element = format!(
+        r#"<animate attributeName="{}" values="{}" keyTimes="{}" dur="{}s" repeatCount="{}" fill="freeze""#,
+        attribute,
+        values,
+        key_times,
+        number(animation.duration.as_secs_f32()),
+        repeat_count
+    );
+    // Each key frame's easing applies to the span leading up to it.
+    let splines: Vec<String> = stops
+        .iter()
+        .skip(1)
+        .map(|stop| match stop.2 {
+            Easing::Linear => "0 0 1 1".to_owned(),
+            Easing::CubicBezier(control_point1, control_point2) => format!(
+                "{} {} {} {}",
+                number(control_point1.X),
+                number(control_point1.Y),
+                number(control_point2.X),
+                number(control_point2.Y)
+            ),
+        })
+        .collect();
+    if splines.iter().any(|spline| spline != "0 0 1 1") {
+        let _ = write!(
+            element,
+            r#" calcMode="spline" keySplines="{}""#,
+            splines.join(";")
+        );
+    }
+    element.push_str("/>");
+    element
+}
+
+// OpenType features and font axes at time zero as CSS. Only the ones
+// that apply to all of the text are written, since lines aren't split into
+// runs.
+fn font_settings(visual: &TextVisual) -> String {
+    let length = visual.text.chars().count();
+    let mut settings = Vec::new();
+    if let [(_, features)] = resolve_runs(&visual.typography, length).as_slice()
+        && !features.is_empty()
+    {
+        let features: Vec<String> = features
+            .iter()
+            .map(|feature| format!("'{}' {}", feature.tag, feature.value))
+            .collect();
+        settings.push(format!("font-feature-settings: {}", features.join(", ")));
+    }
+    let visual = visual.axes_at(Duration::ZERO);
+    if let [(_, axes)] = resolve_axes(&visual.typography, length).as_slice()
+        && !axes.is_empty()
+    {
+        let axes: Vec<String> = axes
+            .iter()
+            .map(|axis| format!("'{}' {}", axis.tag, number(axis.value)))
+            .collect();
+        settings.push(format!("font-variation-settings: {}", axes.join(", ")));
+    }
+    if settings.is_empty() {
+        String::new()
+    } else {
+        format!(r#" style="{}""#, settings.join("; "))
+    }
+}
+
+// Writes the path the text follows and the opening <text> holding a
+// <textPath> along it. SVG places text on a path the same way from the
+// alignment and offset, though it doesn't carry text past the end of a
+// circle.
+fn write_text_path(svg: &mut String, visual: &TextVisual, path: &TextPath, color: Color) {
+    let (points, _) = path.frame(visual.font_size);
+    let mut data = PathBuilder::default();
+    for (index, point) in points.iter().enumerate() {
+        if index == 0 {
+            data.move_to(*point);
+        } else {
+            data.line_to(*point);
+        }
+    }
+    let length: f32 = points
+        .windows(2)
+        .map(|segment| (segment[1].X - segment[0].X).hypot(segment[1].Y - segment[0].Y))
+        .sum();
+    let (anchor, start) = match path.alignment {
+        PathAlignment::Start => ("start", 0.0),
+        PathAlignment::Center => ("middle", length / 2.0),
+        PathAlignment::End => ("end", length),
+    };
+    let direction = if visual.direction.is_right_to_left(&visual.text) {
+        r#" direction="rtl""#
+    } else {
+        ""
+    };
+    let id = format!("{}-path", escape(&visual.id));
+    let _ = writeln!(
+        svg,
+        r#"    <defs><path id="{}" d="{}"/></defs>"#,
+        id,
+        data.finish()
+    );
+    let _ = writeln!(
+        svg,
+        r#"    <text font-family="{}" font-size="{}" xml:lang="{}" xml:space="preserve" text-anchor="{}"{}{}{}>"#,
+        escape(&visual.font_family),
+        number(visual.font_size),
+        escape(&visual.locale),
+        anchor,
+        direction,
+        font_settings(visual),
+        fill(color)
+    );
+    let _ = writeln!(
+        svg,
+        r##"      <textPath href="#{}" startOffset="{}">{}</textPath>"##,
+        id,
+        number(start + path.start_offset),
+        escape(&visual.layout_text())
+    );
+}
+
+fn svg_matrix(matrix: &Matrix3x2) -> String {
+    format!(
+        "matrix({} {} {} {} {} {})",
+        number(matrix.M11),
+        number(matrix.M12),
+        number(matrix.M21),
+        number(matrix.M22),
+        number(matrix.M31),
+        number(matrix.M32)
+    )
+}
+
+fn fill(color: Color) -> String {
+    if color.a == 255 {
+        format!(r#" fill="{}""#, hex(color))
+    } else {
+        format!(
+            r#" fill="{}" fill-opacity="{}""#,
+            hex(color),
+            number(color.a as f32 / 255.0)
+        )
+    }
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+// At most three decimals, without trailing zeros or a negative zero.
+fn number(value: f32) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" | "" => "0".to_owned(),
+        _ => text.to_owned(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_file::parse_scene;
+
+    const OPTIONS: SvgOptions = SvgOptions {
+        width: 200,
+        height: 100,
+        text_mode: TextMode::Text,
+    };
+
+    fn block(text: &str) -> TextBlock {
+        TextBlock {
+            size: Vector2::new(80.0, 20.0),
+            lines: vec![TextLine {
+                text: text.to_owned(),
+                x: 0.0,
+                baseline: 16.0,
+            }],
+            outline: Some("M0 0L1 1Z".to_owned()),
+        }
+    }
+
+    fn export(source: &str, blocks: &[(&str, TextBlock)], options: &SvgOptions) -> String {
+        let scene = parse_scene(source).unwrap();
+        let blocks = blocks
+            .iter()
+            .map(|(id, block)| (id.to_string(), block.clone()))
+            .collect();
+        scene_to_svg(&scene, &blocks, options)
+    }
+
+    #[test]
+    fn writes_text_with_its_transform_and_border() {
+        let svg = export(
+            r##"{
+                "background": "#102030",
+                "visuals": [{
+                    "id": "a&b",
+                    "text": "x < y",
+                    "fontFamily": "Segoe UI",
+                    "fontSize": 16,
+                    "maxWidth": 80,
+                    "color": "#80FF0000",
+                    "anchorPoint": [0.5, 0.5],
+                    "relativeOffset": [0.5, 0.5, 0],
+                    "transform": { "offset": [10, 0, 0], "opacity": 0.5 },
+                    "border": { "thickness": 2, "color": "#0000FF" }
+                }]
+            }"##,
+            &[("a&b", block("x < y"))],
+            &OPTIONS,
+        );
+        assert_eq!(
+            svg,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 200 100">"#,
+                "\n",
+                r##"  <rect width="200" height="100" fill="#102030"/>"##,
+                "\n",
+                r#"  <g id="a&amp;b" transform="matrix(1 0 0 1 70 40)" opacity="0.5">"#,
+                "\n",
+                r##"    <text font-family="Segoe UI" font-size="16" xml:lang="en-us" xml:space="preserve" fill="#FF0000" fill-opacity="0.502">"##,
+                "\n",
+                r#"      <tspan x="0" y="16">x &lt; y</tspan>"#,
+                "\n",
+                "    </text>\n",
+                r##"    <path d="M-2 -2L82 -2L82 0L-2 0ZM-2 20L82 20L82 22L-2 22ZM-2 0L0 0L0 20L-2 20ZM80 0L82 0L82 20L80 20Z" fill="#0000FF"/>"##,
+                "\n",
+                "  </g>\n",
+                "</svg>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn writes_outlines_only_when_asked_and_available() {
+        let source = r#"{ "visuals": [
+            { "id": "a", "text": "a", "maxWidth": 80 },
+            { "id": "b", "text": "b", "maxWidth": 80 }
+        ] }"#;
+        let mut without_outline = block("b");
+        without_outline.outline = None;
+        let blocks = [("a", block("a")), ("b", without_outline)];
+
+        let svg = export(source, &blocks, &OPTIONS);
+        assert!(!svg.contains("<path"));
+
+        let options = SvgOptions {
+            text_mode: TextMode::Outlines,
+            ..OPTIONS
+        };
+        let svg = export(source, &blocks, &options);
+        assert!(svg.contains(r##"<path d="M0 0L1 1Z" fill="#000000">"##));
+        assert!(svg.contains(r#"<tspan x="0" y="16">b</tspan>"#));
+        assert!(!svg.contains(">a</tspan>"));
+    }
+
+    #[test]
+    fn estimates_blocks_that_are_missing() {
+        let svg = export(
+            r#"{ "visuals": [
+                { "id": "ltr", "text": "abc", "fontSize": 20, "maxWidth": 100 },
+                { "id": "rtl", "text": "שלום", "fontSize": 20, "maxWidth": 100 },
+                { "id": "vertical", "text": "縦", "fontSize": 20, "maxWidth": 100, "vertical": true }
+            ] }"#,
+            &[],
+            &OPTIONS,
+        );
+        assert!(svg.contains(r#"<tspan x="0" y="20">abc</tspan>"#));
+        assert!(svg.contains(r#" direction="rtl""#));
+        assert!(svg.contains(r#"<tspan x="100" y="20">שלום</tspan>"#));
+        assert!(svg.contains(r#" writing-mode="vertical-rl""#));
+        assert!(svg.contains(r#"<tspan x="12.5" y="0">縦</tspan>"#));
+    }
+
+    #[test]
+    fn animates_color_and_opacity_key_frames() {
+        let svg = export(
+            r##"{ "visuals": [{
+                "id": "a",
+                "text": "a",
+                "maxWidth": 80,
+                "color": {
+                    "value": "#FF0000",
+                    "keyFrames": {
+                        "duration": 2,
+                        "iterations": "forever",
+                        "frames": [
+                            { "progress": 0.5, "value": "#800000FF", "easing": [0.4, 0, 0.2, 1] }
+                        ]
+                    }
+                },
+                "transform": {
+                    "opacity": {
+                        "keyFrames": {
+                            "duration": 1.5,
+                            "iterations": 2,
+                            "frames": [
+                                { "progress": 0, "value": 0 },
+                                { "progress": 1, "value": 2 }
+                            ]
+                        }
+                    }
+                }
+            }] }"##,
+            &[("a", block("a"))],
+            &OPTIONS,
+        );
+        assert!(svg.contains(
+            r#"<animate attributeName="opacity" values="0;1" keyTimes="0;1" dur="1.5s" repeatCount="2" fill="freeze"/>"#
+        ));
+        assert!(svg.contains(
+            r##"<animate attributeName="fill" values="#FF0000;#0000FF;#0000FF" keyTimes="0;0.5;1" dur="2s" repeatCount="indefinite" fill="freeze" calcMode="spline" keySplines="0.4 0 0.2 1;0 0 1 1"/>"##
+        ));
+        assert!(svg.contains(
+            r#"<animate attributeName="fill-opacity" values="1;0.502;0.502" keyTimes="0;0.5;1" dur="2s" repeatCount="indefinite" fill="freeze" calcMode="spline" keySplines="0.4 0 0.2 1;0 0 1 1"/>"#
+        ));
+    }
+
+    #[test]
+    fn writes_font_settings_that_cover_all_the_text() {
+        let source = |runs: &str| {
+            format!(
+                r#"{{ "visuals": [{{ "id": "a", "text": "abcdef", "maxWidth": 80, "typography": {} }}] }}"#,
+                runs
+            )
+        };
+        let svg = export(
+            &source(
+                r#"[{ "features": { "smcp": true, "liga": false }, "axes": { "wght": 650 } }]"#,
+            ),
+            &[],
+            &OPTIONS,
+        );
+        assert!(svg.contains(
+            r#" style="font-feature-settings: 'liga' 0, 'smcp' 1; font-variation-settings: 'wght' 650""#
+        ));
+        let svg = export(
+            &source(r#"[{ "start": 2, "features": { "smcp": true } }]"#),
+            &[],
+            &OPTIONS,
+        );
+        assert!(!svg.contains("style="));
+    }
+
+    #[test]
+    fn writes_text_on_a_path() {
+        let svg = export(
+            r#"{ "visuals": [{
+                "id": "a",
+                "text": "along",
+                "fontSize": 10,
+                "path": {
+                    "bezier": [[0, 0], [10, 0], [20, 0], [30, 0]],
+                    "alignment": "center",
+                    "startOffset": 2
+                }
+            }] }"#,
+            &[],
+            &OPTIONS,
+        );
+        // The path is moved into the visual's frame, which leaves room for
+        // the glyphs around it.
+        assert!(svg.contains(r#"<defs><path id="a-path" d="M15 15L15.938 15"#));
+        assert!(svg.contains(r#"L45 15"/></defs>"#));
+        assert!(svg.contains(r#" text-anchor="middle""#));
+        assert!(svg.contains(r##"<textPath href="#a-path" startOffset="17">along</textPath>"##));
+    }
+
+    #[test]
+    fn formats_numbers_and_escapes_text() {
+        assert_eq!(number(1.0), "1");
+        assert_eq!(number(0.12345), "0.123");
+        assert_eq!(number(-0.0001), "0");
+        assert_eq!(number(-2.5), "-2.5");
+        assert_eq!(number(100.0), "100");
+        assert_eq!(
+            escape(r#"<a href="x">&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&apos;&lt;/a&gt;"
+        );
+
+        let mut path = PathBuilder::default();
+        path.move_to(Vector2::new(0.5, -1.0));
+        path.cubic_to(
+            Vector2::new(1.0, 2.0),
+            Vector2::new(3.0, 4.0),
+            Vector2::new(5.0, 6.0),
+        );
+        path.close();
+        path.rect(0.0, 0.0, 2.0, 1.0);
+        assert_eq!(path.finish(), "M0.5 -1C1 2 3 4 5 6ZM0 0L2 0L2 1L0 1Z");
+    }
+}
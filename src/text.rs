@@ -0,0 +1,399 @@
+use std::ops::Range;
+
+use tracing::{debug, instrument};
+use windows::{
+    Graphics::SizeInt32,
+    Win32::Graphics::DirectWrite::{
+        DWRITE_CLUSTER_METRICS, DWRITE_FACTORY_TYPE_SHARED, DWRITE_FLOW_DIRECTION_RIGHT_TO_LEFT,
+        DWRITE_FONT_AXIS_TAG, DWRITE_FONT_AXIS_VALUE, DWRITE_FONT_FEATURE, DWRITE_FONT_FEATURE_TAG,
+        DWRITE_FONT_STRETCH, DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE,
+        DWRITE_FONT_STYLE_NORMAL, DWRITE_FONT_WEIGHT, DWRITE_FONT_WEIGHT_NORMAL,
+        DWRITE_HIT_TEST_METRICS, DWRITE_LINE_METRICS, DWRITE_READING_DIRECTION_RIGHT_TO_LEFT,
+        DWRITE_READING_DIRECTION_TOP_TO_BOTTOM, DWRITE_TEXT_METRICS, DWRITE_TEXT_RANGE,
+        DWRITE_WORD_WRAPPING_NO_WRAP, DWriteCreateFactory, IDWriteFactory, IDWriteFontCollection,
+        IDWriteTextFormat, IDWriteTextLayout, IDWriteTextLayout4,
+    },
+    core::{HSTRING, Interface, Result},
+};
+use windows_numerics::Vector2;
+
+use crate::{
+    metrics::{Cluster, LineBox, Overhang, TextMetrics},
+    scene::TextVisual,
+    svg::TextLine,
+    typography::{
+        TypographyRun, font_stretch, font_style, font_weight, resolve_axes, resolve_runs,
+    },
+};
+
+pub struct TextFactory {
+    dwrite_factory: IDWriteFactory,
+    font_collection: IDWriteFontCollection,
+}
+
+impl TextFactory {
+    pub fn new() -> Result<Self> {
+        let dwrite_factory: IDWriteFactory =
+            unsafe { DWriteCreateFactory(DWRITE_FACTORY_TYPE_SHARED)? };
+        let font_collection: IDWriteFontCollection = unsafe {
+            let mut font_collection = None;
+            dwrite_factory.GetSystemFontCollection(&mut font_collection, false)?;
+            font_collection.unwrap()
+        };
+        Ok(Self {
+            dwrite_factory,
+            font_collection,
+        })
+    }
+
+    pub fn text_format<'a>(
+        &'a self,
+        font_family: &'a str,
+        font_size: f32,
+    ) -> TextFormatBuilder<'a> {
+        TextFormatBuilder {
+            factory: self,
+            font_family,
+            font_size,
+            locale: "en-us",
+            right_to_left: false,
+            vertical: false,
+        }
+    }
+
+    // Right to left and vertical layouts are shrunk to fit their text, since
+    // their text starts from the far edge of the layout box and visuals are
+    // sized from the near one. Vertical text is laid out in columns as long
+    // as the visual's max width.
+    #[instrument(
+        level = "debug",
+        skip_all,
+        fields(id = %visual.id, font_family = %visual.font_family, font_size = visual.font_size)
+    )]
+    pub fn create_text_layout(&self, visual: &TextVisual) -> Result<IDWriteTextLayout> {
+        let right_to_left = visual.direction.is_right_to_left(&visual.text);
+        let text_format = self
+            .text_format(&visual.font_family, visual.font_size)
+            .locale(&visual.locale)
+            .right_to_left(right_to_left)
+            .vertical(visual.vertical)
+            .build()?;
+        let max_height = if visual.vertical {
+            visual.max_width
+        } else {
+            0.0
+        };
+        unsafe {
+            let text_data: Vec<_> = visual.layout_text().encode_utf16().collect();
+            let text_layout = self.dwrite_factory.CreateTextLayout(
+                &text_data,
+                &text_format,
+                visual.max_width,
+                max_height,
+            )?;
+            if visual.path.is_some() {
+                text_layout.SetWordWrapping(DWRITE_WORD_WRAPPING_NO_WRAP)?;
+            }
+            self.set_typography(&text_layout, &visual.text, &visual.typography)?;
+            if right_to_left || visual.vertical {
+                let mut metrics = DWRITE_TEXT_METRICS::default();
+                text_layout.GetMetrics(&mut metrics)?;
+                text_layout.SetMaxWidth(metrics.widthIncludingTrailingWhitespace)?;
+            }
+            Ok(text_layout)
+        }
+    }
+
+    // Runs are in chars and layouts count UTF-16 code units. Axes pick the
+    // closest weight, stretch and style too, so families of separate faces
+    // follow them as well as variable fonts do. Layouts from before
+    // Windows 10 1809 can't take axis values and only get those.
+    fn set_typography(
+        &self,
+        text_layout: &IDWriteTextLayout,
+        text: &str,
+        runs: &[TypographyRun],
+    ) -> Result<()> {
+        let mut offsets = vec![0];
+        offsets.extend(text.chars().scan(0, |offset, character| {
+            *offset += character.len_utf16() as u32;
+            Some(*offset)
+        }));
+        let text_range = |range: Range<usize>| DWRITE_TEXT_RANGE {
+            startPosition: offsets[range.start],
+            length: offsets[range.end] - offsets[range.start],
+        };
+        for (range, features) in resolve_runs(runs, offsets.len() - 1) {
+            if features.is_empty() {
+                continue;
+            }
+            unsafe {
+                let typography = self.dwrite_factory.CreateTypography()?;
+                for feature in features {
+                    typography.AddFontFeature(DWRITE_FONT_FEATURE {
+                        nameTag: DWRITE_FONT_FEATURE_TAG(feature.tag.0),
+                        parameter: feature.value,
+                    })?;
+                }
+                text_layout.SetTypography(&typography, text_range(range))?;
+            }
+        }
+        let text_layout4 = text_layout.cast::<IDWriteTextLayout4>().ok();
+        for (range, axes) in resolve_axes(runs, offsets.len() - 1) {
+            if axes.is_empty() {
+                continue;
+            }
+            let text_range = text_range(range);
+            unsafe {
+                text_layout
+                    .SetFontWeight(DWRITE_FONT_WEIGHT(font_weight(&axes) as i32), text_range)?;
+                text_layout
+                    .SetFontStretch(DWRITE_FONT_STRETCH(font_stretch(&axes) as i32), text_range)?;
+                text_layout
+                    .SetFontStyle(DWRITE_FONT_STYLE(font_style(&axes) as i32), text_range)?;
+                if let Some(text_layout4) = &text_layout4 {
+                    let values: Vec<DWRITE_FONT_AXIS_VALUE> = axes
+                        .iter()
+                        .map(|axis| DWRITE_FONT_AXIS_VALUE {
+                            axisTag: DWRITE_FONT_AXIS_TAG(axis.tag.0),
+                            value: axis.value,
+                        })
+                        .collect();
+                    text_layout4.SetFontAxisValues(&values, text_range)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct TextFormatBuilder<'a> {
+    factory: &'a TextFactory,
+    font_family: &'a str,
+    font_size: f32,
+    locale: &'a str,
+    right_to_left: bool,
+    vertical: bool,
+}
+
+impl<'a> TextFormatBuilder<'a> {
+    pub fn locale(mut self, locale: &'a str) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn right_to_left(mut self, right_to_left: bool) -> Self {
+        self.right_to_left = right_to_left;
+        self
+    }
+
+    // Lines read top to bottom and follow each other right to left.
+    pub fn vertical(mut self, vertical: bool) -> Self {
+        self.vertical = vertical;
+        self
+    }
+
+    pub fn build(self) -> Result<IDWriteTextFormat> {
+        unsafe {
+            let font_name = HSTRING::from(self.font_family);
+            let locale = HSTRING::from(self.locale);
+            let text_format = self.factory.dwrite_factory.CreateTextFormat(
+                &font_name,
+                &self.factory.font_collection,
+                DWRITE_FONT_WEIGHT_NORMAL,
+                DWRITE_FONT_STYLE_NORMAL,
+                DWRITE_FONT_STRETCH_NORMAL,
+                self.font_size,
+                &locale,
+            )?;
+            if self.vertical {
+                text_format.SetReadingDirection(DWRITE_READING_DIRECTION_TOP_TO_BOTTOM)?;
+                text_format.SetFlowDirection(DWRITE_FLOW_DIRECTION_RIGHT_TO_LEFT)?;
+            } else if self.right_to_left {
+                text_format.SetReadingDirection(DWRITE_READING_DIRECTION_RIGHT_TO_LEFT)?;
+            }
+            Ok(text_format)
+        }
+    }
+}
+
+// The size of the surface needed to hold the layout, including any overhang.
+pub fn text_size(text_layout: &IDWriteTextLayout) -> Result<SizeInt32> {
+    let metrics = unsafe { text_layout.GetOverhangMetrics()? };
+    let max_width = unsafe { text_layout.GetMaxWidth() };
+    let max_height = unsafe { text_layout.GetMaxHeight() };
+    Ok(SizeInt32 {
+        Width: (metrics.right + max_width + -metrics.left) as i32,
+        Height: (metrics.bottom + max_height + -metrics.top) as i32,
+    })
+}
+
+fn line_metrics(text_layout: &IDWriteTextLayout) -> Result<Vec<DWRITE_LINE_METRICS>> {
+    unsafe {
+        let mut line_count = 0;
+        // The first call only reports how many lines there are.
+        let _ = text_layout.GetLineMetrics(None, &mut line_count);
+        let mut line_metrics = vec![DWRITE_LINE_METRICS::default(); line_count as usize];
+        text_layout.GetLineMetrics(Some(&mut line_metrics), &mut line_count)?;
+        Ok(line_metrics)
+    }
+}
+
+fn is_vertical(text_layout: &IDWriteTextLayout) -> bool {
+    unsafe { text_layout.GetReadingDirection() == DWRITE_READING_DIRECTION_TOP_TO_BOTTOM }
+}
+
+// The text of each line in the layout along with where it starts: the left
+// edge of its baseline, the right edge for right to left text, or the top
+// of its column's baseline for vertical text.
+pub fn text_lines(text: &str, text_layout: &IDWriteTextLayout) -> Result<Vec<TextLine>> {
+    let line_metrics = line_metrics(text_layout)?;
+    let vertical = is_vertical(text_layout);
+    let right_to_left =
+        unsafe { text_layout.GetReadingDirection() == DWRITE_READING_DIRECTION_RIGHT_TO_LEFT };
+    let layout_width = unsafe { text_layout.GetMaxWidth() };
+
+    let text: Vec<u16> = text.encode_utf16().collect();
+    let mut lines = Vec::with_capacity(line_metrics.len());
+    let mut start = 0;
+    let mut top = 0.0;
+    for metrics in line_metrics {
+        let end = (start + metrics.length as usize).min(text.len());
+        let content_end = end
+            .saturating_sub(metrics.newlineLength as usize)
+            .max(start);
+        let (x, baseline) = if vertical {
+            (layout_width - top - metrics.baseline, 0.0)
+        } else if right_to_left {
+            (layout_width, top + metrics.baseline)
+        } else {
+            (0.0, top + metrics.baseline)
+        };
+        lines.push(TextLine {
+            text: String::from_utf16_lossy(&text[start..content_end]),
+            x,
+            baseline,
+        });
+        start = end;
+        top += metrics.height;
+    }
+    Ok(lines)
+}
+
+// Vertical lines are columns placed from the right edge of the layout, so
+// their tops and heights come from where their text is.
+pub fn text_metrics(text_layout: &IDWriteTextLayout) -> Result<TextMetrics> {
+    let overhang = unsafe { text_layout.GetOverhangMetrics()? };
+    let vertical = is_vertical(text_layout);
+    let layout_width = unsafe { text_layout.GetMaxWidth() };
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut top = 0.0;
+    for metrics in line_metrics(text_layout)? {
+        let length = metrics.length - metrics.trailingWhitespaceLength;
+        let [left, text_top, right, text_bottom] = text_range_bounds(text_layout, start, length)?;
+        let (left, line_top, width, height) = if vertical {
+            (
+                layout_width - top - metrics.height,
+                text_top,
+                metrics.height,
+                text_bottom - text_top,
+            )
+        } else {
+            (left, top, right - left, metrics.height)
+        };
+        lines.push(LineBox {
+            left,
+            top: line_top,
+            width,
+            height,
+            baseline: metrics.baseline,
+            length: metrics.length,
+            trailing_whitespace_length: metrics.trailingWhitespaceLength,
+            newline_length: metrics.newlineLength,
+            is_trimmed: metrics.isTrimmed.as_bool(),
+        });
+        start += metrics.length;
+        top += metrics.height;
+    }
+    let clusters = unsafe {
+        let mut cluster_count = 0;
+        let _ = text_layout.GetClusterMetrics(None, &mut cluster_count);
+        let mut clusters = vec![DWRITE_CLUSTER_METRICS::default(); cluster_count as usize];
+        text_layout.GetClusterMetrics(Some(&mut clusters), &mut cluster_count)?;
+        clusters
+    };
+    debug!(
+        lines = lines.len(),
+        clusters = clusters.len(),
+        left = overhang.left,
+        top = overhang.top,
+        right = overhang.right,
+        bottom = overhang.bottom,
+        "Measured text layout"
+    );
+    let metrics = TextMetrics::new(
+        unsafe { Vector2::new(layout_width, text_layout.GetMaxHeight()) },
+        Overhang {
+            left: overhang.left,
+            top: overhang.top,
+            right: overhang.right,
+            bottom: overhang.bottom,
+        },
+        lines,
+        clusters
+            .iter()
+            .map(|cluster| {
+                // The flags are packed into a bitfield in the order they're
+                // declared.
+                let flag = |bit: u16| cluster._bitfield & (1 << bit) != 0;
+                Cluster {
+                    width: cluster.width,
+                    length: cluster.length as u32,
+                    can_wrap_line_after: flag(0),
+                    is_whitespace: flag(1),
+                    is_newline: flag(2),
+                    is_soft_hyphen: flag(3),
+                    is_right_to_left: flag(4),
+                }
+            })
+            .collect(),
+    );
+    Ok(TextMetrics {
+        is_vertical: vertical,
+        ..metrics
+    })
+}
+
+// The left, top, right and bottom edges of a range of text on a single
+// line.
+fn text_range_bounds(text_layout: &IDWriteTextLayout, start: u32, length: u32) -> Result<[f32; 4]> {
+    if length == 0 {
+        return Ok([0.0; 4]);
+    }
+    let ranges = unsafe {
+        let mut range_count = 0;
+        let _ = text_layout.HitTestTextRange(start, length, 0.0, 0.0, None, &mut range_count);
+        let mut ranges = vec![DWRITE_HIT_TEST_METRICS::default(); range_count as usize];
+        text_layout.HitTestTextRange(
+            start,
+            length,
+            0.0,
+            0.0,
+            Some(&mut ranges),
+            &mut range_count,
+        )?;
+        ranges
+    };
+    Ok(ranges.iter().fold(
+        [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+        |[left, top, right, bottom], range| {
+            [
+                left.min(range.left),
+                top.min(range.top),
+                right.max(range.left + range.width),
+                bottom.max(range.top + range.height),
+            ]
+        },
+    ))
+}
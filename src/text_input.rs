@@ -0,0 +1,457 @@
+use std::ops::Range;
+
+use tracing::{debug, trace};
+
+use windows::{
+    Win32::{
+        Graphics::DirectWrite::{DWRITE_HIT_TEST_METRICS, IDWriteTextLayout},
+        UI::{
+            Input::KeyboardAndMouse::{
+                GetKeyState, ReleaseCapture, SetCapture, VIRTUAL_KEY, VK_A, VK_BACK, VK_C,
+                VK_CONTROL, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_HOME, VK_LEFT, VK_RIGHT,
+                VK_SHIFT, VK_TAB, VK_UP, VK_V, VK_X,
+            },
+            WindowsAndMessaging::{
+                MSG, WM_CHAR, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEMOVE,
+            },
+        },
+    },
+    core::{BOOL, Result},
+};
+use windows_numerics::Vector2;
+
+use crate::{
+    clipboard,
+    editing::{Caret, CaretLayout, Movement, TextEditor, byte_offset, utf16_offset},
+    hit_test::TextHit,
+    metrics::LineBox,
+    scene_host::SceneHost,
+    window::{ImeEvent, Window, mouse_position},
+};
+
+// Editing positions hit tested in a DirectWrite layout of 'text', with its
+// lines from the layout's metrics.
+struct LayoutCarets<'a> {
+    text: &'a str,
+    text_layout: &'a IDWriteTextLayout,
+    lines: &'a [LineBox],
+}
+
+impl LayoutCarets<'_> {
+    fn line_start(&self, line: usize) -> u32 {
+        self.lines[..line].iter().map(|line| line.length).sum()
+    }
+
+    fn line_of(&self, position: u32) -> usize {
+        let mut start = 0;
+        for (index, line) in self.lines.iter().enumerate() {
+            if position < start + line.length {
+                return index;
+            }
+            start += line.length;
+        }
+        self.lines.len().saturating_sub(1)
+    }
+}
+
+impl CaretLayout for LayoutCarets<'_> {
+    fn line_count(&self) -> usize {
+        self.lines.len().max(1)
+    }
+
+    fn caret(&self, position: usize) -> Caret {
+        let position = utf16_offset(self.text, position);
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut metrics = DWRITE_HIT_TEST_METRICS::default();
+        let _ = unsafe {
+            self.text_layout
+                .HitTestTextPosition(position, false, &mut x, &mut y, &mut metrics)
+        };
+        Caret {
+            line: self.line_of(position),
+            x,
+            top: metrics.top,
+            height: metrics.height,
+        }
+    }
+
+    fn line_range(&self, line: usize) -> Range<usize> {
+        let Some(line_box) = self.lines.get(line) else {
+            return self.text.len()..self.text.len();
+        };
+        let start = self.line_start(line);
+        let end = start + line_box.length - line_box.newline_length;
+        byte_offset(self.text, start)..byte_offset(self.text, end)
+    }
+
+    fn position_at(&self, line: usize, x: f32) -> usize {
+        let Some(line_box) = self.lines.get(line) else {
+            return self.text.len();
+        };
+        let mut is_trailing = BOOL::default();
+        let mut is_inside = BOOL::default();
+        let mut metrics = DWRITE_HIT_TEST_METRICS::default();
+        let _ = unsafe {
+            self.text_layout.HitTestPoint(
+                x,
+                line_box.top + line_box.height / 2.0,
+                &mut is_trailing,
+                &mut is_inside,
+                &mut metrics,
+            )
+        };
+        let position = if is_trailing.as_bool() {
+            metrics.textPosition + metrics.length
+        } else {
+            metrics.textPosition
+        };
+        let range = self.line_range(line);
+        byte_offset(self.text, position).clamp(range.start, range.end)
+    }
+
+    fn range_rects(&self, range: Range<usize>) -> Vec<[f32; 4]> {
+        let start = utf16_offset(self.text, range.start);
+        let length = utf16_offset(self.text, range.end) - start;
+        unsafe {
+            let mut range_count = 0;
+            let _ =
+                self.text_layout
+                    .HitTestTextRange(start, length, 0.0, 0.0, None, &mut range_count);
+            let mut ranges = vec![DWRITE_HIT_TEST_METRICS::default(); range_count as usize];
+            if self
+                .text_layout
+                .HitTestTextRange(start, length, 0.0, 0.0, Some(&mut ranges), &mut range_count)
+                .is_err()
+            {
+                return Vec::new();
+            }
+            ranges
+                .iter()
+                .map(|range| [range.left, range.top, range.width, range.height])
+                .collect()
+        }
+    }
+}
+
+// Keyboard, mouse and IME input for the editable visual with focus. Tab
+// moves focus between editable visuals and Escape drops it, giving keys
+// back to the window. Clicking into an editable visual focuses it and puts
+// the caret where it was clicked, and dragging selects.
+#[derive(Default)]
+pub struct TextInput {
+    focus: Option<(String, TextEditor)>,
+    // Characters outside the BMP come as two WM_CHAR messages.
+    high_surrogate: Option<u16>,
+    is_selecting: bool,
+}
+
+impl TextInput {
+    // Focuses the editable visual after the focused one, or the first.
+    pub fn focus_next(&mut self, window: &mut Window, host: &mut SceneHost) -> Result<()> {
+        let editable: Vec<&str> = host
+            .scene()
+            .visuals
+            .iter()
+            .filter(|visual| visual.editable)
+            .map(|visual| visual.id.as_str())
+            .collect();
+        if editable.is_empty() {
+            return Ok(());
+        }
+        let next = match &self.focus {
+            Some((id, _)) => editable
+                .iter()
+                .position(|editable| editable == id)
+                .map_or(0, |index| (index + 1) % editable.len()),
+            None => 0,
+        };
+        let next = editable[next].to_owned();
+        self.set_focus(Some(next), window, host)
+    }
+
+    // Returns whether the message was used. While a visual has focus every
+    // key press goes to it.
+    pub fn handle_message(
+        &mut self,
+        message: &MSG,
+        window: &mut Window,
+        host: &mut SceneHost,
+    ) -> Result<bool> {
+        match message.message {
+            WM_KEYDOWN => self.key_down(VIRTUAL_KEY(message.wParam.0 as u16), window, host),
+            WM_CHAR if self.focus.is_some() => {
+                self.character(message.wParam.0 as u16, host)?;
+                Ok(true)
+            }
+            WM_LBUTTONDOWN => self.mouse_down(mouse_position(message.lParam), window, host),
+            WM_MOUSEMOVE if self.is_selecting => {
+                let Some((id, _)) = &self.focus else {
+                    return Ok(true);
+                };
+                let point = mouse_position(message.lParam);
+                if let Some(hit) = host.hit_test_text(id, point, window.size()?) {
+                    self.place_caret(hit, true, host)?;
+                }
+                Ok(true)
+            }
+            WM_LBUTTONUP if self.is_selecting => {
+                self.is_selecting = false;
+                let _ = unsafe { ReleaseCapture() };
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn handle_ime_event(&mut self, event: ImeEvent, host: &mut SceneHost) -> Result<()> {
+        let Some((_, editor)) = &mut self.focus else {
+            return Ok(());
+        };
+        match event {
+            ImeEvent::Composition { text, caret } => {
+                editor.set_composition(&text, byte_offset(&text, caret))
+            }
+            ImeEvent::Commit(text) => editor.commit_composition(&text),
+            ImeEvent::End => editor.cancel_composition(),
+        }
+        self.changed(host)
+    }
+
+    // Catches up with a reloaded scene. Text changed in the file replaces
+    // what was typed, and focus is dropped from a visual that's gone or
+    // can't be edited any more.
+    pub fn scene_changed(&mut self, window: &mut Window, host: &mut SceneHost) -> Result<()> {
+        let Some((id, editor)) = &mut self.focus else {
+            return Ok(());
+        };
+        match host.scene().visual(id) {
+            Some(visual) if visual.editable => {
+                if visual.text != editor.text() {
+                    editor.set_text(visual.text.clone());
+                }
+                self.show(host)
+            }
+            _ => {
+                let id = id.clone();
+                self.focus = None;
+                window.set_text_input(false);
+                window.take_ime_events();
+                host.show_edit_shapes(&id, None)
+            }
+        }
+    }
+
+    // Anything still being composed is dropped when focus moves.
+    fn set_focus(
+        &mut self,
+        id: Option<String>,
+        window: &mut Window,
+        host: &mut SceneHost,
+    ) -> Result<()> {
+        window.set_text_input(false);
+        window.take_ime_events();
+        if let Some((id, mut editor)) = self.focus.take() {
+            if editor.composition().is_some() {
+                editor.cancel_composition();
+                host.set_text(&id, editor.text())?;
+            }
+            host.show_edit_shapes(&id, None)?;
+        }
+        self.high_surrogate = None;
+        let Some(text) = id
+            .as_ref()
+            .and_then(|id| host.scene().visual(id))
+            .map(|visual| visual.text.clone())
+        else {
+            return Ok(());
+        };
+        window.set_text_input(true);
+        self.focus = id.map(|id| (id, TextEditor::new(text)));
+        self.show(host)
+    }
+
+    // Clicking into text that's being composed drops the composition, like
+    // moving focus does. Shift+click extends the selection.
+    fn mouse_down(
+        &mut self,
+        point: Vector2,
+        window: &mut Window,
+        host: &mut SceneHost,
+    ) -> Result<bool> {
+        let Some((id, hit)) = host.hit_test(point, window.size()?) else {
+            return Ok(false);
+        };
+        debug!(id = id.as_str(), ?hit, "Clicked text");
+        if !host
+            .scene()
+            .visual(&id)
+            .is_some_and(|visual| visual.editable)
+        {
+            return Ok(false);
+        }
+        let is_focused = matches!(
+            &self.focus,
+            Some((focused, editor)) if *focused == id && editor.composition().is_none()
+        );
+        if !is_focused {
+            self.set_focus(Some(id), window, host)?;
+        }
+        self.place_caret(hit, is_key_down(VK_SHIFT), host)?;
+        self.is_selecting = true;
+        unsafe { SetCapture(window.handle()) };
+        Ok(true)
+    }
+
+    fn place_caret(&mut self, hit: TextHit, extend: bool, host: &mut SceneHost) -> Result<()> {
+        let Some((_, editor)) = &mut self.focus else {
+            return Ok(());
+        };
+        let text = editor.text();
+        let caret = text
+            .char_indices()
+            .nth(hit.caret())
+            .map_or(text.len(), |(offset, _)| offset);
+        editor.set_caret(caret, extend);
+        self.show(host)
+    }
+
+    fn key_down(
+        &mut self,
+        key: VIRTUAL_KEY,
+        window: &mut Window,
+        host: &mut SceneHost,
+    ) -> Result<bool> {
+        if key == VK_TAB {
+            if !host.scene().visuals.iter().any(|visual| visual.editable) {
+                return Ok(false);
+            }
+            self.focus_next(window, host)?;
+            return Ok(true);
+        }
+        if key == VK_ESCAPE && self.focus.is_some() {
+            self.set_focus(None, window, host)?;
+            return Ok(true);
+        }
+        let Some((id, editor)) = &mut self.focus else {
+            return Ok(false);
+        };
+        let Some((text_layout, metrics)) = host.text_layout(id)? else {
+            return Ok(true);
+        };
+        let text = editor.text().to_owned();
+        let layout = LayoutCarets {
+            text: &text,
+            text_layout: &text_layout,
+            lines: &metrics.lines,
+        };
+        let control = is_key_down(VK_CONTROL);
+        let shift = is_key_down(VK_SHIFT);
+        let by_word = |word, grapheme| if control { word } else { grapheme };
+        match key {
+            VK_LEFT => editor.move_caret(
+                by_word(Movement::PreviousWord, Movement::PreviousGrapheme),
+                shift,
+                &layout,
+            ),
+            VK_RIGHT => editor.move_caret(
+                by_word(Movement::NextWord, Movement::NextGrapheme),
+                shift,
+                &layout,
+            ),
+            VK_UP => editor.move_caret(Movement::PreviousLine, shift, &layout),
+            VK_DOWN => editor.move_caret(Movement::NextLine, shift, &layout),
+            VK_HOME => editor.move_caret(
+                by_word(Movement::TextStart, Movement::LineStart),
+                shift,
+                &layout,
+            ),
+            VK_END => editor.move_caret(
+                by_word(Movement::TextEnd, Movement::LineEnd),
+                shift,
+                &layout,
+            ),
+            VK_BACK => editor.delete(
+                by_word(Movement::PreviousWord, Movement::PreviousGrapheme),
+                &layout,
+            ),
+            VK_DELETE => {
+                editor.delete(by_word(Movement::NextWord, Movement::NextGrapheme), &layout)
+            }
+            VK_A if control => editor.select_all(),
+            VK_C | VK_X if control => {
+                if !editor.selection().is_empty() {
+                    clipboard::set_text(window.handle(), editor.selected_text())?;
+                    if key == VK_X {
+                        editor.insert("");
+                    }
+                }
+            }
+            VK_V if control => {
+                if let Some(text) = clipboard::get_text(window.handle())? {
+                    editor.insert(&text.replace("\r\n", "\n"));
+                }
+            }
+            // Typed characters come as WM_CHAR.
+            _ => return Ok(true),
+        }
+        self.changed(host)?;
+        Ok(true)
+    }
+
+    fn character(&mut self, unit: u16, host: &mut SceneHost) -> Result<()> {
+        let Some((_, editor)) = &mut self.focus else {
+            return Ok(());
+        };
+        let text = match unit {
+            0xD800..=0xDBFF => {
+                self.high_surrogate = Some(unit);
+                return Ok(());
+            }
+            0xDC00..=0xDFFF => match self.high_surrogate.take() {
+                Some(high) => String::from_utf16_lossy(&[high, unit]),
+                None => return Ok(()),
+            },
+            0x0D => "\n".to_owned(),
+            // Other control characters come from keys already handled on
+            // WM_KEYDOWN, like Backspace, Tab and Ctrl+A.
+            0x00..=0x1F | 0x7F => return Ok(()),
+            _ => String::from_utf16_lossy(&[unit]),
+        };
+        editor.insert(&text);
+        self.changed(host)
+    }
+
+    // Shows the edited text and the caret and selection in it.
+    fn changed(&mut self, host: &mut SceneHost) -> Result<()> {
+        if let Some((id, editor)) = &self.focus {
+            host.set_text(id, editor.text())?;
+        }
+        self.show(host)
+    }
+
+    fn show(&mut self, host: &mut SceneHost) -> Result<()> {
+        let Some((id, editor)) = &self.focus else {
+            return Ok(());
+        };
+        let Some((text_layout, metrics)) = host.text_layout(id)? else {
+            return Ok(());
+        };
+        trace!(
+            id = id.as_str(),
+            caret = editor.caret(),
+            selection = ?editor.selection(),
+            composition = ?editor.composition(),
+            "Showing edit shapes"
+        );
+        let shapes = editor.shapes(&LayoutCarets {
+            text: editor.text(),
+            text_layout: &text_layout,
+            lines: &metrics.lines,
+        });
+        host.show_edit_shapes(id, Some(&shapes))
+    }
+}
+
+fn is_key_down(key: VIRTUAL_KEY) -> bool {
+    unsafe { GetKeyState(key.0 as i32) < 0 }
+}
@@ -0,0 +1,595 @@
+use windows_numerics::{Matrix3x2, Vector2};
+
+// The curve text follows. Angles are in degrees, clockwise from the
+// positive x axis since y points down, so an arc from 180 sweeping 180
+// runs over the top of its circle.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathShape {
+    Arc {
+        center: Vector2,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+    },
+    Circle {
+        center: Vector2,
+        radius: f32,
+        start_angle: f32,
+    },
+    // A start point followed by the two control points and end point of
+    // each cubic segment, like SVG's "C" commands.
+    Bezier(Vec<Vector2>),
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PathAlignment {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+// Lays a visual's text out along a path instead of in lines. The text is
+// moved 'start_offset' along the path from where 'alignment' puts it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextPath {
+    pub shape: PathShape,
+    pub start_offset: f32,
+    pub alignment: PathAlignment,
+}
+
+// Where a glyph goes: its baseline origin and how far it's turned to
+// follow the path.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlyphPlacement {
+    pub position: Vector2,
+    pub angle_in_degrees: f32,
+}
+
+impl GlyphPlacement {
+    // Maps the glyph's own coordinates, with its baseline origin at zero,
+    // to the path's.
+    pub fn matrix(&self) -> Matrix3x2 {
+        let (sin, cos) = self.angle_in_degrees.to_radians().sin_cos();
+        Matrix3x2 {
+            M11: cos,
+            M12: sin,
+            M21: -sin,
+            M22: cos,
+            M31: self.position.X,
+            M32: self.position.Y,
+        }
+    }
+}
+
+// Curves are flattened finely enough that glyphs don't visibly jump from
+// one segment's direction to the next.
+const DEGREES_PER_SEGMENT: f32 = 2.0;
+const SEGMENTS_PER_CUBIC: usize = 32;
+
+impl TextPath {
+    // Text that runs off the end of a closed path carries on from its
+    // start.
+    pub fn is_closed(&self) -> bool {
+        matches!(self.shape, PathShape::Circle { .. })
+    }
+
+    // The path as a polyline.
+    pub fn points(&self) -> Vec<Vector2> {
+        match &self.shape {
+            PathShape::Arc {
+                center,
+                radius,
+                start_angle,
+                sweep_angle,
+            } => arc_points(*center, *radius, *start_angle, *sweep_angle),
+            PathShape::Circle {
+                center,
+                radius,
+                start_angle,
+            } => {
+                let mut points = arc_points(*center, *radius, *start_angle, 360.0);
+                // Close the circle exactly, whatever rounding did to the
+                // last point.
+                let first = points[0];
+                *points.last_mut().unwrap() = first;
+                points
+            }
+            PathShape::Bezier(points) => {
+                let Some(&start) = points.first() else {
+                    return Vec::new();
+                };
+                let mut flattened = vec![start];
+                for segment in points[1..].chunks_exact(3) {
+                    let from = *flattened.last().unwrap();
+                    flattened.extend((1..=SEGMENTS_PER_CUBIC).map(|step| {
+                        let t = step as f32 / SEGMENTS_PER_CUBIC as f32;
+                        cubic_point(from, segment[0], segment[1], segment[2], t)
+                    }));
+                }
+                flattened
+            }
+        }
+    }
+
+    // The flattened path moved into a box with room around it for glyphs
+    // to rise above and hang below it, and the size of that box. This is
+    // what a visual with text on a path is sized from.
+    pub fn frame(&self, font_size: f32) -> (Vec<Vector2>, Vector2) {
+        let points = self.points();
+        if points.is_empty() {
+            return (points, Vector2::zero());
+        }
+        // Accents and descenders reach a little past the font size either
+        // side of the baseline.
+        let margin = font_size * 1.5;
+        let (left, top, right, bottom) = points.iter().fold(
+            (f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+            |(left, top, right, bottom), point| {
+                (
+                    left.min(point.X),
+                    top.min(point.Y),
+                    right.max(point.X),
+                    bottom.max(point.Y),
+                )
+            },
+        );
+        let origin = Vector2::new(left - margin, top - margin);
+        let points = points
+            .iter()
+            .map(|point| Vector2::new(point.X - origin.X, point.Y - origin.Y))
+            .collect();
+        let size = Vector2::new(right - left + margin * 2.0, bottom - top + margin * 2.0);
+        (points, size)
+    }
+
+    // Places glyphs given as (x, advance) along a line of text on the
+    // polyline 'points'. Each glyph sits where the path passes through its
+    // middle, turned to the path's direction there. Glyphs that fall off
+    // either end of an open path are None.
+    pub fn place_glyphs(
+        &self,
+        points: &[Vector2],
+        glyphs: &[(f32, f32)],
+    ) -> Vec<Option<GlyphPlacement>> {
+        let mut lengths = Vec::with_capacity(points.len());
+        let mut length = 0.0;
+        for (index, point) in points.iter().enumerate() {
+            if index > 0 {
+                length += distance(points[index - 1], *point);
+            }
+            lengths.push(length);
+        }
+        if length <= 0.0 {
+            return vec![None; glyphs.len()];
+        }
+
+        let line_start = glyphs.iter().map(|&(x, _)| x).fold(f32::MAX, f32::min);
+        let line_end = glyphs
+            .iter()
+            .map(|&(x, advance)| x + advance)
+            .fold(f32::MIN, f32::max);
+        let line_width = line_end - line_start;
+        let start = self.start_offset
+            + match self.alignment {
+                PathAlignment::Start => 0.0,
+                PathAlignment::Center => (length - line_width) / 2.0,
+                PathAlignment::End => length - line_width,
+            };
+
+        glyphs
+            .iter()
+            .map(|&(x, advance)| {
+                let middle = start + x - line_start + advance / 2.0;
+                let middle = if self.is_closed() {
+                    middle.rem_euclid(length)
+                } else if (0.0..=length).contains(&middle) {
+                    middle
+                } else {
+                    return None;
+                };
+                let (point, direction) = point_at(points, &lengths, middle);
+                Some(GlyphPlacement {
+                    position: Vector2::new(
+                        point.X - direction.X * advance / 2.0,
+                        point.Y - direction.Y * advance / 2.0,
+                    ),
+                    angle_in_degrees: direction.Y.atan2(direction.X).to_degrees(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn arc_points(center: Vector2, radius: f32, start_angle: f32, sweep_angle: f32) -> Vec<Vector2> {
+    let segments = ((sweep_angle.abs() / DEGREES_PER_SEGMENT).ceil() as usize).max(1);
+    (0..=segments)
+        .map(|step| {
+            let angle = start_angle + sweep_angle * step as f32 / segments as f32;
+            let (sin, cos) = angle.to_radians().sin_cos();
+            Vector2::new(center.X + radius * cos, center.Y + radius * sin)
+        })
+        .collect()
+}
+
+fn cubic_point(
+    from: Vector2,
+    control_point1: Vector2,
+    control_point2: Vector2,
+    to: Vector2,
+    t: f32,
+) -> Vector2 {
+    let inverse = 1.0 - t;
+    let weights = [
+        inverse * inverse * inverse,
+        3.0 * inverse * inverse * t,
+        3.0 * inverse * t * t,
+        t * t * t,
+    ];
+    let points = [from, control_point1, control_point2, to];
+    points
+        .iter()
+        .zip(weights)
+        .fold(Vector2::zero(), |sum, (point, weight)| {
+            Vector2::new(sum.X + point.X * weight, sum.Y + point.Y * weight)
+        })
+}
+
+fn distance(from: Vector2, to: Vector2) -> f32 {
+    (to.X - from.X).hypot(to.Y - from.Y)
+}
+
+// The point 'length' along the polyline and the unit direction of the
+// segment it's on. 'lengths' are the distances to each point.
+fn point_at(points: &[Vector2], lengths: &[f32], length: f32) -> (Vector2, Vector2) {
+    // The first segment that ends at or past 'length' and isn't empty.
+    let end = lengths
+        .partition_point(|&end| end < length)
+        .clamp(1, points.len() - 1);
+    let end = (end..points.len())
+        .find(|&index| lengths[index] > lengths[index - 1])
+        .or_else(|| {
+            (1..end)
+                .rev()
+                .find(|&index| lengths[index] > lengths[index - 1])
+        })
+        .unwrap_or(end);
+    let (from, to) = (points[end - 1], points[end]);
+    let span = lengths[end] - lengths[end - 1];
+    let t = ((length - lengths[end - 1]) / span).clamp(0.0, 1.0);
+    let direction = Vector2::new((to.X - from.X) / span, (to.Y - from.Y) / span);
+    (
+        Vector2::new(from.X + (to.X - from.X) * t, from.Y + (to.Y - from.Y) * t),
+        direction,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(shape: PathShape, start_offset: f32, alignment: PathAlignment) -> TextPath {
+        TextPath {
+            shape,
+            start_offset,
+            alignment,
+        }
+    }
+
+    // An open path; place_glyphs is given its points directly.
+    fn open(start_offset: f32, alignment: PathAlignment) -> TextPath {
+        path(PathShape::Bezier(Vec::new()), start_offset, alignment)
+    }
+
+    fn circle() -> TextPath {
+        let shape = PathShape::Circle {
+            center: Vector2::new(0.0, 0.0),
+            radius: 50.0,
+            start_angle: 0.0,
+        };
+        path(shape, 0.0, PathAlignment::Start)
+    }
+
+    // The arc over the top of a circle, left to right.
+    fn top_arc() -> TextPath {
+        let shape = PathShape::Arc {
+            center: Vector2::new(100.0, 100.0),
+            radius: 50.0,
+            start_angle: 180.0,
+            sweep_angle: 180.0,
+        };
+        path(shape, 0.0, PathAlignment::Start)
+    }
+
+    fn line(to: Vector2) -> Vec<Vector2> {
+        vec![Vector2::zero(), to]
+    }
+
+    // Glyphs 'advance' wide, one after the other from x = 0.
+    fn glyphs(count: usize, advance: f32) -> Vec<(f32, f32)> {
+        (0..count)
+            .map(|index| (index as f32 * advance, advance))
+            .collect()
+    }
+
+    fn length(points: &[Vector2]) -> f32 {
+        points
+            .windows(2)
+            .map(|pair| distance(pair[0], pair[1]))
+            .sum()
+    }
+
+    // The glyphs' x positions, rounded to a thousandth.
+    fn xs(placements: &[Option<GlyphPlacement>]) -> Vec<Option<f32>> {
+        placements
+            .iter()
+            .map(|placement| {
+                placement.map(|placement| (placement.position.X * 1000.0).round() / 1000.0)
+            })
+            .collect()
+    }
+
+    fn assert_near(actual: Vector2, expected: Vector2) {
+        assert!(
+            (actual.X - expected.X).abs() < 1e-3 && (actual.Y - expected.Y).abs() < 1e-3,
+            "{actual:?} isn't {expected:?}"
+        );
+    }
+
+    #[test]
+    fn arcs_are_flattened_every_two_degrees() {
+        let center = Vector2::new(10.0, 20.0);
+        let points = arc_points(center, 5.0, 0.0, 90.0);
+        assert_eq!(points.len(), 46);
+        assert_near(points[0], Vector2::new(15.0, 20.0));
+        // Clockwise, since y points down.
+        assert_near(points[45], Vector2::new(10.0, 25.0));
+        for point in &points {
+            assert!((distance(center, *point) - 5.0).abs() < 1e-3);
+        }
+
+        let points = arc_points(center, 5.0, 0.0, -90.0);
+        assert_near(points[45], Vector2::new(10.0, 15.0));
+
+        assert_eq!(arc_points(center, 5.0, 0.0, 3.0).len(), 3);
+        assert_eq!(arc_points(center, 5.0, 30.0, 0.0).len(), 2);
+    }
+
+    #[test]
+    fn circles_close_exactly() {
+        let circle = circle();
+        assert!(circle.is_closed());
+        assert!(!top_arc().is_closed());
+        assert!(!open(0.0, PathAlignment::Start).is_closed());
+
+        let points = circle.points();
+        assert_eq!(points.len(), 181);
+        assert_eq!(points.first(), points.last());
+        assert!((length(&points) - 100.0 * std::f32::consts::PI).abs() < 0.1);
+    }
+
+    #[test]
+    fn cubic_points_run_from_start_to_end() {
+        let from = Vector2::zero();
+        let control_point1 = Vector2::new(0.0, 10.0);
+        let control_point2 = Vector2::new(10.0, 10.0);
+        let to = Vector2::new(10.0, 0.0);
+        let point = |t| cubic_point(from, control_point1, control_point2, to, t);
+        assert_near(point(0.0), from);
+        assert_near(point(1.0), to);
+        assert_near(point(0.5), Vector2::new(5.0, 7.5));
+        assert_near(point(0.25), Vector2::new(1.5625, 5.625));
+    }
+
+    #[test]
+    fn bezier_paths_are_flattened_per_segment() {
+        assert!(
+            path(PathShape::Bezier(Vec::new()), 0.0, PathAlignment::Start)
+                .points()
+                .is_empty()
+        );
+
+        let start = Vector2::new(1.0, 2.0);
+        let single = path(PathShape::Bezier(vec![start]), 0.0, PathAlignment::Start);
+        assert_eq!(single.points(), [start]);
+
+        let end = Vector2::new(30.0, 0.0);
+        let mut points = vec![
+            start,
+            Vector2::new(10.0, 10.0),
+            Vector2::new(20.0, 10.0),
+            end,
+        ];
+        let bezier = path(PathShape::Bezier(points.clone()), 0.0, PathAlignment::Start);
+        let flattened = bezier.points();
+        assert_eq!(flattened.len(), SEGMENTS_PER_CUBIC + 1);
+        assert_eq!(flattened[0], start);
+        assert_near(*flattened.last().unwrap(), end);
+
+        // Each segment carries on from where the last one ended, and
+        // points that don't make up a whole segment are left out.
+        points.extend([Vector2::new(40.0, -10.0), Vector2::new(50.0, -10.0)]);
+        let partial = path(PathShape::Bezier(points.clone()), 0.0, PathAlignment::Start);
+        assert_eq!(partial.points().len(), SEGMENTS_PER_CUBIC + 1);
+        points.push(Vector2::new(60.0, 0.0));
+        let two = path(PathShape::Bezier(points), 0.0, PathAlignment::Start);
+        let flattened = two.points();
+        assert_eq!(flattened.len(), SEGMENTS_PER_CUBIC * 2 + 1);
+        assert_near(flattened[SEGMENTS_PER_CUBIC], end);
+        assert_near(*flattened.last().unwrap(), Vector2::new(60.0, 0.0));
+    }
+
+    #[test]
+    fn points_are_found_along_the_polyline() {
+        let points = [
+            Vector2::zero(),
+            Vector2::new(10.0, 0.0),
+            Vector2::new(10.0, 10.0),
+        ];
+        let lengths = [0.0, 10.0, 20.0];
+        let at = |length| point_at(&points, &lengths, length);
+        assert_eq!(at(5.0), (Vector2::new(5.0, 0.0), Vector2::new(1.0, 0.0)));
+        assert_eq!(at(15.0), (Vector2::new(10.0, 5.0), Vector2::new(0.0, 1.0)));
+        // A corner belongs to the segment that ends there.
+        assert_eq!(at(10.0), (Vector2::new(10.0, 0.0), Vector2::new(1.0, 0.0)));
+        assert_eq!(at(0.0), (Vector2::zero(), Vector2::new(1.0, 0.0)));
+        assert_eq!(at(20.0), (Vector2::new(10.0, 10.0), Vector2::new(0.0, 1.0)));
+    }
+
+    #[test]
+    fn empty_segments_are_skipped() {
+        let points = [Vector2::zero(), Vector2::zero(), Vector2::new(10.0, 0.0)];
+        let lengths = [0.0, 0.0, 10.0];
+        assert_eq!(
+            point_at(&points, &lengths, 0.0),
+            (Vector2::zero(), Vector2::new(1.0, 0.0))
+        );
+
+        let points = [
+            Vector2::zero(),
+            Vector2::new(0.0, 10.0),
+            Vector2::new(0.0, 10.0),
+        ];
+        let lengths = [0.0, 10.0, 10.0];
+        assert_eq!(
+            point_at(&points, &lengths, 10.0),
+            (Vector2::new(0.0, 10.0), Vector2::new(0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn frames_leave_room_around_the_path() {
+        let (points, size) = top_arc().frame(10.0);
+        // The arc spans 50 to 150 across and 50 to 100 down, with 15 of
+        // margin all around.
+        assert_near(size, Vector2::new(130.0, 80.0));
+        assert_near(points[0], Vector2::new(15.0, 65.0));
+        assert_near(points[points.len() / 2], Vector2::new(65.0, 15.0));
+        assert_near(*points.last().unwrap(), Vector2::new(115.0, 65.0));
+
+        let (points, size) = open(0.0, PathAlignment::Start).frame(10.0);
+        assert!(points.is_empty());
+        assert_eq!(size, Vector2::zero());
+    }
+
+    #[test]
+    fn glyphs_are_placed_by_alignment() {
+        let points = line(Vector2::new(100.0, 0.0));
+        let place = |alignment| xs(&open(0.0, alignment).place_glyphs(&points, &glyphs(3, 10.0)));
+        assert_eq!(
+            place(PathAlignment::Start),
+            [Some(0.0), Some(10.0), Some(20.0)]
+        );
+        assert_eq!(
+            place(PathAlignment::Center),
+            [Some(35.0), Some(45.0), Some(55.0)]
+        );
+        assert_eq!(
+            place(PathAlignment::End),
+            [Some(70.0), Some(80.0), Some(90.0)]
+        );
+    }
+
+    #[test]
+    fn start_offsets_move_text_from_where_its_aligned() {
+        let points = line(Vector2::new(100.0, 0.0));
+        let place = |start_offset, alignment| {
+            xs(&open(start_offset, alignment).place_glyphs(&points, &glyphs(2, 10.0)))
+        };
+        assert_eq!(place(5.0, PathAlignment::Start), [Some(5.0), Some(15.0)]);
+        assert_eq!(place(5.0, PathAlignment::Center), [Some(45.0), Some(55.0)]);
+        assert_eq!(place(-5.0, PathAlignment::End), [Some(75.0), Some(85.0)]);
+    }
+
+    #[test]
+    fn lines_are_placed_from_their_first_glyph() {
+        let points = line(Vector2::new(100.0, 0.0));
+        let glyphs = [(50.0, 10.0), (60.0, 10.0)];
+        let placed = open(0.0, PathAlignment::Start).place_glyphs(&points, &glyphs);
+        assert_eq!(xs(&placed), [Some(0.0), Some(10.0)]);
+    }
+
+    #[test]
+    fn glyphs_turn_with_the_path() {
+        let down = open(0.0, PathAlignment::Start)
+            .place_glyphs(&line(Vector2::new(0.0, 100.0)), &glyphs(2, 10.0));
+        let down: Vec<_> = down.into_iter().map(Option::unwrap).collect();
+        assert_eq!(down[0].angle_in_degrees, 90.0);
+        assert_near(down[0].position, Vector2::zero());
+        assert_near(down[1].position, Vector2::new(0.0, 10.0));
+
+        let points = [Vector2::new(0.0, 100.0), Vector2::zero()];
+        let up = open(0.0, PathAlignment::Start).place_glyphs(&points, &glyphs(1, 10.0));
+        assert_eq!(up[0].unwrap().angle_in_degrees, -90.0);
+
+        // Over the top of the arc, text starts out going up and is level
+        // halfway.
+        let arc = top_arc();
+        let points = arc.points();
+        let start = arc.place_glyphs(&points, &glyphs(1, 2.0))[0].unwrap();
+        assert!((start.angle_in_degrees + 90.0).abs() < 3.0);
+        let middle = path(arc.shape.clone(), 0.0, PathAlignment::Center)
+            .place_glyphs(&points, &glyphs(1, 2.0))[0]
+            .unwrap();
+        assert!(middle.angle_in_degrees.abs() < 1.5);
+        assert!((middle.position.Y - 50.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn placement_matrices_turn_then_move_glyphs() {
+        let placement = GlyphPlacement {
+            position: Vector2::new(3.0, 4.0),
+            angle_in_degrees: 90.0,
+        };
+        let matrix = placement.matrix();
+        let transform = |x: f32, y: f32| {
+            Vector2::new(
+                x * matrix.M11 + y * matrix.M21 + matrix.M31,
+                x * matrix.M12 + y * matrix.M22 + matrix.M32,
+            )
+        };
+        assert_near(transform(0.0, 0.0), Vector2::new(3.0, 4.0));
+        assert_near(transform(1.0, 0.0), Vector2::new(3.0, 5.0));
+        assert_near(transform(0.0, -1.0), Vector2::new(4.0, 4.0));
+    }
+
+    #[test]
+    fn text_longer_than_an_open_path_falls_off_its_ends() {
+        let points = line(Vector2::new(100.0, 0.0));
+        let placed = open(0.0, PathAlignment::Start).place_glyphs(&points, &glyphs(12, 10.0));
+        assert!(placed[..10].iter().all(Option::is_some));
+        assert_eq!(placed[10..], [None, None]);
+
+        let placed = open(0.0, PathAlignment::Center).place_glyphs(&points, &glyphs(12, 10.0));
+        assert_eq!(placed[0], None);
+        assert_eq!(placed[11], None);
+        assert!(placed[1..11].iter().all(Option::is_some));
+
+        let placed = open(-20.0, PathAlignment::Start).place_glyphs(&points, &glyphs(3, 10.0));
+        assert_eq!(xs(&placed), [None, None, Some(0.0)]);
+    }
+
+    #[test]
+    fn text_longer_than_a_closed_path_wraps_around() {
+        let circle = circle();
+        let points = circle.points();
+        let around = length(&points);
+        let placed = circle.place_glyphs(&points, &[(0.0, 10.0), (around, 10.0)]);
+        let (first, wrapped) = (placed[0].unwrap(), placed[1].unwrap());
+        assert_near(first.position, wrapped.position);
+
+        let before = path(circle.shape.clone(), -around, PathAlignment::Start)
+            .place_glyphs(&points, &glyphs(1, 10.0))[0]
+            .unwrap();
+        assert_near(before.position, first.position);
+    }
+
+    #[test]
+    fn paths_without_length_place_nothing() {
+        let glyphs = glyphs(2, 10.0);
+        let path = open(0.0, PathAlignment::Start);
+        assert_eq!(path.place_glyphs(&[], &glyphs), [None, None]);
+        let point = [Vector2::new(5.0, 5.0); 3];
+        assert_eq!(path.place_glyphs(&point, &glyphs), [None, None]);
+    }
+}
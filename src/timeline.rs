@@ -0,0 +1,385 @@
+use std::time::Duration;
+
+use crate::animation::IterationBehavior;
+
+pub type ClipId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimelineKind {
+    Clip {
+        id: ClipId,
+        duration: Duration,
+        iteration_behavior: IterationBehavior,
+    },
+    Sequence(Vec<Timeline>),
+    Parallel(Vec<Timeline>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timeline {
+    pub begin_offset: Duration,
+    pub kind: TimelineKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScheduledClip {
+    pub id: ClipId,
+    pub start: Duration,
+    pub duration: Duration,
+    pub iteration_behavior: IterationBehavior,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClipState {
+    Pending {
+        starts_in: Duration,
+    },
+    Active {
+        local_time: Duration,
+        iteration: u32,
+        progress: f32,
+    },
+    Completed,
+}
+
+impl Timeline {
+    pub fn clip(id: ClipId, duration: Duration, iteration_behavior: IterationBehavior) -> Self {
+        Self {
+            begin_offset: Duration::ZERO,
+            kind: TimelineKind::Clip {
+                id,
+                duration,
+                iteration_behavior,
+            },
+        }
+    }
+
+    pub fn sequence(children: Vec<Timeline>) -> Self {
+        Self {
+            begin_offset: Duration::ZERO,
+            kind: TimelineKind::Sequence(children),
+        }
+    }
+
+    pub fn parallel(children: Vec<Timeline>) -> Self {
+        Self {
+            begin_offset: Duration::ZERO,
+            kind: TimelineKind::Parallel(children),
+        }
+    }
+
+    pub fn with_begin_offset(mut self, begin_offset: Duration) -> Self {
+        self.begin_offset = begin_offset;
+        self
+    }
+
+    // Total length including the begin offset, or None if something in the
+    // timeline repeats forever.
+    pub fn duration(&self) -> Option<Duration> {
+        let inner = match &self.kind {
+            TimelineKind::Clip {
+                duration,
+                iteration_behavior,
+                ..
+            } => match iteration_behavior {
+                IterationBehavior::Count(count) => Some(*duration * *count),
+                IterationBehavior::Forever => None,
+            },
+            TimelineKind::Sequence(children) => {
+                children.iter().try_fold(Duration::ZERO, |total, child| {
+                    Some(total + child.duration()?)
+                })
+            }
+            TimelineKind::Parallel(children) => {
+                children.iter().try_fold(Duration::ZERO, |longest, child| {
+                    Some(longest.max(child.duration()?))
+                })
+            }
+        };
+        inner.map(|inner| self.begin_offset + inner)
+    }
+
+    // Flattens the timeline into clips with absolute start times. Clips that
+    // are sequenced after one that repeats forever never start, so they are
+    // left out.
+    pub fn schedule(&self) -> Vec<ScheduledClip> {
+        let mut clips = Vec::new();
+        self.schedule_into(Duration::ZERO, &mut clips);
+        clips
+    }
+
+    fn schedule_into(&self, parent_start: Duration, clips: &mut Vec<ScheduledClip>) {
+        let start = parent_start + self.begin_offset;
+        match &self.kind {
+            TimelineKind::Clip {
+                id,
+                duration,
+                iteration_behavior,
+            } => clips.push(ScheduledClip {
+                id: *id,
+                start,
+                duration: *duration,
+                iteration_behavior: *iteration_behavior,
+            }),
+            TimelineKind::Sequence(children) => {
+                let mut child_start = start;
+                for child in children {
+                    child.schedule_into(child_start, clips);
+                    match child.duration() {
+                        Some(duration) => child_start += duration,
+                        None => break,
+                    }
+                }
+            }
+            TimelineKind::Parallel(children) => {
+                for child in children {
+                    child.schedule_into(start, clips);
+                }
+            }
+        }
+    }
+
+    pub fn clip_states(&self, time: Duration) -> Vec<(ClipId, ClipState)> {
+        self.schedule()
+            .iter()
+            .map(|clip| (clip.id, clip.state_at(time)))
+            .collect()
+    }
+}
+
+impl ScheduledClip {
+    pub fn active_duration(&self) -> Option<Duration> {
+        match self.iteration_behavior {
+            IterationBehavior::Count(count) => Some(self.duration * count),
+            IterationBehavior::Forever => None,
+        }
+    }
+
+    pub fn state_at(&self, time: Duration) -> ClipState {
+        if time < self.start {
+            return ClipState::Pending {
+                starts_in: self.start - time,
+            };
+        }
+        let local_time = time - self.start;
+        if let Some(active_duration) = self.active_duration()
+            && local_time >= active_duration
+        {
+            return ClipState::Completed;
+        }
+        if self.duration.is_zero() {
+            return ClipState::Completed;
+        }
+        let iterations = local_time.as_secs_f64() / self.duration.as_secs_f64();
+        ClipState::Active {
+            local_time,
+            iteration: iterations.floor() as u32,
+            progress: iterations.fract() as f32,
+        }
+    }
+}
+
+// Maps a monotonically increasing clock onto a position in a timeline that
+// can be paused, resumed and seeked.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Playhead {
+    resumed_at: Duration,
+    resumed_position: Duration,
+    paused_at: Option<Duration>,
+}
+
+impl Playhead {
+    pub fn new(now: Duration) -> Self {
+        Self {
+            resumed_at: now,
+            resumed_position: Duration::ZERO,
+            paused_at: None,
+        }
+    }
+
+    pub fn position(&self, now: Duration) -> Duration {
+        match self.paused_at {
+            Some(position) => position,
+            None => self.resumed_position + now.saturating_sub(self.resumed_at),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn pause(&mut self, now: Duration) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.position(now));
+        }
+    }
+
+    pub fn resume(&mut self, now: Duration) {
+        if let Some(position) = self.paused_at.take() {
+            self.resumed_at = now;
+            self.resumed_position = position;
+        }
+    }
+
+    pub fn seek(&mut self, now: Duration, position: Duration) {
+        match &mut self.paused_at {
+            Some(paused_at) => *paused_at = position,
+            None => {
+                self.resumed_at = now;
+                self.resumed_position = position;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(milliseconds: u64) -> Duration {
+        Duration::from_millis(milliseconds)
+    }
+
+    fn once(id: ClipId, milliseconds: u64) -> Timeline {
+        Timeline::clip(id, ms(milliseconds), IterationBehavior::Count(1))
+    }
+
+    fn starts(timeline: &Timeline) -> Vec<(ClipId, Duration)> {
+        timeline
+            .schedule()
+            .iter()
+            .map(|clip| (clip.id, clip.start))
+            .collect()
+    }
+
+    #[test]
+    fn sequences_clips_one_after_another() {
+        let timeline = Timeline::sequence(vec![
+            once(0, 100),
+            once(1, 200).with_begin_offset(ms(50)),
+            Timeline::clip(2, ms(100), IterationBehavior::Count(3)),
+            once(3, 10),
+        ]);
+        assert_eq!(
+            starts(&timeline),
+            [(0, ms(0)), (1, ms(150)), (2, ms(350)), (3, ms(650))]
+        );
+        assert_eq!(timeline.duration(), Some(ms(660)));
+    }
+
+    #[test]
+    fn runs_parallel_clips_together() {
+        let timeline = Timeline::parallel(vec![
+            once(0, 100),
+            once(1, 300).with_begin_offset(ms(50)),
+            Timeline::sequence(vec![once(2, 100), once(3, 100)]).with_begin_offset(ms(20)),
+        ])
+        .with_begin_offset(ms(1000));
+        assert_eq!(
+            starts(&timeline),
+            [(0, ms(1000)), (1, ms(1050)), (2, ms(1020)), (3, ms(1120))]
+        );
+        assert_eq!(timeline.duration(), Some(ms(1350)));
+    }
+
+    #[test]
+    fn forever_clips_cut_off_the_rest_of_a_sequence() {
+        let timeline = Timeline::parallel(vec![
+            Timeline::sequence(vec![
+                once(0, 100),
+                Timeline::clip(1, ms(100), IterationBehavior::Forever),
+                once(2, 100),
+            ]),
+            once(3, 100),
+        ]);
+        assert_eq!(starts(&timeline), [(0, ms(0)), (1, ms(100)), (3, ms(0))]);
+        assert_eq!(timeline.duration(), None);
+        assert_eq!(
+            timeline.clip_states(ms(10_050))[1],
+            (
+                1,
+                ClipState::Active {
+                    local_time: ms(9950),
+                    iteration: 99,
+                    progress: 0.5,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn reports_iterations_and_progress_at_boundaries() {
+        let clip = ScheduledClip {
+            id: 0,
+            start: ms(100),
+            duration: ms(200),
+            iteration_behavior: IterationBehavior::Count(2),
+        };
+        assert_eq!(
+            clip.state_at(ms(0)),
+            ClipState::Pending { starts_in: ms(100) }
+        );
+        assert_eq!(
+            clip.state_at(ms(100)),
+            ClipState::Active {
+                local_time: ms(0),
+                iteration: 0,
+                progress: 0.0,
+            }
+        );
+        assert_eq!(
+            clip.state_at(ms(250)),
+            ClipState::Active {
+                local_time: ms(150),
+                iteration: 0,
+                progress: 0.75,
+            }
+        );
+        assert_eq!(
+            clip.state_at(ms(300)),
+            ClipState::Active {
+                local_time: ms(200),
+                iteration: 1,
+                progress: 0.0,
+            }
+        );
+        assert_eq!(clip.state_at(ms(500)), ClipState::Completed);
+        assert_eq!(clip.active_duration(), Some(ms(400)));
+
+        let empty = ScheduledClip {
+            duration: Duration::ZERO,
+            ..clip
+        };
+        assert_eq!(empty.state_at(ms(100)), ClipState::Completed);
+    }
+
+    #[test]
+    fn playhead_pauses_resumes_and_seeks() {
+        let mut playhead = Playhead::new(ms(1000));
+        assert_eq!(playhead.position(ms(1500)), ms(500));
+        assert_eq!(playhead.position(ms(900)), ms(0));
+
+        playhead.pause(ms(1500));
+        assert!(playhead.is_paused());
+        assert_eq!(playhead.position(ms(3000)), ms(500));
+        // Pausing again doesn't move the paused position.
+        playhead.pause(ms(4000));
+        assert_eq!(playhead.position(ms(4000)), ms(500));
+
+        playhead.resume(ms(5000));
+        assert!(!playhead.is_paused());
+        assert_eq!(playhead.position(ms(5250)), ms(750));
+        // Resuming while playing changes nothing.
+        playhead.resume(ms(6000));
+        assert_eq!(playhead.position(ms(6000)), ms(1500));
+
+        playhead.seek(ms(6000), ms(100));
+        assert_eq!(playhead.position(ms(6100)), ms(200));
+
+        playhead.pause(ms(6100));
+        playhead.seek(ms(7000), ms(50));
+        assert_eq!(playhead.position(ms(8000)), ms(50));
+        playhead.resume(ms(9000));
+        assert_eq!(playhead.position(ms(9010)), ms(60));
+    }
+}
@@ -0,0 +1,677 @@
+use std::{fmt, ops::Range, time::Duration};
+
+use crate::animation::KeyFrameAnimation;
+
+// An OpenType feature tag like "liga", packed the way DirectWrite's
+// DWRITE_MAKE_OPENTYPE_TAG packs it: first character in the low byte.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FeatureTag(pub u32);
+
+impl FeatureTag {
+    pub const STANDARD_LIGATURES: Self = Self::from_bytes(*b"liga");
+    pub const DISCRETIONARY_LIGATURES: Self = Self::from_bytes(*b"dlig");
+    pub const CONTEXTUAL_LIGATURES: Self = Self::from_bytes(*b"clig");
+    pub const CONTEXTUAL_ALTERNATES: Self = Self::from_bytes(*b"calt");
+    pub const SMALL_CAPS: Self = Self::from_bytes(*b"smcp");
+    pub const KERNING: Self = Self::from_bytes(*b"kern");
+
+    // Registered variable font axes. Axis tags are packed like feature tags.
+    pub const WEIGHT: Self = Self::from_bytes(*b"wght");
+    pub const WIDTH: Self = Self::from_bytes(*b"wdth");
+    pub const SLANT: Self = Self::from_bytes(*b"slnt");
+    pub const ITALIC: Self = Self::from_bytes(*b"ital");
+    pub const OPTICAL_SIZE: Self = Self::from_bytes(*b"opsz");
+
+    const fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self(u32::from_le_bytes(bytes))
+    }
+
+    // Tags are four letters, digits or trailing spaces, like "tnum", the
+    // stylistic set "ss01" or the character variant "cv05".
+    pub fn new(tag: &str) -> Option<Self> {
+        let bytes: [u8; 4] = tag.as_bytes().try_into().ok()?;
+        let letters = bytes.iter().position(|&byte| byte == b' ').unwrap_or(4);
+        let valid = letters > 0
+            && bytes[..letters].iter().all(u8::is_ascii_alphanumeric)
+            && bytes[letters..].iter().all(|&byte| byte == b' ');
+        valid.then(|| Self::from_bytes(bytes))
+    }
+
+    // Features fonts apply unless they're turned off.
+    fn is_on_by_default(self) -> bool {
+        [
+            Self::STANDARD_LIGATURES,
+            Self::CONTEXTUAL_LIGATURES,
+            Self::CONTEXTUAL_ALTERNATES,
+            Self::KERNING,
+        ]
+        .contains(&self)
+    }
+}
+
+impl fmt::Display for FeatureTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.0.to_le_bytes();
+        write!(f, "{}", String::from_utf8_lossy(&bytes))
+    }
+}
+
+// A feature's value is 0 to turn it off, 1 to turn it on, or which of a
+// glyph's alternates to use for features like "salt" and "cv01".
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Feature {
+    pub tag: FeatureTag,
+    pub value: u32,
+}
+
+// A variable font axis set to 'value', or animated through key frames.
+// Values are in the axis's own units: weight from 1 to 1000, width as a
+// percentage of normal, slant in degrees, italic from 0 to 1 and optical
+// size in points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontAxis {
+    pub tag: FeatureTag,
+    pub value: f32,
+    pub animation: Option<KeyFrameAnimation<f32>>,
+}
+
+impl FontAxis {
+    pub fn new(tag: FeatureTag, value: f32) -> Self {
+        Self {
+            tag,
+            value,
+            animation: None,
+        }
+    }
+
+    // Eased key frames can overshoot, so values are kept to what the axis
+    // allows.
+    pub fn value_at(&self, time: Duration) -> f32 {
+        let value = match &self.animation {
+            Some(animation) => animation.value_at(time, self.value),
+            None => self.value,
+        };
+        clamp_axis(self.tag, value)
+    }
+}
+
+fn clamp_axis(tag: FeatureTag, value: f32) -> f32 {
+    match tag {
+        FeatureTag::WEIGHT => value.clamp(1.0, 1000.0),
+        FeatureTag::WIDTH | FeatureTag::OPTICAL_SIZE => value.max(f32::EPSILON),
+        FeatureTag::SLANT => value.clamp(-90.0, 90.0),
+        FeatureTag::ITALIC => value.clamp(0.0, 1.0),
+        _ => value,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AxisValue {
+    pub tag: FeatureTag,
+    pub value: f32,
+}
+
+// Features and font axes for a range of a visual's text, in chars. Where
+// runs overlap, the later run's value for a feature or axis wins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypographyRun {
+    pub range: Range<usize>,
+    pub features: Vec<Feature>,
+    pub axes: Vec<FontAxis>,
+}
+
+impl TypographyRun {
+    pub fn has_animated_axes(&self) -> bool {
+        self.axes.iter().any(|axis| axis.animation.is_some())
+    }
+
+    // The run with its axes fixed at their values at 'time'.
+    pub fn at(&self, time: Duration) -> Self {
+        Self {
+            range: self.range.clone(),
+            features: self.features.clone(),
+            axes: self
+                .axes
+                .iter()
+                .map(|axis| FontAxis::new(axis.tag, axis.value_at(time)))
+                .collect(),
+        }
+    }
+}
+
+// Splits text of 'length' chars wherever a run starts or ends and merges
+// the features of the runs covering each piece. Pieces no run covers have
+// no features.
+pub fn resolve_runs(runs: &[TypographyRun], length: usize) -> Vec<(Range<usize>, Vec<Feature>)> {
+    resolve(
+        runs,
+        length,
+        |run| run.features.clone(),
+        |feature| feature.tag,
+    )
+}
+
+// Like resolve_runs, for the axes' static values.
+pub fn resolve_axes(runs: &[TypographyRun], length: usize) -> Vec<(Range<usize>, Vec<AxisValue>)> {
+    let values = |run: &TypographyRun| {
+        run.axes
+            .iter()
+            .map(|axis| AxisValue {
+                tag: axis.tag,
+                value: clamp_axis(axis.tag, axis.value),
+            })
+            .collect()
+    };
+    resolve(runs, length, values, |axis| axis.tag)
+}
+
+fn resolve<T: Copy + PartialEq>(
+    runs: &[TypographyRun],
+    length: usize,
+    values: impl Fn(&TypographyRun) -> Vec<T>,
+    tag: impl Fn(&T) -> FeatureTag,
+) -> Vec<(Range<usize>, Vec<T>)> {
+    let mut boundaries: Vec<usize> = runs
+        .iter()
+        .flat_map(|run| [run.range.start.min(length), run.range.end.min(length)])
+        .chain([0, length])
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut pieces: Vec<(Range<usize>, Vec<T>)> = Vec::new();
+    for range in boundaries.windows(2).map(|pair| pair[0]..pair[1]) {
+        let covering = runs
+            .iter()
+            .filter(|run| run.range.start <= range.start && run.range.end >= range.end);
+        let mut merged: Vec<T> = Vec::new();
+        for value in covering.flat_map(&values) {
+            match merged
+                .iter_mut()
+                .find(|existing| tag(existing) == tag(&value))
+            {
+                Some(existing) => *existing = value,
+                None => merged.push(value),
+            }
+        }
+        // Neighbors with the same values are one piece.
+        match pieces.last_mut() {
+            Some((last, last_merged)) if last.end == range.start && *last_merged == merged => {
+                last.end = range.end;
+            }
+            _ => pieces.push((range, merged)),
+        }
+    }
+    pieces
+}
+
+pub fn is_enabled(features: &[Feature], tag: FeatureTag) -> bool {
+    features
+        .iter()
+        .find(|feature| feature.tag == tag)
+        .map_or(tag.is_on_by_default(), |feature| feature.value != 0)
+}
+
+// Letter sequences that typical Latin fonts join into one glyph, longest
+// first.
+const STANDARD_LIGATURES: &[&str] = &["ffi", "ffl", "ff", "fi", "fl"];
+const DISCRETIONARY_LIGATURES: &[&str] = &["ct", "st"];
+
+// How many of the characters at the start of 'characters' the ligatures
+// that 'features' turn on join into one cluster, or 1 if none do.
+pub fn ligature_length(characters: &[char], features: &[Feature]) -> usize {
+    let ligatures = [
+        (FeatureTag::STANDARD_LIGATURES, STANDARD_LIGATURES),
+        (FeatureTag::DISCRETIONARY_LIGATURES, DISCRETIONARY_LIGATURES),
+    ];
+    ligatures
+        .iter()
+        .filter(|(tag, _)| is_enabled(features, *tag))
+        .flat_map(|(_, ligatures)| ligatures.iter())
+        .find(|ligature| {
+            let length = ligature.chars().count();
+            characters.len() >= length && ligature.chars().eq(characters[..length].iter().copied())
+        })
+        .map_or(1, |ligature| ligature.chars().count())
+}
+
+fn axis_value(axes: &[AxisValue], tag: FeatureTag) -> Option<f32> {
+    axes.iter()
+        .find(|axis| axis.tag == tag)
+        .map(|axis| axis.value)
+}
+
+// The closest DWRITE_FONT_WEIGHT, DWRITE_FONT_STRETCH and DWRITE_FONT_STYLE
+// to a set of axes, which pick the face from families that come as
+// separate faces rather than one variable font.
+pub fn font_weight(axes: &[AxisValue]) -> u16 {
+    axis_value(axes, FeatureTag::WEIGHT).map_or(400, |weight| weight.round().min(999.0) as u16)
+}
+
+// Stretches 1 to 9 are these widths, in percent of normal.
+const STRETCH_WIDTHS: [f32; 9] = [50.0, 62.5, 75.0, 87.5, 100.0, 112.5, 125.0, 150.0, 200.0];
+
+pub fn font_stretch(axes: &[AxisValue]) -> u8 {
+    let width = axis_value(axes, FeatureTag::WIDTH).unwrap_or(100.0);
+    let nearest = STRETCH_WIDTHS
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - width).abs().total_cmp(&(*b - width).abs()))
+        .map_or(4, |(index, _)| index);
+    nearest as u8 + 1
+}
+
+// Italic wins over slant, like CSS's font-style.
+pub fn font_style(axes: &[AxisValue]) -> u8 {
+    let italic = axis_value(axes, FeatureTag::ITALIC).unwrap_or(0.0);
+    let slant = axis_value(axes, FeatureTag::SLANT).unwrap_or(0.0);
+    if italic >= 0.5 {
+        2
+    } else if slant != 0.0 {
+        1
+    } else {
+        0
+    }
+}
+
+// Roughly how much wider than normal text the axes make it: width scales
+// advances directly and bold text runs about a tenth wider at 700.
+pub fn advance_scale(axes: &[AxisValue]) -> f32 {
+    let width = axis_value(axes, FeatureTag::WIDTH).unwrap_or(100.0) / 100.0;
+    let weight = axis_value(axes, FeatureTag::WEIGHT).unwrap_or(400.0);
+    width * (1.0 + (weight - 400.0) / 3000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use windows_numerics::Vector2;
+
+    use super::*;
+    use crate::animation::Easing;
+
+    fn feature(tag: FeatureTag, value: u32) -> Feature {
+        Feature { tag, value }
+    }
+
+    fn feature_run(range: Range<usize>, features: Vec<Feature>) -> TypographyRun {
+        TypographyRun {
+            range,
+            features,
+            axes: Vec::new(),
+        }
+    }
+
+    fn axis_run(range: Range<usize>, axes: Vec<FontAxis>) -> TypographyRun {
+        TypographyRun {
+            range,
+            features: Vec::new(),
+            axes,
+        }
+    }
+
+    fn value(tag: FeatureTag, value: f32) -> AxisValue {
+        AxisValue { tag, value }
+    }
+
+    // An axis going from 'from' to 'to' over a second.
+    fn animated(tag: FeatureTag, from: f32, to: f32, easing: Easing) -> FontAxis {
+        let mut animation = KeyFrameAnimation::new(Duration::from_secs(1));
+        animation.insert_key_frame_with_easing(1.0, to, easing);
+        FontAxis {
+            animation: Some(animation),
+            ..FontAxis::new(tag, from)
+        }
+    }
+
+    // Easing that reaches about 1.475 halfway, or -0.475 with 'under'.
+    fn overshoot(under: bool) -> Easing {
+        let y = if under { -0.8 } else { 1.8 };
+        Easing::CubicBezier(Vector2::new(0.3, y), Vector2::new(0.7, y))
+    }
+
+    fn half_second() -> Duration {
+        Duration::from_millis(500)
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{actual} isn't {expected}"
+        );
+    }
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn tags_are_four_letters_or_digits() {
+        for tag in ["liga", "ss01", "cv05", "tnum"] {
+            let parsed = FeatureTag::new(tag).unwrap();
+            assert_eq!(parsed.to_string(), tag);
+        }
+        assert_eq!(
+            FeatureTag::new("liga"),
+            Some(FeatureTag::STANDARD_LIGATURES)
+        );
+        assert_eq!(
+            FeatureTag::new("ss01"),
+            Some(FeatureTag(u32::from_le_bytes(*b"ss01")))
+        );
+    }
+
+    #[test]
+    fn tags_can_end_in_spaces() {
+        assert_eq!(FeatureTag::new("cv1 ").unwrap().to_string(), "cv1 ");
+        assert_eq!(FeatureTag::new("ab  ").unwrap().to_string(), "ab  ");
+        assert_eq!(FeatureTag::new("a   ").unwrap().to_string(), "a   ");
+    }
+
+    #[test]
+    fn tags_with_other_characters_or_lengths_are_rejected() {
+        for tag in [
+            "", "lig", "ligat", "    ", " abc", "a bc", "ab c", "ss-1", "ss_1", "é12", "ẞ", "lig\0",
+        ] {
+            assert_eq!(FeatureTag::new(tag), None, "{tag:?}");
+        }
+    }
+
+    #[test]
+    fn default_features_are_on_until_turned_off() {
+        for tag in [
+            FeatureTag::STANDARD_LIGATURES,
+            FeatureTag::CONTEXTUAL_LIGATURES,
+            FeatureTag::CONTEXTUAL_ALTERNATES,
+            FeatureTag::KERNING,
+        ] {
+            assert!(is_enabled(&[], tag), "{tag}");
+            assert!(is_enabled(&[feature(tag, 1)], tag), "{tag}");
+            assert!(!is_enabled(&[feature(tag, 0)], tag), "{tag}");
+        }
+    }
+
+    #[test]
+    fn other_features_are_off_until_turned_on() {
+        for tag in [
+            FeatureTag::DISCRETIONARY_LIGATURES,
+            FeatureTag::SMALL_CAPS,
+            FeatureTag::new("ss01").unwrap(),
+        ] {
+            assert!(!is_enabled(&[], tag), "{tag}");
+            assert!(!is_enabled(&[feature(tag, 0)], tag), "{tag}");
+            assert!(is_enabled(&[feature(tag, 1)], tag), "{tag}");
+            // Alternates other than 1 turn features on too.
+            assert!(is_enabled(&[feature(tag, 3)], tag), "{tag}");
+        }
+    }
+
+    #[test]
+    fn the_longest_ligature_matches_first() {
+        assert_eq!(ligature_length(&chars("ffix"), &[]), 3);
+        assert_eq!(ligature_length(&chars("fflx"), &[]), 3);
+        assert_eq!(ligature_length(&chars("ffx"), &[]), 2);
+        assert_eq!(ligature_length(&chars("fix"), &[]), 2);
+        assert_eq!(ligature_length(&chars("flx"), &[]), 2);
+        assert_eq!(ligature_length(&chars("fx"), &[]), 1);
+        // Ligatures only join characters that are there.
+        assert_eq!(ligature_length(&chars("ff"), &[]), 2);
+        assert_eq!(ligature_length(&chars("f"), &[]), 1);
+        assert_eq!(ligature_length(&[], &[]), 1);
+    }
+
+    #[test]
+    fn ligatures_follow_their_features() {
+        let no_liga = [feature(FeatureTag::STANDARD_LIGATURES, 0)];
+        assert_eq!(ligature_length(&chars("ffi"), &no_liga), 1);
+
+        let dlig = [feature(FeatureTag::DISCRETIONARY_LIGATURES, 1)];
+        assert_eq!(ligature_length(&chars("st"), &[]), 1);
+        assert_eq!(ligature_length(&chars("st"), &dlig), 2);
+        assert_eq!(ligature_length(&chars("ct"), &dlig), 2);
+        // Turning discretionary ligatures on keeps the standard ones.
+        assert_eq!(ligature_length(&chars("ffi"), &dlig), 3);
+    }
+
+    #[test]
+    fn runs_without_dlig_leave_it_off() {
+        let runs = [feature_run(
+            0..2,
+            vec![feature(FeatureTag::DISCRETIONARY_LIGATURES, 1)],
+        )];
+        let resolved = resolve_runs(&runs, 4);
+        assert_eq!(resolved.len(), 2);
+        let (first, first_features) = &resolved[0];
+        let (rest, rest_features) = &resolved[1];
+        assert_eq!((first.clone(), rest.clone()), (0..2, 2..4));
+        assert!(is_enabled(
+            first_features,
+            FeatureTag::DISCRETIONARY_LIGATURES
+        ));
+        assert!(!is_enabled(
+            rest_features,
+            FeatureTag::DISCRETIONARY_LIGATURES
+        ));
+        assert!(is_enabled(rest_features, FeatureTag::STANDARD_LIGATURES));
+
+        let text = chars("stst");
+        assert_eq!(ligature_length(&text[0..], first_features), 2);
+        assert_eq!(ligature_length(&text[2..], rest_features), 1);
+    }
+
+    #[test]
+    fn axes_without_animations_keep_their_value() {
+        let axis = FontAxis::new(FeatureTag::WEIGHT, 650.0);
+        assert_eq!(axis.value_at(Duration::ZERO), 650.0);
+        assert_eq!(axis.value_at(Duration::from_secs(5)), 650.0);
+    }
+
+    #[test]
+    fn animated_axes_follow_their_key_frames() {
+        let axis = animated(FeatureTag::WEIGHT, 400.0, 800.0, Easing::Linear);
+        assert_near(axis.value_at(Duration::ZERO), 400.0);
+        assert_near(axis.value_at(Duration::from_millis(250)), 500.0);
+        assert_near(axis.value_at(half_second()), 600.0);
+        assert_near(axis.value_at(Duration::from_secs(1)), 800.0);
+        // Played once, so it stays at the last key frame.
+        assert_near(axis.value_at(Duration::from_secs(3)), 800.0);
+    }
+
+    #[test]
+    fn eased_overshoot_is_clamped_to_the_axis() {
+        let weight = animated(FeatureTag::WEIGHT, 900.0, 1000.0, overshoot(false));
+        assert_eq!(weight.value_at(half_second()), 1000.0);
+        let weight = animated(FeatureTag::WEIGHT, 100.0, 1000.0, overshoot(true));
+        assert_eq!(weight.value_at(half_second()), 1.0);
+
+        let slant = animated(FeatureTag::SLANT, 0.0, -90.0, overshoot(false));
+        assert_eq!(slant.value_at(half_second()), -90.0);
+        let slant = animated(FeatureTag::SLANT, 0.0, 90.0, overshoot(false));
+        assert_eq!(slant.value_at(half_second()), 90.0);
+
+        let italic = animated(FeatureTag::ITALIC, 0.0, 1.0, overshoot(false));
+        assert_eq!(italic.value_at(half_second()), 1.0);
+        let italic = animated(FeatureTag::ITALIC, 0.0, 1.0, overshoot(true));
+        assert_eq!(italic.value_at(half_second()), 0.0);
+
+        // Without overshoot the values are left alone.
+        let italic = animated(FeatureTag::ITALIC, 0.0, 1.0, Easing::Linear);
+        assert_near(italic.value_at(half_second()), 0.5);
+    }
+
+    #[test]
+    fn axis_values_are_clamped_to_their_range() {
+        assert_eq!(clamp_axis(FeatureTag::WEIGHT, 0.0), 1.0);
+        assert_eq!(clamp_axis(FeatureTag::WEIGHT, 1200.0), 1000.0);
+        assert_eq!(clamp_axis(FeatureTag::WEIGHT, 700.0), 700.0);
+        assert_eq!(clamp_axis(FeatureTag::WIDTH, -5.0), f32::EPSILON);
+        assert_eq!(clamp_axis(FeatureTag::WIDTH, 300.0), 300.0);
+        assert_eq!(clamp_axis(FeatureTag::OPTICAL_SIZE, 0.0), f32::EPSILON);
+        assert_eq!(clamp_axis(FeatureTag::SLANT, -120.0), -90.0);
+        assert_eq!(clamp_axis(FeatureTag::ITALIC, 2.0), 1.0);
+        // Axes the demo doesn't know about are up to the font.
+        let grade = FeatureTag::new("GRAD").unwrap();
+        assert_eq!(clamp_axis(grade, -500.0), -500.0);
+    }
+
+    #[test]
+    fn runs_at_a_time_fix_their_axes() {
+        let run = TypographyRun {
+            range: 2..7,
+            features: vec![feature(FeatureTag::SMALL_CAPS, 1)],
+            axes: vec![
+                animated(FeatureTag::WEIGHT, 400.0, 800.0, Easing::Linear),
+                FontAxis::new(FeatureTag::WIDTH, 75.0),
+            ],
+        };
+        assert!(run.has_animated_axes());
+
+        let fixed = run.at(half_second());
+        assert!(!fixed.has_animated_axes());
+        assert_eq!(fixed.range, 2..7);
+        assert_eq!(fixed.features, run.features);
+        assert_eq!(fixed.axes.len(), 2);
+        assert_eq!(fixed.axes[0].tag, FeatureTag::WEIGHT);
+        assert_near(fixed.axes[0].value, 600.0);
+        assert_eq!(fixed.axes[1], FontAxis::new(FeatureTag::WIDTH, 75.0));
+    }
+
+    #[test]
+    fn later_overlapping_runs_win() {
+        let runs = [
+            axis_run(0..6, vec![FontAxis::new(FeatureTag::WEIGHT, 700.0)]),
+            axis_run(
+                3..6,
+                vec![
+                    FontAxis::new(FeatureTag::WEIGHT, 300.0),
+                    FontAxis::new(FeatureTag::ITALIC, 1.0),
+                ],
+            ),
+        ];
+        let weight = value(FeatureTag::WEIGHT, 700.0);
+        let light_italic = vec![
+            value(FeatureTag::WEIGHT, 300.0),
+            value(FeatureTag::ITALIC, 1.0),
+        ];
+        assert_eq!(
+            resolve_axes(&runs, 8),
+            [
+                (0..3, vec![weight]),
+                (3..6, light_italic),
+                (6..8, Vec::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn neighbors_that_resolve_the_same_are_merged() {
+        let liga_off = || vec![feature(FeatureTag::STANDARD_LIGATURES, 0)];
+        let runs = [feature_run(0..2, liga_off()), feature_run(2..4, liga_off())];
+        assert_eq!(
+            resolve_runs(&runs, 6),
+            [(0..4, liga_off()), (4..6, Vec::new())]
+        );
+
+        // Overlapping runs that set the same values don't split the text.
+        let runs = [
+            axis_run(0..4, vec![FontAxis::new(FeatureTag::WEIGHT, 700.0)]),
+            axis_run(2..4, vec![FontAxis::new(FeatureTag::WEIGHT, 700.0)]),
+        ];
+        assert_eq!(
+            resolve_axes(&runs, 4),
+            [(0..4, vec![value(FeatureTag::WEIGHT, 700.0)])]
+        );
+
+        assert_eq!(resolve_runs(&[], 5), [(0..5, Vec::new())]);
+    }
+
+    #[test]
+    fn runs_past_the_end_of_the_text_are_cut_off() {
+        let runs = [
+            axis_run(2..10, vec![FontAxis::new(FeatureTag::SLANT, -10.0)]),
+            axis_run(12..20, vec![FontAxis::new(FeatureTag::ITALIC, 1.0)]),
+        ];
+        assert_eq!(
+            resolve_axes(&runs, 5),
+            [
+                (0..2, Vec::new()),
+                (2..5, vec![value(FeatureTag::SLANT, -10.0)])
+            ]
+        );
+    }
+
+    #[test]
+    fn resolved_axes_are_clamped() {
+        let runs = [axis_run(
+            0..3,
+            vec![FontAxis::new(FeatureTag::WEIGHT, 2000.0)],
+        )];
+        assert_eq!(
+            resolve_axes(&runs, 3),
+            [(0..3, vec![value(FeatureTag::WEIGHT, 1000.0)])]
+        );
+    }
+
+    #[test]
+    fn weights_are_rounded_and_kept_below_1000() {
+        assert_eq!(font_weight(&[]), 400);
+        assert_eq!(font_weight(&[value(FeatureTag::WEIGHT, 650.4)]), 650);
+        assert_eq!(font_weight(&[value(FeatureTag::WEIGHT, 650.5)]), 651);
+        assert_eq!(font_weight(&[value(FeatureTag::WEIGHT, 1.0)]), 1);
+        assert_eq!(font_weight(&[value(FeatureTag::WEIGHT, 1000.0)]), 999);
+        assert_eq!(font_weight(&[value(FeatureTag::WIDTH, 50.0)]), 400);
+    }
+
+    #[test]
+    fn widths_pick_the_nearest_stretch() {
+        let stretch = |width| font_stretch(&[value(FeatureTag::WIDTH, width)]);
+        assert_eq!(font_stretch(&[]), 5);
+        for (index, width) in STRETCH_WIDTHS.into_iter().enumerate() {
+            assert_eq!(stretch(width), index as u8 + 1, "{width}");
+        }
+        assert_eq!(stretch(10.0), 1);
+        assert_eq!(stretch(56.0), 1);
+        assert_eq!(stretch(57.0), 2);
+        assert_eq!(stretch(95.0), 5);
+        assert_eq!(stretch(170.0), 8);
+        assert_eq!(stretch(180.0), 9);
+        assert_eq!(stretch(300.0), 9);
+    }
+
+    #[test]
+    fn italic_wins_over_slant() {
+        assert_eq!(font_style(&[]), 0);
+        assert_eq!(font_style(&[value(FeatureTag::SLANT, -10.0)]), 1);
+        assert_eq!(font_style(&[value(FeatureTag::SLANT, 0.0)]), 0);
+        assert_eq!(font_style(&[value(FeatureTag::ITALIC, 0.5)]), 2);
+        assert_eq!(font_style(&[value(FeatureTag::ITALIC, 0.4)]), 0);
+        assert_eq!(
+            font_style(&[
+                value(FeatureTag::SLANT, -10.0),
+                value(FeatureTag::ITALIC, 1.0)
+            ]),
+            2
+        );
+        assert_eq!(
+            font_style(&[
+                value(FeatureTag::SLANT, -10.0),
+                value(FeatureTag::ITALIC, 0.0)
+            ]),
+            1
+        );
+    }
+
+    #[test]
+    fn width_and_weight_scale_advances() {
+        assert_eq!(advance_scale(&[]), 1.0);
+        assert_near(advance_scale(&[value(FeatureTag::WIDTH, 50.0)]), 0.5);
+        assert_near(advance_scale(&[value(FeatureTag::WEIGHT, 700.0)]), 1.1);
+        assert_near(advance_scale(&[value(FeatureTag::WEIGHT, 100.0)]), 0.9);
+        assert_near(
+            advance_scale(&[
+                value(FeatureTag::WIDTH, 200.0),
+                value(FeatureTag::WEIGHT, 700.0),
+            ]),
+            2.2,
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::cmp::Ordering;
+
+// Finds the entry covering 'character' in a table of code point ranges,
+// which are inclusive, don't overlap and are sorted. 'range' gets an
+// entry's first and last code points.
+pub fn find_range<T>(table: &[T], character: char, range: impl Fn(&T) -> (u32, u32)) -> Option<&T> {
+    let code = character as u32;
+    table
+        .binary_search_by(|entry| {
+            let (start, end) = range(entry);
+            if end < code {
+                Ordering::Less
+            } else if start > code {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+        .ok()
+        .map(|index| &table[index])
+}
+
+// Whether 'character' is in one of the ranges, as with find_range.
+pub fn in_ranges(ranges: &[(u32, u32)], character: char) -> bool {
+    find_range(ranges, character, |&range| range).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANGES: &[(u32, u32)] = &[(0x41, 0x43), (0x61, 0x61), (0x1F600, 0x1F64F)];
+
+    #[test]
+    fn characters_in_a_range_are_found() {
+        for character in ['A', 'B', 'C', 'a', '\u{1F600}', '\u{1F62E}', '\u{1F64F}'] {
+            assert!(in_ranges(RANGES, character), "{character:?}");
+        }
+    }
+
+    #[test]
+    fn characters_between_and_around_the_ranges_arent() {
+        for character in [
+            '\0',
+            '@',
+            'D',
+            '`',
+            'b',
+            '\u{1F5FF}',
+            '\u{1F650}',
+            char::MAX,
+        ] {
+            assert!(!in_ranges(RANGES, character), "{character:?}");
+        }
+        assert!(!in_ranges(&[], 'a'));
+    }
+
+    #[test]
+    fn find_range_returns_the_entry() {
+        let table = [(0x30, 0x39, "digit"), (0x41, 0x5A, "upper")];
+        let range = |&(start, end, _): &(u32, u32, &str)| (start, end);
+        assert_eq!(
+            find_range(&table, '7', range).map(|entry| entry.2),
+            Some("digit")
+        );
+        assert_eq!(
+            find_range(&table, 'Q', range).map(|entry| entry.2),
+            Some("upper")
+        );
+        assert_eq!(find_range(&table, 'q', range), None);
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// Notices changes to a file by polling its modification time and length,
+// which catches both in-place writes and editors that save by replacing
+// the file.
+pub struct FileWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            stamp: stamp(path),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns true once per change. A file that disappears (as it can for a
+    // moment mid-save) isn't reported until it's back.
+    pub fn poll(&mut self) -> bool {
+        let stamp = stamp(&self.path);
+        if stamp == self.stamp {
+            return false;
+        }
+        self.stamp = stamp;
+        stamp.is_some()
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -5,21 +5,50 @@ use windows::{
     Win32::{
         Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
         System::{LibraryLoader::GetModuleHandleW, WinRT::Composition::ICompositorDesktopInterop},
-        UI::WindowsAndMessaging::{
-            AdjustWindowRectEx, CREATESTRUCTW, CW_USEDEFAULT, CreateWindowExW, DefWindowProcW,
-            GWLP_USERDATA, GetWindowLongPtrW, IDC_ARROW, LoadCursorW, PostQuitMessage,
-            RegisterClassW, SW_SHOW, SetWindowLongPtrW, ShowWindow, WM_DESTROY, WM_NCCREATE,
-            WNDCLASSW, WS_EX_NOREDIRECTIONBITMAP, WS_OVERLAPPEDWINDOW,
+        UI::{
+            Input::{
+                Ime::{
+                    CPS_CANCEL, GCS_COMPSTR, GCS_CURSORPOS, GCS_RESULTSTR, HIMC,
+                    IME_COMPOSITION_STRING, ISC_SHOWUICOMPOSITIONWINDOW, ImmGetCompositionStringW,
+                    ImmGetContext, ImmNotifyIME, ImmReleaseContext, NI_COMPOSITIONSTR,
+                },
+                KeyboardAndMouse::{TME_LEAVE, TRACKMOUSEEVENT, TrackMouseEvent},
+            },
+            WindowsAndMessaging::{
+                AdjustWindowRectEx, CREATESTRUCTW, CW_USEDEFAULT, CreateWindowExW, DefWindowProcW,
+                GWLP_USERDATA, GetClientRect, GetWindowLongPtrW, HTCLIENT, IDC_ARROW, IDC_HAND,
+                LoadCursorW, PostQuitMessage, RegisterClassW, SW_SHOW, SetCursor,
+                SetWindowLongPtrW, ShowWindow, WM_DESTROY, WM_IME_COMPOSITION,
+                WM_IME_ENDCOMPOSITION, WM_IME_SETCONTEXT, WM_IME_STARTCOMPOSITION, WM_NCCREATE,
+                WM_SETCURSOR, WNDCLASSW, WS_EX_NOREDIRECTIONBITMAP, WS_OVERLAPPEDWINDOW,
+            },
         },
     },
     core::{HSTRING, Interface, PCWSTR, Result, w},
 };
+use windows_numerics::Vector2;
 
 static REGISTER_WINDOW_CLASS: Once = Once::new();
 const WINDOW_CLASS_NAME: PCWSTR = w!("comptextdemo.Window");
 
+// What an IME is doing with the text being typed. The caret is counted in
+// UTF-16 code units into the composition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImeEvent {
+    Composition { text: String, caret: u32 },
+    Commit(String),
+    End,
+}
+
 pub struct Window {
     handle: HWND,
+    // While text input is on, IME compositions are reported as events
+    // instead of shown in the IME's own window.
+    text_input: bool,
+    ime_events: Vec<ImeEvent>,
+    // Shows the hand cursor, and has WM_MOUSELEAVE posted when the mouse
+    // leaves the window.
+    is_over_link: bool,
 }
 
 impl Window {
@@ -56,6 +85,9 @@ impl Window {
 
         let mut result = Box::new(Self {
             handle: HWND(std::ptr::null_mut()),
+            text_input: false,
+            ime_events: Vec::new(),
+            is_over_link: false,
         });
 
         let window = unsafe {
@@ -83,6 +115,16 @@ impl Window {
         self.handle
     }
 
+    // The size of the client area, which the root visual fills.
+    pub fn size(&self) -> Result<Vector2> {
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(self.handle, &mut rect)? };
+        Ok(Vector2::new(
+            (rect.right - rect.left) as f32,
+            (rect.bottom - rect.top) as f32,
+        ))
+    }
+
     pub fn create_window_target(
         &self,
         compositor: &Compositor,
@@ -92,17 +134,90 @@ impl Window {
         unsafe { compositor_desktop.CreateDesktopWindowTarget(self.handle(), is_topmost) }
     }
 
+    // Turning text input off cancels anything the IME is composing.
+    pub fn set_text_input(&mut self, text_input: bool) {
+        if self.text_input && !text_input {
+            unsafe {
+                let context = ImmGetContext(self.handle);
+                let _ = ImmNotifyIME(context, NI_COMPOSITIONSTR, CPS_CANCEL, 0);
+                let _ = ImmReleaseContext(self.handle, context);
+            }
+        }
+        self.text_input = text_input;
+    }
+
+    pub fn take_ime_events(&mut self) -> Vec<ImeEvent> {
+        std::mem::take(&mut self.ime_events)
+    }
+
+    pub fn set_over_link(&mut self, is_over_link: bool) {
+        self.is_over_link = is_over_link;
+        unsafe {
+            let cursor = if is_over_link { IDC_HAND } else { IDC_ARROW };
+            SetCursor(LoadCursorW(None, cursor).ok());
+            if is_over_link {
+                let mut track = TRACKMOUSEEVENT {
+                    cbSize: std::mem::size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: self.handle,
+                    dwHoverTime: 0,
+                };
+                let _ = TrackMouseEvent(&mut track);
+            }
+        }
+    }
+
     fn message_handler(&mut self, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
         match message {
             WM_DESTROY => {
                 unsafe { PostQuitMessage(0) };
                 return LRESULT(0);
             }
+            // The composition is drawn in the text, so the IME's window
+            // isn't needed.
+            WM_IME_SETCONTEXT if self.text_input => {
+                let lparam = LPARAM(lparam.0 & !(ISC_SHOWUICOMPOSITIONWINDOW as isize));
+                return unsafe { DefWindowProcW(self.handle, message, wparam, lparam) };
+            }
+            WM_SETCURSOR if self.is_over_link && lparam.0 as u32 & 0xFFFF == HTCLIENT => {
+                unsafe { SetCursor(LoadCursorW(None, IDC_HAND).ok()) };
+                return LRESULT(1);
+            }
+            WM_IME_STARTCOMPOSITION if self.text_input => return LRESULT(0),
+            WM_IME_COMPOSITION if self.text_input => {
+                self.read_composition(lparam.0 as u32);
+                return LRESULT(0);
+            }
+            WM_IME_ENDCOMPOSITION if self.text_input => {
+                self.ime_events.push(ImeEvent::End);
+                return LRESULT(0);
+            }
             _ => {}
         }
         unsafe { DefWindowProcW(self.handle, message, wparam, lparam) }
     }
 
+    // A composition message can carry both the text the IME settled on and
+    // the start of the next composition.
+    fn read_composition(&mut self, changes: u32) {
+        unsafe {
+            let context = ImmGetContext(self.handle);
+            if changes & GCS_RESULTSTR.0 != 0 {
+                let text = composition_string(context, GCS_RESULTSTR);
+                self.ime_events.push(ImeEvent::Commit(text));
+            }
+            if changes & GCS_COMPSTR.0 != 0 {
+                let text = composition_string(context, GCS_COMPSTR);
+                let caret = ImmGetCompositionStringW(context, GCS_CURSORPOS, None, 0);
+                self.ime_events.push(ImeEvent::Composition {
+                    text,
+                    caret: caret.max(0) as u32,
+                });
+            }
+            let _ = ImmReleaseContext(self.handle, context);
+        }
+    }
+
     unsafe extern "system" fn wnd_proc(
         window: HWND,
         message: u32,
@@ -127,3 +242,26 @@ impl Window {
         }
     }
 }
+
+fn composition_string(context: HIMC, kind: IME_COMPOSITION_STRING) -> String {
+    unsafe {
+        // Lengths are in bytes, and negative for errors.
+        let length = ImmGetCompositionStringW(context, kind, None, 0);
+        if length <= 0 {
+            return String::new();
+        }
+        let mut buffer = vec![0u16; length as usize / 2];
+        let length =
+            ImmGetCompositionStringW(context, kind, Some(buffer.as_mut_ptr() as _), length as u32);
+        buffer.truncate(length.max(0) as usize / 2);
+        String::from_utf16_lossy(&buffer)
+    }
+}
+
+// Mouse messages carry the client area coordinates as signed 16 bit
+// values, negative when the mouse is captured and left of or above it.
+pub fn mouse_position(lparam: LPARAM) -> Vector2 {
+    let x = lparam.0 as u16 as i16;
+    let y = (lparam.0 >> 16) as u16 as i16;
+    Vector2::new(x as f32, y as f32)
+}